@@ -1,6 +1,7 @@
 use clap::Parser;
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
+use std::time::Instant;
 
 #[derive(Parser)]
 struct Args {
@@ -12,6 +13,19 @@ struct Args {
     repetitions: usize,
     #[arg(short, long, default_value_t = false)]
     quiet: bool,
+    /// minimum atom length of the prefilter
+    #[arg(short, long, default_value_t = 3)]
+    atom_len: usize,
+    /// enable the bigrams pre-screen
+    #[arg(long, default_value_t = false)]
+    prescreen: bool,
+    /// also match every regex against every haystack, without the
+    /// prefilter, and report the speedup
+    #[arg(long, default_value_t = false)]
+    baseline: bool,
+    /// report the regexes with the largest compiled size
+    #[arg(long, default_value_t = 0)]
+    largest: usize,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -20,21 +34,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         user_agents,
         repetitions,
         quiet,
+        atom_len,
+        prescreen,
+        baseline,
+        largest,
     } = Args::parse();
 
-    let start = std::time::Instant::now();
+    let start = Instant::now();
     let regexes = BufReader::new(std::fs::File::open(regexes)?)
         .lines()
         .collect::<Result<Vec<String>, _>>()?;
 
-    let f = regex_filtered::Builder::new().push_all(&regexes)?.build()?;
+    let f = regex_filtered::Builder::new_atom_len(atom_len)
+        .prescreen(prescreen)
+        .push_all(&regexes)?
+        .build()?;
     eprintln!(
-        "{} regexes in {}s",
+        "{} regexes in {}s ({} unfiltered, prefilter {} bytes)",
         regexes.len(),
-        start.elapsed().as_secs_f32()
+        start.elapsed().as_secs_f32(),
+        f.unfiltered().len(),
+        f.memory_usage(),
     );
 
-    let start = std::time::Instant::now();
+    let start = Instant::now();
     let user_agents = BufReader::new(std::fs::File::open(user_agents)?)
         .lines()
         .collect::<Result<Vec<String>, _>>()?;
@@ -44,9 +67,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         start.elapsed().as_secs_f32()
     );
 
+    let mut latencies = Vec::with_capacity(repetitions * user_agents.len());
+    let start = Instant::now();
     for _ in 0..repetitions {
         for ua in user_agents.iter() {
+            let t = Instant::now();
             let n = f.matching(ua).next();
+            latencies.push(t.elapsed());
             if !quiet {
                 if let Some((n, _)) = n {
                     println!("{n:3}");
@@ -56,6 +83,110 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
     }
+    let elapsed = start.elapsed();
+
+    let lines = latencies.len();
+    let bytes = repetitions * user_agents.iter().map(String::len).sum::<usize>();
+    eprintln!(
+        "{lines} matches in {}s: {:.0} lines/s, {:.1} MB/s",
+        elapsed.as_secs_f32(),
+        lines as f64 / elapsed.as_secs_f64(),
+        bytes as f64 / elapsed.as_secs_f64() / 1e6,
+    );
+
+    latencies.sort_unstable();
+    eprintln!(
+        "latency: p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+        percentile(&latencies, 50),
+        percentile(&latencies, 90),
+        percentile(&latencies, 99),
+        latencies.last().copied().unwrap_or_default(),
+    );
+
+    let mut candidates = user_agents
+        .iter()
+        .map(|ua| f.candidates(ua).count())
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+    eprintln!(
+        "candidates: mean {:.1}, p50 {}, p99 {}, max {}",
+        candidates.iter().sum::<usize>() as f64 / candidates.len().max(1) as f64,
+        percentile(&candidates, 50),
+        percentile(&candidates, 99),
+        candidates.last().copied().unwrap_or_default(),
+    );
+
+    if baseline {
+        let start = Instant::now();
+        let mut naive = Vec::with_capacity(user_agents.len());
+        for _ in 0..repetitions {
+            naive.clear();
+            naive.extend(
+                user_agents
+                    .iter()
+                    .map(|ua| f.regexes().iter().position(|r| r.is_match(ua))),
+            );
+        }
+        let naive_elapsed = start.elapsed();
+        eprintln!(
+            "baseline: {lines} naive scans in {}s, prefilter speedup {:.1}x",
+            naive_elapsed.as_secs_f32(),
+            naive_elapsed.as_secs_f64() / elapsed.as_secs_f64(),
+        );
+
+        let divergences = user_agents
+            .iter()
+            .zip(naive)
+            .filter(|(ua, n)| f.matching(ua).next().map(|(idx, _)| idx) != *n)
+            .count();
+        if divergences > 0 {
+            eprintln!("baseline: {divergences} results differ from the prefiltered matching");
+        }
+    }
+
+    if largest > 0 {
+        let mut sizes = f
+            .compiled_sizes()
+            .into_iter()
+            .enumerate()
+            .collect::<Vec<_>>();
+        sizes.sort_unstable_by_key(|&(_, size)| std::cmp::Reverse(size));
+        eprintln!(
+            "compiled size: {} bytes, largest regexes:",
+            sizes.iter().map(|(_, size)| size).sum::<usize>()
+        );
+        for &(idx, size) in sizes.iter().take(largest) {
+            eprintln!("{size:>10} {idx:>5} {}", regexes[idx]);
+        }
+    }
+
+    match peak_rss() {
+        Some(kb) => eprintln!("peak RSS: {kb} kB"),
+        None => eprintln!("peak RSS: unavailable"),
+    }
 
     Ok(())
 }
+
+/// Value at the `p`th percentile of the sorted `values`, or the
+/// default if empty.
+fn percentile<T: Copy + Default>(values: &[T], p: usize) -> T {
+    if values.is_empty() {
+        return T::default();
+    }
+    values[(values.len() - 1) * p / 100]
+}
+
+/// Peak resident set size of the process in kB, only available on
+/// linux (from `/proc`).
+fn peak_rss() -> Option<u64> {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()?
+        .lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
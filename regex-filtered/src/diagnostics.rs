@@ -0,0 +1,52 @@
+//! Build-time warnings about patterns which are likely to degrade
+//! the performances or memory use of the set, emitted through the
+//! [`log`] facade under the `regex_filtered` target.
+use crate::model::Model;
+use crate::{Engine, Options, ParseError};
+
+/// Default [`regex::RegexBuilder::size_limit`], used if the options
+/// don't specify one.
+const SIZE_LIMIT: usize = 10 * (1 << 20);
+/// Number of atoms above which a pattern is considered to have
+/// exploded, usually because of character class expansion.
+const MAX_ATOMS: usize = 64;
+
+pub fn check_model(index: usize, pattern: &str, model: &Model) {
+    let atoms = model.atoms();
+    if atoms > MAX_ATOMS {
+        log::warn!(
+            target: "regex_filtered",
+            "regex {index} ({pattern:?}) expands to {atoms} atoms, consider simplifying its character classes"
+        );
+    }
+}
+
+/// Compiles the regex, warning if it is over half the size limit:
+/// that does not cost an additional compilation unless the regex is
+/// actually large (or invalid).
+pub fn compile<R: Engine>(index: usize, pattern: &str, opts: &Options) -> Result<R, ParseError> {
+    let mut half = opts.clone();
+    half.size_limit(opts.size_limit.unwrap_or(SIZE_LIMIT) / 2);
+    if let Ok(r) = R::compile(pattern, &half) {
+        return Ok(r);
+    }
+
+    // Not all engines report size errors distinctly, so assume any
+    // failure which does not reproduce at full size is a size issue.
+    let r = R::compile(pattern, opts)?;
+    log::warn!(
+        target: "regex_filtered",
+        "regex {index} ({pattern:?}) compiles to more than half the size limit"
+    );
+    Ok(r)
+}
+
+pub fn check_unfiltered<R: Engine>(regexes: &[R], unfiltered: &[usize]) {
+    for &index in unfiltered {
+        log::warn!(
+            target: "regex_filtered",
+            "regex {index} ({:?}) has no atoms, it will be checked against every haystack",
+            regexes[index].as_str(),
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{Options, ParseError};
+
+/// Regex engine used to check the candidates nominated by the
+/// prefilter against the haystack.
+///
+/// The prefilter itself is always computed from the [`regex_syntax`]
+/// parse of the pattern, the engine is only in charge of the actual
+/// matching, so a pattern has to be valid for both.
+///
+/// Implemented for [`regex::Regex`] (the default), for
+/// [`regex::bytes::Regex`], and for `regex_lite::Regex` if the
+/// `regex-lite` feature is enabled.
+pub trait Engine: Sized {
+    /// Compiles the pattern according to the options.
+    fn compile(pattern: &str, options: &Options) -> Result<Self, ParseError>;
+    /// Returns whether the regex matches anywhere in the haystack.
+    fn is_match(&self, haystack: &str) -> bool;
+    /// Returns the source pattern of the regex.
+    fn as_str(&self) -> &str;
+}
+
+macro_rules! configure {
+    ($builder:path, $pattern:expr, $options:expr) => {{
+        let Options {
+            case_insensitive,
+            dot_matches_new_line,
+            ignore_whitespace,
+            multi_line,
+            crlf,
+            size_limit,
+        } = $options;
+        let mut builder = <$builder>::new($pattern);
+        builder
+            .case_insensitive(*case_insensitive)
+            .dot_matches_new_line(*dot_matches_new_line)
+            .ignore_whitespace(*ignore_whitespace)
+            .multi_line(*multi_line)
+            .crlf(*crlf);
+        if let Some(limit) = size_limit {
+            builder.size_limit(*limit);
+        }
+        builder
+    }};
+}
+
+impl Engine for regex::Regex {
+    fn compile(pattern: &str, options: &Options) -> Result<Self, ParseError> {
+        Ok(configure!(regex::RegexBuilder, pattern, options).build()?)
+    }
+    #[inline]
+    fn is_match(&self, haystack: &str) -> bool {
+        regex::Regex::is_match(self, haystack)
+    }
+    fn as_str(&self) -> &str {
+        regex::Regex::as_str(self)
+    }
+}
+
+impl Engine for regex::bytes::Regex {
+    fn compile(pattern: &str, options: &Options) -> Result<Self, ParseError> {
+        Ok(configure!(regex::bytes::RegexBuilder, pattern, options).build()?)
+    }
+    #[inline]
+    fn is_match(&self, haystack: &str) -> bool {
+        regex::bytes::Regex::is_match(self, haystack.as_bytes())
+    }
+    fn as_str(&self) -> &str {
+        regex::bytes::Regex::as_str(self)
+    }
+}
+
+#[cfg(feature = "regex-lite")]
+impl Engine for regex_lite::Regex {
+    fn compile(pattern: &str, options: &Options) -> Result<Self, ParseError> {
+        configure!(regex_lite::RegexBuilder, pattern, options)
+            .build()
+            .map_err(|e| ParseError::SyntaxError(e.to_string()))
+    }
+    #[inline]
+    fn is_match(&self, haystack: &str) -> bool {
+        regex_lite::Regex::is_match(self, haystack)
+    }
+    fn as_str(&self) -> &str {
+        regex_lite::Regex::as_str(self)
+    }
+}
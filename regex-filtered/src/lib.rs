@@ -4,30 +4,42 @@
 
 use aho_corasick::AhoCorasick;
 
+#[cfg(feature = "log")]
+mod diagnostics;
+mod engine;
 mod int_set;
 mod mapper;
 mod model;
-pub use model::Error as ModelError;
+mod prescreen;
+pub use engine::Engine;
+pub use model::{Error as ModelError, ModelView};
 
-/// Builder for the regexes set
-pub struct Builder {
-    regexes: Vec<regex::Regex>,
+/// Builder for the regexes set, generic over the regex [`Engine`]
+/// used to confirm prefiltered candidates.
+pub struct Builder<R = regex::Regex> {
+    regexes: Vec<R>,
+    options: Vec<Options>,
     mapper_builder: mapper::Builder,
+    prescreen: bool,
+    case_insensitive: bool,
+    fold_case: Vec<bool>,
 }
 
 /// Parser configuration, can be used to tune the regex parsing when
-/// adding it to the [`Builder`]. Every option defaults to `false`
-/// whether through [`Default`] or [`Options::new`].
+/// adding it to the [`Builder`]. Every flag defaults to `false` and
+/// the size limit to the engine's own, whether through [`Default`] or
+/// [`Options::new`].
 ///
 /// The parser can also be configured via standard [`regex`] inline
 /// flags.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Options {
     case_insensitive: bool,
     dot_matches_new_line: bool,
     ignore_whitespace: bool,
     multi_line: bool,
     crlf: bool,
+    size_limit: Option<usize>,
 }
 
 impl Options {
@@ -69,14 +81,75 @@ impl Options {
         self.crlf = yes;
         self
     }
-    fn to_regex(&self, pattern: &str) -> Result<regex::Regex, regex::Error> {
-        regex::RegexBuilder::new(pattern)
-            .case_insensitive(self.case_insensitive)
-            .dot_matches_new_line(self.dot_matches_new_line)
-            .ignore_whitespace(self.ignore_whitespace)
-            .multi_line(self.multi_line)
-            .crlf(self.crlf)
-            .build()
+    /// Configures the approximate size limit, in bytes, of the
+    /// compiled regex. Regexes which exceed it fail to compile with
+    /// [`ParseError::RegexTooLarge`] (or [`ParseError::SyntaxError`]
+    /// depending on the engine).
+    pub fn size_limit(&mut self, limit: usize) -> &mut Self {
+        self.size_limit = Some(limit);
+        self
+    }
+    /// Whether [`Self::case_insensitive`] is enabled.
+    pub fn get_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+    /// Whether [`Self::dot_matches_new_line`] is enabled.
+    pub fn get_dot_matches_new_line(&self) -> bool {
+        self.dot_matches_new_line
+    }
+    /// Whether [`Self::ignore_whitespace`] is enabled.
+    pub fn get_ignore_whitespace(&self) -> bool {
+        self.ignore_whitespace
+    }
+    /// Whether [`Self::multi_line`] is enabled.
+    pub fn get_multi_line(&self) -> bool {
+        self.multi_line
+    }
+    /// Whether [`Self::crlf`] is enabled.
+    pub fn get_crlf(&self) -> bool {
+        self.crlf
+    }
+    /// Creates options from a string of standard [`regex`] inline
+    /// flag letters e.g. `"is"`:
+    ///
+    /// - `i`, [`Self::case_insensitive`]
+    /// - `s`, [`Self::dot_matches_new_line`]
+    /// - `m`, [`Self::multi_line`]
+    /// - `x`, [`Self::ignore_whitespace`]
+    /// - `R`, [`Self::crlf`]
+    ///
+    /// Any other letter is rejected.
+    pub fn from_flags(flags: &str) -> Result<Self, UnknownFlag> {
+        let mut opts = Self::new();
+        for c in flags.chars() {
+            match c {
+                'i' => opts.case_insensitive(true),
+                's' => opts.dot_matches_new_line(true),
+                'm' => opts.multi_line(true),
+                'x' => opts.ignore_whitespace(true),
+                'R' => opts.crlf(true),
+                c => return Err(UnknownFlag(c)),
+            };
+        }
+        Ok(opts)
+    }
+}
+impl std::str::FromStr for Options {
+    type Err = UnknownFlag;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_flags(s)
+    }
+}
+
+/// Error returned by [`Options::from_flags`] when the flags string
+/// contains an unsupported letter.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct UnknownFlag(pub char);
+impl std::error::Error for UnknownFlag {}
+impl std::fmt::Display for UnknownFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
     }
 }
 impl From<Options> for regex_syntax::Parser {
@@ -92,6 +165,7 @@ impl From<&Options> for regex_syntax::Parser {
             ignore_whitespace,
             multi_line,
             crlf,
+            size_limit: _,
         }: &Options,
     ) -> Self {
         regex_syntax::ParserBuilder::new()
@@ -189,14 +263,100 @@ impl Builder {
     /// which can increase matching costs.
     #[must_use]
     pub fn new_atom_len(min_atom_len: usize) -> Self {
+        Self::with_atom_len(min_atom_len)
+    }
+
+    /// Push a regex compiled elsewhere into the builder, e.g. with
+    /// [`regex::RegexBuilder`] settings which [`Options`] does not
+    /// expose, without recompiling it.
+    ///
+    /// The prefilter model is derived from the regex's pattern
+    /// ([`regex::Regex::as_str`]). As the settings it was built with
+    /// are unknown, the regex is always prefiltered
+    /// case-insensitively (see [`Self::case_insensitive_prefilter`]),
+    /// and settings which change the meaning of the pattern itself
+    /// (e.g. [`regex::RegexBuilder::ignore_whitespace`]) must be set
+    /// as inline flags instead, or the regex pushed with
+    /// [`Self::push_compiled`] and the corresponding [`Options`].
+    ///
+    /// ```
+    /// let re = regex::RegexBuilder::new("foo")
+    ///     .case_insensitive(true)
+    ///     .build()?;
+    /// let matcher = regex_filtered::Builder::new()
+    ///     .push_regex(re)?
+    ///     .push("bar")?
+    ///     .build()?;
+    /// assert!(matcher.is_match("FOO"));
+    /// assert!(!matcher.is_match("BAR"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn push_regex(mut self, regex: regex::Regex) -> Result<Self, ParseError> {
+        let opts = Options::new();
+        self.push_model(regex.as_str(), &opts, true)?;
+        self.regexes.push(regex);
+        self.options.push(opts);
+        Ok(self)
+    }
+}
+
+impl<R: Engine> Builder<R> {
+    /// Instantiate a builder for an arbitrary [`Engine`] with a
+    /// custom minimum atom length, [`Builder::new`] and
+    /// [`Builder::new_atom_len`] are limited to the default engine
+    /// for inference reasons.
+    ///
+    /// ```
+    /// let matcher = regex_filtered::Builder::<regex::bytes::Regex>::with_atom_len(3)
+    ///     .push("foo")?
+    ///     .build()?;
+    /// assert!(matcher.is_match("foo"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[must_use]
+    pub fn with_atom_len(min_atom_len: usize) -> Self {
         Self {
             regexes: Vec::new(),
+            options: Vec::new(),
             mapper_builder: mapper::Builder::new(min_atom_len),
+            prescreen: false,
+            case_insensitive: true,
+            fold_case: Vec::new(),
         }
     }
 
+    /// Enables a cheap pre-screen in front of the prefilter: a bitset
+    /// of the leading bigram of every atom, checked against the
+    /// haystack before running the full aho-corasick scan.
+    ///
+    /// For very large sets of atoms where most haystacks match
+    /// nothing, this avoids most of the prefiltering cost. It is
+    /// silently disabled if any atom is shorter than two bytes (which
+    /// can only happen with a minimum atom length below 2).
+    #[must_use]
+    pub fn prescreen(mut self, yes: bool) -> Self {
+        self.prescreen = yes;
+        self
+    }
+
+    /// Configures whether the prefilter ignores ascii case when
+    /// looking for the regexes' literals, the default.
+    ///
+    /// Disabling it keeps the case of the literals of the regexes
+    /// pushed afterwards, so case-sensitive regexes yield fewer
+    /// candidates. Case-insensitive regexes (through
+    /// [`Options::case_insensitive`] or an inline `(?i)` flag) are
+    /// still prefiltered case-insensitively, and the prefilter is
+    /// case-insensitive if any regex was prefiltered that way, as it
+    /// would otherwise miss candidates.
+    #[must_use]
+    pub fn case_insensitive_prefilter(mut self, yes: bool) -> Self {
+        self.case_insensitive = yes;
+        self
+    }
+
     /// Currently loaded regexes.
-    pub fn regexes(&self) -> &[regex::Regex] {
+    pub fn regexes(&self) -> &[R] {
         &self.regexes
     }
 
@@ -209,11 +369,55 @@ impl Builder {
     /// Push a single regex into the builder, using custom parsing
     /// options.
     pub fn push_opt(mut self, regex: &str, opts: &Options) -> Result<Self, ParseError> {
+        let re = self.compile(regex, opts)?;
+        self.push_compiled_mut(re, opts)?;
+        Ok(self)
+    }
+
+    /// Compiles a regex as [`Self::push_opt`] would, without pushing
+    /// it, e.g. to inspect it before committing to the push.
+    pub fn compile(&self, regex: &str, opts: &Options) -> Result<R, ParseError> {
+        #[cfg(feature = "log")]
+        return diagnostics::compile(self.regexes.len(), regex, opts);
+        #[cfg(not(feature = "log"))]
+        R::compile(regex, opts)
+    }
+
+    /// Push an already compiled regex into the builder, e.g. to share
+    /// a regex between multiple sets when the engine's regexes are
+    /// cheap to clone.
+    ///
+    /// `opts` must be the options the regex was compiled with, as the
+    /// regex is parsed again for atoms extraction.
+    pub fn push_compiled(mut self, regex: R, opts: &Options) -> Result<Self, ParseError> {
+        self.push_compiled_mut(regex, opts)?;
+        Ok(self)
+    }
+
+    /// In-place version of [`Self::push_compiled`], the builder is
+    /// left unchanged if the regex can not be processed.
+    pub fn push_compiled_mut(&mut self, regex: R, opts: &Options) -> Result<(), ParseError> {
+        let fold_case = self.case_insensitive || opts.case_insensitive;
+        self.push_model(regex.as_str(), opts, fold_case)?;
+        self.regexes.push(regex);
+        self.options.push(opts.clone());
+        Ok(())
+    }
+
+    fn push_model(
+        &mut self,
+        regex: &str,
+        opts: &Options,
+        fold_case: bool,
+    ) -> Result<(), ParseError> {
         let hir = regex_syntax::Parser::from(opts).parse(regex)?;
-        let pf = model::Model::new(&hir)?;
+        let fold_case = fold_case || model::Model::folds_case(&hir);
+        let pf = model::Model::new(&hir, fold_case)?;
+        #[cfg(feature = "log")]
+        diagnostics::check_model(self.regexes.len(), regex, &pf);
         self.mapper_builder.push(pf);
-        self.regexes.push(opts.to_regex(regex)?);
-        Ok(self)
+        self.fold_case.push(fold_case);
+        Ok(())
     }
 
     /// Push a batch of regexes into the builder, using the default
@@ -230,84 +434,429 @@ impl Builder {
     ///
     /// Building a regexes set from no regexes is useless but not an
     /// error.
-    pub fn build(self) -> Result<Regexes, BuildError> {
+    pub fn build(self) -> Result<Regexes<R>, BuildError> {
         let Self {
             regexes,
+            options,
             mapper_builder,
+            prescreen,
+            case_insensitive: _,
+            fold_case,
         } = self;
+        let min_atom_len = mapper_builder.min_atom_len();
+        let prefilter = Prefilter::new(mapper_builder, prescreen, fold_case.contains(&true))?;
+        #[cfg(feature = "log")]
+        diagnostics::check_unfiltered(&regexes, prefilter.mapper.unfiltered());
+
+        Ok(Regexes {
+            regexes,
+            options,
+            fold_case,
+            min_atom_len,
+            prefilter,
+            hook: None,
+        })
+    }
+}
+
+impl<R: Engine> Default for Builder<R> {
+    fn default() -> Self {
+        Self::with_atom_len(3)
+    }
+}
+
+/// Approximate compiled size of a regex, see
+/// [`Regexes::compiled_sizes`]. Regexes the NFA compiler refuses
+/// (which the engine would likely refuse as well) have no size.
+fn compiled_size(regex: &str, opts: &Options) -> usize {
+    regex_syntax::Parser::from(opts)
+        .parse(regex)
+        .ok()
+        .and_then(|hir| {
+            regex_automata::nfa::thompson::Compiler::new()
+                .build_from_hir(&hir)
+                .ok()
+        })
+        .map_or(0, |nfa| nfa.memory_usage())
+}
+
+/// Standalone prefilter covering the regexes of one or more
+/// [`Builder`], without the regexes themselves.
+///
+/// This allows scanning a haystack once for the candidates of several
+/// regexes sets, which are then verified individually via
+/// [`Regexes::verify`]. The candidates of each builder are offset by
+/// the number of regexes in the builders preceding it.
+pub struct Prefilter {
+    mapper: mapper::Mapper,
+    automaton: AhoCorasick,
+    prescreen: Option<prescreen::Bigrams>,
+}
+
+impl Prefilter {
+    /// Builds a prefilter covering the regexes of all the `builders`,
+    /// in order. The pre-screen is enabled if it is enabled on any of
+    /// the builders, and the prefilter is case-insensitive if any of
+    /// the builders' is.
+    pub fn build<'a, R: 'a>(
+        builders: impl IntoIterator<Item = &'a Builder<R>>,
+    ) -> Result<Self, BuildError> {
+        let mut mapper_builder = None;
+        let mut prescreen = false;
+        let mut folded = false;
+        for b in builders {
+            prescreen |= b.prescreen;
+            folded |= b.fold_case.contains(&true);
+            mapper_builder
+                .get_or_insert_with(|| mapper::Builder::new(b.mapper_builder.min_atom_len()))
+                .extend(&b.mapper_builder);
+        }
+        Self::new(
+            mapper_builder.unwrap_or_else(|| mapper::Builder::new(3)),
+            prescreen,
+            folded,
+        )
+    }
+
+    fn new(
+        mapper_builder: mapper::Builder,
+        prescreen: bool,
+        case_insensitive: bool,
+    ) -> Result<Self, BuildError> {
         let (mapper, atoms) = mapper_builder.build();
 
+        let prescreen = if prescreen {
+            prescreen::Bigrams::new(&atoms, case_insensitive)
+        } else {
+            None
+        };
+
         // Instead of returning a bunch of atoms for the user to
         // manage, since `regex` depends on aho-corasick by default we
         // can use that directly and not bother the user.
-        let prefilter = AhoCorasick::builder()
-            .ascii_case_insensitive(true)
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(case_insensitive)
             .prefilter(true)
             .build(atoms)?;
 
-        Ok(Regexes {
-            regexes,
+        Ok(Self {
             mapper,
-            prefilter,
+            automaton,
+            prescreen,
         })
     }
-}
 
-impl Default for Builder {
-    fn default() -> Self {
-        Self::new()
+    #[inline]
+    fn atoms<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = usize> + 'a {
+        let screened = self
+            .prescreen
+            .as_ref()
+            .map_or(true, |p| p.may_match(haystack.as_bytes()));
+        screened
+            .then(|| self.automaton.find_overlapping_iter(haystack))
+            .into_iter()
+            .flatten()
+            .map(|m| m.pattern().as_usize())
+    }
+
+    /// Yields the indices of the regexes which *may* match the
+    /// haystack, in ascending order. These candidates still have to
+    /// be verified.
+    pub fn candidates(&self, haystack: &str) -> impl Iterator<Item = usize> {
+        self.mapper.atom_to_re(self.atoms(haystack)).into_iter()
+    }
+
+    /// Approximate heap memory used by the prefilter, in bytes: the
+    /// atoms automaton, the atoms to regexes mapping, and the
+    /// pre-screen if enabled.
+    pub fn memory_usage(&self) -> usize {
+        self.automaton.memory_usage()
+            + self.mapper.memory_usage()
+            + self
+                .prescreen
+                .as_ref()
+                .map_or(0, prescreen::Bigrams::memory_usage)
     }
 }
 
 /// Regexes set, allows testing inputs against a *large* number of
 /// *non-trivial* regexes.
-pub struct Regexes {
-    regexes: Vec<regex::Regex>,
-    mapper: mapper::Mapper,
-    prefilter: AhoCorasick,
+pub struct Regexes<R = regex::Regex> {
+    regexes: Vec<R>,
+    /// Options each regex was compiled with.
+    options: Vec<Options>,
+    /// Whether the atoms of each regex were case-folded.
+    fold_case: Vec<bool>,
+    min_atom_len: usize,
+    prefilter: Prefilter,
+    hook: Option<Hook>,
 }
 
-impl Regexes {
-    // TODO:
-    // - number of tokens (prefilter.patterns_len())
-    // - number of regexes
-    // - number of unfiltered regexes (from mapper)
-    // - ratio of checked regexes to successes (cfg-gated)
-    // - total / prefiltered (- unfiltered?) so atom size can be manipulated
+type Hook = Box<dyn Fn(&Verification) + Send + Sync>;
+
+/// Record of a candidate regex (as nominated by the prefilter) being
+/// checked against a haystack, passed to the hook registered via
+/// [`Regexes::set_verification_hook`].
+#[derive(Debug, Clone, Copy)]
+pub struct Verification {
+    /// Index of the candidate regex in the set.
+    pub index: usize,
+    /// Whether the candidate regex did match the haystack.
+    pub matched: bool,
+    /// Time spent checking the regex against the haystack.
+    pub elapsed: std::time::Duration,
+}
+
+impl<R: Engine> Regexes<R> {
     #[inline]
-    fn prefilter<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = usize> + 'a {
-        self.prefilter
-            .find_overlapping_iter(haystack)
-            .map(|m| m.pattern().as_usize())
+    fn prefiltered(&self, haystack: &str) -> impl Iterator<Item = usize> {
+        self.prefilter.candidates(haystack)
+    }
+
+    /// Yields the indices of the regexes which *may* match the
+    /// haystack according to the prefilter, in ascending order.
+    ///
+    /// The candidates still have to be checked with [`Self::verify`],
+    /// which allows e.g. checking them concurrently.
+    pub fn candidates(&self, haystack: &str) -> impl Iterator<Item = usize> {
+        self.prefiltered(haystack)
     }
 
+    /// Checks the regex at `idx` against the haystack, bypassing the
+    /// prefilter, and calls the verification hook if one is set.
+    ///
+    /// Mostly useful to verify the candidates of a shared
+    /// [`Prefilter`].
+    ///
+    /// # Panics
+    ///
+    /// If `idx` is not the index of a regex in the set.
     #[inline]
-    fn prefiltered(&self, haystack: &str) -> impl Iterator<Item = usize> {
-        self.mapper.atom_to_re(self.prefilter(haystack)).into_iter()
+    pub fn verify(&self, idx: usize, haystack: &str) -> bool {
+        self.check(&self.regexes[idx], idx, haystack)
+    }
+
+    #[inline]
+    fn check(&self, r: &R, idx: usize, haystack: &str) -> bool {
+        let Some(hook) = &self.hook else {
+            return r.is_match(haystack);
+        };
+
+        let start = std::time::Instant::now();
+        let matched = r.is_match(haystack);
+        hook(&Verification {
+            index: idx,
+            matched,
+            elapsed: start.elapsed(),
+        });
+        matched
+    }
+
+    /// Registers a hook called every time a candidate regex is
+    /// checked against a haystack by [`Self::is_match`] or
+    /// [`Self::matching`], replacing the previous hook if any.
+    ///
+    /// This allows feeding metrics systems, or finding out which
+    /// patterns are pathologically slow or badly prefiltered on
+    /// actual inputs. Timing verifications has a cost, so there is no
+    /// hook by default.
+    pub fn set_verification_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&Verification) + Send + Sync + 'static,
+    {
+        self.hook = Some(Box::new(hook));
+    }
+
+    /// Removes the verification hook, if any.
+    pub fn clear_verification_hook(&mut self) {
+        self.hook = None;
     }
 
     /// Returns *whether* any regex in the set matches the haystack.
     pub fn is_match(&self, haystack: &str) -> bool {
         self.prefiltered(haystack)
-            .any(|idx| self.regexes[idx].is_match(haystack))
+            .any(|idx| self.verify(idx, haystack))
     }
 
     /// Yields the regexes matching the haystack along with their
     /// index.
     ///
     /// The results are guaranteed to be returned in ascending order.
-    pub fn matching<'a>(
+    pub fn matching<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = (usize, &'a R)> + 'a {
+        self.prefiltered(haystack)
+            .filter(move |&idx| self.verify(idx, haystack))
+            .map(|idx| (idx, &self.regexes[idx]))
+    }
+
+    /// Creates a [`MatchContext`] for this set, to be used with
+    /// [`Self::is_match_in`] and [`Self::matching_in`].
+    pub fn context(&self) -> MatchContext<R>
+    where
+        R: Clone,
+    {
+        MatchContext {
+            regexes: self.regexes.clone(),
+        }
+    }
+
+    /// Same as [`Self::is_match`], but checks the candidates with
+    /// the regexes of the context.
+    ///
+    /// # Panics
+    ///
+    /// If the context was not created from this set.
+    pub fn is_match_in(&self, context: &MatchContext<R>, haystack: &str) -> bool {
+        let regexes = context.regexes_for(self);
+        self.prefiltered(haystack)
+            .any(|idx| self.check(&regexes[idx], idx, haystack))
+    }
+
+    /// Same as [`Self::matching`], but checks the candidates with
+    /// the regexes of the context, which are the ones returned.
+    ///
+    /// # Panics
+    ///
+    /// If the context was not created from this set.
+    pub fn matching_in<'a>(
         &'a self,
+        context: &'a MatchContext<R>,
         haystack: &'a str,
-    ) -> impl Iterator<Item = (usize, &'a regex::Regex)> + 'a {
-        self.prefiltered(haystack).filter_map(move |idx| {
-            let r = &self.regexes[idx];
-            r.is_match(haystack).then_some((idx, r))
-        })
+    ) -> impl Iterator<Item = (usize, &'a R)> + 'a {
+        let regexes = context.regexes_for(self);
+        self.prefiltered(haystack)
+            .filter(move |&idx| self.check(&regexes[idx], idx, haystack))
+            .map(move |idx| (idx, &regexes[idx]))
     }
 
     /// Returns a reference to all the regexes in the set.
-    pub fn regexes(&self) -> &[regex::Regex] {
+    pub fn regexes(&self) -> &[R] {
+        &self.regexes
+    }
+
+    /// Indices of the regexes for which no distinguishing atom could
+    /// be found, in ascending order.
+    ///
+    /// These regexes bypass the prefilter and are checked against
+    /// every haystack, so a large number of unfiltered regexes
+    /// severely degrades matching performances, and tuning these
+    /// patterns (or the minimum atom length) may be worth it.
+    pub fn unfiltered(&self) -> &[usize] {
+        self.prefilter.mapper.unfiltered()
+    }
+
+    /// Approximate heap memory used by the set, in bytes.
+    ///
+    /// This covers the [`Prefilter`] and the storage of the regexes,
+    /// but not the internal structures of the compiled regexes as the
+    /// engines do not expose them, see [`Self::compiled_sizes`].
+    pub fn memory_usage(&self) -> usize {
+        self.prefilter.memory_usage()
+            + self.regexes.capacity() * std::mem::size_of::<R>()
+            + self.options.capacity() * std::mem::size_of::<Options>()
+            + self.fold_case.capacity()
+    }
+
+    /// Approximate compiled size of each regex in bytes, by index.
+    /// The sizes are computed on every call, by compiling the regexes
+    /// again.
+    ///
+    /// This is the size of the regex's Thompson NFA, as built by
+    /// `regex-automata` (which the `regex` engines use internally).
+    /// The engines build further structures (e.g. a reverse NFA, lazy
+    /// DFA caches) which tend to be proportional to it, so this is
+    /// mostly useful to find the few regexes responsible for most of
+    /// the memory footprint of a set, typically large bounded
+    /// repetitions and unicode classes.
+    ///
+    /// ```
+    /// let set = regex_filtered::Builder::new()
+    ///     .push("foo")?
+    ///     .push(r"\w{1,50}")?
+    ///     .build()?;
+    /// let sizes = set.compiled_sizes();
+    /// assert!(sizes[1] > 10 * sizes[0]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn compiled_sizes(&self) -> Vec<usize> {
+        std::iter::zip(&self.regexes, &self.options)
+            .map(|(re, opts)| compiled_size(re.as_str(), opts))
+            .collect()
+    }
+
+    /// Prefilter model of each regex, by index: the atoms (literals)
+    /// which a haystack must contain for the regex to be a candidate,
+    /// combined by [`ModelView::And`] and [`ModelView::Or`]. Regexes
+    /// which can not be prefiltered (see [`Self::unfiltered`]) have
+    /// the model [`ModelView::All`].
+    ///
+    /// The models are computed on every call, by processing the
+    /// regexes again.
+    ///
+    /// ```
+    /// # use regex_filtered::ModelView;
+    /// let set = regex_filtered::Builder::new()
+    ///     .push("foo.*(bar|baz)")?
+    ///     .push(".*")?
+    ///     .build()?;
+    /// assert_eq!(
+    ///     set.models(),
+    ///     [
+    ///         ModelView::And(vec![
+    ///             ModelView::Atom("foo".into()),
+    ///             ModelView::Or(vec![
+    ///                 ModelView::Atom("bar".into()),
+    ///                 ModelView::Atom("baz".into()),
+    ///             ]),
+    ///         ]),
+    ///         ModelView::All,
+    ///     ],
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn models(&self) -> Vec<ModelView> {
+        let mut builder = mapper::Builder::new(self.min_atom_len);
+        for ((re, opts), &fold_case) in std::iter::zip(
+            std::iter::zip(&self.regexes, &self.options),
+            &self.fold_case,
+        ) {
+            // the regexes were already processed successfully
+            let model = regex_syntax::Parser::from(opts)
+                .parse(re.as_str())
+                .ok()
+                .and_then(|hir| model::Model::new(&hir, fold_case).ok())
+                .unwrap_or_else(model::Model::all);
+            builder.push(model);
+        }
+        builder.models().iter().map(ModelView::from).collect()
+    }
+}
+
+/// Per-thread matching state for a [`Regexes`] set, created by
+/// [`Regexes::context`].
+///
+/// A [`regex::Regex`] can be used from multiple threads at once, but
+/// the scratch space of its searches lives in a pool shared by all of
+/// them, which shows up as contention when many threads match short
+/// haystacks concurrently. The context holds clones of the set's
+/// regexes, which share their compiled form with the originals but
+/// have their own pools: a thread using its own context (e.g. from a
+/// `thread_local!`, or created by each worker) gets uncontended
+/// access to its scratch space.
+///
+/// Cloning a regex is cheap, but it is still best to create a context
+/// once per thread rather than once per match.
+#[derive(Clone)]
+pub struct MatchContext<R = regex::Regex> {
+    regexes: Vec<R>,
+}
+
+impl<R> MatchContext<R> {
+    fn regexes_for<T>(&self, set: &Regexes<T>) -> &[R] {
+        assert_eq!(
+            self.regexes.len(),
+            set.regexes.len(),
+            "the context should have been created from this set"
+        );
         &self.regexes
     }
 }
@@ -317,10 +866,68 @@ mod test {
     use super::*;
     use itertools::Itertools;
 
+    #[test]
+    fn options_from_flags() {
+        let o: Options = "isR".parse().unwrap();
+        assert!(o.case_insensitive && o.dot_matches_new_line && o.crlf);
+        assert!(!o.multi_line && !o.ignore_whitespace);
+
+        let o = Options::from_flags("mx").unwrap();
+        assert!(o.multi_line && o.ignore_whitespace);
+        assert!(!o.case_insensitive);
+
+        assert!(Options::from_flags("").is_ok());
+        assert_eq!(Options::from_flags("iU").err(), Some(UnknownFlag('U')));
+
+        let r = Builder::new_atom_len(2)
+            .push_opt("ab", &"i".parse().unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(r.is_match("xABx"));
+    }
+
+    #[test]
+    fn match_context() {
+        let f = Builder::new()
+            .push("foo")
+            .unwrap()
+            .push("bar")
+            .unwrap()
+            .push("fo+")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let ctx = f.context();
+                    assert!(f.is_match_in(&ctx, "xbarx"));
+                    assert!(!f.is_match_in(&ctx, "baz"));
+                    assert_eq!(
+                        f.matching_in(&ctx, "foo")
+                            .map(|(idx, r)| (idx, r.as_str()))
+                            .collect_vec(),
+                        [(0, "foo"), (2, "fo+")],
+                    );
+                });
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn match_context_mismatch() {
+        let f = Builder::new().push("foo").unwrap().build().unwrap();
+        let g = Builder::new().build().unwrap();
+        f.is_match_in(&g.context(), "foo");
+    }
+
     #[test]
     fn empty_filter() {
         let f = Builder::new().build().unwrap();
-        assert_eq!(f.prefilter("0123").collect_vec(), vec![]);
+        assert_eq!(f.prefilter.atoms("0123").collect_vec(), vec![]);
 
         assert_eq!(f.matching("foo").count(), 0);
     }
@@ -329,7 +936,7 @@ mod test {
     fn empty_pattern() {
         let f = Builder::new().push("").unwrap().build().unwrap();
 
-        assert_eq!(f.prefilter("0123").collect_vec(), vec![]);
+        assert_eq!(f.prefilter.atoms("0123").collect_vec(), vec![]);
 
         assert_eq!(
             f.matching("0123").map(|(idx, _)| idx).collect_vec(),
@@ -345,7 +952,7 @@ mod test {
             .build()
             .unwrap();
 
-        assert_eq!(f.prefilter("lemurs bar").collect_vec(), vec![]);
+        assert_eq!(f.prefilter.atoms("lemurs bar").collect_vec(), vec![]);
 
         assert_eq!(
             f.matching("lemurs bar").map(|(idx, _)| idx).collect_vec(),
@@ -354,7 +961,7 @@ mod test {
 
         let f = Builder::new().push("(foo|bar)").unwrap().build().unwrap();
 
-        assert_eq!(f.prefilter("lemurs bar").collect_vec(), vec![1]);
+        assert_eq!(f.prefilter.atoms("lemurs bar").collect_vec(), vec![1]);
 
         assert_eq!(
             f.matching("lemurs bar").map(|(idx, _)| idx).collect_vec(),
@@ -362,6 +969,198 @@ mod test {
         );
     }
 
+    #[test]
+    fn unfiltered() {
+        let f = Builder::new()
+            .push("foo")
+            .unwrap()
+            .push("(a|b)")
+            .unwrap()
+            .push("bar.*")
+            .unwrap()
+            .push(".*")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(f.unfiltered(), &[1, 3]);
+    }
+
+    #[test]
+    fn case_sensitivity() {
+        // the prefilter ignores case by default
+        let f = Builder::new().push("Foo").unwrap().build().unwrap();
+        assert_eq!(f.candidates("FOO").collect_vec(), vec![0]);
+        assert!(!f.is_match("FOO"));
+
+        let sensitive = || Builder::new().case_insensitive_prefilter(false);
+        for prescreen in [false, true] {
+            let f = sensitive()
+                .prescreen(prescreen)
+                .push("Foo")
+                .unwrap()
+                .build()
+                .unwrap();
+            assert!(f.is_match("Foo"));
+            assert_eq!(f.candidates("FOO").collect_vec(), vec![]);
+        }
+
+        // a case-insensitive regex makes the entire prefilter so
+        let ci = Options::from_flags("i").unwrap();
+        for b in [
+            sensitive().push("Foo").unwrap().push_opt("bar", &ci),
+            sensitive().push("Foo").unwrap().push("(?i)bar"),
+        ] {
+            let f = b.unwrap().build().unwrap();
+            assert!(f.unfiltered().is_empty());
+            assert_eq!(f.candidates("foo BaR").collect_vec(), vec![0, 1]);
+            assert_eq!(f.matching("foo BaR").map(|(i, _)| i).collect_vec(), vec![1]);
+        }
+    }
+
+    #[test]
+    fn inline_case_insensitive() {
+        for b in [
+            Builder::new(),
+            Builder::new().case_insensitive_prefilter(false),
+        ] {
+            let f = b
+                .push(r"(?i)firefox/(\d+)")
+                .unwrap()
+                .push(r"Fennec(?i:Mobile)")
+                .unwrap()
+                .build()
+                .unwrap();
+            // folded rather than expanded to every case variant
+            assert_eq!(
+                f.models(),
+                [
+                    ModelView::Atom("firefox/".into()),
+                    ModelView::Atom("fennecmobile".into()),
+                ]
+            );
+            assert_eq!(f.candidates("FireFox/121").collect_vec(), vec![0]);
+            assert!(f.is_match("FIREFOX/121"));
+            assert!(f.is_match("FennecMOBILE"));
+        }
+    }
+
+    #[test]
+    fn memory_usage() {
+        let small = Builder::new().push("foo").unwrap().build().unwrap();
+        let large = Builder::new()
+            .push_all(["foo", "bar", "baz.*qux", "(quux|corge)"])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(small.memory_usage() > 0);
+        assert!(large.memory_usage() > small.memory_usage());
+        let screened = Builder::new()
+            .prescreen(true)
+            .push("foo")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(screened.memory_usage() >= small.memory_usage() + (1 << 16) / 8);
+    }
+
+    #[test]
+    fn compiled_sizes() {
+        let f = Builder::new()
+            .push("foo")
+            .unwrap()
+            .push_opt("foo", &Options::from_flags("i").unwrap())
+            .unwrap()
+            .push(r"\w{1,50}")
+            .unwrap()
+            .push(r"(?-u:\w){1,50}")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let sizes = f.compiled_sizes();
+        assert_eq!(sizes.len(), 4);
+        assert!(sizes.iter().all(|&s| s > 0));
+        // the options are accounted for
+        assert!(sizes[1] > sizes[0]);
+        // unicode classes are much larger than ascii ones
+        assert!(sizes[2] > 10 * sizes[3]);
+    }
+
+    #[test]
+    fn push_regex() {
+        let f = Builder::new()
+            .push_regex(
+                regex::RegexBuilder::new("Foo")
+                    .case_insensitive(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .push_regex(regex::Regex::new("(?x) b a r").unwrap())
+            .unwrap()
+            .push_regex(regex::Regex::new("Baz").unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(f.regexes()[0].as_str(), "Foo");
+        assert!(f.unfiltered().is_empty());
+        assert_eq!(f.matching("fOO bar").map(|(i, _)| i).collect_vec(), [0, 1]);
+        // the prefilter ignores case, but not the regex
+        assert_eq!(f.candidates("baz").collect_vec(), [2]);
+        assert!(!f.is_match("baz"));
+        assert_eq!(
+            f.models(),
+            [
+                ModelView::Atom("foo".into()),
+                ModelView::Atom("bar".into()),
+                ModelView::Atom("baz".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn models() {
+        let f = Builder::new()
+            .case_insensitive_prefilter(false)
+            .push("Foo")
+            .unwrap()
+            .push_opt("Bar", &Options::from_flags("i").unwrap())
+            .unwrap()
+            // too short for the atom length
+            .push("ab|cde")
+            .unwrap()
+            .push("(Foo|Bar).*(baz|quux)")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let models = f.models();
+        assert_eq!(
+            models[..3],
+            [
+                ModelView::Atom("Foo".into()),
+                ModelView::Atom("bar".into()),
+                ModelView::All,
+            ]
+        );
+        assert_eq!(
+            models[3].atoms(),
+            ["Bar", "Foo", "baz", "quux"],
+            "{:?}",
+            models[3]
+        );
+        for (i, m) in models.iter().enumerate() {
+            assert_eq!(
+                *m == ModelView::All,
+                f.unfiltered().contains(&i),
+                "{i}: {m:?}"
+            );
+        }
+    }
+
     #[test]
     fn basic_matches() {
         let f = Builder::new()
@@ -423,6 +1222,140 @@ mod test {
         );
     }
 
+    #[test]
+    fn prescreen() {
+        let f = Builder::new()
+            .prescreen(true)
+            .push("foo\\d+")
+            .unwrap()
+            .push("(bar|baz)")
+            .unwrap()
+            .push("x")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(f.prefilter.prescreen.is_some());
+        assert_eq!(f.prefilter.atoms("abc xyz").collect_vec(), vec![]);
+        assert_eq!(
+            f.matching("abc xyz").map(|(idx, _)| idx).collect_vec(),
+            vec![2],
+        );
+        assert_eq!(
+            f.matching("abc FOO1 xyz").map(|(idx, _)| idx).collect_vec(),
+            vec![2],
+        );
+        assert_eq!(
+            f.matching("abc foo1 baz").map(|(idx, _)| idx).collect_vec(),
+            vec![0, 1],
+        );
+
+        let f = Builder::new_atom_len(1)
+            .prescreen(true)
+            .push("(a|b)")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(f.prefilter.prescreen.is_none());
+        assert_eq!(f.matching("a").map(|(idx, _)| idx).collect_vec(), vec![0]);
+    }
+
+    #[test]
+    fn shared_prefilter() {
+        let a = Builder::new().push("foo\\d+").unwrap().push("bar").unwrap();
+        let b = Builder::new().push("(x|y)").unwrap().push("baz").unwrap();
+        let p = Prefilter::build([&a, &b]).unwrap();
+
+        assert_eq!(p.candidates("foo1 baz").collect_vec(), vec![0, 2, 3]);
+        assert_eq!(p.candidates("bar").collect_vec(), vec![1, 2]);
+
+        let (a, b) = (a.build().unwrap(), b.build().unwrap());
+        assert!(a.verify(0, "foo1 baz"));
+        assert!(!b.verify(0, "foo1 baz"));
+        assert!(b.verify(1, "foo1 baz"));
+    }
+
+    #[test]
+    fn verification_hook() {
+        use std::sync::{Arc, Mutex};
+
+        let mut f = Builder::new()
+            .push("foo\\d+")
+            .unwrap()
+            .push("foo")
+            .unwrap()
+            .push("bar")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let s = seen.clone();
+        f.set_verification_hook(move |v| s.lock().unwrap().push((v.index, v.matched)));
+
+        assert_eq!(
+            f.matching("foo bar").map(|(idx, _)| idx).collect_vec(),
+            vec![1, 2],
+        );
+        assert_eq!(
+            std::mem::take(&mut *seen.lock().unwrap()),
+            vec![(0, false), (1, true), (2, true)],
+        );
+
+        assert!(f.is_match("foo1"));
+        assert_eq!(std::mem::take(&mut *seen.lock().unwrap()), vec![(0, true)],);
+
+        f.clear_verification_hook();
+        assert!(f.is_match("foo1"));
+        assert!(seen.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn engines() {
+        fn check<R: Engine>() {
+            let f = Builder::<R>::default()
+                .push("foo\\d+")
+                .unwrap()
+                .push("(bar|baz)")
+                .unwrap()
+                .push_opt("quux", Options::new().case_insensitive(true))
+                .unwrap()
+                .build()
+                .unwrap();
+
+            assert_eq!(
+                f.matching("foo1 baz QUUX")
+                    .map(|(idx, r)| (idx, r.as_str()))
+                    .collect_vec(),
+                vec![(0, "foo\\d+"), (1, "(bar|baz)"), (2, "quux")],
+            );
+            assert!(!f.is_match("foo"));
+        }
+
+        check::<regex::Regex>();
+        check::<regex::bytes::Regex>();
+        #[cfg(feature = "regex-lite")]
+        check::<regex_lite::Regex>();
+    }
+
+    #[test]
+    fn push_compiled() {
+        let re = regex::Regex::new("(?i)foo\\d+").unwrap();
+        let f = Builder::new()
+            .push_compiled(re.clone(), &Options::new())
+            .unwrap()
+            .push("bar")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(f.regexes()[0].as_str(), re.as_str());
+        assert_eq!(
+            f.matching("FOO1 bar").map(|(idx, _)| idx).collect_vec(),
+            vec![0, 1],
+        );
+    }
+
     #[test]
     fn bulk_api() {
         use std::io::BufRead as _;
@@ -18,6 +18,25 @@ impl Builder {
         }
     }
 
+    pub fn min_atom_len(&self) -> usize {
+        self.min_atom_len
+    }
+
+    /// Models of the regexes, after pruning.
+    pub fn models(&self) -> &[Model] {
+        &self.models
+    }
+
+    /// Appends the models of `other` after those of `self`, the
+    /// models have already been pruned according to `other`'s atom
+    /// length.
+    pub fn extend(&mut self, other: &Builder) {
+        let offset = self.models.len();
+        self.unfiltered
+            .extend(other.unfiltered.iter().map(|i| i + offset));
+        self.models.extend(other.models.iter().cloned());
+    }
+
     pub fn push(&mut self, mut pf: Model) {
         if !self.keep_node(&mut pf) {
             self.unfiltered.push(self.models.len());
@@ -243,6 +262,22 @@ pub struct Mapper {
     atom_to_entry: Vec<usize>,
 }
 impl Mapper {
+    pub fn unfiltered(&self) -> &[usize] {
+        &self.unfiltered
+    }
+
+    /// Heap memory used by the mapping tables, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let usize = std::mem::size_of::<usize>();
+        self.entries.capacity() * std::mem::size_of::<Entry>()
+            + self
+                .entries
+                .iter()
+                .map(|e| (e.parents.capacity() + e.regexps.capacity()) * usize)
+                .sum::<usize>()
+            + (self.unfiltered.capacity() + self.atom_to_entry.capacity()) * usize
+    }
+
     // name is shit and also needs to see if we can generate stuff on the fly
     pub fn atom_to_re(&self, atoms: impl IntoIterator<Item = usize>) -> Vec<usize> {
         let mut matched_atom_ids = IntSet::new(self.entries.len());
@@ -304,7 +339,7 @@ mod test {
     #[test]
     fn empty_pattern() {
         let mut b = Builder::new(3);
-        b.push(Model::new(&parse("").unwrap()).unwrap());
+        b.push(Model::new(&parse("").unwrap(), true).unwrap());
         let (m, atoms) = b.build();
         assert_eq!(atoms.len(), 0);
         assert_eq!(&m.unfiltered, &[0]);
@@ -313,7 +348,7 @@ mod test {
     #[test]
     fn small_or_test() {
         let mut b = Builder::new(4);
-        b.push(Model::new(&parse("(foo|bar)").unwrap()).unwrap());
+        b.push(Model::new(&parse("(foo|bar)").unwrap(), true).unwrap());
         let (m, atoms) = b.build();
         assert_eq!(atoms.len(), 0);
         assert_eq!(&m.unfiltered, &[0]);
@@ -323,7 +358,7 @@ mod test {
     #[test]
     fn reverse_index() {
         let mut b = Builder::new(3);
-        b.push(Model::new(&parse("(foo|bar)").unwrap()).unwrap());
+        b.push(Model::new(&parse("(foo|bar)").unwrap(), true).unwrap());
         let (m, _) = b.build();
 
         assert_eq!(m.entries.len(), 3);
@@ -339,7 +374,7 @@ mod test {
     fn check_patterns(patterns: &'static [&'static str], expected: &'static [&'static str]) {
         let mut b = Builder::new(3);
         for pattern in patterns {
-            b.push(Model::new(&parse(pattern).unwrap()).unwrap());
+            b.push(Model::new(&parse(pattern).unwrap(), true).unwrap());
         }
         let (_, mut atoms) = b.build();
 
@@ -404,9 +439,11 @@ mod test {
                 "ΛΜΝΟΠ",
                 "ψρστυ",
             ],
+            // unlike re2, only ascii is folded: the haystack is not
+            // lowercased and aho-corasick only ignores ascii case
             &[
                 //"δδπππσσσ",
-                "λμνοπ",
+                "ΛΜΝΟΠ",
                 "ψρστυ",
             ],
         );
@@ -415,7 +452,7 @@ mod test {
     #[test]
     fn test_empty_string_in_string_set() {
         let mut b = Builder::new(0);
-        b.push(Model::new(&parse("-R.+(|ADD=;AA){12}}").unwrap()).unwrap());
+        b.push(Model::new(&parse("-R.+(|ADD=;AA){12}}").unwrap(), true).unwrap());
         let (_, mut atoms) = b.build();
         atoms.sort();
 
@@ -86,6 +86,55 @@ impl Display for Model {
     }
 }
 
+/// Public view of the prefilter model of a regex, see
+/// [`crate::Regexes::models`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ModelView {
+    /// Every haystack is a candidate, the regex is not prefiltered.
+    All,
+    /// No haystack is a candidate.
+    None,
+    /// Haystacks containing the atom are candidates. The atom is
+    /// ascii lowercase if the regex is prefiltered case-insensitively,
+    /// see [`crate::Builder::case_insensitive_prefilter`].
+    Atom(String),
+    /// Haystacks matching all the sub-models are candidates.
+    And(Vec<ModelView>),
+    /// Haystacks matching any of the sub-models are candidates.
+    Or(Vec<ModelView>),
+}
+
+impl ModelView {
+    /// All the atoms of the model, depth-first.
+    pub fn atoms(&self) -> Vec<&str> {
+        let mut atoms = Vec::new();
+        let mut stack = vec![self];
+        while let Some(m) = stack.pop() {
+            match m {
+                ModelView::All | ModelView::None => (),
+                ModelView::Atom(s) => atoms.push(s.as_str()),
+                ModelView::And(subs) | ModelView::Or(subs) => stack.extend(subs.iter().rev()),
+            }
+        }
+        atoms
+    }
+}
+
+impl From<&Model> for ModelView {
+    /// Converts the model, collapsing the combinations of a single
+    /// sub-model into it.
+    fn from(m: &Model) -> Self {
+        match m {
+            All(_) => ModelView::All,
+            None(_) => ModelView::None,
+            Atom(_, s) => ModelView::Atom(s.clone()),
+            And(_, subs) | Or(_, subs) if subs.len() == 1 => Self::from(&subs[0]),
+            And(_, subs) => ModelView::And(subs.iter().map(Self::from).collect()),
+            Or(_, subs) => ModelView::Or(subs.iter().map(Self::from).collect()),
+        }
+    }
+}
+
 /// Processing errors
 #[derive(Debug)]
 pub enum Error {
@@ -111,8 +160,34 @@ impl From<Utf8Error> for Error {
 }
 
 impl Model {
-    pub fn new(r: &Hir) -> Result<Self, Error> {
-        visit(r, InfoVisitor::default())
+    /// Builds the model of `r`, if `fold_case` the atoms are ascii
+    /// lowercased and only valid for a case-insensitive prefilter.
+    pub fn new(r: &Hir, fold_case: bool) -> Result<Self, Error> {
+        visit(r, InfoVisitor::new(fold_case))
+    }
+
+    /// Whether `r` matches letters case-insensitively (e.g. through
+    /// an inline `(?i)` flag), as a case-insensitive letter is
+    /// translated to the class of its case variants.
+    pub fn folds_case(r: &Hir) -> bool {
+        match r.kind() {
+            HirKind::Class(hir::Class::Unicode(c)) => c.ranges().first().is_some_and(|r| {
+                let mut folded =
+                    hir::ClassUnicode::new([hir::ClassUnicodeRange::new(r.start(), r.start())]);
+                folded.case_fold_simple();
+                r.start().is_ascii_alphabetic() && *c == folded
+            }),
+            HirKind::Class(hir::Class::Bytes(c)) => c.ranges().first().is_some_and(|r| {
+                let mut folded =
+                    hir::ClassBytes::new([hir::ClassBytesRange::new(r.start(), r.start())]);
+                folded.case_fold_simple();
+                r.start().is_ascii_alphabetic() && *c == folded
+            }),
+            HirKind::Repetition(r) => Self::folds_case(&r.sub),
+            HirKind::Capture(c) => Self::folds_case(&c.sub),
+            HirKind::Concat(hs) | HirKind::Alternation(hs) => hs.iter().any(Self::folds_case),
+            HirKind::Empty | HirKind::Literal(_) | HirKind::Look(_) => false,
+        }
     }
 
     pub fn unique_id(&self) -> usize {
@@ -126,6 +201,16 @@ impl Model {
         }
     }
 
+    /// Number of atoms in the model.
+    #[cfg(feature = "log")]
+    pub fn atoms(&self) -> usize {
+        match self {
+            All(_) | None(_) => 0,
+            Atom(_, _) => 1,
+            And(_, subs) | Or(_, subs) => subs.iter().map(Model::atoms).sum(),
+        }
+    }
+
     pub fn all() -> Self {
         All(Cell::new(usize::MAX))
     }
@@ -311,12 +396,22 @@ impl Info {
 struct InfoVisitor {
     stack: Vec<Info>,
     max_visits: usize,
+    fold_case: bool,
 }
-impl Default for InfoVisitor {
-    fn default() -> Self {
+impl InfoVisitor {
+    fn new(fold_case: bool) -> Self {
         Self {
             max_visits: 100_000,
             stack: Vec::new(),
+            fold_case,
+        }
+    }
+
+    fn atom(&self, s: &str) -> String {
+        if self.fold_case {
+            s.to_ascii_lowercase()
+        } else {
+            s.to_string()
         }
     }
 }
@@ -361,7 +456,7 @@ impl Visitor for InfoVisitor {
                     // I understand that's just a complicated way to
                     // build a singleton set of the payload?
                     self.stack.push(Info::Exact(
-                        [LengthThenLex(std::str::from_utf8(data)?.to_lowercase())].into(),
+                        [LengthThenLex(self.atom(std::str::from_utf8(data)?))].into(),
                     ));
                 }
             }
@@ -382,9 +477,8 @@ impl Visitor for InfoVisitor {
                     } else {
                         Info::Exact(
                             c.iter()
-                                .flat_map(|r| (r.start()..=r.end()))
-                                .map(char::to_lowercase)
-                                .map(String::from_iter)
+                                .flat_map(|r| r.start()..=r.end())
+                                .map(|c| self.atom(c.encode_utf8(&mut [0; 4])))
                                 .map(LengthThenLex)
                                 .collect(),
                         )
@@ -453,12 +547,7 @@ impl Visitor for InfoVisitor {
                     if !exacts.is_empty() {
                         matches.push(Model::or_strings(exacts));
                     }
-                    Info::Match(
-                        matches
-                            .into_iter()
-                            .map(From::from)
-                            .fold(Model::none(), Model::or),
-                    )
+                    Info::Match(matches.into_iter().fold(Model::none(), Model::or))
                 });
             }
             // and this one gets really painful, like above we need to
@@ -0,0 +1,80 @@
+/// Cheap first-stage screen for the prefilter: a bitset of the
+/// leading bigram of every atom, ascii-folded if the prefilter is
+/// case-insensitive.
+///
+/// If none of the haystack's bigrams is in the set then no atom can
+/// be present, and the (much more expensive) overlapping aho-corasick
+/// scan can be skipped entirely. The screen is only sound if every
+/// atom is at least two bytes long, otherwise it is not created.
+pub struct Bigrams {
+    bits: Box<[u64]>,
+    fold_case: bool,
+}
+
+const BITS: usize = 1 << 16;
+
+#[inline]
+fn bigram(a: u8, b: u8, fold_case: bool) -> usize {
+    // if aho-corasick is configured to be ascii case insensitive the
+    // screen has to fold in the same way
+    if fold_case {
+        usize::from(a.to_ascii_lowercase()) << 8 | usize::from(b.to_ascii_lowercase())
+    } else {
+        usize::from(a) << 8 | usize::from(b)
+    }
+}
+
+impl Bigrams {
+    pub fn new<S: AsRef<[u8]>>(atoms: &[S], fold_case: bool) -> Option<Self> {
+        let mut bits = vec![0u64; BITS / 64].into_boxed_slice();
+        for atom in atoms {
+            let &[a, b, ..] = atom.as_ref() else {
+                return None;
+            };
+            let bg = bigram(a, b, fold_case);
+            bits[bg / 64] |= 1 << (bg % 64);
+        }
+        Some(Self { bits, fold_case })
+    }
+
+    #[inline]
+    pub fn may_match(&self, haystack: &[u8]) -> bool {
+        haystack.windows(2).any(|w| {
+            let bg = bigram(w[0], w[1], self.fold_case);
+            self.bits[bg / 64] & (1 << (bg % 64)) != 0
+        })
+    }
+
+    /// Heap memory used by the bitset, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of_val(&*self.bits)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn short_atoms_disable_screen() {
+        assert!(Bigrams::new(&["foo", "b"], true).is_none());
+        assert!(Bigrams::new(&["foo", ""], true).is_none());
+        assert!(Bigrams::new::<&str>(&[], true).is_some());
+    }
+
+    #[test]
+    fn screen() {
+        let b = Bigrams::new(&["foo", "bar"], true).unwrap();
+        assert!(b.may_match(b"xxfoxx"));
+        assert!(b.may_match(b"BAZ"));
+        assert!(!b.may_match(b"oof"));
+        assert!(!b.may_match(b"f"));
+        assert!(!b.may_match(b""));
+
+        let b = Bigrams::new(&["foo", "Bar"], false).unwrap();
+        assert!(b.may_match(b"xxfoxx"));
+        assert!(b.may_match(b"Baz"));
+        assert!(!b.may_match(b"BAZ"));
+        assert!(!b.may_match(b"FOO"));
+    }
+}
@@ -0,0 +1,234 @@
+//! Property-based check of the prefilter's soundness: any regex which
+//! matches a haystack must be nominated as a candidate for it, the
+//! prefilter may have false positives but never false negatives.
+//!
+//! Patterns are generated from a small regex AST, which is also used
+//! to generate strings matching them so that matches are actually
+//! exercised, and embedded in random noise. Every case is derived from
+//! its seed, which is reported on failure. The number of cases can be
+//! raised through `REGEX_FILTERED_PROPTEST_CASES`.
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use regex_filtered::{Builder, Options};
+
+/// Small alphabet so atoms collide and overlap a lot, with some
+/// non-ascii and case-folding troublemakers (`K` folds to the kelvin
+/// sign, `ſ` to `s`).
+const ALPHABET: &[char] = &[
+    'a', 'b', 'c', 'k', 's', 'A', 'K', 'S', '1', ' ', '/', 'é', 'ſ',
+];
+
+#[derive(Debug)]
+enum Node {
+    Literal(String),
+    Class {
+        chars: Vec<char>,
+        negated: bool,
+    },
+    Any,
+    Digit,
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+fn pick(rng: &mut SmallRng) -> char {
+    ALPHABET[rng.random_range(0..ALPHABET.len())]
+}
+
+fn generate(rng: &mut SmallRng, depth: u32) -> Node {
+    let leaf = depth == 0 || rng.random_bool(0.4);
+    match rng.random_range(0..if leaf { 4 } else { 7 }) {
+        0 | 1 => Node::Literal((0..rng.random_range(1..5)).map(|_| pick(rng)).collect()),
+        2 => match rng.random_range(0..3) {
+            0 => Node::Any,
+            1 => Node::Digit,
+            _ => Node::Class {
+                chars: (0..rng.random_range(1..4)).map(|_| pick(rng)).collect(),
+                negated: rng.random_bool(0.2),
+            },
+        },
+        3 => Node::Literal(String::new()),
+        4 => Node::Concat(
+            (0..rng.random_range(2..5))
+                .map(|_| generate(rng, depth - 1))
+                .collect(),
+        ),
+        5 => Node::Alternation(
+            (0..rng.random_range(2..4))
+                .map(|_| generate(rng, depth - 1))
+                .collect(),
+        ),
+        _ => {
+            let (min, max) = match rng.random_range(0..5) {
+                0 => (0, Some(1)),
+                1 => (0, None),
+                2 => (1, None),
+                3 => (0, Some(rng.random_range(1..4))),
+                _ => {
+                    let min = rng.random_range(1..3);
+                    (min, Some(min + rng.random_range(0..2)))
+                }
+            };
+            Node::Repeat {
+                node: Box::new(generate(rng, depth - 1)),
+                min,
+                max,
+            }
+        }
+    }
+}
+
+fn escape(c: char, out: &mut String) {
+    if regex_syntax::is_meta_character(c) || c == ' ' {
+        out.push('\\');
+    }
+    out.push(c);
+}
+
+fn render(node: &Node, out: &mut String) {
+    match node {
+        Node::Literal(s) => s.chars().for_each(|c| escape(c, out)),
+        Node::Class { chars, negated } => {
+            out.push('[');
+            if *negated {
+                out.push('^');
+            }
+            chars.iter().for_each(|&c| escape(c, out));
+            out.push(']');
+        }
+        Node::Any => out.push('.'),
+        Node::Digit => out.push_str(r"\d"),
+        Node::Concat(nodes) => {
+            out.push_str("(?:");
+            nodes.iter().for_each(|n| render(n, out));
+            out.push(')');
+        }
+        Node::Alternation(nodes) => {
+            out.push_str("(?:");
+            for (i, n) in nodes.iter().enumerate() {
+                if i > 0 {
+                    out.push('|');
+                }
+                render(n, out);
+            }
+            out.push(')');
+        }
+        Node::Repeat { node, min, max } => {
+            out.push_str("(?:");
+            render(node, out);
+            out.push(')');
+            match (min, max) {
+                (0, Some(1)) => out.push('?'),
+                (0, None) => out.push('*'),
+                (1, None) => out.push('+'),
+                (min, None) => out.push_str(&format!("{{{min},}}")),
+                (min, Some(max)) => out.push_str(&format!("{{{min},{max}}}")),
+            }
+        }
+    }
+}
+
+/// Generates a string matched by `node` (modulo case folding, which
+/// can only make more strings match).
+fn sample(rng: &mut SmallRng, node: &Node, out: &mut String) {
+    match node {
+        Node::Literal(s) => out.push_str(s),
+        Node::Class { chars, negated } => {
+            let c = if *negated {
+                std::iter::repeat_with(|| pick(rng))
+                    .find(|c| !chars.contains(c))
+                    .unwrap()
+            } else {
+                chars[rng.random_range(0..chars.len())]
+            };
+            out.push(c);
+        }
+        Node::Any => out.push(pick(rng)),
+        Node::Digit => out.push(char::from(b'0' + rng.random_range(0..10))),
+        Node::Concat(nodes) => nodes.iter().for_each(|n| sample(rng, n, out)),
+        Node::Alternation(nodes) => {
+            let n = rng.random_range(0..nodes.len());
+            sample(rng, &nodes[n], out);
+        }
+        Node::Repeat { node, min, max } => {
+            for _ in 0..rng.random_range(*min..=max.unwrap_or(min + 3)) {
+                sample(rng, node, out);
+            }
+        }
+    }
+}
+
+fn noise(rng: &mut SmallRng, out: &mut String) {
+    for _ in 0..rng.random_range(0..6) {
+        let c = pick(rng);
+        out.push(if rng.random_bool(0.3) {
+            c.to_uppercase().next().unwrap()
+        } else {
+            c
+        });
+    }
+}
+
+fn check(seed: u64) {
+    let mut rng = SmallRng::seed_from_u64(seed);
+
+    let atom_len = rng.random_range(1..5);
+    let mut builder = Builder::new_atom_len(atom_len).prescreen(rng.random_bool(0.5));
+    let mut nodes = Vec::new();
+    let mut flags = Vec::new();
+    for _ in 0..rng.random_range(1..8) {
+        let node = generate(&mut rng, 3);
+        let mut pattern = String::new();
+        render(&node, &mut pattern);
+        let f = match rng.random_range(0..4) {
+            0 => "i",
+            1 => "s",
+            2 => "is",
+            _ => "",
+        };
+        let Ok(b) = builder.push_opt(&pattern, &Options::from_flags(f).unwrap()) else {
+            // too large or some other refusal, not the point here
+            return;
+        };
+        builder = b;
+        nodes.push(node);
+        flags.push(f);
+    }
+    let regexes = builder.build().unwrap();
+
+    for _ in 0..20 {
+        let mut haystack = String::new();
+        noise(&mut rng, &mut haystack);
+        if rng.random_bool(0.8) {
+            let node = &nodes[rng.random_range(0..nodes.len())];
+            sample(&mut rng, node, &mut haystack);
+            noise(&mut rng, &mut haystack);
+        }
+
+        let candidates = regexes.candidates(&haystack).collect::<Vec<_>>();
+        for (idx, re) in regexes.regexes().iter().enumerate() {
+            assert!(
+                !re.is_match(&haystack) || candidates.contains(&idx),
+                "seed {seed} (atom length {atom_len}): /{}/{} matches {haystack:?} but was not nominated (candidates {candidates:?})",
+                re.as_str(),
+                flags[idx],
+            );
+        }
+    }
+}
+
+#[test]
+fn prefilter_soundness() {
+    let cases = std::env::var("REGEX_FILTERED_PROPTEST_CASES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(500);
+    for seed in 0..cases {
+        check(seed);
+    }
+}
@@ -0,0 +1,283 @@
+#![deny(unsafe_code)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use ua_parser::{device, os, user_agent, Regexes};
+
+/// Error returned by the code generator.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read the input or write the output.
+    Io(std::io::Error),
+    /// Failed to deserialize the input.
+    Yaml(serde_yaml::Error),
+    /// The input data is invalid, and could not be built into an
+    /// [`ua_parser::Extractor`].
+    Invalid(ua_parser::Error),
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Yaml(e) => Some(e),
+            Error::Invalid(e) => Some(e),
+        }
+    }
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+impl From<serde_yaml::Error> for Error {
+    fn from(value: serde_yaml::Error) -> Self {
+        Self::Yaml(value)
+    }
+}
+impl From<ua_parser::Error> for Error {
+    fn from(value: ua_parser::Error) -> Self {
+        Self::Invalid(value)
+    }
+}
+
+/// Reads the `input` regexes file, and writes the corresponding Rust
+/// source to `output`. Only writes `output` if its content changed,
+/// to avoid needlessly triggering recompilations.
+pub fn generate_file(input: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<(), Error> {
+    let yaml = std::fs::read_to_string(input)?;
+    let code = generate(&yaml)?;
+    let output = output.as_ref();
+    if std::fs::read_to_string(output).ok().as_deref() != Some(&*code) {
+        std::fs::write(output, code)?;
+    }
+    Ok(())
+}
+
+/// Converts the content of a regexes file to Rust source, after
+/// having checked that it can be built into an
+/// [`ua_parser::Extractor`].
+pub fn generate(yaml: &str) -> Result<String, Error> {
+    let regexes = serde_yaml::from_str::<Regexes>(yaml)?;
+    let mut code = emit(&regexes);
+    let _ = writeln!(
+        code,
+        "\n/// Digest of the source data file, see `ua_parser::DataInfo::digest`.\npub const DATA_DIGEST: u64 = {:#x};",
+        ua_parser::DataInfo::digest(yaml.as_bytes())
+    );
+    ua_parser::Extractor::try_from(regexes)?;
+    Ok(code)
+}
+
+fn opt(out: &mut String, field: &str, value: &Option<Cow<'_, str>>) {
+    match value {
+        Some(v) => writeln!(
+            out,
+            "        {field}: Some(::std::borrow::Cow::Borrowed({:?})),",
+            &**v
+        ),
+        None => writeln!(out, "        {field}: None,"),
+    }
+    .expect("writing to a string can not fail");
+}
+
+fn flag(out: &mut String, flag: &Option<device::Flags>) {
+    let Some(flags) = flag else {
+        out.push_str("        regex_flag: None,\n");
+        return;
+    };
+    let flags = [
+        (device::Flags::IGNORE_CASE, "IGNORE_CASE"),
+        (device::Flags::DOT_ALL, "DOT_ALL"),
+        (device::Flags::MULTI_LINE, "MULTI_LINE"),
+        (device::Flags::VERBOSE, "VERBOSE"),
+        (device::Flags::CRLF, "CRLF"),
+    ]
+    .into_iter()
+    .filter(|&(f, _)| flags.contains(f))
+    .map(|(_, name)| format!("::ua_parser::device::Flags::{name}"))
+    .reduce(|acc, f| format!("{acc}.union({f})"))
+    .unwrap_or_else(|| "::ua_parser::device::Flags::empty()".into());
+    let _ = writeln!(out, "        regex_flag: Some({flags}),");
+}
+
+fn emit(regexes: &Regexes) -> String {
+    let mut out = String::new();
+    out.push_str("// @generated by ua-parser-codegen, do not edit\n\n");
+
+    out.push_str(
+        "pub static USER_AGENT_PARSERS: &[::ua_parser::user_agent::Parser<'static>] = &[\n",
+    );
+    for user_agent::Parser {
+        regex,
+        regex_flag,
+        family_replacement,
+        v1_replacement,
+        v2_replacement,
+        v3_replacement,
+        v4_replacement,
+    } in &regexes.user_agent_parsers
+    {
+        out.push_str("    ::ua_parser::user_agent::Parser {\n");
+        let _ = writeln!(
+            out,
+            "        regex: ::std::borrow::Cow::Borrowed({:?}),",
+            &**regex
+        );
+        flag(&mut out, regex_flag);
+        opt(&mut out, "family_replacement", family_replacement);
+        opt(&mut out, "v1_replacement", v1_replacement);
+        opt(&mut out, "v2_replacement", v2_replacement);
+        opt(&mut out, "v3_replacement", v3_replacement);
+        opt(&mut out, "v4_replacement", v4_replacement);
+        out.push_str("    },\n");
+    }
+    out.push_str("];\n\n");
+
+    out.push_str("pub static OS_PARSERS: &[::ua_parser::os::Parser<'static>] = &[\n");
+    for os::Parser {
+        regex,
+        regex_flag,
+        os_replacement,
+        os_v1_replacement,
+        os_v2_replacement,
+        os_v3_replacement,
+        os_v4_replacement,
+    } in &regexes.os_parsers
+    {
+        out.push_str("    ::ua_parser::os::Parser {\n");
+        let _ = writeln!(
+            out,
+            "        regex: ::std::borrow::Cow::Borrowed({:?}),",
+            &**regex
+        );
+        flag(&mut out, regex_flag);
+        opt(&mut out, "os_replacement", os_replacement);
+        opt(&mut out, "os_v1_replacement", os_v1_replacement);
+        opt(&mut out, "os_v2_replacement", os_v2_replacement);
+        opt(&mut out, "os_v3_replacement", os_v3_replacement);
+        opt(&mut out, "os_v4_replacement", os_v4_replacement);
+        out.push_str("    },\n");
+    }
+    out.push_str("];\n\n");
+
+    // maps can't be built in statics, the extension fields are
+    // emitted separately and inserted by `regexes()`
+    let mut extras = Vec::new();
+    out.push_str("pub static DEVICE_PARSERS: &[::ua_parser::device::Parser<'static>] = &[\n");
+    for (
+        idx,
+        device::Parser {
+            regex,
+            regex_flag,
+            device_replacement,
+            brand_replacement,
+            model_replacement,
+            extra,
+        },
+    ) in regexes.device_parsers.iter().enumerate()
+    {
+        extras.extend(extra.iter().map(|(k, v)| (idx, k, v)));
+        out.push_str("    ::ua_parser::device::Parser {\n");
+        let _ = writeln!(
+            out,
+            "        regex: ::std::borrow::Cow::Borrowed({:?}),",
+            &**regex
+        );
+        flag(&mut out, regex_flag);
+        opt(&mut out, "device_replacement", device_replacement);
+        opt(&mut out, "brand_replacement", brand_replacement);
+        opt(&mut out, "model_replacement", model_replacement);
+        out.push_str("        extra: ::std::collections::BTreeMap::new(),\n");
+        out.push_str("    },\n");
+    }
+    out.push_str("];\n\n");
+
+    out.push_str("pub static DEVICE_EXTRA: &[(usize, &str, &str)] = &[\n");
+    for (idx, k, v) in extras {
+        let _ = writeln!(out, "    ({idx}, {k:?}, {v:?}),");
+    }
+    out.push_str("];\n\n");
+
+    out.push_str(REGEXES_FN);
+    out
+}
+
+/// Assembles the statics into a [`Regexes`], cloning the parsers
+/// only copies the references to the statics' strings.
+const REGEXES_FN: &str = r#"pub fn regexes() -> ::ua_parser::Regexes<'static> {
+    let mut device_parsers = DEVICE_PARSERS.to_vec();
+    for &(idx, key, value) in DEVICE_EXTRA {
+        device_parsers[idx].extra.insert(key.into(), value.into());
+    }
+    ::ua_parser::Regexes {
+        user_agent_parsers: USER_AGENT_PARSERS.to_vec(),
+        os_parsers: OS_PARSERS.to_vec(),
+        device_parsers,
+    }
+}
+"#;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const YAML: &str = r#"
+user_agent_parsers:
+  - regex: '(Firefox)/(\d+)\.(\d+)'
+  - regex: 'Fennec/(\d+)'
+    family_replacement: 'Firefox "Mobile"'
+os_parsers:
+  - regex: 'Windows NT (\d+)\.(\d+)'
+    os_replacement: 'Windows'
+device_parsers:
+  - regex: '; *(Pixel \w+)'
+    regex_flag: 'i'
+    brand_replacement: 'Google'
+  - regex: 'Pixel (.+)'
+    regex_flag: 'is'
+    device_type: 'smartphone'
+"#;
+
+    #[test]
+    fn generate_literals() {
+        let code = generate(YAML).unwrap();
+
+        assert!(
+            code.contains(r#"regex: ::std::borrow::Cow::Borrowed("(Firefox)/(\\d+)\\.(\\d+)"),"#)
+        );
+        assert!(code.contains(
+            r#"family_replacement: Some(::std::borrow::Cow::Borrowed("Firefox \"Mobile\"")),"#
+        ));
+        assert!(code.contains("regex_flag: Some(::ua_parser::device::Flags::IGNORE_CASE),"));
+        assert!(code.contains(
+            "regex_flag: Some(::ua_parser::device::Flags::IGNORE_CASE.union(::ua_parser::device::Flags::DOT_ALL)),"
+        ));
+        assert_eq!(code.matches("::ua_parser::user_agent::Parser {").count(), 2);
+        assert!(code.contains(r#"    (1, "device_type", "smartphone"),"#));
+        assert!(code.contains(&format!(
+            "pub const DATA_DIGEST: u64 = {:#x};",
+            ua_parser::DataInfo::digest(YAML.as_bytes())
+        )));
+    }
+
+    #[test]
+    fn validate() {
+        assert!(matches!(
+            generate("user_agent_parsers: [{regex: '('}]\nos_parsers: []\ndevice_parsers: []"),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            generate("user_agent_parsers: 1"),
+            Err(Error::Yaml(_))
+        ));
+    }
+}
@@ -0,0 +1,39 @@
+#![deny(unsafe_code)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+
+use proc_macro::TokenStream;
+use std::path::Path;
+
+/// Embeds a regexes file at compile time, expanding to a
+/// `ua_parser::Regexes<'static>`.
+///
+/// The path is relative to the crate's root (the directory containing
+/// its `Cargo.toml`). The file is parsed and validated during the
+/// expansion, so invalid YAML or regexes are compile errors.
+#[proc_macro]
+pub fn include_regexes(input: TokenStream) -> TokenStream {
+    let path = syn::parse_macro_input!(input as syn::LitStr);
+    match expand(&path.value()) {
+        Ok(code) => code,
+        Err(e) => syn::Error::new(path.span(), e).to_compile_error().into(),
+    }
+}
+
+fn expand(path: &str) -> Result<TokenStream, String> {
+    let root = std::env::var("CARGO_MANIFEST_DIR").map_err(|e| e.to_string())?;
+    let path = Path::new(&root).join(path);
+    let yaml = std::fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let code = ua_parser_codegen::generate(&yaml)
+        .map_err(|e| format!("invalid regexes file {}: {e}", path.display()))?;
+
+    // the `include_bytes` makes cargo track the regexes file, so the
+    // expansion is rerun when it changes
+    format!(
+        "{{ const _: &[u8] = include_bytes!({:?}); {code} regexes() }}",
+        path.display().to_string(),
+    )
+    .parse()
+    .map_err(|e| format!("{e}"))
+}
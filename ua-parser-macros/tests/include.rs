@@ -0,0 +1,23 @@
+use ua_parser::Extractor;
+use ua_parser_macros::include_regexes;
+
+#[test]
+fn include() {
+    let regexes = include_regexes!("tests/regexes.yaml");
+    assert_eq!(regexes.user_agent_parsers.len(), 2);
+    assert_eq!(regexes.os_parsers.len(), 1);
+    assert_eq!(regexes.device_parsers.len(), 1);
+    assert_eq!(
+        regexes.device_parsers[0]
+            .extra
+            .get("device_type")
+            .map(|s| &**s),
+        Some("smartphone")
+    );
+
+    let extractor = Extractor::try_from(regexes).unwrap();
+    let c = extractor.extract("Mozilla/5.0 (Windows NT 10.0; PIXEL 8) Fennec/120");
+    assert_eq!(c.family(), "Firefox Mobile");
+    assert_eq!(c.os_family(), "Windows");
+    assert_eq!(c.device.unwrap().brand, Some("Google".into()));
+}
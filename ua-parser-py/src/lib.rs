@@ -26,8 +26,65 @@
 /// global Parser object, unless *that* gets renamed to Extractor on
 /// the python side, or something.
 use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
 use pyo3::{exceptions::PyValueError, types::PyString};
-use std::borrow::Cow::Owned;
+use std::borrow::Cow::{self, Owned};
+
+/// Accumulates the parsers an extractor is built from, to report
+/// their number and digest (see `ua_parser::DataInfo::digest`).
+#[derive(Default)]
+struct Provenance {
+    parsers: usize,
+    data: Vec<u8>,
+}
+impl Provenance {
+    fn push<'a>(&mut self, fields: impl IntoIterator<Item = Option<&'a str>>) {
+        self.parsers += 1;
+        for f in fields {
+            match f {
+                Some(s) => {
+                    self.data.extend(s.as_bytes());
+                    self.data.push(0);
+                }
+                None => self.data.push(1),
+            }
+        }
+    }
+
+    fn digest(&self) -> u64 {
+        ua_parser::DataInfo::digest(&self.data)
+    }
+}
+
+/// Field of a result object, the Python string is only created on
+/// first access: most callers only read one or two fields of a
+/// result.
+struct LazyStr {
+    value: String,
+    py: GILOnceCell<Py<PyString>>,
+}
+impl LazyStr {
+    fn new(value: Cow<'_, str>) -> Self {
+        Self {
+            value: value.into_owned(),
+            py: GILOnceCell::new(),
+        }
+    }
+
+    fn get(&self, py: Python<'_>) -> Py<PyString> {
+        self.py
+            .get_or_init(py, || PyString::new(py, &self.value).unbind())
+            .clone_ref(py)
+    }
+}
+
+fn lazy(value: Option<Cow<'_, str>>) -> Option<LazyStr> {
+    value.map(LazyStr::new)
+}
+
+fn get(py: Python<'_>, field: &Option<LazyStr>) -> Option<Py<PyString>> {
+    field.as_ref().map(|f| f.get(py))
+}
 
 type UAParser = (
     String,
@@ -38,30 +95,69 @@ type UAParser = (
     Option<String>,
 );
 #[pyclass(frozen)]
-struct UserAgentExtractor(ua_parser::user_agent::Extractor<'static>);
-#[pyclass(frozen)]
-struct UserAgent {
-    #[pyo3(get)]
-    family: Py<PyString>,
+struct UserAgentExtractor {
+    extractor: ua_parser::user_agent::Extractor<'static>,
+    /// Number of parsers the extractor was built from.
     #[pyo3(get)]
-    major: Option<Py<PyString>>,
+    parser_count: usize,
     #[pyo3(get)]
-    minor: Option<Py<PyString>>,
-    #[pyo3(get)]
-    patch: Option<Py<PyString>>,
-    #[pyo3(get)]
-    patch_minor: Option<Py<PyString>>,
+    digest: u64,
+    /// Whether to return the uap-core defaults rather than `None`.
+    defaults: bool,
+}
+#[pyclass(frozen)]
+struct UserAgent {
+    family: LazyStr,
+    major: Option<LazyStr>,
+    minor: Option<LazyStr>,
+    patch: Option<LazyStr>,
+    patch_minor: Option<LazyStr>,
+}
+#[pymethods]
+impl UserAgent {
+    #[getter]
+    fn family(&self, py: Python<'_>) -> Py<PyString> {
+        self.family.get(py)
+    }
+    #[getter]
+    fn major(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.major)
+    }
+    #[getter]
+    fn minor(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.minor)
+    }
+    #[getter]
+    fn patch(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.patch)
+    }
+    #[getter]
+    fn patch_minor(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.patch_minor)
+    }
 }
 #[pymethods]
 impl UserAgentExtractor {
     #[new]
-    fn new(it: &Bound<PyAny>) -> PyResult<Self> {
+    #[pyo3(signature = (it, /, *, defaults = false))]
+    fn new(it: &Bound<PyAny>, defaults: bool) -> PyResult<Self> {
         use ua_parser::user_agent::{Builder, Parser};
-        it.try_iter()?
+        let mut provenance = Provenance::default();
+        let extractor = it
+            .try_iter()?
             .try_fold(Builder::new(), |s, p| {
                 let p: UAParser = p?.extract()?;
+                provenance.push([
+                    Some(&*p.0),
+                    p.1.as_deref(),
+                    p.2.as_deref(),
+                    p.3.as_deref(),
+                    p.4.as_deref(),
+                    p.5.as_deref(),
+                ]);
                 s.push(Parser {
                     regex: Owned(p.0),
+                    regex_flag: None,
                     family_replacement: p.1.map(Owned),
                     v1_replacement: p.2.map(Owned),
                     v2_replacement: p.3.map(Owned),
@@ -71,16 +167,26 @@ impl UserAgentExtractor {
                 .map_err(|e| PyValueError::new_err(e.to_string()))
             })?
             .build()
-            .map_err(|e| PyValueError::new_err(e.to_string()))
-            .map(Self)
-    }
-    fn extract(&self, py: Python<'_>, s: &str) -> PyResult<Option<UserAgent>> {
-        Ok(self.0.extract(s).map(|v| UserAgent {
-            family: PyString::new(py, &v.family).unbind(),
-            major: v.major.map(|s| PyString::new(py, s).unbind()),
-            minor: v.minor.map(|s| PyString::new(py, s).unbind()),
-            patch: v.patch.map(|s| PyString::new(py, s).unbind()),
-            patch_minor: v.patch_minor.map(|s| PyString::new(py, s).unbind()),
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self {
+            extractor,
+            parser_count: provenance.parsers,
+            digest: provenance.digest(),
+            defaults,
+        })
+    }
+    fn extract(&self, s: &str) -> PyResult<Option<UserAgent>> {
+        let v = if self.defaults {
+            Some(self.extractor.extract_or_default(s))
+        } else {
+            self.extractor.extract(s)
+        };
+        Ok(v.map(|v| UserAgent {
+            family: LazyStr::new(v.family),
+            major: lazy(v.major),
+            minor: lazy(v.minor),
+            patch: lazy(v.patch),
+            patch_minor: lazy(v.patch_minor),
         }))
     }
 }
@@ -94,30 +200,69 @@ type OSParser = (
     Option<String>,
 );
 #[pyclass(frozen)]
-struct OSExtractor(ua_parser::os::Extractor<'static>);
-#[pyclass(frozen)]
-struct OS {
+struct OSExtractor {
+    extractor: ua_parser::os::Extractor<'static>,
+    /// Number of parsers the extractor was built from.
     #[pyo3(get)]
-    family: Py<PyString>,
+    parser_count: usize,
     #[pyo3(get)]
-    major: Option<Py<PyString>>,
-    #[pyo3(get)]
-    minor: Option<Py<PyString>>,
-    #[pyo3(get)]
-    patch: Option<Py<PyString>>,
-    #[pyo3(get)]
-    patch_minor: Option<Py<PyString>>,
+    digest: u64,
+    /// Whether to return the uap-core defaults rather than `None`.
+    defaults: bool,
+}
+#[pyclass(frozen)]
+struct OS {
+    family: LazyStr,
+    major: Option<LazyStr>,
+    minor: Option<LazyStr>,
+    patch: Option<LazyStr>,
+    patch_minor: Option<LazyStr>,
+}
+#[pymethods]
+impl OS {
+    #[getter]
+    fn family(&self, py: Python<'_>) -> Py<PyString> {
+        self.family.get(py)
+    }
+    #[getter]
+    fn major(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.major)
+    }
+    #[getter]
+    fn minor(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.minor)
+    }
+    #[getter]
+    fn patch(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.patch)
+    }
+    #[getter]
+    fn patch_minor(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.patch_minor)
+    }
 }
 #[pymethods]
 impl OSExtractor {
     #[new]
-    fn new(it: &Bound<PyAny>) -> PyResult<Self> {
+    #[pyo3(signature = (it, /, *, defaults = false))]
+    fn new(it: &Bound<PyAny>, defaults: bool) -> PyResult<Self> {
         use ua_parser::os::{Builder, Parser};
-        it.try_iter()?
+        let mut provenance = Provenance::default();
+        let extractor = it
+            .try_iter()?
             .try_fold(Builder::new(), |s, p| {
                 let p: OSParser = p?.extract()?;
+                provenance.push([
+                    Some(&*p.0),
+                    p.1.as_deref(),
+                    p.2.as_deref(),
+                    p.3.as_deref(),
+                    p.4.as_deref(),
+                    p.5.as_deref(),
+                ]);
                 s.push(Parser {
                     regex: Owned(p.0),
+                    regex_flag: None,
                     os_replacement: p.1.map(Owned),
                     os_v1_replacement: p.2.map(Owned),
                     os_v2_replacement: p.3.map(Owned),
@@ -127,16 +272,26 @@ impl OSExtractor {
                 .map_err(|e| PyValueError::new_err(e.to_string()))
             })?
             .build()
-            .map_err(|e| PyValueError::new_err(e.to_string()))
-            .map(Self)
-    }
-    fn extract(&self, py: Python<'_>, s: &str) -> PyResult<Option<OS>> {
-        Ok(self.0.extract(s).map(|v| OS {
-            family: PyString::new(py, &v.os).unbind(),
-            major: v.major.map(|s| PyString::new(py, &s).unbind()),
-            minor: v.minor.map(|s| PyString::new(py, &s).unbind()),
-            patch: v.patch.map(|s| PyString::new(py, &s).unbind()),
-            patch_minor: v.patch_minor.map(|s| PyString::new(py, &s).unbind()),
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self {
+            extractor,
+            parser_count: provenance.parsers,
+            digest: provenance.digest(),
+            defaults,
+        })
+    }
+    fn extract(&self, s: &str) -> PyResult<Option<OS>> {
+        let v = if self.defaults {
+            Some(self.extractor.extract_or_default(s))
+        } else {
+            self.extractor.extract(s)
+        };
+        Ok(v.map(|v| OS {
+            family: LazyStr::new(v.os),
+            major: lazy(v.major),
+            minor: lazy(v.minor),
+            patch: lazy(v.patch),
+            patch_minor: lazy(v.patch_minor),
         }))
     }
 }
@@ -149,52 +304,97 @@ type DeviceParser = (
     Option<String>,
 );
 #[pyclass(frozen)]
-struct DeviceExtractor(ua_parser::device::Extractor<'static>);
-#[pyclass(frozen)]
-struct Device {
-    #[pyo3(get)]
-    family: Py<PyString>,
+struct DeviceExtractor {
+    extractor: ua_parser::device::Extractor<'static>,
+    /// Number of parsers the extractor was built from.
     #[pyo3(get)]
-    brand: Option<Py<PyString>>,
+    parser_count: usize,
     #[pyo3(get)]
-    model: Option<Py<PyString>>,
+    digest: u64,
+    /// Whether to return the uap-core defaults rather than `None`.
+    defaults: bool,
+}
+#[pyclass(frozen)]
+struct Device {
+    family: LazyStr,
+    brand: Option<LazyStr>,
+    model: Option<LazyStr>,
+}
+#[pymethods]
+impl Device {
+    #[getter]
+    fn family(&self, py: Python<'_>) -> Py<PyString> {
+        self.family.get(py)
+    }
+    #[getter]
+    fn brand(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.brand)
+    }
+    #[getter]
+    fn model(&self, py: Python<'_>) -> Option<Py<PyString>> {
+        get(py, &self.model)
+    }
 }
 #[pymethods]
 impl DeviceExtractor {
     #[new]
-    fn new(it: &Bound<PyAny>) -> PyResult<Self> {
-        use ua_parser::device::{Builder, Flag, Parser};
-        it.try_iter()?
+    #[pyo3(signature = (it, /, *, defaults = false))]
+    fn new(it: &Bound<PyAny>, defaults: bool) -> PyResult<Self> {
+        use ua_parser::device::{Builder, Flags, Parser};
+        let mut provenance = Provenance::default();
+        let extractor = it
+            .try_iter()?
             .try_fold(Builder::new(), |s, p| {
                 let p: DeviceParser = p?.extract()?;
+                provenance.push([
+                    Some(&*p.0),
+                    p.1.as_deref(),
+                    p.2.as_deref(),
+                    p.3.as_deref(),
+                    p.4.as_deref(),
+                ]);
                 s.push(Parser {
                     regex: Owned(p.0),
-                    regex_flag: if p.1.as_deref() == Some("i") {
-                        Some(Flag::IgnoreCase)
-                    } else {
-                        None
-                    },
+                    regex_flag: p
+                        .1
+                        .as_deref()
+                        .map(str::parse::<Flags>)
+                        .transpose()
+                        .map_err(|e| PyValueError::new_err(e.to_string()))?,
                     device_replacement: p.2.map(Owned),
                     brand_replacement: p.3.map(Owned),
                     model_replacement: p.4.map(Owned),
+                    extra: Default::default(),
                 })
                 .map_err(|e| PyValueError::new_err(e.to_string()))
             })?
             .build()
-            .map_err(|e| PyValueError::new_err(e.to_string()))
-            .map(Self)
-    }
-    fn extract(&self, py: Python<'_>, s: &str) -> PyResult<Option<Device>> {
-        Ok(self.0.extract(s).map(|v| Device {
-            family: PyString::new(py, &v.device).unbind(),
-            brand: v.brand.map(|s| PyString::new(py, &s).unbind()),
-            model: v.model.map(|s| PyString::new(py, &s).unbind()),
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self {
+            extractor,
+            parser_count: provenance.parsers,
+            digest: provenance.digest(),
+            defaults,
+        })
+    }
+    fn extract(&self, s: &str) -> PyResult<Option<Device>> {
+        let v = if self.defaults {
+            Some(self.extractor.extract_or_default(s))
+        } else {
+            self.extractor.extract(s)
+        };
+        Ok(v.map(|v| Device {
+            family: LazyStr::new(v.device),
+            brand: lazy(v.brand),
+            model: lazy(v.model),
         }))
     }
 }
 
 #[pymodule]
 fn ua_parser_rs(m: &Bound<PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("ua_parser_version", ua_parser::VERSION)?;
     m.add_class::<UserAgentExtractor>()?;
     m.add_class::<OSExtractor>()?;
     m.add_class::<DeviceExtractor>()?;
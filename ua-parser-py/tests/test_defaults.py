@@ -0,0 +1,18 @@
+import ua_parser_rs
+
+
+def test_defaults() -> None:
+    parsers = [(r"(Firefox)/(\d+)", None, None, None, None, None)]
+    assert ua_parser_rs.UserAgentExtractor(parsers).extract("curl/8.0") is None
+
+    ua = ua_parser_rs.UserAgentExtractor(parsers, defaults=True).extract("curl/8.0")
+    assert ua is not None
+    assert (ua.family, ua.major, ua.minor) == ("Other", None, None)
+    ua = ua_parser_rs.UserAgentExtractor(parsers, defaults=True).extract("Firefox/121")
+    assert ua is not None
+    assert (ua.family, ua.major) == ("Firefox", "121")
+
+    os = ua_parser_rs.OSExtractor([], defaults=True).extract("curl/8.0")
+    assert os is not None and os.family == "Other"
+    device = ua_parser_rs.DeviceExtractor([], defaults=True).extract("curl/8.0")
+    assert device is not None and (device.family, device.brand) == ("Other", None)
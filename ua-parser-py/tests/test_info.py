@@ -0,0 +1,30 @@
+import ua_parser_rs
+
+
+def test_versions() -> None:
+    assert ua_parser_rs.__version__
+    assert ua_parser_rs.ua_parser_version
+
+
+def test_provenance() -> None:
+    parsers = [
+        (r"(Firefox)/(\d+)", None, None, None, None, None),
+        (r"(Chrome)/(\d+)", None, "1", None, None, None),
+    ]
+    e = ua_parser_rs.UserAgentExtractor(parsers)
+    assert e.parser_count == 2
+    assert e.digest == ua_parser_rs.UserAgentExtractor(iter(parsers)).digest
+    assert e.digest != ua_parser_rs.UserAgentExtractor(parsers[:1]).digest
+
+    assert ua_parser_rs.DeviceExtractor([]).parser_count == 0
+
+
+def test_lazy_fields() -> None:
+    e = ua_parser_rs.UserAgentExtractor(
+        [(r"(Firefox)/(\d+)(?:\.(\d+))?", None, None, None, None, None)]
+    )
+    r = e.extract("Firefox/121")
+    assert r is not None
+    assert (r.family, r.major, r.minor) == ("Firefox", "121", None)
+    # the string is created once, then reused
+    assert r.family is r.family
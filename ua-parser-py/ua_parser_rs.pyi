@@ -1,6 +1,9 @@
 from collections.abc import Iterable
 from typing import Literal, Protocol
 
+__version__: str
+ua_parser_version: str
+
 UAParser = tuple[
     str,
     str | None,
@@ -18,7 +21,11 @@ class UserAgent(Protocol):
     patch_minor: str | None
 
 class UserAgentExtractor:
-    def __init__(self, it: Iterable[UAParser], /) -> None: ...
+    def __init__(self, it: Iterable[UAParser], /, *, defaults: bool = False) -> None: ...
+    @property
+    def parser_count(self) -> int: ...
+    @property
+    def digest(self) -> int: ...
     def extract(self, s: str, /) -> UserAgent | None: ...
 
 OSParser = tuple[
@@ -38,7 +45,11 @@ class OS(Protocol):
     patch_minor: str | None
 
 class OSExtractor:
-    def __init__(self, it: Iterable[OSParser], /) -> None: ...
+    def __init__(self, it: Iterable[OSParser], /, *, defaults: bool = False) -> None: ...
+    @property
+    def parser_count(self) -> int: ...
+    @property
+    def digest(self) -> int: ...
     def extract(self, s: str, /) -> OS | None: ...
 
 DeviceParser = tuple[
@@ -55,5 +66,9 @@ class Device(Protocol):
     model: str | None
 
 class DeviceExtractor:
-    def __init__(self, it: Iterable[DeviceParser], /) -> None: ...
+    def __init__(self, it: Iterable[DeviceParser], /, *, defaults: bool = False) -> None: ...
+    @property
+    def parser_count(self) -> int: ...
+    @property
+    def digest(self) -> int: ...
     def extract(self, s: str, /) -> Device | None: ...
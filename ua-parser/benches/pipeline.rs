@@ -0,0 +1,117 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use ua_parser::{Extractor, Regexes};
+
+/// Number of user agents of the sample corpus to use, the complete
+/// corpus makes individual iterations take very long.
+const SAMPLE: usize = 1000;
+
+fn regexes() -> Regexes<'static> {
+    let p = concat!(env!("CARGO_MANIFEST_DIR"), "/uap-core/regexes.yaml");
+    let f = std::fs::File::open(p).expect("the uap-core submodule should be checked out");
+    serde_yaml::from_reader(f).unwrap()
+}
+
+fn user_agents() -> Vec<String> {
+    let p = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../regex-filtered/samples/useragents.txt"
+    );
+    std::fs::read_to_string(p)
+        .unwrap()
+        .lines()
+        .take(SAMPLE)
+        .map(String::from)
+        .collect()
+}
+
+/// Building the extractor, including the regex rewriting and
+/// compilation, and the prefilters.
+fn bench_build(c: &mut Criterion) {
+    let regexes = regexes();
+    let mut g = c.benchmark_group("build");
+    g.sample_size(10);
+    g.bench_function("extractor", |b| {
+        b.iter_batched(
+            || regexes.clone(),
+            |r| Extractor::try_from(r).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    g.bench_function("user agent", |b| {
+        b.iter_batched(
+            || regexes.user_agent_parsers.clone(),
+            |r| {
+                ua_parser::user_agent::Builder::new()
+                    .push_all(r)
+                    .unwrap()
+                    .build()
+                    .unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    g.finish();
+}
+
+/// Extraction over the sample corpus, for each domain separately
+/// and all at once.
+fn bench_extract(c: &mut Criterion) {
+    let extractor = Extractor::try_from(regexes()).unwrap();
+    let uas = user_agents();
+
+    let mut g = c.benchmark_group("extract");
+    g.bench_function("user agent", |b| {
+        b.iter(|| uas.iter().for_each(|ua| drop(extractor.ua.extract(ua))))
+    });
+    g.bench_function("os", |b| {
+        b.iter(|| uas.iter().for_each(|ua| drop(extractor.os.extract(ua))))
+    });
+    g.bench_function("device", |b| {
+        b.iter(|| uas.iter().for_each(|ua| drop(extractor.dev.extract(ua))))
+    });
+    g.bench_function("full", |b| {
+        b.iter(|| uas.iter().for_each(|ua| drop(extractor.extract(ua))))
+    });
+    g.bench_function("full owned", |b| {
+        b.iter(|| {
+            uas.iter()
+                .for_each(|ua| drop(extractor.extract(ua).into_owned()))
+        })
+    });
+    g.finish();
+}
+
+/// Repeated user agents, as in real traffic: every user agent of the
+/// sample is seen 10 times.
+#[cfg(feature = "cache")]
+fn bench_repeated(c: &mut Criterion) {
+    use std::num::NonZeroUsize;
+    use ua_parser::CachingExtractor;
+
+    let uas = user_agents();
+    let repeated = uas
+        .iter()
+        .flat_map(|ua| std::iter::repeat(ua).take(10))
+        .collect::<Vec<_>>();
+
+    let mut g = c.benchmark_group("repeated");
+    let extractor = Extractor::try_from(regexes()).unwrap();
+    g.bench_function("uncached", |b| {
+        b.iter(|| {
+            repeated
+                .iter()
+                .for_each(|ua| drop(extractor.extract(ua).into_owned()))
+        })
+    });
+    let cache = CachingExtractor::new(extractor, NonZeroUsize::new(SAMPLE).unwrap());
+    g.bench_function("cached", |b| {
+        b.iter(|| repeated.iter().for_each(|ua| drop(cache.extract(ua))))
+    });
+    g.finish();
+}
+#[cfg(not(feature = "cache"))]
+fn bench_repeated(_: &mut Criterion) {}
+
+criterion_group!(benches, bench_build, bench_extract, bench_repeated);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+//! Bundles `uap-core/regexes.yaml` for the `bundled` feature.
+
+use std::path::{Path, PathBuf};
+
+fn main() {
+    if std::env::var_os("CARGO_FEATURE_BUNDLED").is_none() {
+        return;
+    }
+
+    let src = Path::new("uap-core/regexes.yaml");
+    println!("cargo:rerun-if-changed={}", src.display());
+    let out = PathBuf::from(std::env::var_os("OUT_DIR").unwrap()).join("regexes.yaml");
+    if let Err(e) = std::fs::copy(src, out) {
+        panic!(
+            "{} could not be bundled ({e}), check out the uap-core submodule or disable the `bundled` feature",
+            src.display()
+        );
+    }
+}
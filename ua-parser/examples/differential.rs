@@ -0,0 +1,133 @@
+//! Differential testing against a reference implementation.
+//!
+//! Runs a corpus of user agents through ua-parser and compares the
+//! results with those of a reference, reporting every divergence.
+//! The reference results are either recorded in a file (`--expected`),
+//! or produced by a command (`--command`) which gets the user agents
+//! on its stdin, one per line. In both cases the results are JSON
+//! objects, one per line and in the order of the corpus:
+//!
+//! ```json
+//! {"user_agent_string": "...", "user_agent": {"family": "...", "major": "...", ...}, "os": {...}, "device": {...}}
+//! ```
+//!
+//! using the same fields as the uap-core test suites. For instance,
+//! with uap-python:
+//!
+//! ```python
+//! import json, sys
+//! from ua_parser import parse
+//!
+//! for line in sys.stdin:
+//!     ua = line.rstrip("\n")
+//!     r = parse(ua).with_defaults()
+//!     print(json.dumps({
+//!         "user_agent_string": ua,
+//!         "user_agent": {k: getattr(r.user_agent, k) for k in ["family", "major", "minor", "patch", "patch_minor"]},
+//!         "os": {k: getattr(r.os, k) for k in ["family", "major", "minor", "patch", "patch_minor"]},
+//!         "device": {k: getattr(r.device, k) for k in ["family", "brand", "model"]},
+//!     }))
+//! ```
+use clap::Parser;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use ua_parser::conformance::{Device, Os, UserAgent};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// regexes.yaml file to parse the data file with
+    regexes: PathBuf,
+    /// user agents file, required with `--command`
+    user_agents: Option<PathBuf>,
+    /// recorded reference results, the corpus is their user agents
+    #[arg(long, required_unless_present = "command", conflicts_with = "command")]
+    expected: Option<PathBuf>,
+    /// reference command, run through `sh -c`
+    #[arg(long, requires = "user_agents")]
+    command: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    user_agent_string: String,
+    user_agent: UserAgent,
+    os: Os,
+    device: Device,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Args {
+        regexes,
+        user_agents,
+        expected,
+        command,
+    } = Args::parse();
+
+    let f = std::fs::File::open(regexes)?;
+    let r = ua_parser::Extractor::try_from(serde_yaml::from_reader::<_, ua_parser::Regexes>(f)?)?;
+
+    let output = match (expected, command, user_agents) {
+        (Some(expected), _, _) => std::fs::read_to_string(expected)?,
+        (None, Some(command), Some(user_agents)) => {
+            let corpus = std::fs::read(user_agents)?;
+            let mut child = Command::new("sh")
+                .args(["-c", &command])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?;
+            // feed stdin from a thread so a reference streaming its
+            // results can't deadlock on a full stdout pipe
+            let mut stdin = child.stdin.take().expect("stdin should be piped");
+            let feeder = std::thread::spawn(move || stdin.write_all(&corpus));
+            let output = child.wait_with_output()?;
+            feeder.join().expect("the stdin feeder should not panic")?;
+            if !output.status.success() {
+                return Err(format!("reference command failed: {}", output.status).into());
+            }
+            String::from_utf8(output.stdout)?
+        }
+        _ => unreachable!("ensured by the arguments parser"),
+    };
+
+    let mut total = 0;
+    let mut divergent = 0;
+    for line in output.lines().filter(|l| !l.trim().is_empty()) {
+        let expected: Record = serde_json::from_str(line)?;
+        let ua = &*expected.user_agent_string;
+        let user_agent = UserAgent::from(r.ua.extract_or_default(ua));
+        let os = Os::from(r.os.extract_or_default(ua));
+        let device = Device::from(r.dev.extract_or_default(ua));
+
+        total += 1;
+        let mut diverged = false;
+        if user_agent != expected.user_agent {
+            println!(
+                "{ua}\n\tuser agent: expected {:?}\n\t            got {user_agent:?}",
+                expected.user_agent
+            );
+            diverged = true;
+        }
+        if os != expected.os {
+            println!("{ua}\n\tos: expected {:?}\n\t    got {os:?}", expected.os);
+            diverged = true;
+        }
+        if device != expected.device {
+            println!(
+                "{ua}\n\tdevice: expected {:?}\n\t        got {device:?}",
+                expected.device
+            );
+            diverged = true;
+        }
+        divergent += usize::from(diverged);
+    }
+
+    println!("Lines: {total}");
+    println!("Divergences: {divergent}");
+    if divergent > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Memory profiling of loading the regexes and running a corpus,
+//! using dhat. Requires the `dhat-heap` feature:
+//!
+//! ```sh
+//! cargo run -r -p ua-parser --features dhat-heap --example memory -- regexes.yaml useragents.txt
+//! ```
+//!
+//! Prints the allocation figures of each phase, and writes the
+//! complete profile to `dhat-heap.json`, which can be explored with
+//! dhat's viewer.
+use clap::Parser;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// regexes.yaml file to parse the data file with
+    regexes: PathBuf,
+    /// user agents file
+    user_agents: PathBuf,
+}
+
+fn report(phase: &str) {
+    let dhat::HeapStats {
+        total_blocks,
+        total_bytes,
+        max_blocks,
+        max_bytes,
+        curr_blocks,
+        curr_bytes,
+        ..
+    } = dhat::HeapStats::get();
+    println!("{phase}:");
+    println!("\ttotal: {total_bytes} bytes in {total_blocks} blocks");
+    println!("\tpeak: {max_bytes} bytes in {max_blocks} blocks");
+    println!("\tcurrent: {curr_bytes} bytes in {curr_blocks} blocks");
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Args {
+        regexes,
+        user_agents,
+    } = Args::parse();
+
+    let uas = BufReader::new(std::fs::File::open(user_agents)?)
+        .lines()
+        .collect::<Result<Vec<String>, _>>()?;
+
+    let _profiler = dhat::Profiler::new_heap();
+
+    let f = std::fs::File::open(regexes)?;
+    let regexes = serde_yaml::from_reader::<_, ua_parser::Regexes>(f)?;
+    report("deserialized");
+
+    let extractor = ua_parser::Extractor::try_from(regexes)?;
+    report("built");
+    println!(
+        "\testimated extractor size: {} bytes",
+        extractor.memory_usage()
+    );
+
+    for ua in &uas {
+        drop(extractor.extract(ua));
+    }
+    report("extracted");
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Profiles the parsers of a regexes file over a user agents file,
+//! and reports the slowest and hottest ones, as well as the ones
+//! whose regexes are estimated to be slow.
+use clap::Parser;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+use ua_parser::{Domains, ParserReport, Profiler};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// regexes.yaml file to parse the data file with
+    regexes: PathBuf,
+    /// user agents file
+    user_agents: PathBuf,
+    /// number of parsers to report
+    #[arg(short, long, default_value_t = 20)]
+    count: usize,
+}
+
+fn domain(d: Domains) -> &'static str {
+    match d {
+        Domains::UA => "user_agent",
+        Domains::OS => "os",
+        _ => "device",
+    }
+}
+
+fn print(title: &str, reports: &[ParserReport<'_>]) {
+    println!("{title}:");
+    for r in reports {
+        let domain = domain(r.domain);
+        println!(
+            "{:>12?} {:>8} verified {:>8} matched  {domain}[{}] {}",
+            r.profile.time, r.profile.verified, r.profile.matched, r.index, r.regex,
+        );
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Args {
+        regexes,
+        user_agents,
+        count,
+    } = Args::parse();
+
+    let f = std::fs::File::open(regexes)?;
+    let r = ua_parser::Extractor::try_from(serde_yaml::from_reader::<_, ua_parser::Regexes>(f)?)?;
+
+    let mut profiler = Profiler::new(&r);
+    for ua in BufReader::new(std::fs::File::open(user_agents)?).lines() {
+        profiler.profile(&ua?);
+    }
+
+    println!("Lines: {}", profiler.user_agents());
+    print("Slowest", &profiler.slowest(count));
+    print("Hottest", &profiler.hottest(count));
+
+    println!("Likely slow:");
+    for r in r.complexity() {
+        let c = r.complexity;
+        if c.is_likely_slow() {
+            println!(
+                "{:>12} states {:>2} nesting {:>10}  {}[{}] {}",
+                c.states,
+                c.nesting,
+                if c.prefiltered { "" } else { "unfiltered" },
+                domain(r.domain),
+                r.index,
+                r.regex,
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,689 @@
+//! Binary artifact format for [`Extractor`].
+//!
+//! The artifact stores the regexes (as rewritten when building the
+//! extractor), their flags, and the replacement tables of every
+//! domain, along with the extractor's settings. Neither the compiled
+//! regexes nor the prefilter automaton can be serialized, so they are
+//! rebuilt when loading, but the loading skips the deserialization of
+//! the data file and the replacement strings are borrowed from the
+//! artifact buffer.
+//!
+//! Layout, all integers are little-endian `u32` unless specified:
+//!
+//! - the `UAPX` magic, followed by the format version
+//! - for each of the user agent, os, and device domains, its settings
+//!   followed by the number of parsers and the parsers
+//! - the settings of a domain are a `u8` of [`rewrite::Config`] bits,
+//!   a `u8` of [`EmptyCapture`], and a `u8` of whether templates are
+//!   strict, followed for the user agent
+//!   domain by a `u8` of [`FamilyReplacement`] and one of
+//!   [`VersionReplacement`]
+//! - each parser is its regex, a `u8` of flags, followed by its
+//!   replacements in declaration order, device parsers then have
+//!   the number of their extension fields followed by the key and
+//!   value of each
+//! - for each domain, the number of hit counts followed by the
+//!   `u64` counts
+//! - the [`Budget`] as an optional `u64` number of regexes and an
+//!   optional duration (`u64` seconds and `u32` nanoseconds), the
+//!   maximum length as an optional `u64`, the data version as an
+//!   optional string and its digest as an optional `u64`
+//! - the number of precomputed results, followed by each user agent
+//!   and its optional user agent, os, and device values, ordered by
+//!   user agent
+//! - strings are a length followed by as many bytes of UTF-8
+//! - optional values are a `u8` tag (0 for absent, 1 for present)
+//!   followed by the value if present
+//!
+//! [`FamilyReplacement`]: user_agent::FamilyReplacement
+//! [`VersionReplacement`]: user_agent::VersionReplacement
+
+use std::borrow::Cow;
+use std::time::Duration;
+
+use crate::user_agent::{FamilyReplacement, VersionReplacement};
+use crate::{
+    dedup, device, os, rewrite, user_agent, Budget, Client, DataInfo, EmptyCapture, Error,
+    Extractor, Regexes,
+};
+
+const MAGIC: &[u8; 4] = b"UAPX";
+const VERSION: u32 = 3;
+
+struct Writer(Vec<u8>);
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+    fn u32(&mut self, v: usize) {
+        let v = u32::try_from(v).expect("artifact values should fit in an u32");
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn str(&mut self, s: &str) {
+        self.u32(s.len());
+        self.0.extend_from_slice(s.as_bytes());
+    }
+    fn flag(&mut self, flag: Option<device::Flags>) {
+        self.u8(flag.unwrap_or_default().bits());
+    }
+    fn tagged<T>(&mut self, v: Option<T>, f: impl FnOnce(&mut Self, T)) {
+        if let Some(v) = v {
+            self.u8(1);
+            f(self, v);
+        } else {
+            self.u8(0);
+        }
+    }
+    fn opt(&mut self, s: Option<&str>) {
+        self.tagged(s, Self::str);
+    }
+    fn settings(&mut self, rewrite: rewrite::Config, empty: EmptyCapture, strict: bool) {
+        self.u8(rewrite.bits());
+        self.u8(match empty {
+            EmptyCapture::Absent => 0,
+            EmptyCapture::Keep => 1,
+        });
+        self.u8(strict.into());
+    }
+    fn counts(&mut self, counts: &[usize]) {
+        self.u32(counts.len());
+        for &c in counts {
+            self.u64(c as u64);
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < n {
+            return Err(Error::InvalidArtifact("truncated artifact"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+    fn u32(&mut self) -> Result<usize, Error> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    }
+    fn u64(&mut self) -> Result<u64, Error> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+    fn usize(&mut self) -> Result<usize, Error> {
+        usize::try_from(self.u64()?).map_err(|_| Error::InvalidArtifact("value out of range"))
+    }
+    /// A `u8` which must be `0` or `1`.
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidArtifact("invalid setting")),
+        }
+    }
+    fn str(&mut self) -> Result<Cow<'a, str>, Error> {
+        let n = self.u32()?;
+        std::str::from_utf8(self.take(n)?)
+            .map(Cow::Borrowed)
+            .map_err(|_| Error::InvalidArtifact("invalid string"))
+    }
+    fn owned(&mut self) -> Result<String, Error> {
+        self.str().map(Cow::into_owned)
+    }
+    fn flag(&mut self) -> Result<Option<device::Flags>, Error> {
+        match self.u8()? {
+            0 => Ok(None),
+            bits => device::Flags::from_bits(bits)
+                .map(Some)
+                .ok_or(Error::InvalidArtifact("unknown regex flags")),
+        }
+    }
+    fn tagged<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => f(self).map(Some),
+            _ => Err(Error::InvalidArtifact("invalid optional tag")),
+        }
+    }
+    fn opt(&mut self) -> Result<Option<Cow<'a, str>>, Error> {
+        self.tagged(Self::str)
+    }
+    fn owned_opt(&mut self) -> Result<Option<String>, Error> {
+        self.tagged(Self::owned)
+    }
+    fn settings(&mut self) -> Result<(rewrite::Config, EmptyCapture, bool), Error> {
+        let rewrite = rewrite::Config::from_bits(self.u8()?)
+            .ok_or(Error::InvalidArtifact("unknown rewrite configuration"))?;
+        let empty = if self.bool()? {
+            EmptyCapture::Keep
+        } else {
+            EmptyCapture::Absent
+        };
+        Ok((rewrite, empty, self.bool()?))
+    }
+    fn counts(&mut self) -> Result<Vec<usize>, Error> {
+        self.list(Self::usize)
+    }
+    fn list<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        let n = self.u32()?;
+        // don't trust the count for pre-allocation
+        let mut v = Vec::with_capacity(n.min(self.0.len()));
+        for _ in 0..n {
+            v.push(f(self)?);
+        }
+        Ok(v)
+    }
+}
+
+impl<'a> Extractor<'a> {
+    /// Serializes the extractor to a versioned binary artifact, which
+    /// can be loaded back using [`Extractor::from_bytes`].
+    ///
+    /// This allows validating and preparing the data once (e.g. in
+    /// CI) and shipping the result alongside a service. Note that the
+    /// regexes and prefilters are *not* precompiled, they are rebuilt
+    /// on load.
+    ///
+    /// The artifact holds the regexes as rewritten when building the
+    /// extractor, and all of its settings: the rewriting
+    /// configuration of each domain (for later [`Self::extend`]), the
+    /// empty capture and replacement handling, the hit counts,
+    /// budget, maximum length, data metadata, and precomputed
+    /// results.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(MAGIC.to_vec());
+        w.u32(VERSION as usize);
+
+        w.settings(
+            self.ua.core.rewrite,
+            self.ua.core.empty,
+            self.ua.core.strict,
+        );
+        w.u8(match self.ua.family {
+            FamilyReplacement::Restricted => 0,
+            FamilyReplacement::Templated => 1,
+        });
+        w.u8(match self.ua.versions {
+            VersionReplacement::Verbatim => 0,
+            VersionReplacement::Templated => 1,
+        });
+        let uas = self.ua.parsers().collect::<Vec<_>>();
+        w.u32(uas.len());
+        for p in uas {
+            w.str(&p.regex);
+            w.flag(p.regex_flag);
+            for r in [
+                &p.family_replacement,
+                &p.v1_replacement,
+                &p.v2_replacement,
+                &p.v3_replacement,
+                &p.v4_replacement,
+            ] {
+                w.opt(r.as_deref());
+            }
+        }
+
+        w.settings(
+            self.os.core.rewrite,
+            self.os.core.empty,
+            self.os.core.strict,
+        );
+        let oss = self.os.parsers().collect::<Vec<_>>();
+        w.u32(oss.len());
+        for p in oss {
+            w.str(&p.regex);
+            w.flag(p.regex_flag);
+            for r in [
+                &p.os_replacement,
+                &p.os_v1_replacement,
+                &p.os_v2_replacement,
+                &p.os_v3_replacement,
+                &p.os_v4_replacement,
+            ] {
+                w.opt(r.as_deref());
+            }
+        }
+
+        w.settings(
+            self.dev.core.rewrite,
+            self.dev.core.empty,
+            self.dev.core.strict,
+        );
+        let devs = self.dev.parsers().collect::<Vec<_>>();
+        w.u32(devs.len());
+        for p in devs {
+            w.str(&p.regex);
+            w.flag(p.regex_flag);
+            for r in [
+                &p.device_replacement,
+                &p.brand_replacement,
+                &p.model_replacement,
+            ] {
+                w.opt(r.as_deref());
+            }
+            w.u32(p.extra.len());
+            for (k, v) in &p.extra {
+                w.str(k);
+                w.str(v);
+            }
+        }
+
+        w.counts(self.ua.hit_counts());
+        w.counts(self.os.hit_counts());
+        w.counts(self.dev.hit_counts());
+
+        w.tagged(self.budget.verified, |w, n| w.u64(n as u64));
+        w.tagged(self.budget.duration, |w, d| {
+            w.u64(d.as_secs());
+            w.u32(d.subsec_nanos() as usize);
+        });
+        w.tagged(self.max_length, |w, n| w.u64(n as u64));
+        w.opt(self.info.version.as_deref());
+        w.tagged(self.info.digest, Writer::u64);
+
+        // sorted, so the artifact doesn't depend on the hash order
+        let mut precomputed = self.precomputed.iter().collect::<Vec<_>>();
+        precomputed.sort_unstable_by_key(|(ua, _)| *ua);
+        w.u32(precomputed.len());
+        for (ua, c) in precomputed {
+            w.str(ua);
+            w.tagged(c.ua.as_ref(), |w, v| {
+                w.str(&v.family);
+                for f in [&v.major, &v.minor, &v.patch, &v.patch_minor] {
+                    w.opt(f.as_deref());
+                }
+            });
+            w.tagged(c.os.as_ref(), |w, v| {
+                w.str(&v.os);
+                for f in [&v.major, &v.minor, &v.patch, &v.patch_minor] {
+                    w.opt(f.as_deref());
+                }
+            });
+            w.tagged(c.device.as_ref(), |w, v| {
+                w.str(&v.device);
+                w.opt(v.brand.as_deref());
+                w.opt(v.model.as_deref());
+            });
+        }
+
+        w.0
+    }
+
+    /// Loads an extractor from an artifact created by
+    /// [`Extractor::to_bytes`], the replacement data is borrowed from
+    /// the artifact.
+    ///
+    /// Fails with [`Error::InvalidArtifact`] if the artifact is
+    /// corrupted or from an unsupported version of the format.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        let mut r = Reader(bytes);
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(Error::InvalidArtifact("not an extractor artifact"));
+        }
+        if r.u32()? != VERSION as usize {
+            return Err(Error::InvalidArtifact("unsupported artifact version"));
+        }
+
+        // the regexes have already been rewritten, they're pushed
+        // as-is then the configurations are restored for `extend`
+        let (ua_rewrite, empty, strict) = r.settings()?;
+        let ua = user_agent::Builder::new()
+            .rewrite(rewrite::Config::verbatim())
+            .empty_captures(empty)
+            .strict_templates(strict)
+            .family_replacement(if r.bool()? {
+                FamilyReplacement::Templated
+            } else {
+                FamilyReplacement::Restricted
+            })
+            .version_replacement(if r.bool()? {
+                VersionReplacement::Templated
+            } else {
+                VersionReplacement::Verbatim
+            });
+        let user_agent_parsers = r.list(|r| {
+            Ok(user_agent::Parser {
+                regex: r.str()?,
+                regex_flag: r.flag()?,
+                family_replacement: r.opt()?,
+                v1_replacement: r.opt()?,
+                v2_replacement: r.opt()?,
+                v3_replacement: r.opt()?,
+                v4_replacement: r.opt()?,
+            })
+        })?;
+
+        let (os_rewrite, empty, strict) = r.settings()?;
+        let os = os::Builder::new()
+            .rewrite(rewrite::Config::verbatim())
+            .empty_captures(empty)
+            .strict_templates(strict);
+        let os_parsers = r.list(|r| {
+            Ok(os::Parser {
+                regex: r.str()?,
+                regex_flag: r.flag()?,
+                os_replacement: r.opt()?,
+                os_v1_replacement: r.opt()?,
+                os_v2_replacement: r.opt()?,
+                os_v3_replacement: r.opt()?,
+                os_v4_replacement: r.opt()?,
+            })
+        })?;
+
+        let (dev_rewrite, empty, strict) = r.settings()?;
+        let dev = device::Builder::new()
+            .rewrite(rewrite::Config::verbatim())
+            .empty_captures(empty)
+            .strict_templates(strict);
+        let device_parsers = r.list(|r| {
+            Ok(device::Parser {
+                regex: r.str()?,
+                regex_flag: r.flag()?,
+                device_replacement: r.opt()?,
+                brand_replacement: r.opt()?,
+                model_replacement: r.opt()?,
+                extra: r
+                    .list(|r| Ok((r.owned()?, r.owned()?)))?
+                    .into_iter()
+                    .collect(),
+            })
+        })?;
+
+        let frequencies = [r.counts()?, r.counts()?, r.counts()?];
+        let budget = Budget {
+            verified: r.tagged(Reader::usize)?,
+            duration: r.tagged(|r| {
+                let secs = r.u64()?;
+                let nanos = u32::try_from(r.u32()?)
+                    .ok()
+                    .filter(|&n| n < 1_000_000_000)
+                    .ok_or(Error::InvalidArtifact("invalid duration"))?;
+                Ok(Duration::new(secs, nanos))
+            })?,
+        };
+        let max_length = r.tagged(Reader::usize)?;
+        let info = DataInfo {
+            version: r.owned_opt()?,
+            digest: r.tagged(Reader::u64)?,
+            parsers: [0; 3],
+        };
+        let precomputed = r.list(|r| {
+            let ua = r.owned()?;
+            let client = Client {
+                ua: r.tagged(|r| {
+                    Ok(user_agent::Value {
+                        family: r.owned()?,
+                        major: r.owned_opt()?,
+                        minor: r.owned_opt()?,
+                        patch: r.owned_opt()?,
+                        patch_minor: r.owned_opt()?,
+                    })
+                })?,
+                os: r.tagged(|r| {
+                    Ok(os::Value {
+                        os: r.owned()?,
+                        major: r.owned_opt()?,
+                        minor: r.owned_opt()?,
+                        patch: r.owned_opt()?,
+                        patch_minor: r.owned_opt()?,
+                    })
+                })?,
+                device: r.tagged(|r| {
+                    Ok(device::Value {
+                        device: r.owned()?,
+                        brand: r.owned_opt()?,
+                        model: r.owned_opt()?,
+                    })
+                })?,
+            };
+            Ok((ua, client))
+        })?;
+        if !r.0.is_empty() {
+            return Err(Error::InvalidArtifact("trailing data"));
+        }
+
+        let regexes = Regexes {
+            user_agent_parsers,
+            os_parsers,
+            device_parsers,
+        };
+        let mut e = Self::assemble_from(ua, os, dev, dedup::Dedup::default(), regexes, Err)?
+            .frequencies(frequencies)
+            .budget(budget)
+            .precomputed(precomputed);
+        e.ua.core.rewrite = ua_rewrite;
+        e.os.core.rewrite = os_rewrite;
+        e.dev.core.rewrite = dev_rewrite;
+        e.max_length = max_length;
+        e.info = info;
+        Ok(e)
+    }
+}
+
+impl Extractor<'static> {
+    /// Loads an extractor from an artifact mapped in memory for the
+    /// rest of the process, e.g. a read-only file mapping shared by
+    /// the workers of a preforked server.
+    ///
+    /// The replacement data is borrowed from the mapping rather than
+    /// copied, so every process shares its pages. The compiled
+    /// regexes and prefilters can not be shared, they are still
+    /// built and owned by each process.
+    ///
+    /// - The mapping has no alignment requirement, the format only
+    ///   contains bytes and unaligned little-endian integers.
+    /// - The mapping must stay mapped, and must not be modified
+    ///   (including through the underlying file), for as long as the
+    ///   process runs, hence the `'static` lifetime: the mapping is
+    ///   typically leaked after creation. The artifact is validated
+    ///   when loading, modifying it afterwards would corrupt the
+    ///   extractor.
+    ///
+    /// Fails with [`Error::InvalidArtifact`] under the same conditions
+    /// as [`Extractor::from_bytes`].
+    pub fn from_mapping(mapping: &'static [u8]) -> Result<Self, Error> {
+        Self::from_bytes(mapping)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ClientRef;
+
+    fn extractor() -> Extractor<'static> {
+        Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser {
+                    regex: r"(Firefox)/(\d+)\.(\d+)".into(),
+                    ..Default::default()
+                },
+                user_agent::Parser {
+                    regex: r"Fennec/(\d+)".into(),
+                    family_replacement: Some("Firefox Mobile".into()),
+                    v2_replacement: Some("0".into()),
+                    ..Default::default()
+                },
+            ],
+            os_parsers: vec![os::Parser {
+                regex: r"Windows NT (\d+)\.(\d+)".into(),
+                regex_flag: Some(device::Flags::IGNORE_CASE),
+                os_replacement: Some("Windows".into()),
+                os_v1_replacement: Some("$1".into()),
+                ..Default::default()
+            }],
+            device_parsers: vec![device::Parser {
+                regex: r"; *(Pixel \w+)".into(),
+                regex_flag: Some(device::Flags::IGNORE_CASE | device::Flags::DOT_ALL),
+                brand_replacement: Some("Google".into()),
+                extra: [("device_type".into(), "smartphone".into())].into(),
+                ..Default::default()
+            }],
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let e = extractor();
+        let bytes = e.to_bytes();
+        let e2 = Extractor::from_bytes(&bytes).unwrap();
+
+        assert_eq!(bytes, e2.to_bytes());
+        for ua in [
+            "Mozilla/5.0 (Windows NT 10.0; rv:121.0) Firefox/121.0",
+            "Mozilla/5.0 (Android 14; PIXEL 8) Fennec/120",
+            "curl/8.0",
+        ] {
+            assert_eq!(e.extract(ua), e2.extract(ua), "{ua}");
+        }
+        assert_eq!(
+            e2.dev.extract("Linux; Android 14; PIXEL 8").unwrap().brand,
+            Some("Google".into()),
+        );
+        assert_eq!(
+            e2.os.extract("windows nt 10.0").unwrap().major,
+            Some("10".into()),
+        );
+    }
+
+    #[test]
+    fn settings() {
+        let regexes = || Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                regex: r"\bFoo/(\d)()".into(),
+                family_replacement: Some("$1 $2".into()),
+                ..Default::default()
+            }],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        };
+        let config = rewrite::Config::new()
+            .ascii_classes(false)
+            .ascii_word_boundaries(false);
+        let ua = user_agent::Builder::new()
+            .rewrite(config)
+            .family_replacement(FamilyReplacement::Templated)
+            .empty_captures(EmptyCapture::Keep);
+        let e = Extractor::assemble_from(
+            ua,
+            os::Builder::new().strict_templates(true),
+            device::Builder::new(),
+            dedup::Dedup::default(),
+            regexes(),
+            Err,
+        )
+        .unwrap();
+        let e = e
+            .frequencies([vec![3], vec![], vec![]])
+            .budget(
+                Budget::new()
+                    .max_verified(5)
+                    .max_duration(Duration::from_millis(2)),
+            )
+            .max_length(100)
+            .data_version("v1")
+            .data_digest(42)
+            .precomputed([("Bar".to_string(), Client::default())]);
+
+        let bytes = e.to_bytes();
+        let e2 = Extractor::from_bytes(&bytes).unwrap();
+        assert_eq!(bytes, e2.to_bytes());
+        assert_eq!(
+            e2.ua.parsers().map(|p| p.regex).collect::<Vec<_>>(),
+            [r"\bFoo/(\d)()"]
+        );
+        assert_eq!(e2.ua.core.rewrite, config);
+        assert_eq!(e2.ua.core.empty, EmptyCapture::Keep);
+        assert_eq!(e2.ua.family, FamilyReplacement::Templated);
+        assert_eq!(e2.os.core.rewrite, rewrite::Config::new());
+        assert!(e2.os.core.strict && !e2.ua.core.strict);
+        assert_eq!(e2.ua.hit_counts(), [3]);
+        assert_eq!(e2.budget, e.budget);
+        assert_eq!(e2.max_length, Some(100));
+        assert_eq!(e2.data_info(), e.data_info());
+        assert_eq!(e2.extract("Bar"), ClientRef::default());
+
+        // Unicode digits are only supported by the regex engine
+        if cfg!(not(feature = "engine-regex-lite")) {
+            let ua = "Foo/\u{663}";
+            assert_eq!(e2.extract(ua).family(), "\u{663} ");
+            // the rewriting configuration applies to extensions
+            let e3 = e2
+                .extend(Regexes {
+                    user_agent_parsers: vec![user_agent::Parser::new(r"\bBar/(\d)")],
+                    ..regexes()
+                })
+                .unwrap();
+            assert_eq!(e3.ua.extract("Bar/\u{663}").unwrap().family, "\u{663}");
+        }
+    }
+
+    #[test]
+    fn mapping() {
+        let bytes = extractor().to_bytes();
+        // leaked, and offset to check that no alignment is required
+        let mut storage = vec![0u8; bytes.len() + 1];
+        storage[1..].copy_from_slice(&bytes);
+        let mapping: &'static [u8] = &Box::leak(storage.into_boxed_slice())[1..];
+
+        let e = Extractor::from_mapping(mapping).unwrap();
+        assert_eq!(e.extract("Fennec/120").ua.unwrap().family, "Firefox Mobile");
+
+        let range = mapping.as_ptr_range();
+        let replacements =
+            e.ua.parsers()
+                .flat_map(|p| p.family_replacement)
+                .chain(e.os.parsers().flat_map(|p| p.os_replacement))
+                .chain(e.dev.parsers().flat_map(|p| p.brand_replacement))
+                .collect::<Vec<_>>();
+        assert_eq!(replacements, ["Firefox Mobile", "Windows", "Google"]);
+        for r in replacements {
+            assert!(
+                range.contains(&r.as_ptr()),
+                "{r:?} should be borrowed from the mapping"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let bytes = extractor().to_bytes();
+
+        assert!(matches!(
+            Extractor::from_bytes(b"nope"),
+            Err(Error::InvalidArtifact(_))
+        ));
+        assert!(matches!(
+            Extractor::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::InvalidArtifact(_))
+        ));
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Extractor::from_bytes(&trailing),
+            Err(Error::InvalidArtifact(_))
+        ));
+        let mut version = bytes;
+        version[4] = 42;
+        assert!(matches!(
+            Extractor::from_bytes(&version),
+            Err(Error::InvalidArtifact(_))
+        ));
+    }
+}
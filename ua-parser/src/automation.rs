@@ -0,0 +1,89 @@
+//! Detection of headless browsers and browser automation, see
+//! [`detect`].
+
+/// Headless browser or automation tool marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Automation {
+    /// Headless Chrome / Chromium.
+    HeadlessChrome,
+    ///
+    PhantomJs,
+    ///
+    SlimerJs,
+    ///
+    HtmlUnit,
+    ///
+    Selenium,
+    /// Generic WebDriver marker.
+    WebDriver,
+    ///
+    Puppeteer,
+    ///
+    Playwright,
+}
+
+const MARKERS: &[(&str, Automation)] = &[
+    ("HeadlessChrome", Automation::HeadlessChrome),
+    ("PhantomJS", Automation::PhantomJs),
+    ("SlimerJS", Automation::SlimerJs),
+    ("HtmlUnit", Automation::HtmlUnit),
+    ("Selenium", Automation::Selenium),
+    ("WebDriver", Automation::WebDriver),
+    ("Puppeteer", Automation::Puppeteer),
+    ("Playwright", Automation::Playwright),
+];
+
+/// Families of the uap-core user agent data which denote headless
+/// browsers, see [`crate::user_agent::ValueRef::is_headless`].
+pub(crate) const HEADLESS_FAMILIES: &[&str] = &["HeadlessChrome", "PhantomJS", "SlimerJS"];
+
+/// Looks for the markers of headless browsers and automation tools in
+/// the user agent string, returns the first found.
+///
+/// This is only a scan for well-known tokens, much cheaper than an
+/// extraction, but it can only detect tools which advertise
+/// themselves.
+///
+/// ```
+/// # use ua_parser::automation::{detect, Automation};
+/// let ua = "Mozilla/5.0 (X11; Linux x86_64) HeadlessChrome/120.0.0.0 Safari/537.36";
+/// assert_eq!(detect(ua), Some(Automation::HeadlessChrome));
+/// ```
+pub fn detect(ua: &str) -> Option<Automation> {
+    MARKERS
+        .iter()
+        .find(|(marker, _)| ua.contains(marker))
+        .map(|&(_, a)| a)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn markers() {
+        assert_eq!(
+            detect("Mozilla/5.0 (Unknown; Linux x86_64) PhantomJS/2.1.1 Safari/538.1"),
+            Some(Automation::PhantomJs)
+        );
+        assert_eq!(
+            detect("Mozilla/5.0 (Windows NT 10.0) Chrome/120.0 Selenium/4.16"),
+            Some(Automation::Selenium)
+        );
+        assert_eq!(
+            detect("Mozilla/5.0 (X11; Linux x86_64) Chrome/120.0 Safari/537.36"),
+            None
+        );
+
+        let ua = crate::user_agent::ValueRef {
+            family: "HeadlessChrome".into(),
+            ..Default::default()
+        };
+        assert!(ua.is_headless());
+        let ua = crate::user_agent::ValueRef {
+            family: "Chrome".into(),
+            ..Default::default()
+        };
+        assert!(!ua.is_headless());
+    }
+}
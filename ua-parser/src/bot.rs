@@ -0,0 +1,265 @@
+//! The bot extraction identifies crawlers, monitoring agents, and
+//! HTTP libraries, returning their *name* and [`Category`].
+//!
+//! It is not part of the uap-core data, so the parsers use their own
+//! list, which follows the same format as the other domains and can
+//! be loaded the same way:
+//!
+//! ```yaml
+//! - regex: '(Googlebot)/'
+//!   category: crawler
+//! - regex: 'UptimeRobot/'
+//!   name_replacement: 'Uptime Robot'
+//!   category: monitoring
+//! - regex: '(curl)/'
+//!   regex_flag: 'i'
+//!   category: library
+//! ```
+//!
+//! ```
+//! # use ua_parser::bot;
+//! let parsers: Vec<bot::Parser> = serde_json::from_str(r#"[
+//!     {"regex": "(Googlebot)/", "category": "crawler"}
+//! ]"#)?;
+//! let extractor = bot::Builder::new().push_all(parsers)?.build()?;
+//!
+//! let bot = extractor
+//!     .extract("Mozilla/5.0 (compatible; Googlebot/2.1)")
+//!     .unwrap();
+//! assert_eq!(bot.name, "Googlebot");
+//! assert_eq!(bot.category, bot::Category::Crawler);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use serde::Deserialize;
+use std::borrow::Cow;
+
+use regex_filtered::BuildError;
+
+use crate::device::Flags;
+use crate::engine;
+use crate::resolvers::Resolver;
+use crate::rewrite;
+use crate::Error;
+
+/// Kind of automated agent.
+#[derive(Deserialize, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    /// Search engine and other indexing crawlers.
+    Crawler,
+    /// Uptime and performance monitoring agents.
+    Monitoring,
+    /// HTTP libraries and command-line clients.
+    Library,
+    /// Any other automated agent.
+    #[default]
+    Other,
+}
+
+/// Bot parser description.
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+pub struct Parser<'a> {
+    /// Regex pattern to use for matching and data extraction.
+    pub regex: Cow<'a, str>,
+    /// Configuration flags for the regex, if any.
+    pub regex_flag: Option<Flags>,
+    /// Name replacement data, fully templated, must be present *or*
+    /// the regex must have at least one group, which will be used
+    /// instead.
+    pub name_replacement: Option<Cow<'a, str>>,
+    /// Category of the bot, defaults to [`Category::Other`].
+    #[serde(default)]
+    pub category: Category,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser with the provided regex and no
+    /// replacements, to be completed by the other methods.
+    pub fn new(regex: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            regex: regex.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets [`Self::regex_flag`].
+    pub fn regex_flag(mut self, flags: Flags) -> Self {
+        self.regex_flag = Some(flags);
+        self
+    }
+
+    /// Sets [`Self::name_replacement`].
+    pub fn name(mut self, name: impl Into<Cow<'a, str>>) -> Self {
+        self.name_replacement = Some(name.into());
+        self
+    }
+
+    /// Sets [`Self::category`].
+    pub fn category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+}
+
+/// Extractor builder.
+#[derive(Default)]
+pub struct Builder<'a> {
+    builder: regex_filtered::Builder<engine::Regex>,
+    repl: Vec<(Resolver<'a>, Category)>,
+    rewrite: rewrite::Config,
+    strict: bool,
+}
+impl<'a> Builder<'a> {
+    /// Initialise an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the extractor, may fail if compiling the prefilter
+    /// fails.
+    pub fn build(self) -> Result<Extractor<'a>, BuildError> {
+        let Self { builder, repl, .. } = self;
+
+        Ok(Extractor {
+            matcher: builder.build()?,
+            repl,
+        })
+    }
+
+    /// Sets the rewriting applied to the regexes of the parsers
+    /// pushed afterwards.
+    pub fn rewrite(mut self, rewrite: rewrite::Config) -> Self {
+        self.rewrite = rewrite;
+        self
+    }
+
+    /// Whether name replacements referencing groups missing from
+    /// their regex are errors, disabled by default: the references
+    /// expand to an empty string.
+    pub fn strict_templates(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Adds a parser to the set, may fail if parsing the regex fails,
+    /// or (with [`Self::strict_templates`]) if the name replacement
+    /// references a group missing from it.
+    pub fn push(mut self, bot: Parser<'a>) -> Result<Self, Error> {
+        let regex = self.rewrite.rewrite(&bot.regex);
+        self.builder = self
+            .builder
+            .push_opt(&regex, &bot.regex_flag.unwrap_or_default().options())?;
+        let r = &self.builder.regexes()[self.builder.regexes().len() - 1];
+        self.repl.push((
+            Resolver::new(bot.name_replacement, r, 1, self.strict)?,
+            bot.category,
+        ));
+        Ok(self)
+    }
+
+    /// Bulk loading of parsers into the builder.
+    pub fn push_all<I>(self, bots: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Parser<'a>>,
+    {
+        bots.into_iter().try_fold(self, |s, p| s.push(p))
+    }
+}
+
+/// Bot extractor.
+pub struct Extractor<'a> {
+    matcher: regex_filtered::Regexes<engine::Regex>,
+    repl: Vec<(Resolver<'a>, Category)>,
+}
+impl<'a> Extractor<'a> {
+    /// Performs the extraction using the first matching parser,
+    /// returns [`None`] if no parser matches (the user agent is not a
+    /// known bot).
+    pub fn extract(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
+        let (idx, re) = self.matcher.matching(ua).next()?;
+        let c = engine::captures(re, ua)?;
+        let (name, category) = &self.repl[idx];
+        Some(ValueRef {
+            name: name.resolve(&c),
+            category: *category,
+        })
+    }
+
+    /// Returns whether the user agent is a known bot, without
+    /// performing the extraction.
+    pub fn is_match(&self, ua: &str) -> bool {
+        self.matcher.is_match(ua)
+    }
+}
+
+/// Extracted bot content, may borrow from one of the [`Parser`] or
+/// from the user agent string.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct ValueRef<'a> {
+    ///
+    pub name: Cow<'a, str>,
+    ///
+    pub category: Category,
+}
+
+impl ValueRef<'_> {
+    /// Converts [`Self`] to an owned [`Value`] getting rid of
+    /// borrowing concerns, may need to allocate and copy the name.
+    pub fn into_owned(self) -> Value {
+        Value {
+            name: self.name.into_owned(),
+            category: self.category,
+        }
+    }
+}
+
+/// Owned version of [`ValueRef`].
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct Value {
+    ///
+    pub name: String,
+    ///
+    pub category: Category,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extract() {
+        let e = Builder::new()
+            .push_all([
+                Parser::new("(Googlebot)/").category(Category::Crawler),
+                Parser::new("UptimeRobot/")
+                    .name("Uptime Robot")
+                    .category(Category::Monitoring),
+                Parser::new("(curl)/")
+                    .regex_flag(Flags::IGNORE_CASE)
+                    .category(Category::Library),
+            ])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            e.extract("Mozilla/5.0 (compatible; Googlebot/2.1)"),
+            Some(ValueRef {
+                name: "Googlebot".into(),
+                category: Category::Crawler,
+            })
+        );
+        assert_eq!(
+            e.extract("Mozilla/5.0 (compatible; UptimeRobot/2.0)")
+                .unwrap()
+                .into_owned(),
+            Value {
+                name: "Uptime Robot".into(),
+                category: Category::Monitoring,
+            }
+        );
+        assert_eq!(e.extract("CURL/8.0").unwrap().name, "CURL");
+        assert!(!e.is_match("Mozilla/5.0 (X11; Linux x86_64) Firefox/121.0"));
+    }
+}
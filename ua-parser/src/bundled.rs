@@ -0,0 +1,81 @@
+//! The uap-core data bundled at build time, requires the `bundled`
+//! feature.
+//!
+//! [`Extractor::global`] is a process-wide extractor over the data as
+//! is, [`regexes`] provides the parsers to customize them first,
+//! e.g. by [registering](Regexes::with_user_agent_parsers) additional
+//! parsers.
+
+use crate::sync::LazyLock;
+use crate::{DataInfo, Extractor, Regexes};
+
+const REGEXES: &str = include_str!(concat!(env!("OUT_DIR"), "/regexes.yaml"));
+
+/// The parsers of the bundled data, deserialized on every call.
+///
+/// ```
+/// use ua_parser::{user_agent, Extractor, MergeStrategy};
+///
+/// let regexes = ua_parser::bundled::regexes().with_user_agent_parsers(
+///     [user_agent::Parser::new(r"(MyCrawler)/(\d+)")],
+///     MergeStrategy::Prepend,
+/// );
+/// let extractor = Extractor::try_from(regexes)?;
+/// assert_eq!(extractor.extract("MyCrawler/2").family(), "MyCrawler");
+/// # Ok::<(), ua_parser::Error>(())
+/// ```
+pub fn regexes() -> Regexes<'static> {
+    serde_yaml::from_str(REGEXES).expect("the bundled regexes should be valid")
+}
+
+// std's `LazyLock` requires Rust 1.80, the `once_cell` feature
+// supports older toolchains
+#[allow(clippy::incompatible_msrv)]
+static GLOBAL: LazyLock<Extractor<'static>> = LazyLock::new(|| {
+    Extractor::try_from(regexes())
+        .expect("the bundled regexes should be valid")
+        .data_digest(DataInfo::digest(REGEXES.as_bytes()))
+});
+
+impl Extractor<'static> {
+    /// Process-wide extractor over the uap-core data bundled at build
+    /// time, built on first use. Requires the `bundled` feature.
+    ///
+    /// This allows sharing a single extractor between all the parts
+    /// of an application, without having to thread it through or to
+    /// wrap it in a lazy static.
+    ///
+    /// ```no_run
+    /// let c = ua_parser::Extractor::global().extract("Mozilla/5.0 (X11; Linux x86_64) Firefox/121.0");
+    /// ```
+    pub fn global() -> &'static Self {
+        &GLOBAL
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn global() {
+        let e = Extractor::global();
+        assert!(std::ptr::eq(e, Extractor::global()));
+        assert!(e.data_info().parsers.iter().all(|&n| n > 0));
+        let c = e.extract("Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0");
+        assert_eq!(c.ua.unwrap().family, "Firefox");
+    }
+
+    #[test]
+    fn regexes() {
+        let r = super::regexes();
+        assert_eq!(
+            [
+                r.user_agent_parsers.len(),
+                r.os_parsers.len(),
+                r.device_parsers.len()
+            ],
+            Extractor::global().data_info().parsers,
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Cache of extraction results, see [`CachingExtractor`].
+
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use lru::LruCache;
+
+use crate::{Client, Extractor};
+
+/// Wrapper around an [`Extractor`] caching the owned results of the
+/// most recently extracted user agents.
+///
+/// Real traffic has a very high repetition of user agent strings, so
+/// even a small cache avoids most of the regex work. The results are
+/// shared via an [`Arc`] to avoid copying them out of the cache.
+///
+/// ```
+/// # use std::num::NonZeroUsize;
+/// # use ua_parser::{CachingExtractor, Extractor, Regexes};
+/// # let regexes = Regexes {
+/// #     user_agent_parsers: vec![ua_parser::user_agent::Parser {
+/// #         regex: r"(Firefox)/(\d+)".into(),
+/// #         ..Default::default()
+/// #     }],
+/// #     os_parsers: vec![],
+/// #     device_parsers: vec![],
+/// # };
+/// let extractor = Extractor::try_from(regexes)?;
+/// let cache = CachingExtractor::new(extractor, NonZeroUsize::new(1000).unwrap());
+///
+/// let values = cache.extract("Mozilla/5.0 Firefox/121");
+/// assert_eq!(values.family(), "Firefox");
+/// # Ok::<(), ua_parser::Error>(())
+/// ```
+pub struct CachingExtractor<'a> {
+    extractor: Extractor<'a>,
+    cache: Mutex<LruCache<Box<str>, Arc<Client>>>,
+}
+
+impl<'a> CachingExtractor<'a> {
+    /// Wraps the extractor with a cache of up to `capacity` results.
+    pub fn new(extractor: Extractor<'a>, capacity: NonZeroUsize) -> Self {
+        Self {
+            extractor,
+            cache: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Performs the extraction on every domain, unless the result for
+    /// this user agent is already cached.
+    pub fn extract(&self, ua: &str) -> Arc<Client> {
+        let cached = self.cache.lock().unwrap().get(ua).cloned();
+        #[cfg(feature = "metrics")]
+        crate::instrument::cache_lookup(cached.is_some());
+        if let Some(v) = cached {
+            return v;
+        }
+
+        // extract outside of the lock, at worst concurrent extractions
+        // of the same user agent are duplicated
+        let v = Arc::new(self.extractor.extract(ua).into_owned());
+        self.cache.lock().unwrap().put(ua.into(), v.clone());
+        v
+    }
+
+    /// Empties the cache.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// The wrapped extractor, for uncached extractions.
+    pub fn extractor(&self) -> &Extractor<'a> {
+        &self.extractor
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{user_agent, Regexes};
+
+    #[test]
+    fn cache() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                regex: r"(Firefox)/(\d+)".into(),
+                ..Default::default()
+            }],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        })
+        .unwrap();
+        let c = CachingExtractor::new(e, NonZeroUsize::new(1).unwrap());
+
+        let a = c.extract("Firefox/121");
+        assert_eq!(a.ua.as_ref().unwrap().major.as_deref(), Some("121"));
+        assert!(Arc::ptr_eq(&a, &c.extract("Firefox/121")));
+
+        let b = c.extract("Firefox/120");
+        assert_eq!(b.ua.as_ref().unwrap().major.as_deref(), Some("120"));
+        // evicted
+        assert!(!Arc::ptr_eq(&a, &c.extract("Firefox/121")));
+
+        c.clear();
+        assert!(c.cache.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+//! Loading of CBOR-encoded regexes, see [`Regexes::from_cbor_reader`].
+
+use std::io::Read;
+
+use crate::Regexes;
+
+impl Regexes<'static> {
+    /// Reads and deserializes regexes encoded in CBOR, with the same
+    /// structure as `regexes.yaml`. Requires the `cbor` feature.
+    pub fn from_cbor_reader<R: Read>(
+        reader: R,
+    ) -> Result<Self, ciborium::de::Error<std::io::Error>> {
+        ciborium::from_reader(reader)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_cbor_reader() {
+        let json = serde_json::json!({
+            "user_agent_parsers": [{"regex": "(Firefox)/(\\d+)"}],
+            "os_parsers": [],
+            "deviceParsers": [{"regex": "(iPhone)", "brandReplacement": "Apple"}],
+        });
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&json, &mut cbor).unwrap();
+
+        let r = Regexes::from_cbor_reader(&cbor[..]).unwrap();
+        assert_eq!(r.user_agent_parsers[0].regex, "(Firefox)/(\\d+)");
+        assert_eq!(
+            r.device_parsers[0].brand_replacement.as_deref(),
+            Some("Apple")
+        );
+        let e = crate::Extractor::try_from(r).unwrap();
+        assert_eq!(e.extract("Firefox/121").ua.unwrap().family, "Firefox");
+
+        assert!(Regexes::from_cbor_reader(&cbor[..cbor.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Results of a complete extraction, see [`ClientRef`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::{device, os, user_agent, Domains};
+
+/// Result of a complete extraction by [`crate::Extractor::extract`],
+/// each domain is [`None`] if no parser matched (or the domain was
+/// not requested).
+#[derive(Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct ClientRef<'a> {
+    ///
+    pub ua: Option<user_agent::ValueRef<'a>>,
+    ///
+    pub os: Option<os::ValueRef<'a>>,
+    ///
+    pub device: Option<device::ValueRef<'a>>,
+}
+
+impl ClientRef<'_> {
+    /// The user agent family, or `"Other"` if unmatched.
+    pub fn family(&self) -> &str {
+        self.ua.as_ref().map_or("Other", |v| &v.family)
+    }
+
+    /// The OS family, or `"Other"` if unmatched.
+    pub fn os_family(&self) -> &str {
+        self.os.as_ref().map_or("Other", |v| &v.os)
+    }
+
+    /// The device family, or `"Other"` if unmatched.
+    pub fn device_family(&self) -> &str {
+        self.device.as_ref().map_or("Other", |v| &v.device)
+    }
+
+    /// Converts the borrowed results into owned ones, independent
+    /// from both the extractor and the user agent string.
+    pub fn into_owned(self) -> Client {
+        Client {
+            ua: self.ua.map(user_agent::ValueRef::into_owned),
+            os: self.os.map(os::ValueRef::into_owned),
+            device: self.device.map(device::ValueRef::into_owned),
+        }
+    }
+}
+
+/// Owned version of [`ClientRef`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct Client {
+    ///
+    pub ua: Option<user_agent::Value>,
+    ///
+    pub os: Option<os::Value>,
+    ///
+    pub device: Option<device::Value>,
+}
+
+impl Client {
+    /// The user agent family, or `"Other"` if unmatched.
+    pub fn family(&self) -> &str {
+        self.ua.as_ref().map_or("Other", |v| &v.family)
+    }
+
+    /// The OS family, or `"Other"` if unmatched.
+    pub fn os_family(&self) -> &str {
+        self.os.as_ref().map_or("Other", |v| &v.os)
+    }
+
+    /// The device family, or `"Other"` if unmatched.
+    pub fn device_family(&self) -> &str {
+        self.device.as_ref().map_or("Other", |v| &v.device)
+    }
+}
+
+impl Client {
+    /// Borrows the values of the requested domains, the others are
+    /// [`None`].
+    pub(crate) fn borrow_with(&self, domains: Domains) -> ClientRef<'_> {
+        ClientRef {
+            ua: self
+                .ua
+                .as_ref()
+                .filter(|_| domains.contains(Domains::UA))
+                .map(|v| user_agent::ValueRef {
+                    family: (&*v.family).into(),
+                    major: v.major.as_deref().map(Into::into),
+                    minor: v.minor.as_deref().map(Into::into),
+                    patch: v.patch.as_deref().map(Into::into),
+                    patch_minor: v.patch_minor.as_deref().map(Into::into),
+                }),
+            os: self
+                .os
+                .as_ref()
+                .filter(|_| domains.contains(Domains::OS))
+                .map(|v| os::ValueRef {
+                    os: (&*v.os).into(),
+                    major: v.major.as_deref().map(Into::into),
+                    minor: v.minor.as_deref().map(Into::into),
+                    patch: v.patch.as_deref().map(Into::into),
+                    patch_minor: v.patch_minor.as_deref().map(Into::into),
+                }),
+            device: self
+                .device
+                .as_ref()
+                .filter(|_| domains.contains(Domains::DEVICE))
+                .map(|v| device::ValueRef {
+                    device: (&*v.device).into(),
+                    brand: v.brand.as_deref().map(Into::into),
+                    model: v.model.as_deref().map(Into::into),
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn client() {
+        let c = ClientRef {
+            ua: Some(user_agent::ValueRef {
+                family: "Firefox".into(),
+                major: Some("121".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(c.family(), "Firefox");
+        assert_eq!(c.os_family(), "Other");
+
+        let json = serde_json::to_string(&c).unwrap();
+        let owned: Client = serde_json::from_str(&json).unwrap();
+        assert_eq!(owned.device_family(), "Other");
+        assert_eq!(owned, c.into_owned());
+    }
+}
@@ -0,0 +1,228 @@
+//! Static estimation of the matching cost of parsers, see
+//! [`Extractor::complexity`].
+
+use regex_syntax::hir::{Class, Hir, HirKind};
+use regex_syntax::utf8::Utf8Sequences;
+
+use crate::{device, engine, Domains, Extractor};
+
+/// Number of states above which a regex is considered slow to match
+/// on its own: the engines' lazy DFAs have to keep rebuilding their
+/// caches.
+const SLOW_STATES: usize = 10_000;
+/// Number of states above which a regex not covered by the prefilter
+/// (so checked against every user agent) is considered slow.
+const SLOW_UNFILTERED_STATES: usize = 1_000;
+
+/// Estimated worst-case matching cost of a parser's regex.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Complexity {
+    /// Approximate number of states of the regex's NFA, counted
+    /// bytewise: every repetition of a bounded repetition and every
+    /// UTF-8 range of a unicode class adds states.
+    pub states: usize,
+    /// Depth of the nesting of unbounded (or large) repetitions, e.g.
+    /// 2 for `(\w+ )+`.
+    pub nesting: usize,
+    /// Whether the prefilter covers the regex, otherwise it is
+    /// checked against every user agent.
+    pub prefiltered: bool,
+}
+
+impl Complexity {
+    /// Whether the parser is likely to slow extraction down: its NFA
+    /// is very large, it nests unbounded repetitions more than twice,
+    /// or it is not prefiltered and has a large NFA or nests
+    /// unbounded repetitions.
+    pub fn is_likely_slow(&self) -> bool {
+        self.states > SLOW_STATES
+            || self.nesting > 2
+            || !self.prefiltered && (self.states > SLOW_UNFILTERED_STATES || self.nesting > 1)
+    }
+
+    fn of(regex: &str, flags: device::Flags, prefiltered: bool) -> Option<Self> {
+        let hir = regex_syntax::Parser::from(flags.options())
+            .parse(regex)
+            .ok()?;
+        Some(Self {
+            states: states(&hir),
+            nesting: nesting(&hir),
+            prefiltered,
+        })
+    }
+}
+
+/// Complexity of a parser, as reported by [`Extractor::complexity`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ComplexityReport<'e> {
+    /// The parser's domain, one of [`Domains::UA`], [`Domains::OS`],
+    /// or [`Domains::DEVICE`].
+    pub domain: Domains,
+    /// Index of the parser in its domain.
+    pub index: usize,
+    /// The parser's regex, as rewritten by the extractor.
+    pub regex: &'e str,
+    ///
+    pub complexity: Complexity,
+}
+
+impl Extractor<'_> {
+    /// Estimates the worst-case matching cost of every parser, in
+    /// domain then parser order, so rules from third-party data which
+    /// are likely to be slow (see [`Complexity::is_likely_slow`]) can
+    /// be rejected or fixed before the extractor is put in
+    /// production.
+    ///
+    /// ```
+    /// # use ua_parser::{user_agent, Domains, Extractor, Regexes};
+    /// let extractor = Extractor::try_from(Regexes {
+    ///     user_agent_parsers: vec![
+    ///         user_agent::Parser::new(r"(Firefox)/(\d+)"),
+    ///         user_agent::Parser::new(r"(\w+ ?)+/(\d+)"),
+    ///     ],
+    ///     os_parsers: vec![],
+    ///     device_parsers: vec![],
+    /// })?;
+    /// let slow = extractor
+    ///     .complexity()
+    ///     .into_iter()
+    ///     .filter(|r| r.complexity.is_likely_slow())
+    ///     .map(|r| (r.domain, r.index))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(slow, [(Domains::UA, 1)]);
+    /// # Ok::<(), ua_parser::Error>(())
+    /// ```
+    pub fn complexity(&self) -> Vec<ComplexityReport<'_>> {
+        let mut reports = Vec::new();
+        report(
+            Domains::UA,
+            self.ua.matcher(),
+            self.ua.parsers().map(|p| p.regex_flag),
+            &mut reports,
+        );
+        report(
+            Domains::OS,
+            self.os.matcher(),
+            self.os.parsers().map(|p| p.regex_flag),
+            &mut reports,
+        );
+        report(
+            Domains::DEVICE,
+            self.dev.matcher(),
+            self.dev.parsers().map(|p| p.regex_flag),
+            &mut reports,
+        );
+        reports
+    }
+}
+
+fn report<'e>(
+    domain: Domains,
+    matcher: &'e regex_filtered::Regexes<engine::Regex>,
+    flags: impl Iterator<Item = Option<device::Flags>>,
+    reports: &mut Vec<ComplexityReport<'e>>,
+) {
+    for (index, (re, flags)) in std::iter::zip(matcher.regexes(), flags).enumerate() {
+        let prefiltered = !matcher.unfiltered().contains(&index);
+        if let Some(complexity) =
+            Complexity::of(re.as_str(), flags.unwrap_or_default(), prefiltered)
+        {
+            reports.push(ComplexityReport {
+                domain,
+                index,
+                regex: re.as_str(),
+                complexity,
+            });
+        }
+    }
+}
+
+/// Approximate number of NFA states of `hir`.
+fn states(hir: &Hir) -> usize {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => 1,
+        HirKind::Literal(l) => l.0.len(),
+        HirKind::Class(Class::Bytes(c)) => c.ranges().len(),
+        HirKind::Class(Class::Unicode(c)) => c
+            .ranges()
+            .iter()
+            .map(|r| Utf8Sequences::new(r.start(), r.end()).count())
+            .sum(),
+        HirKind::Capture(c) => states(&c.sub).saturating_add(2),
+        HirKind::Repetition(r) => {
+            let copies = r.max.unwrap_or(r.min.max(1)) as usize;
+            states(&r.sub).saturating_mul(copies).saturating_add(1)
+        }
+        HirKind::Concat(hs) | HirKind::Alternation(hs) => {
+            hs.iter().map(states).fold(hs.len(), usize::saturating_add)
+        }
+    }
+}
+
+/// Depth of the nesting of unbounded repetitions in `hir`, bounded
+/// repetitions of more than a few items count as unbounded.
+fn nesting(hir: &Hir) -> usize {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) | HirKind::Literal(_) | HirKind::Class(_) => 0,
+        HirKind::Capture(c) => nesting(&c.sub),
+        HirKind::Repetition(r) => nesting(&r.sub) + usize::from(r.max.map_or(true, |max| max > 10)),
+        HirKind::Concat(hs) | HirKind::Alternation(hs) => hs.iter().map(nesting).max().unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{user_agent, Regexes};
+
+    fn complexity(re: &str) -> Complexity {
+        Complexity::of(re, device::Flags::empty(), true).unwrap()
+    }
+
+    #[test]
+    fn estimate() {
+        let simple = complexity(r"(Foo)/(\d+)");
+        assert!(simple.states < 100, "{simple:?}");
+        assert_eq!(simple.nesting, 1);
+        assert!(!simple.is_likely_slow());
+
+        let nested = complexity(r"((\w+ ?)+/)*");
+        assert_eq!(nested.nesting, 3);
+        assert!(nested.is_likely_slow());
+
+        let large = complexity(r"\w{1,100}");
+        assert!(large.states > SLOW_STATES, "{large:?}");
+        // ascii classes are much smaller
+        assert!(complexity(r"(?-u:\w){1,100}").states < SLOW_UNFILTERED_STATES);
+        // case folding adds states
+        assert!(
+            Complexity::of("[a-z]", device::Flags::IGNORE_CASE, true)
+                .unwrap()
+                .states
+                > complexity("[a-z]").states
+        );
+    }
+
+    #[test]
+    fn report() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser::new(r"(Firefox)/(\d+)"),
+                // no literal for the prefilter
+                user_agent::Parser::new(r"(\w+ ?)+/(\d+)"),
+            ],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        })
+        .unwrap();
+        let r = e.complexity();
+        assert_eq!(r.len(), 2);
+        assert_eq!((r[0].domain, r[0].index), (Domains::UA, 0));
+        assert!(r[0].complexity.prefiltered);
+        assert!(!r[0].complexity.is_likely_slow());
+        assert_eq!(r[1].index, 1);
+        assert!(!r[1].complexity.prefiltered);
+        assert_eq!(r[1].complexity.nesting, 2);
+        assert!(r[1].complexity.is_likely_slow());
+    }
+}
@@ -0,0 +1,257 @@
+//! Harness for the [uap-core] conformance test suites, enabled by
+//! the `conformance` feature.
+//!
+//! This allows checking custom regexes files, rewriting
+//! configurations, or regex engines against the official
+//! expectations. The suites are read from their YAML files, and every
+//! case is extracted using the `extract_or_default` method of the
+//! corresponding domain extractor, so unmatched user agents are
+//! expected to be `Other`.
+//!
+//! ```no_run
+//! # use ua_parser::{conformance, Extractor, Regexes};
+//! let core = std::path::Path::new("uap-core");
+//! let regexes: Regexes = serde_yaml::from_reader(std::fs::File::open(core.join("regexes.yaml"))?)?;
+//! let extractor = Extractor::try_from(regexes)?;
+//!
+//! for suite in conformance::USER_AGENT_SUITES {
+//!     let file = std::fs::File::open(core.join(suite))?;
+//!     let report = conformance::user_agent(&extractor.ua, file)?;
+//!     assert!(report.is_success(), "{suite}: {:#?}", report.mismatches);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [uap-core]: https://github.com/ua-parser/uap-core
+
+use std::io::Read;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+
+use crate::{device, os, user_agent};
+
+/// Paths of the user agent suites, relative to the root of uap-core.
+pub const USER_AGENT_SUITES: &[&str] = &[
+    "tests/test_ua.yaml",
+    "test_resources/firefox_user_agent_strings.yaml",
+    "test_resources/pgts_browser_list.yaml",
+    "test_resources/opera_mini_user_agent_strings.yaml",
+    "test_resources/podcasting_user_agent_strings.yaml",
+];
+/// Paths of the OS suites, relative to the root of uap-core.
+pub const OS_SUITES: &[&str] = &[
+    "tests/test_os.yaml",
+    "test_resources/additional_os_tests.yaml",
+];
+/// Paths of the device suites, relative to the root of uap-core.
+pub const DEVICE_SUITES: &[&str] = &["tests/test_device.yaml"];
+
+/// The user agent suites use empty strings for missing values.
+fn empty_is_none<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(d)?.filter(|s| !s.is_empty()))
+}
+
+/// Expected result of a user agent test case.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct UserAgent {
+    ///
+    pub family: String,
+    ///
+    #[serde(deserialize_with = "empty_is_none")]
+    pub major: Option<String>,
+    ///
+    #[serde(deserialize_with = "empty_is_none")]
+    pub minor: Option<String>,
+    ///
+    #[serde(deserialize_with = "empty_is_none")]
+    pub patch: Option<String>,
+    ///
+    #[serde(default, deserialize_with = "empty_is_none")]
+    pub patch_minor: Option<String>,
+}
+impl From<user_agent::ValueRef<'_>> for UserAgent {
+    fn from(value: user_agent::ValueRef<'_>) -> Self {
+        let value = value.into_owned();
+        Self {
+            family: value.family,
+            major: value.major,
+            minor: value.minor,
+            patch: value.patch,
+            patch_minor: value.patch_minor,
+        }
+    }
+}
+
+/// Expected result of an OS test case.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Os {
+    ///
+    pub family: String,
+    ///
+    pub major: Option<String>,
+    ///
+    pub minor: Option<String>,
+    ///
+    pub patch: Option<String>,
+    ///
+    pub patch_minor: Option<String>,
+}
+impl From<os::ValueRef<'_>> for Os {
+    fn from(value: os::ValueRef<'_>) -> Self {
+        let value = value.into_owned();
+        Self {
+            family: value.os,
+            major: value.major,
+            minor: value.minor,
+            patch: value.patch,
+            patch_minor: value.patch_minor,
+        }
+    }
+}
+
+/// Expected result of a device test case.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Device {
+    ///
+    pub family: String,
+    ///
+    pub brand: Option<String>,
+    ///
+    pub model: Option<String>,
+}
+impl From<device::ValueRef<'_>> for Device {
+    fn from(value: device::ValueRef<'_>) -> Self {
+        let value = value.into_owned();
+        Self {
+            family: value.device,
+            brand: value.brand,
+            model: value.model,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Cases<T> {
+    test_cases: Vec<Case<T>>,
+}
+#[derive(Deserialize)]
+struct Case<T> {
+    user_agent_string: String,
+    #[serde(flatten)]
+    expected: T,
+}
+
+/// Test case whose extraction did not yield the expected result.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mismatch<T> {
+    ///
+    pub user_agent: String,
+    ///
+    pub expected: T,
+    ///
+    pub actual: T,
+}
+
+/// Result of running a suite.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Report<T> {
+    /// Number of test cases in the suite.
+    pub cases: usize,
+    /// The failed test cases, in the order of the suite.
+    pub mismatches: Vec<Mismatch<T>>,
+}
+impl<T> Report<T> {
+    /// Whether every test case of the suite succeeded.
+    pub fn is_success(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+fn run<T, F>(suite: impl Read, extract: F) -> Result<Report<T>, serde_yaml::Error>
+where
+    T: DeserializeOwned + PartialEq,
+    F: Fn(&str) -> T,
+{
+    let Cases { test_cases } = serde_yaml::from_reader(suite)?;
+    let cases = test_cases.len();
+    let mismatches = test_cases
+        .into_iter()
+        .filter_map(|case| {
+            let actual = extract(&case.user_agent_string);
+            (actual != case.expected).then_some(Mismatch {
+                user_agent: case.user_agent_string,
+                expected: case.expected,
+                actual,
+            })
+        })
+        .collect();
+    Ok(Report { cases, mismatches })
+}
+
+/// Runs a user agent suite (e.g. one of [`USER_AGENT_SUITES`])
+/// against the extractor.
+pub fn user_agent(
+    extractor: &user_agent::Extractor<'_>,
+    suite: impl Read,
+) -> Result<Report<UserAgent>, serde_yaml::Error> {
+    run(suite, |ua| extractor.extract_or_default(ua).into())
+}
+
+/// Runs an OS suite (e.g. one of [`OS_SUITES`]) against the
+/// extractor.
+pub fn os(
+    extractor: &os::Extractor<'_>,
+    suite: impl Read,
+) -> Result<Report<Os>, serde_yaml::Error> {
+    run(suite, |ua| extractor.extract_or_default(ua).into())
+}
+
+/// Runs a device suite (e.g. one of [`DEVICE_SUITES`]) against the
+/// extractor.
+pub fn device(
+    extractor: &device::Extractor<'_>,
+    suite: impl Read,
+) -> Result<Report<Device>, serde_yaml::Error> {
+    run(suite, |ua| extractor.extract_or_default(ua).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report() {
+        let e = user_agent::Builder::new()
+            .push(user_agent::Parser::new(r"(Firefox)/(\d+)\.(\d+)"))
+            .unwrap()
+            .build()
+            .unwrap();
+        let suite = r#"
+test_cases:
+  - user_agent_string: 'Firefox/121.0'
+    family: 'Firefox'
+    major: '121'
+    minor: '0'
+    patch:
+  - user_agent_string: 'Firefox/121'
+    family: 'Firefox'
+    major: '121'
+    minor: ''
+    patch:
+  - user_agent_string: 'curl/8.0'
+    family: 'Other'
+    major:
+    minor:
+    patch:
+"#;
+        let report = user_agent(&e, suite.as_bytes()).unwrap();
+        assert_eq!(report.cases, 3);
+        assert!(!report.is_success());
+        assert_eq!(report.mismatches.len(), 1);
+        let m = &report.mismatches[0];
+        assert_eq!(m.user_agent, "Firefox/121");
+        assert_eq!(m.expected.major.as_deref(), Some("121"));
+        assert_eq!(m.actual.family, "Other");
+    }
+}
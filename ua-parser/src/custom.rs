@@ -0,0 +1,336 @@
+//! User-defined extraction domains.
+//!
+//! A custom domain is defined by its list of fields, and uses the same
+//! prefiltered machinery as the built-in domains. Its parsers follow
+//! the usual format: a regex, and for each field an optional
+//! `<field>_replacement`, fully templated. Without a replacement, the
+//! `n`th field falls back to the `n`th group of the regex.
+//!
+//! ```yaml
+//! - regex: '(Thunderbird)/(\d+)'
+//! - regex: 'Outlook-iOS/[\d.]+'
+//!   name_replacement: 'Outlook'
+//!   platform_replacement: 'iOS'
+//! ```
+//!
+//! ```
+//! # use ua_parser::custom;
+//! let parsers: Vec<custom::Parser> = serde_json::from_str(r#"[
+//!     {"regex": "(Thunderbird)/(\\d+)"},
+//!     {"regex": "Outlook-iOS/", "name_replacement": "Outlook", "platform_replacement": "iOS"}
+//! ]"#)?;
+//! let extractor = custom::Builder::new(["name", "version", "platform"])
+//!     .push_all(parsers)?
+//!     .build()?;
+//!
+//! let v = extractor
+//!     .extract("Mozilla/5.0 (X11; Linux x86_64; rv:115.0) Thunderbird/115")
+//!     .unwrap();
+//! assert_eq!(v.get("name"), Some("Thunderbird"));
+//! assert_eq!(v.get("version"), Some("115"));
+//! assert_eq!(v.get("platform"), None);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use serde::{Deserialize, Deserializer};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use regex_filtered::BuildError;
+
+use crate::device::Flags;
+use crate::engine;
+use crate::resolvers::OptResolver;
+use crate::rewrite;
+use crate::{EmptyCapture, Error};
+
+/// Custom parser description.
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+pub struct Parser<'a> {
+    /// Regex pattern to use for matching and data extraction.
+    pub regex: Cow<'a, str>,
+    /// Configuration flags for the regex, if any.
+    #[serde(alias = "regexFlag")]
+    pub regex_flag: Option<Flags>,
+    /// Replacements by field name, deserialized from the
+    /// `<field>_replacement` keys. Replacements for fields the
+    /// builder does not know about are ignored.
+    #[serde(flatten, deserialize_with = "replacements")]
+    pub replacements: BTreeMap<String, Cow<'a, str>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser with the provided regex and no
+    /// replacements, to be completed by the other methods.
+    pub fn new(regex: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            regex: regex.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets [`Self::regex_flag`].
+    pub fn regex_flag(mut self, flags: Flags) -> Self {
+        self.regex_flag = Some(flags);
+        self
+    }
+
+    /// Sets the replacement of `field`.
+    pub fn replacement(
+        mut self,
+        field: impl Into<String>,
+        replacement: impl Into<Cow<'a, str>>,
+    ) -> Self {
+        self.replacements.insert(field.into(), replacement.into());
+        self
+    }
+}
+
+/// Collects the `<field>_replacement` entries, keyed by field.
+fn replacements<'de, 'a, D: Deserializer<'de>>(
+    d: D,
+) -> Result<BTreeMap<String, Cow<'a, str>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Entry {
+        String(String),
+        Other(serde::de::IgnoredAny),
+    }
+    Ok(BTreeMap::<String, Entry>::deserialize(d)?
+        .into_iter()
+        .filter_map(|(k, v)| match v {
+            Entry::String(s) => Some((k.strip_suffix("_replacement")?.to_string(), s.into())),
+            Entry::Other(_) => None,
+        })
+        .collect())
+}
+
+/// Extractor builder.
+pub struct Builder<'a> {
+    fields: Vec<String>,
+    builder: regex_filtered::Builder<engine::Regex>,
+    repl: Vec<Vec<OptResolver<'a>>>,
+    rewrite: rewrite::Config,
+    empty: EmptyCapture,
+    strict: bool,
+}
+impl<'a> Builder<'a> {
+    /// Initialise an empty builder for a domain with the provided
+    /// fields, in group order.
+    pub fn new<I>(fields: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            fields: fields.into_iter().map(Into::into).collect(),
+            builder: Default::default(),
+            repl: Vec::new(),
+            rewrite: Default::default(),
+            empty: Default::default(),
+            strict: false,
+        }
+    }
+
+    /// Builds the extractor, may fail if compiling the prefilter
+    /// fails.
+    pub fn build(self) -> Result<Extractor<'a>, BuildError> {
+        let Self {
+            fields,
+            builder,
+            repl,
+            empty,
+            ..
+        } = self;
+
+        Ok(Extractor {
+            fields,
+            matcher: builder.build()?,
+            repl,
+            empty,
+        })
+    }
+
+    /// Sets the rewriting applied to the regexes of the parsers
+    /// pushed afterwards.
+    pub fn rewrite(mut self, rewrite: rewrite::Config) -> Self {
+        self.rewrite = rewrite;
+        self
+    }
+
+    /// Sets the handling of empty capture groups.
+    pub fn empty_captures(mut self, empty: EmptyCapture) -> Self {
+        self.empty = empty;
+        self
+    }
+
+    /// Whether replacements referencing groups missing from their
+    /// regex are errors, disabled by default: the references expand
+    /// to an empty string.
+    pub fn strict_templates(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Adds a parser to the set, may fail if parsing the regex fails,
+    /// or (with [`Self::strict_templates`]) if a replacement
+    /// references a group missing from it.
+    pub fn push(mut self, mut parser: Parser<'a>) -> Result<Self, Error> {
+        let regex = self.rewrite.rewrite(&parser.regex);
+        let flags = parser.regex_flag.unwrap_or_default();
+        let (fields, strict) = (&self.fields, self.strict);
+        let repl = crate::domain::push_regex(&mut self.builder, None, &regex, flags, |r| {
+            fields
+                .iter()
+                .enumerate()
+                .map(|(i, f)| OptResolver::new(parser.replacements.remove(f), r, i + 1, strict))
+                .collect()
+        })?;
+        self.repl.push(repl);
+        Ok(self)
+    }
+
+    /// Bulk loading of parsers into the builder.
+    pub fn push_all<I>(self, parsers: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Parser<'a>>,
+    {
+        parsers.into_iter().try_fold(self, |s, p| s.push(p))
+    }
+}
+
+/// Custom domain extractor.
+pub struct Extractor<'a> {
+    fields: Vec<String>,
+    matcher: regex_filtered::Regexes<engine::Regex>,
+    repl: Vec<Vec<OptResolver<'a>>>,
+    empty: EmptyCapture,
+}
+impl<'a> Extractor<'a> {
+    /// Performs the extraction using the first matching parser,
+    /// returns [`None`] if no parser matches.
+    pub fn extract(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
+        let (idx, re) = self.matcher.matching(ua).next()?;
+        let c = engine::captures(re, ua)?;
+        Some(ValueRef {
+            fields: &self.fields,
+            values: self.repl[idx]
+                .iter()
+                .map(|r| r.resolve(&c, self.empty))
+                .collect(),
+        })
+    }
+
+    /// Returns whether any parser matches the user agent, without
+    /// performing the extraction.
+    pub fn is_match(&self, ua: &str) -> bool {
+        self.matcher.is_match(ua)
+    }
+
+    /// The fields of the domain.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+}
+
+/// Extracted values, may borrow from one of the [`Parser`] or from
+/// the user agent string.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ValueRef<'a> {
+    fields: &'a [String],
+    values: Vec<Option<Cow<'a, str>>>,
+}
+
+impl ValueRef<'_> {
+    /// Value of the field, [`None`] if the field is unset or unknown.
+    pub fn get(&self, field: &str) -> Option<&str> {
+        let idx = self.fields.iter().position(|f| f == field)?;
+        self.values[idx].as_deref()
+    }
+
+    /// Iterates over the fields of the domain and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        std::iter::zip(self.fields, &self.values).map(|(f, v)| (&**f, v.as_deref()))
+    }
+
+    /// Converts [`Self`] to an owned [`Value`] getting rid of
+    /// borrowing concerns.
+    pub fn into_owned(self) -> Value {
+        Value(
+            std::iter::zip(self.fields, self.values)
+                .filter_map(|(f, v)| Some((f.clone(), v?.into_owned())))
+                .collect(),
+        )
+    }
+}
+
+/// Owned version of [`ValueRef`], the fields which are set and their
+/// values.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct Value(pub BTreeMap<String, String>);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extract() {
+        let parsers: Vec<Parser> = serde_yaml::from_str(
+            r#"
+- regex: '(Thunderbird)/(\d+)'
+- regex: 'Outlook-(\w+)/([\d.]+)'
+  name_replacement: 'Outlook'
+  version_replacement: '$2'
+  platform_replacement: '$1'
+  os_replacement: 'ignored'
+  device_type: 4
+"#,
+        )
+        .unwrap();
+        let e = Builder::new(["name", "version", "platform"])
+            .push_all(parsers)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let v = e.extract("Thunderbird/115").unwrap();
+        assert_eq!(
+            v.iter().collect::<Vec<_>>(),
+            [
+                ("name", Some("Thunderbird")),
+                ("version", Some("115")),
+                ("platform", None),
+            ],
+        );
+        let v = e.extract("Outlook-iOS/2.0").unwrap().into_owned();
+        assert_eq!(
+            v.0.iter().map(|(k, v)| (&**k, &**v)).collect::<Vec<_>>(),
+            [("name", "Outlook"), ("platform", "iOS"), ("version", "2.0")],
+        );
+        assert!(e.extract("Mozilla/5.0").is_none());
+        assert!(!e.is_match("Mozilla/5.0"));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            Builder::new(["name"]).push(Parser {
+                regex: "(".into(),
+                ..Default::default()
+            }),
+            Err(Error::ParseError(_))
+        ));
+        assert!(matches!(
+            Builder::new(["name"])
+                .strict_templates(true)
+                .push(Parser::new("(a)").replacement("name", "$2")),
+            Err(Error::MissingGroup(2))
+        ));
+        let e = Builder::new(["name"])
+            .push(Parser::new("(a)").replacement("name", "$1$2"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(e.extract("a").unwrap().get("name"), Some("a"));
+    }
+}
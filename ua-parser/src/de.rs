@@ -0,0 +1,157 @@
+//! Parsing of user agent fields during deserialization.
+//!
+//! Serde has no way to pass context to deserializers, so the
+//! extractor is configured for the current thread with
+//! [`with_extractor`], for the duration of a deserialization. With
+//! the `bundled` feature, `Extractor::global` is used when no
+//! extractor is configured.
+//!
+//! ```
+//! # use ua_parser::{de, Client, Extractor, Regexes};
+//! # let regexes = Regexes {
+//! #     user_agent_parsers: vec![ua_parser::user_agent::Parser {
+//! #         regex: r"(Firefox)/(\d+)".into(),
+//! #         ..Default::default()
+//! #     }],
+//! #     os_parsers: vec![],
+//! #     device_parsers: vec![],
+//! # };
+//! #[derive(serde::Deserialize)]
+//! struct Entry {
+//!     path: String,
+//!     #[serde(deserialize_with = "de::parse")]
+//!     user_agent: Client,
+//! }
+//!
+//! let extractor = Extractor::try_from(regexes)?.into_shared();
+//! let line = r#"{"path": "/", "user_agent": "Mozilla/5.0 Firefox/121"}"#;
+//! let entry: Entry = de::with_extractor(extractor, || serde_json::from_str(line))?;
+//! assert_eq!(entry.user_agent.family(), "Firefox");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::cell::RefCell;
+use std::ops::Deref;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+
+use crate::{Client, SharedExtractor};
+
+thread_local! {
+    static CURRENT: RefCell<Option<SharedExtractor<'static>>> = const { RefCell::new(None) };
+}
+
+/// Restores the previously configured extractor, even on unwinding.
+struct Restore(Option<SharedExtractor<'static>>);
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.with(|c| c.replace(self.0.take()));
+    }
+}
+
+/// Configures `extractor` for the deserializations performed by `f`
+/// on the current thread.
+///
+/// Calls can be nested, the previous extractor is restored when `f`
+/// returns.
+pub fn with_extractor<R>(extractor: SharedExtractor<'static>, f: impl FnOnce() -> R) -> R {
+    let _restore = Restore(CURRENT.with(|c| c.replace(Some(extractor))));
+    f()
+}
+
+fn extract(ua: &str) -> Option<Client> {
+    let current = CURRENT.with(|c| c.borrow().clone());
+    if let Some(e) = current {
+        return Some(e.extract(ua).into_owned());
+    }
+    #[cfg(feature = "bundled")]
+    return Some(crate::Extractor::global().extract(ua).into_owned());
+    #[cfg(not(feature = "bundled"))]
+    None
+}
+
+/// Deserializes a user agent string and extracts it, for use with
+/// `#[serde(deserialize_with = "ua_parser::de::parse")]`.
+///
+/// Fails if no extractor is configured.
+pub fn parse<'de, D: Deserializer<'de>>(d: D) -> Result<Client, D::Error> {
+    let ua = <std::borrow::Cow<'de, str>>::deserialize(d)?;
+    extract(&ua).ok_or_else(|| D::Error::custom("no user agent extractor configured"))
+}
+
+/// Extraction results deserialized from a user agent string, see
+/// [`parse`].
+#[derive(Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub struct ParsedUa(pub Client);
+
+impl<'de> Deserialize<'de> for ParsedUa {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        parse(d).map(Self)
+    }
+}
+
+impl Deref for ParsedUa {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.0
+    }
+}
+
+impl From<ParsedUa> for Client {
+    fn from(value: ParsedUa) -> Self {
+        value.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{os, user_agent, Extractor, Regexes};
+
+    fn extractor(ua: &str, os: &str) -> SharedExtractor<'static> {
+        Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser::new(ua.to_string())],
+            os_parsers: vec![os::Parser::new(os.to_string())],
+            device_parsers: vec![],
+        })
+        .unwrap()
+        .into_shared()
+    }
+
+    #[test]
+    fn parsed() {
+        let e = extractor(r"(Firefox)/(\d+)", r"(Linux)");
+        let uas: Vec<ParsedUa> = with_extractor(e, || {
+            serde_json::from_str(r#"["Firefox/121 (Linux)", "curl/8.0"]"#)
+        })
+        .unwrap();
+        assert_eq!(uas[0].family(), "Firefox");
+        assert_eq!(uas[0].os_family(), "Linux");
+        assert_eq!(uas[1].0, Client::default());
+    }
+
+    #[test]
+    fn nested() {
+        let outer = extractor(r"(Firefox)", r"(Linux)");
+        let inner = extractor(r"(curl)", r"(Linux)");
+        let (a, b, c) = with_extractor(outer, || {
+            let a: ParsedUa = serde_json::from_str(r#""curl Firefox""#).unwrap();
+            let b: ParsedUa =
+                with_extractor(inner, || serde_json::from_str(r#""curl Firefox""#).unwrap());
+            let c: ParsedUa = serde_json::from_str(r#""curl Firefox""#).unwrap();
+            (a, b, c)
+        });
+        assert_eq!(a.family(), "Firefox");
+        assert_eq!(b.family(), "curl");
+        assert_eq!(c.family(), "Firefox");
+    }
+
+    #[cfg(not(feature = "bundled"))]
+    #[test]
+    fn unconfigured() {
+        let e = serde_json::from_str::<ParsedUa>(r#""Firefox/121""#).unwrap_err();
+        assert!(e.to_string().contains("no user agent extractor"));
+    }
+}
@@ -0,0 +1,95 @@
+//! Deduplication of identical regexes across domains.
+//!
+//! Some patterns appear in more than one of the parser lists. When
+//! building a full [`crate::Extractor`] the compiled regexes are
+//! shared between domains (all supported engines' regexes are
+//! reference-counted internally, so cloning them is cheap), and the
+//! duplicates are recorded so the result of verifying one can be
+//! reused for the others.
+
+use std::collections::HashMap;
+
+use regex_filtered::ParseError;
+
+use crate::device::Flags;
+use crate::engine;
+
+#[derive(Default)]
+pub(crate) struct Dedup {
+    /// Compiled regexes by pattern and flags, along with
+    /// the index of their first occurrence.
+    cache: HashMap<(String, Flags), (usize, engine::Regex)>,
+    /// Index of the first occurrence of each pushed regex.
+    canonical: Vec<usize>,
+}
+
+impl Dedup {
+    /// Compiles the regex using `builder`, or reuses the compiled
+    /// regex if it was already recorded for this or another builder.
+    pub(crate) fn compile(
+        &self,
+        builder: &regex_filtered::Builder<engine::Regex>,
+        regex: &str,
+        flags: Flags,
+    ) -> Result<engine::Regex, ParseError> {
+        match self.cache.get(&(regex.to_string(), flags)) {
+            Some((_, re)) => Ok(re.clone()),
+            None => builder.compile(regex, &flags.options()),
+        }
+    }
+
+    /// Records a regex as pushed into its builder, must be called
+    /// exactly once per regex pushed, in order.
+    pub(crate) fn record(&mut self, regex: &engine::Regex, flags: Flags) {
+        let idx = self.canonical.len();
+        let first = self
+            .cache
+            .entry((regex.as_str().to_string(), flags))
+            .or_insert_with(|| (idx, regex.clone()))
+            .0;
+        self.canonical.push(first);
+    }
+
+    /// For each regex pushed, the index of its first occurrence if it
+    /// is duplicated.
+    pub(crate) fn finish(self) -> Box<[Option<usize>]> {
+        let mut duplicated = vec![false; self.canonical.len()];
+        for (idx, &first) in self.canonical.iter().enumerate() {
+            if idx != first {
+                duplicated[first] = true;
+            }
+        }
+        self.canonical
+            .iter()
+            .map(|&first| duplicated[first].then_some(first))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dedup() {
+        fn push(d: &mut Dedup, b: &mut regex_filtered::Builder<engine::Regex>, re: &str, f: Flags) {
+            let re = d.compile(b, re, f).unwrap();
+            b.push_compiled_mut(re.clone(), &f.options()).unwrap();
+            d.record(&re, f);
+        }
+
+        let mut d = Dedup::default();
+        let mut b = regex_filtered::Builder::<engine::Regex>::default();
+        push(&mut d, &mut b, "foo", Flags::default());
+        push(&mut d, &mut b, "bar", Flags::default());
+        push(&mut d, &mut b, "foo", Flags::IGNORE_CASE);
+        let mut other = regex_filtered::Builder::<engine::Regex>::default();
+        push(&mut d, &mut other, "foo", Flags::default());
+        // invalid regexes are neither pushed nor recorded
+        assert!(d.compile(&other, "(", Flags::default()).is_err());
+
+        assert_eq!(b.regexes().len(), 3);
+        assert_eq!(other.regexes().len(), 1);
+        assert_eq!(&*d.finish(), &[Some(0), None, None, Some(0)]);
+    }
+}
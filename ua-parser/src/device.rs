@@ -0,0 +1,447 @@
+//! Extraction module for the device data of the user agent string.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use regex_filtered::{BuildError, ParseError};
+
+use crate::dedup::Dedup;
+use crate::domain;
+use crate::engine;
+use crate::resolvers::{OptResolver, Resolver};
+use crate::Interner;
+
+/// Set of regex flags, deserializes from a string of flag letters
+/// e.g. `"i"` or `"is"`, as supported by
+/// [`regex_filtered::Options::from_flags`]. Flags can be combined
+/// using `|`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Default, Debug)]
+pub struct Flags(u8);
+impl Flags {
+    /// `i`, enables case-insensitive matching.
+    pub const IGNORE_CASE: Self = Self(1);
+    /// `s`, allows `.` to match `\n`.
+    pub const DOT_ALL: Self = Self(1 << 1);
+    /// `m`, makes `^` and `$` match at the start and end of lines.
+    pub const MULTI_LINE: Self = Self(1 << 2);
+    /// `x`, ignores whitespace and allows `#` comments.
+    pub const VERBOSE: Self = Self(1 << 3);
+    /// `R`, makes `\r` a line terminator along with `\n`.
+    pub const CRLF: Self = Self(1 << 4);
+    const ALL: Self = Self(0b1_1111);
+
+    /// No flag set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Flags set in either `self` or `other`, same as `|` but
+    /// usable in constants.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns whether all the flags of `other` are in `self`.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub(crate) fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        Self::ALL.contains(Self(bits)).then_some(Self(bits))
+    }
+
+    pub(crate) fn options(self) -> regex_filtered::Options {
+        let mut opts = regex_filtered::Options::new();
+        opts.case_insensitive(self.contains(Self::IGNORE_CASE))
+            .dot_matches_new_line(self.contains(Self::DOT_ALL))
+            .multi_line(self.contains(Self::MULTI_LINE))
+            .ignore_whitespace(self.contains(Self::VERBOSE))
+            .crlf(self.contains(Self::CRLF));
+        opts
+    }
+}
+impl std::ops::BitOr for Flags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+impl std::ops::BitOrAssign for Flags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+impl std::str::FromStr for Flags {
+    type Err = regex_filtered::UnknownFlag;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let opts = regex_filtered::Options::from_flags(s)?;
+        Ok([
+            (opts.get_case_insensitive(), Self::IGNORE_CASE),
+            (opts.get_dot_matches_new_line(), Self::DOT_ALL),
+            (opts.get_multi_line(), Self::MULTI_LINE),
+            (opts.get_ignore_whitespace(), Self::VERBOSE),
+            (opts.get_crlf(), Self::CRLF),
+        ]
+        .into_iter()
+        .filter(|&(set, _)| set)
+        .fold(Self::empty(), |flags, (_, flag)| flags | flag))
+    }
+}
+impl<'de> Deserialize<'de> for Flags {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Cow::<str>::deserialize(d)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Device parser description.
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+pub struct Parser<'a> {
+    /// Regex pattern to use for matching and data extraction.
+    pub regex: Cow<'a, str>,
+    /// Configuration flags for the regex, if any.
+    #[serde(alias = "regexFlag")]
+    pub regex_flag: Option<Flags>,
+    /// Device replacement data, fully templated, must be present
+    /// *or* the regex must have at least one group, which will be
+    /// used instead.
+    #[serde(alias = "deviceReplacement")]
+    pub device_replacement: Option<Cow<'a, str>>,
+    /// Brand replacement data, fully templated, optional, if
+    /// missing there is no fallback.
+    #[serde(alias = "brandReplacement")]
+    pub brand_replacement: Option<Cow<'a, str>>,
+    /// Model replacement data, fully templated, optional, if
+    /// missing will be replaced by the first group if the regex
+    /// has one.
+    #[serde(alias = "modelReplacement")]
+    pub model_replacement: Option<Cow<'a, str>>,
+    /// Extension fields of the parser (e.g. `device_type` in some
+    /// forks of `regexes.yaml`), with their values as strings.
+    /// Preserved for the application's use, they are not used by
+    /// the extraction. Only strings, numbers, and booleans are
+    /// supported, other values (e.g. lists) fail the deserialization.
+    #[serde(flatten, deserialize_with = "extra")]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser with the provided regex and no
+    /// replacements, to be completed by the other methods.
+    pub fn new(regex: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            regex: regex.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets [`Self::regex_flag`].
+    pub fn regex_flag(mut self, flags: Flags) -> Self {
+        self.regex_flag = Some(flags);
+        self
+    }
+
+    /// Sets [`Self::device_replacement`].
+    pub fn device(mut self, device: impl Into<Cow<'a, str>>) -> Self {
+        self.device_replacement = Some(device.into());
+        self
+    }
+
+    /// Sets [`Self::brand_replacement`].
+    pub fn brand(mut self, brand: impl Into<Cow<'a, str>>) -> Self {
+        self.brand_replacement = Some(brand.into());
+        self
+    }
+
+    /// Sets [`Self::model_replacement`].
+    pub fn model(mut self, model: impl Into<Cow<'a, str>>) -> Self {
+        self.model_replacement = Some(model.into());
+        self
+    }
+}
+
+/// Deserializes the extension fields of a [`Parser`], keeping
+/// scalars as strings and rejecting other values.
+fn extra<'de, D: Deserializer<'de>>(d: D) -> Result<BTreeMap<String, String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Scalar {
+        String(String),
+        Bool(bool),
+        Int(i64),
+        Float(f64),
+        Other(serde::de::IgnoredAny),
+    }
+
+    BTreeMap::<String, Scalar>::deserialize(d)?
+        .into_iter()
+        .map(|(k, v)| {
+            let v = match v {
+                Scalar::String(s) => s,
+                Scalar::Bool(b) => b.to_string(),
+                Scalar::Int(i) => i.to_string(),
+                Scalar::Float(f) => f.to_string(),
+                Scalar::Other(_) => {
+                    return Err(serde::de::Error::custom(format!(
+                        "extension field {k:?} must be a string, number, or boolean"
+                    )))
+                }
+            };
+            Ok((k, v))
+        })
+        .collect()
+}
+
+type Repl<'a> = (Resolver<'a>, OptResolver<'a>, OptResolver<'a>);
+
+/// Extractor builder.
+#[derive(Default)]
+pub struct Builder<'a> {
+    core: domain::Builder<Repl<'a>>,
+    /// [`Parser::extra`] of every parser.
+    extra: Vec<BTreeMap<String, String>>,
+}
+impl<'a> Builder<'a> {
+    /// Creates a builder in the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds an Extractor, may fail if compiling the prefilter fails.
+    pub fn build(self) -> Result<Extractor<'a>, BuildError> {
+        Ok(Extractor {
+            core: self.core.build()?,
+            extra: self.extra,
+        })
+    }
+
+    /// Add a parser to the set, may fail if parsing the regex
+    /// fails *or* if [`Parser::device_replacement`] is unset and
+    /// [`Parser::regex`] does not have at least one group, or (with
+    /// [`Self::strict_templates`]) a templated replacement requests
+    /// groups which [`Parser::regex`] is missing.
+    pub fn push(mut self, device: Parser<'a>) -> Result<Self, crate::Error> {
+        self.push_mut(device)?;
+        Ok(self)
+    }
+
+    /// Pushes a parser, leaving the builder unchanged on failure.
+    /// `index` is only used to report errors.
+    pub(crate) fn push_with(
+        &mut self,
+        device: Parser<'a>,
+        index: usize,
+        dedup: Option<&mut Dedup>,
+    ) -> Result<(), crate::Error> {
+        let strict = self.core.strict;
+        self.core
+            .push(&device.regex, device.regex_flag, dedup, |r| {
+                Ok((
+                    Resolver::new(device.device_replacement, r, 1, strict)?,
+                    // no fallback group for the brand
+                    OptResolver::new(device.brand_replacement, r, usize::MAX, strict)?,
+                    OptResolver::new(device.model_replacement, r, 1, strict)?,
+                ))
+            })
+            .map_err(|e| e.in_parser("device_parsers", index, &device.regex))?;
+        self.extra.push(device.extra);
+        Ok(())
+    }
+
+    domain::builder_methods!();
+}
+
+/// Collects parsers into a builder, failing on the first invalid
+/// parser.
+impl<'a> FromIterator<Parser<'a>> for Result<Builder<'a>, crate::Error> {
+    fn from_iter<I: IntoIterator<Item = Parser<'a>>>(iter: I) -> Self {
+        Builder::new().push_all(iter)
+    }
+}
+
+/// Device extractor object.
+pub struct Extractor<'a> {
+    pub(crate) core: domain::Extractor<Repl<'a>>,
+    extra: Vec<BTreeMap<String, String>>,
+}
+impl<'a> Extractor<'a> {
+    /// Perform data extraction from the user agent string,
+    /// returns `None` if no regex in the [`Extractor`] matches
+    /// the input.
+    pub fn extract(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
+        let (idx, _) = self.core.first(ua)?;
+        self.extract_at(idx, ua)
+    }
+
+    /// Same as [`Self::extract`], but if no parser matches falls
+    /// back to guessing the device from the user agent's tokens,
+    /// see [`crate::device_guess::guess`].
+    ///
+    /// The [`Confidence`](crate::device_guess::Confidence) tells
+    /// whether the value was extracted or guessed.
+    pub fn extract_or_guess(
+        &'a self,
+        ua: &'a str,
+    ) -> Option<(ValueRef<'a>, crate::device_guess::Confidence)> {
+        use crate::device_guess::{guess, Confidence};
+        match self.extract(ua) {
+            Some(v) => Some((v, Confidence::Parser)),
+            None => guess(ua).map(|v| (v, Confidence::Heuristic)),
+        }
+    }
+
+    fn resolve(&'a self, idx: usize, c: &engine::Captures<'a>) -> ValueRef<'a> {
+        let (d, v1, v2) = self.core.repl(idx);
+        let empty = self.core.empty;
+
+        ValueRef {
+            device: d.resolve(c),
+            brand: v1.resolve(c, empty),
+            model: v2.resolve(c, empty),
+        }
+    }
+
+    domain::extractor_methods!();
+
+    /// Turns the extractor back into a builder, reusing the
+    /// compiled regexes.
+    pub(crate) fn into_builder(self, dedup: Option<&mut Dedup>) -> Result<Builder<'a>, ParseError> {
+        Ok(Builder {
+            core: self.core.into_builder(dedup)?,
+            extra: self.extra,
+        })
+    }
+
+    /// Reconstructs the parsers the extractor was built from,
+    /// with their regexes as rewritten during building.
+    pub(crate) fn parsers(&self) -> impl Iterator<Item = Parser<'_>> {
+        std::iter::zip(self.core.parsers(), &self.extra).map(|((re, flag, (d, v1, v2)), extra)| {
+            Parser {
+                regex: re.as_str().into(),
+                regex_flag: flag,
+                device_replacement: d.replacement().map(From::from),
+                brand_replacement: v1.replacement().map(From::from),
+                model_replacement: v2.replacement().map(From::from),
+                extra: extra.clone(),
+            }
+        })
+    }
+}
+
+/// Extracted device content, may borrow from one of the
+/// [`Parser`] or from the user agent string.
+#[derive(Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct ValueRef<'a> {
+    ///
+    pub device: Cow<'a, str>,
+    ///
+    pub brand: Option<Cow<'a, str>>,
+    ///
+    pub model: Option<Cow<'a, str>>,
+}
+
+impl<'a> ValueRef<'a> {
+    /// The uap-core fallback value, for user agents no parser
+    /// matches: `device` is `"Other"`, everything else is unset.
+    pub const fn other() -> Self {
+        Self {
+            device: Cow::Borrowed("Other"),
+            brand: None,
+            model: None,
+        }
+    }
+    /// Converts [`Self`] to an owned [`Value`] getting rid of
+    /// borrowing concerns, may need to allocate and copy if any
+    /// of the attributes actually borrows from a [`Parser`] or
+    /// the user agent string.
+    pub fn into_owned(self) -> Value {
+        Value {
+            device: self.device.into_owned(),
+            brand: self.brand.map(|c| c.into_owned()),
+            model: self.model.map(|c| c.into_owned()),
+        }
+    }
+
+    /// Converts [`Self`] to a [`Value`] whose strings are shared
+    /// through the `interner`.
+    pub fn into_interned(self, interner: &Interner) -> Value<Arc<str>> {
+        Value {
+            device: interner.intern(&self.device),
+            brand: self.brand.map(|c| interner.intern(&c)),
+            model: self.model.map(|c| interner.intern(&c)),
+        }
+    }
+}
+
+impl ValueRef<'_> {
+    /// The fields of the value as `(name, value)` pairs, see
+    /// [`crate::user_agent::ValueRef::fields`].
+    pub fn fields(&self) -> [(&'static str, Option<&str>); 3] {
+        [
+            ("device", Some(&self.device)),
+            ("brand", self.brand.as_deref()),
+            ("model", self.model.as_deref()),
+        ]
+    }
+
+    /// The fields of the value as a map, see [`Self::fields`].
+    pub fn to_map(&self) -> BTreeMap<&'static str, Option<&str>> {
+        self.fields().into_iter().collect()
+    }
+}
+
+/// Formats the device family, e.g. `iPhone`.
+impl std::fmt::Display for ValueRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.device)
+    }
+}
+
+/// Owned version of [`ValueRef`], with [`String`] by default or
+/// interned [`Arc<str>`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct Value<S = String> {
+    ///
+    pub device: S,
+    ///
+    pub brand: Option<S>,
+    ///
+    pub model: Option<S>,
+}
+
+impl Value {
+    /// Owned version of [`ValueRef::other`].
+    pub fn other() -> Self {
+        ValueRef::other().into_owned()
+    }
+}
+
+impl<S: AsRef<str>> Value<S> {
+    /// Same as [`ValueRef::fields`].
+    pub fn fields(&self) -> [(&'static str, Option<&str>); 3] {
+        [
+            ("device", Some(self.device.as_ref())),
+            ("brand", self.brand.as_ref().map(AsRef::as_ref)),
+            ("model", self.model.as_ref().map(AsRef::as_ref)),
+        ]
+    }
+
+    /// The fields of the value as a map, see [`Self::fields`].
+    pub fn to_map(&self) -> BTreeMap<&'static str, Option<&str>> {
+        self.fields().into_iter().collect()
+    }
+}
+
+impl<S: AsRef<str>> std::fmt::Display for Value<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.device.as_ref())
+    }
+}
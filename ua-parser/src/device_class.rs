@@ -0,0 +1,153 @@
+//! Coarse classification of user agents by kind of device, see
+//! [`classify`].
+//!
+//! The uap-core device data identifies *which* device a user agent
+//! runs on (when it can), which does not directly answer the more
+//! common question of *what kind* of device it is. The classification
+//! combines the extracted values with well-known user agent tokens;
+//! it is heuristic, and may need adjusting for specific traffic.
+
+use crate::{device, os};
+
+/// Kind of device a user agent runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceClass {
+    ///
+    Desktop,
+    ///
+    Mobile,
+    ///
+    Tablet,
+    /// Smart TVs, set-top boxes, and streaming sticks.
+    Tv,
+    /// Gaming consoles, home or handheld.
+    Console,
+    /// Crawlers, spiders, and other automated agents.
+    Bot,
+    /// Anything unrecognised.
+    Other,
+}
+
+/// Matched as words (or word prefixes, e.g. `HeadlessChrome`), or at
+/// the end of a product name (e.g. `Googlebot/2.1`), but not within
+/// other words e.g. the `CUBOT` brand.
+const BOTS: &[&str] = &["bot", "crawler", "spider", "slurp", "headless"];
+const CONSOLES: &[&str] = &["playstation", "xbox", "nintendo"];
+const TVS: &[&str] = &[
+    "smart-tv", "smarttv", "googletv", "appletv", "hbbtv", "crkey", "roku", "bravia", "web0s",
+    "tizen tv",
+];
+const TABLETS: &[&str] = &["ipad", "tablet", "kindle", "silk/", "playbook"];
+const MOBILES: &[&str] = &["mobile", "iphone", "ipod", "android", "phone"];
+const DESKTOP_OSES: &[&str] = &[
+    "Windows",
+    "Mac OS X",
+    "Linux",
+    "Ubuntu",
+    "Fedora",
+    "Chrome OS",
+    "FreeBSD",
+    "OpenBSD",
+    "NetBSD",
+];
+
+/// Classifies the user agent string `ua`, using the OS and device
+/// extracted from it if available.
+///
+/// ```
+/// # use ua_parser::device_class::{classify, DeviceClass};
+/// let ua = "Mozilla/5.0 (iPad; CPU OS 17_0 like Mac OS X) Mobile/15E148";
+/// assert_eq!(classify(ua, None, None), DeviceClass::Tablet);
+/// ```
+pub fn classify(
+    ua: &str,
+    os: Option<&os::ValueRef<'_>>,
+    device: Option<&device::ValueRef<'_>>,
+) -> DeviceClass {
+    let lower = ua.to_ascii_lowercase();
+    let has = |tokens: &[&str]| tokens.iter().any(|t| lower.contains(t));
+    let device_family = device.map_or("", |d| &d.device);
+
+    if device_family == "Spider" || BOTS.iter().any(|t| has_word(&lower, t)) {
+        DeviceClass::Bot
+    } else if has(CONSOLES) {
+        DeviceClass::Console
+    } else if has(TVS) {
+        DeviceClass::Tv
+    } else if has(TABLETS)
+        // android tablets don't advertise themselves as mobile
+        || (lower.contains("android") && !lower.contains("mobile"))
+    {
+        DeviceClass::Tablet
+    } else if has(MOBILES) {
+        DeviceClass::Mobile
+    } else if os.is_some_and(|os| DESKTOP_OSES.iter().any(|d| os.os.starts_with(d)))
+        || device_family == "Mac"
+        || ["windows nt", "macintosh", "x11", "cros"]
+            .iter()
+            .any(|t| lower.contains(t))
+    {
+        DeviceClass::Desktop
+    } else {
+        DeviceClass::Other
+    }
+}
+
+/// Whether `token` appears in `s` at the start of a word, or at the
+/// end of a word which is followed by a product version (`/`) or
+/// suffix (`-`, `+`).
+fn has_word(s: &str, token: &str) -> bool {
+    s.match_indices(token).any(|(idx, _)| {
+        let before = s[..idx].chars().next_back();
+        let after = s[idx + token.len()..].chars().next();
+        before.map_or(true, |c| !c.is_ascii_alphanumeric())
+            || after.map_or(true, |c| matches!(c, '/' | '-' | '+'))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        for (ua, class) in [
+            ("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)", DeviceClass::Bot),
+            ("Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)", DeviceClass::Bot),
+            ("Baiduspider+(+http://www.baidu.com/search/spider.htm)", DeviceClass::Bot),
+            ("AdsBot-Google (+http://www.google.com/adsbot.html)", DeviceClass::Bot),
+            ("Mozilla/5.0 (compatible; Yahoo! Slurp; http://help.yahoo.com/help/us/ysearch/slurp)", DeviceClass::Bot),
+            ("Mozilla/5.0 (X11; Linux x86_64) HeadlessChrome/120.0.0.0 Safari/537.36", DeviceClass::Bot),
+            ("Mozilla/5.0 (Linux; Android 10; CUBOT X30) AppleWebKit/537.36 Chrome/120.0 Mobile Safari/537.36", DeviceClass::Mobile),
+            ("Mozilla/5.0 (Linux; Android 9; CUBOT_NOTE_7 Build/PPR1) AppleWebKit/537.36 Chrome/120.0 Mobile Safari/537.36", DeviceClass::Mobile),
+            ("Mozilla/5.0 (PlayStation 5 3.21) AppleWebKit/605.1.15", DeviceClass::Console),
+            ("Mozilla/5.0 (Web0S; Linux/SmartTV) AppleWebKit/537.36", DeviceClass::Tv),
+            ("Mozilla/5.0 (Linux; Android 13; SM-X200) AppleWebKit/537.36 Chrome/120.0 Safari/537.36", DeviceClass::Tablet),
+            ("Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 Chrome/120.0 Mobile Safari/537.36", DeviceClass::Mobile),
+            ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) Mobile/15E148", DeviceClass::Mobile),
+            ("Mozilla/5.0 (Windows NT 10.0; Win64; x64) Gecko/20100101 Firefox/121.0", DeviceClass::Desktop),
+            ("Mozilla/5.0 (X11; Linux x86_64) Gecko/20100101 Firefox/121.0", DeviceClass::Desktop),
+            ("curl/8.0", DeviceClass::Other),
+        ] {
+            assert_eq!(classify(ua, None, None), class, "{ua}");
+        }
+    }
+
+    #[test]
+    fn values() {
+        let spider = device::ValueRef {
+            device: "Spider".into(),
+            ..Default::default()
+        };
+        assert_eq!(classify("Foo/1.0", None, Some(&spider)), DeviceClass::Bot);
+
+        let windows = os::ValueRef {
+            os: "Windows".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            classify("Foo/1.0", Some(&windows), None),
+            DeviceClass::Desktop
+        );
+    }
+}
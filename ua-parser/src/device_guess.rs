@@ -0,0 +1,177 @@
+//! Heuristic device detection for user agents no device parser
+//! matches, see [`guess`].
+//!
+//! The long tail of Android devices is hardly covered by the uap-core
+//! device data, even though their user agents usually name the model,
+//! either before the `Build/` fragment or in a `Model/` token. The
+//! guessed values are much less reliable than those of the parsers,
+//! and are flagged as such by [`device::Extractor::extract_or_guess`].
+
+use std::borrow::Cow;
+
+use crate::device;
+
+/// Origin of a device value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Confidence {
+    /// Extracted by a device parser.
+    Parser,
+    /// Guessed by the heuristic, see [`guess`].
+    Heuristic,
+}
+
+/// Brands of well-known model prefixes, named as in uap-core.
+const BRANDS: &[(&str, &str)] = &[
+    ("SM-", "Samsung"),
+    ("GT-", "Samsung"),
+    ("SAMSUNG", "Samsung"),
+    ("Pixel", "Google"),
+    ("Redmi", "XiaoMi"),
+    ("Mi ", "XiaoMi"),
+    ("moto", "Motorola"),
+    ("Nokia", "Nokia"),
+    ("LG-", "LG"),
+    ("HUAWEI", "Huawei"),
+    ("ONEPLUS", "OnePlus"),
+    ("CPH", "Oppo"),
+    ("RMX", "Realme"),
+    ("vivo", "vivo"),
+];
+
+/// Tokens of the user agent which are not device models.
+const NOT_MODELS: &[&str] = &["Linux", "U", "K", "wv", "Mobile", "Tablet"];
+
+/// The model named before the `Build/` fragment, as in `(Linux;
+/// Android 10; SM-G973F Build/QP1A.190711.020)`.
+fn build_model(ua: &str) -> Option<&str> {
+    let end = ua.find(" Build/")?;
+    // models may contain parentheses, e.g. `moto g(60)`
+    let before = &ua[..end];
+    let start = before
+        .rfind(';')
+        .or_else(|| before.find('('))
+        .map_or(0, |i| i + 1);
+    Some(ua[start..end].trim())
+}
+
+/// The model of a `Model/XYZ` token.
+fn model_token(ua: &str) -> Option<&str> {
+    let start = ua.find("Model/")? + "Model/".len();
+    let rest = &ua[start..];
+    let rest = &rest[..rest.find([';', ')']).unwrap_or(rest.len())];
+    // models can contain spaces, but stop at the next product token
+    let end = rest
+        .match_indices(' ')
+        .find(|&(i, _)| {
+            rest[i + 1..]
+                .split(' ')
+                .next()
+                .is_some_and(|t| t.contains('/'))
+        })
+        .map_or(rest.len(), |(i, _)| i);
+    Some(rest[..end].trim())
+}
+
+/// Guesses the device of `ua` from its tokens, for user agents no
+/// device parser matches.
+///
+/// The model is taken as-is from the user agent, and the brand is
+/// only set for well-known model prefixes (e.g. `SM-` for Samsung).
+///
+/// ```
+/// # use ua_parser::device_guess::guess;
+/// let ua = "Mozilla/5.0 (Linux; Android 10; SM-G973F Build/QP1A.190711.020) Mobile Safari/537.36";
+/// let device = guess(ua).unwrap();
+/// assert_eq!(device.device, "Samsung SM-G973F");
+/// assert_eq!(device.brand.as_deref(), Some("Samsung"));
+/// assert_eq!(device.model.as_deref(), Some("SM-G973F"));
+/// ```
+pub fn guess(ua: &str) -> Option<device::ValueRef<'_>> {
+    let model = build_model(ua)
+        .or_else(|| model_token(ua))
+        .filter(|m| !m.is_empty() && !NOT_MODELS.contains(m) && !m.starts_with("Android"))?;
+    let brand = BRANDS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|&(_, brand)| brand);
+
+    Some(device::ValueRef {
+        device: match brand {
+            Some(b) if !model.starts_with(b) => Cow::Owned(format!("{b} {model}")),
+            _ => Cow::Borrowed(model),
+        },
+        brand: brand.map(Cow::Borrowed),
+        model: Some(Cow::Borrowed(model)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn guesses() {
+        for (ua, expected) in [
+            (
+                "Mozilla/5.0 (Linux; U; Android 4.4.2; en-us; GT-I9505 Build/KOT49H) Mobile Safari/534.30",
+                Some(("Samsung GT-I9505", Some("Samsung"), "GT-I9505")),
+            ),
+            (
+                "Dalvik/2.1.0 (Linux; U; Android 11; TECNO KF6i Build/RP1A.200720.011)",
+                Some(("TECNO KF6i", None, "TECNO KF6i")),
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 12; moto g(60) Build/S2RI32.32-20-9)",
+                Some(("Motorola moto g(60)", Some("Motorola"), "moto g(60)")),
+            ),
+            (
+                "SomeApp/1.2 (Android 13; Model/Pixel 7; Scale/2.6)",
+                Some(("Google Pixel 7", Some("Google"), "Pixel 7")),
+            ),
+            (
+                "SomeApp/1.2 Model/XYZ-1 Foo/2.0",
+                Some(("XYZ-1", None, "XYZ-1")),
+            ),
+            (
+                "Nokia 3.4 Build/RKQ1",
+                Some(("Nokia 3.4", Some("Nokia"), "Nokia 3.4")),
+            ),
+            // reduced user agents don't name the model
+            ("Mozilla/5.0 (Linux; Android 10; K) Chrome/120.0 Mobile Safari/537.36", None),
+            ("Mozilla/5.0 (Linux; Android 10; wv Build/QP1A)", None),
+            ("curl/8.0", None),
+        ] {
+            let actual = guess(ua);
+            assert_eq!(
+                actual
+                    .as_ref()
+                    .map(|d| (&*d.device, d.brand.as_deref(), d.model.as_deref().unwrap())),
+                expected,
+                "{ua}"
+            );
+        }
+    }
+
+    #[test]
+    fn extract_or_guess() {
+        let e = device::Builder::new()
+            .push(device::Parser::new("(Pixel) (\\d+)").model("$1 $2"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let (v, c) = e.extract_or_guess("(Linux; Android 14; Pixel 8)").unwrap();
+        assert_eq!(
+            (v.model.as_deref(), c),
+            (Some("Pixel 8"), Confidence::Parser)
+        );
+        let (v, c) = e
+            .extract_or_guess("(Linux; Android 14; SM-S918B Build/UP1A)")
+            .unwrap();
+        assert_eq!(
+            (v.model.as_deref(), c),
+            (Some("SM-S918B"), Confidence::Heuristic)
+        );
+        assert!(e.extract_or_guess("curl/8.0").is_none());
+    }
+}
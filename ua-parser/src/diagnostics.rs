@@ -0,0 +1,229 @@
+//! Rich rendering of build errors through [`miette`], see
+//! [`ErrorReport`].
+
+use std::fmt::{Display, Formatter};
+
+use miette::{Diagnostic, LabeledSpan, SourceCode};
+use regex_syntax::ast;
+
+use crate::{Error, ParseError};
+
+/// Wrapper of an [`Error`] implementing [`miette::Diagnostic`], for
+/// reporting to humans: errors in a parser are rendered with the
+/// parser's location, its regex, and a label pointing at the cause
+/// of the error within the regex (e.g. where parsing failed).
+///
+/// ```
+/// # use ua_parser::{diagnostics::ErrorReport, user_agent, Extractor, Regexes};
+/// let err = Extractor::try_from(Regexes {
+///     user_agent_parsers: vec![
+///         user_agent::Parser::new(r"(Foo)/(\d+)"),
+///         user_agent::Parser::new(r"(Bar)/(\d+"),
+///     ],
+///     os_parsers: vec![],
+///     device_parsers: vec![],
+/// })
+/// .err()
+/// .unwrap();
+/// let report = ErrorReport::from(err);
+/// assert_eq!(
+///     report.to_string(),
+///     "invalid parser user_agent_parsers[1]: invalid regex",
+/// );
+/// // e.g. `Err(report)?` in a function returning `miette::Result`
+/// let report = miette::Report::new(report);
+/// ```
+#[derive(Debug)]
+pub struct ErrorReport(Error);
+
+impl ErrorReport {
+    /// The reported error.
+    pub fn error(&self) -> &Error {
+        &self.0
+    }
+
+    /// Unwraps the reported error.
+    pub fn into_error(self) -> Error {
+        self.0
+    }
+
+    /// The regex of the failing parser, and the cause of the
+    /// failure, if the error is located in a parser.
+    fn located(&self) -> Option<(&String, &Error)> {
+        match &self.0 {
+            Error::InvalidParser { regex, source, .. } => Some((regex, source)),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for ErrorReport {
+    fn from(value: Error) -> Self {
+        Self(value)
+    }
+}
+
+impl std::error::Error for ErrorReport {}
+
+impl Display for ErrorReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Summary(&self.0).fmt(f)
+    }
+}
+
+/// Short human-readable description of an error.
+struct Summary<'e>(&'e Error);
+
+impl Display for Summary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Error::InvalidParser {
+                domain,
+                index,
+                source,
+                ..
+            } => write!(f, "invalid parser {domain}[{index}]: {}", Summary(source)),
+            Error::ParseError(ParseError::SyntaxError(_)) => f.write_str("invalid regex"),
+            Error::ParseError(ParseError::ProcessingError(_)) => {
+                f.write_str("regex could not be prefiltered")
+            }
+            Error::ParseError(ParseError::RegexTooLarge(_)) => f.write_str("regex too large"),
+            Error::BuildError(e) => write!(f, "prefilter could not be built: {e}"),
+            Error::MissingGroup(n) => write!(f, "replacement references missing group {n}"),
+            Error::MissingNamedGroup(name) => {
+                write!(f, "replacement references missing group `{name}`")
+            }
+            Error::InvalidArtifact(reason) => write!(f, "invalid artifact: {reason}"),
+        }
+    }
+}
+
+impl Diagnostic for ErrorReport {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let (regex, cause) = self.located()?;
+        let help = match cause {
+            Error::ParseError(ParseError::SyntaxError(_)) => match regex_syntax::parse(regex) {
+                Err(regex_syntax::Error::Parse(e))
+                    if matches!(
+                        e.kind(),
+                        ast::ErrorKind::UnsupportedLookAround
+                            | ast::ErrorKind::UnsupportedBackreference
+                    ) =>
+                {
+                    "the regex engines support neither look-around nor backreferences, \
+                     `Regexes::translate_js` can translate or strip them"
+                }
+                _ => return None,
+            },
+            Error::ParseError(ParseError::RegexTooLarge(_)) => {
+                "large bounded repetitions and unicode classes are the usual culprits, \
+                 e.g. `(?-u:\\w)` is much smaller than `\\w`"
+            }
+            Error::MissingGroup(_) | Error::MissingNamedGroup(_) => {
+                "add the group to the regex, or fix the parser's replacement"
+            }
+            _ => return None,
+        };
+        Some(Box::new(help))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.located().map(|(regex, _)| regex as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let (regex, cause) = self.located()?;
+        Some(Box::new(std::iter::once(label(regex, cause))))
+    }
+}
+
+/// Labels the part of `regex` responsible for `cause`, or the entire
+/// regex if it can not be narrowed down.
+fn label(regex: &str, cause: &Error) -> LabeledSpan {
+    let whole = |label: String| LabeledSpan::new_with_span(Some(label), 0..regex.len());
+    match cause {
+        Error::ParseError(ParseError::SyntaxError(message)) => {
+            let (span, kind) = match regex_syntax::parse(regex) {
+                Err(regex_syntax::Error::Parse(e)) => (*e.span(), e.kind().to_string()),
+                Err(regex_syntax::Error::Translate(e)) => (*e.span(), e.kind().to_string()),
+                // the regex was rewritten into an invalid one, or
+                // the engine has a different syntax
+                _ => return whole(message.lines().last().unwrap_or_default().to_string()),
+            };
+            LabeledSpan::new_with_span(Some(kind), span.start.offset..span.end.offset)
+        }
+        Error::ParseError(ParseError::ProcessingError(e)) => whole(e.to_string()),
+        Error::ParseError(ParseError::RegexTooLarge(limit)) => {
+            whole(format!("compiles to more than {limit} bytes"))
+        }
+        Error::MissingGroup(_) => match regex_syntax::parse(regex) {
+            Ok(hir) => whole(format!(
+                "has {} groups",
+                hir.properties().explicit_captures_len()
+            )),
+            Err(_) => whole("missing the group".to_string()),
+        },
+        Error::MissingNamedGroup(name) => whole(format!("has no group named `{name}`")),
+        e => whole(Summary(e).to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{device, user_agent, Extractor, Regexes};
+
+    fn report(regexes: Regexes) -> String {
+        let err = ErrorReport::from(Extractor::try_from(regexes).err().unwrap());
+        let mut out = String::new();
+        miette::NarratableReportHandler::new()
+            .render_report(&mut out, &err)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn syntax() {
+        let out = report(Regexes {
+            user_agent_parsers: vec![user_agent::Parser::new(r"(Foo)/(\d+")],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        });
+        assert!(
+            out.starts_with("invalid parser user_agent_parsers[0]: invalid regex"),
+            "{out}"
+        );
+        // the unclosed group
+        assert!(
+            out.contains("label at line 1, column 7: unclosed group"),
+            "{out}"
+        );
+
+        let out = report(Regexes {
+            user_agent_parsers: vec![],
+            os_parsers: vec![],
+            device_parsers: vec![device::Parser::new(r"Foo(?=Bar)")],
+        });
+        assert!(out.contains("device_parsers[0]"), "{out}");
+        assert!(out.contains("columns 4 to 6: look-around"), "{out}");
+        assert!(
+            out.contains("neither look-around nor backreferences"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn groups() {
+        let out = report(Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                family_replacement: Some("$1".into()),
+                ..user_agent::Parser::new(r"Foo/\d+")
+            }],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        });
+        assert!(out.contains("missing group 1"), "{out}");
+        assert!(out.contains("columns 1 to 7: has 0 groups"), "{out}");
+        assert!(out.contains("add the group to the regex"), "{out}");
+    }
+}
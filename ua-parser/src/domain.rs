@@ -0,0 +1,427 @@
+//! State and methods shared by the [`user_agent`](crate::user_agent),
+//! [`os`](crate::os), and [`device`](crate::device) extractors, which
+//! only differ by their parsers and by how a match is resolved to a
+//! value.
+//!
+//! Each domain's builder and extractor wrap a [`Builder`] and an
+//! [`Extractor`], generic over the resolvers of a parser, and get
+//! their common public methods from [`builder_methods`] and
+//! [`extractor_methods`].
+
+use regex_filtered::{BuildError, ParseError};
+
+use crate::dedup::Dedup;
+use crate::device::Flags;
+use crate::resolvers::HeapSize;
+use crate::{engine, rewrite, EmptyCapture, Error};
+
+/// Builder state of a domain, `R` being the resolvers of a parser.
+pub(crate) struct Builder<R> {
+    builder: regex_filtered::Builder<engine::Regex>,
+    repl: Vec<R>,
+    flags: Vec<Option<Flags>>,
+    pub(crate) rewrite: rewrite::Config,
+    pub(crate) empty: EmptyCapture,
+    /// Whether replacements referencing missing groups are errors.
+    pub(crate) strict: bool,
+}
+impl<R> Default for Builder<R> {
+    fn default() -> Self {
+        Self {
+            builder: Default::default(),
+            repl: Vec::new(),
+            flags: Vec::new(),
+            rewrite: Default::default(),
+            empty: Default::default(),
+            strict: false,
+        }
+    }
+}
+impl<R> Builder<R> {
+    pub(crate) fn build(self) -> Result<Extractor<R>, BuildError> {
+        let Self {
+            builder,
+            repl,
+            flags,
+            rewrite,
+            empty,
+            strict,
+        } = self;
+
+        Ok(Extractor {
+            matcher: builder.build()?,
+            frequencies: Vec::new(),
+            repl,
+            flags,
+            rewrite,
+            empty,
+            strict,
+        })
+    }
+
+    /// Number of parsers pushed so far.
+    pub(crate) fn len(&self) -> usize {
+        self.repl.len()
+    }
+
+    /// Rewrites and pushes the regex of a parser, along with the
+    /// resolvers `prepare` creates from the compiled regex. The
+    /// builder is left unchanged on failure.
+    pub(crate) fn push(
+        &mut self,
+        regex: &str,
+        flag: Option<Flags>,
+        dedup: Option<&mut Dedup>,
+        prepare: impl FnOnce(&engine::Regex) -> Result<R, Error>,
+    ) -> Result<(), Error> {
+        let regex = self.rewrite.rewrite(regex);
+        let flags = flag.unwrap_or_default();
+        let repl = push_regex(&mut self.builder, dedup, &regex, flags, prepare)?;
+        self.repl.push(repl);
+        self.flags.push(flag);
+        Ok(())
+    }
+
+    pub(crate) fn matcher(&self) -> &regex_filtered::Builder<engine::Regex> {
+        &self.builder
+    }
+}
+
+/// Extractor state of a domain, `R` being the resolvers of a parser.
+pub(crate) struct Extractor<R> {
+    matcher: regex_filtered::Regexes<engine::Regex>,
+    /// Hit counts of the parsers, see `frequencies`.
+    pub(crate) frequencies: Vec<usize>,
+    repl: Vec<R>,
+    flags: Vec<Option<Flags>>,
+    pub(crate) rewrite: rewrite::Config,
+    pub(crate) empty: EmptyCapture,
+    pub(crate) strict: bool,
+}
+impl<R> Extractor<R> {
+    pub(crate) fn matcher(&self) -> &regex_filtered::Regexes<engine::Regex> {
+        &self.matcher
+    }
+
+    /// Resolvers of the parser at `idx`.
+    pub(crate) fn repl(&self, idx: usize) -> &R {
+        &self.repl[idx]
+    }
+
+    /// Index and regex of the first matching parser.
+    pub(crate) fn first<'s>(&'s self, ua: &'s str) -> Option<(usize, &'s engine::Regex)> {
+        self.matcher.matching(ua).next()
+    }
+
+    /// Whether any parser matches `ua`, verifying the candidates by
+    /// decreasing hit counts if any.
+    pub(crate) fn is_match(&self, ua: &str) -> bool {
+        matches_by_frequency(&self.matcher, &self.frequencies, ua)
+    }
+
+    pub(crate) fn memory_usage(&self) -> usize
+    where
+        R: HeapSize,
+    {
+        self.matcher.memory_usage() + self.repl.heap_size() + self.flags.heap_size()
+    }
+
+    /// Turns the extractor back into a builder, reusing the compiled
+    /// regexes. The frequencies are reset.
+    pub(crate) fn into_builder(
+        self,
+        mut dedup: Option<&mut Dedup>,
+    ) -> Result<Builder<R>, ParseError> {
+        let Self {
+            matcher,
+            frequencies: _,
+            repl,
+            flags,
+            rewrite,
+            empty,
+            strict,
+        } = self;
+        let mut builder = regex_filtered::Builder::default();
+        for (re, flag) in std::iter::zip(matcher.regexes(), &flags) {
+            let flags = flag.unwrap_or_default();
+            builder.push_compiled_mut(re.clone(), &flags.options())?;
+            if let Some(d) = dedup.as_deref_mut() {
+                d.record(re, flags);
+            }
+        }
+        Ok(Builder {
+            builder,
+            repl,
+            flags,
+            rewrite,
+            empty,
+            strict,
+        })
+    }
+
+    /// The regex (as rewritten during building), flags, and resolvers
+    /// of every parser, in order.
+    pub(crate) fn parsers(&self) -> impl Iterator<Item = (&engine::Regex, Option<Flags>, &R)> {
+        std::iter::zip(self.matcher.regexes(), &self.flags)
+            .zip(&self.repl)
+            .map(|((re, flag), repl)| (re, *flag, repl))
+    }
+}
+
+/// Public builder methods common to all domains, expanded in the
+/// `impl` block of each domain's `Builder`, which must have a `core`
+/// [`Builder`] and a `push_with` method.
+macro_rules! builder_methods {
+    () => {
+        /// Sets the rewriting applied to the regexes of the parsers
+        /// pushed afterwards.
+        pub fn rewrite(mut self, rewrite: crate::rewrite::Config) -> Self {
+            self.core.rewrite = rewrite;
+            self
+        }
+
+        /// Sets the handling of empty capture groups.
+        pub fn empty_captures(mut self, empty: crate::EmptyCapture) -> Self {
+            self.core.empty = empty;
+            self
+        }
+
+        /// Whether pushing a parser whose replacements reference
+        /// groups missing from its regex fails with
+        /// [`Error::MissingGroup`](crate::Error::MissingGroup) or
+        /// [`Error::MissingNamedGroup`](crate::Error::MissingNamedGroup),
+        /// disabled by default: the references expand to an empty
+        /// string.
+        pub fn strict_templates(mut self, strict: bool) -> Self {
+            self.core.strict = strict;
+            self
+        }
+
+        /// In-place version of [`Self::push`], the builder is left
+        /// unchanged if the parser is invalid.
+        pub fn push_mut(&mut self, parser: Parser<'a>) -> Result<(), crate::Error> {
+            self.push_with(parser, self.core.len(), None)
+        }
+
+        /// Bulk loading of parsers into the builder.
+        pub fn push_all<I>(self, parsers: I) -> Result<Self, crate::Error>
+        where
+            I: IntoIterator<Item = Parser<'a>>,
+        {
+            parsers.into_iter().try_fold(self, |s, p| s.push(p))
+        }
+
+        /// In-place version of [`Self::push_all`], stops at the first
+        /// invalid parser, the parsers before it are kept.
+        ///
+        /// This is the builders' equivalent of [`Extend`], which they
+        /// don't implement as it can't report invalid parsers.
+        pub fn push_all_mut<I>(&mut self, parsers: I) -> Result<(), crate::Error>
+        where
+            I: IntoIterator<Item = Parser<'a>>,
+        {
+            parsers.into_iter().try_for_each(|p| self.push_mut(p))
+        }
+
+        pub(crate) fn matcher(&self) -> &regex_filtered::Builder<crate::engine::Regex> {
+            self.core.matcher()
+        }
+    };
+}
+pub(crate) use builder_methods;
+
+/// Public extractor methods common to all domains, expanded in the
+/// `impl` block of each domain's `Extractor`, which must have a
+/// `core` [`Extractor`] as well as `extract`, `resolve`, and
+/// `into_builder` methods.
+macro_rules! extractor_methods {
+    () => {
+        /// Same as [`Self::extract`], but returns the fallback
+        /// [`ValueRef::other`] if no parser matches, as specified by
+        /// uap-core.
+        pub fn extract_or_default(&'a self, ua: &'a str) -> ValueRef<'a> {
+            self.extract(ua).unwrap_or(ValueRef::other())
+        }
+
+        /// Returns whether any parser matches the user agent, without
+        /// performing the extraction (and paying for captures and
+        /// replacements).
+        pub fn is_match(&self, ua: &str) -> bool {
+            self.core.is_match(ua)
+        }
+
+        /// Sets the historical hit counts of the parsers, by index
+        /// (e.g. from [`crate::Profiler::frequencies`]), missing
+        /// parsers counting as never hit.
+        ///
+        /// [`Self::is_match`] then verifies the candidates by
+        /// decreasing frequency, so the common case stops after a
+        /// single regex. Extractions are not affected, as they have to
+        /// find the first matching parser. The frequencies are reset
+        /// by [`Self::extend`].
+        pub fn frequencies(mut self, hits: Vec<usize>) -> Self {
+            self.core.frequencies = hits;
+            self
+        }
+
+        /// Approximate heap memory used by the extractor, in bytes:
+        /// the regex set (see [`regex_filtered::Regexes::memory_usage`])
+        /// and the replacement tables. Replacements borrowed from the
+        /// parsers are not counted.
+        pub fn memory_usage(&self) -> usize {
+            self.core.memory_usage()
+        }
+
+        /// Performs the extraction using the parser at `idx`, which
+        /// is known to match.
+        pub(crate) fn extract_at(&'a self, idx: usize, ua: &'a str) -> Option<ValueRef<'a>> {
+            let c = crate::engine::captures(&self.core.matcher().regexes()[idx], ua)?;
+            Some(self.resolve(idx, &c))
+        }
+
+        /// Yields every matching parser along with its index and
+        /// resolved value, in parser order: the first item is the
+        /// result of [`Self::extract`].
+        ///
+        /// Allows detecting shadowed parsers, or implementing custom
+        /// precedence policies.
+        pub fn extract_all(
+            &'a self,
+            ua: &'a str,
+        ) -> impl Iterator<Item = (usize, ValueRef<'a>)> + 'a {
+            self.core
+                .matcher()
+                .matching(ua)
+                .filter_map(move |(idx, re)| {
+                    let c = crate::engine::captures(re, ua)?;
+                    Some((idx, self.resolve(idx, &c)))
+                })
+        }
+
+        /// Alternative to [`Self::extract`] which resolves the *best*
+        /// matching parser according to [`specificity`](crate#best-match)
+        /// rather than the first, for parser lists which are not
+        /// carefully ordered.
+        ///
+        /// More expensive than [`Self::extract`], as every matching
+        /// parser has to be found and captured.
+        pub fn extract_best(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
+            let (idx, c) = self
+                .core
+                .matcher()
+                .matching(ua)
+                .filter_map(|(idx, re)| Some((idx, crate::engine::captures(re, ua)?)))
+                .max_by_key(|(idx, c)| (crate::specificity(c), std::cmp::Reverse(*idx)))?;
+            Some(self.resolve(idx, &c))
+        }
+
+        /// Same as [`Self::extract`], but the prefilter's candidates
+        /// are verified in parallel over rayon's global thread pool,
+        /// in shards of `shard` candidates. The first matching parser
+        /// still wins, later shards being abandoned once an earlier
+        /// one matched.
+        ///
+        /// Only worth it for very large parser lists (e.g. thousands
+        /// of custom rules) where a single user agent can have many
+        /// candidates, to bound the latency of the worst cases.
+        #[cfg(feature = "rayon")]
+        pub fn par_extract(&'a self, ua: &'a str, shard: usize) -> Option<ValueRef<'a>> {
+            let idx = crate::domain::par_first_match(self.core.matcher(), ua, shard)?;
+            self.extract_at(idx, ua)
+        }
+
+        /// Same as [`Self::extract`], but also returns which parser
+        /// matched and its raw captures, to trace a result back to
+        /// the rule responsible for it.
+        pub fn extract_traced(
+            &'a self,
+            ua: &'a str,
+        ) -> Option<(ValueRef<'a>, crate::MatchInfo<'a>)> {
+            let (idx, re) = self.core.first(ua)?;
+            let c = crate::engine::captures(re, ua)?;
+            Some((self.resolve(idx, &c), crate::MatchInfo::new(idx, &c)))
+        }
+
+        /// Sets the handling of empty capture groups.
+        pub fn empty_captures(mut self, empty: crate::EmptyCapture) -> Self {
+            self.core.empty = empty;
+            self
+        }
+
+        /// Appends parsers to the extractor, e.g. to register
+        /// additional rules at runtime. The existing regexes are not
+        /// recompiled, but the prefilter is rebuilt entirely.
+        pub fn extend<I>(self, parsers: I) -> Result<Self, crate::Error>
+        where
+            I: IntoIterator<Item = Parser<'a>>,
+        {
+            Ok(self.into_builder(None)?.push_all(parsers)?.build()?)
+        }
+
+        pub(crate) fn matcher(&self) -> &regex_filtered::Regexes<crate::engine::Regex> {
+            self.core.matcher()
+        }
+
+        pub(crate) fn hit_counts(&self) -> &[usize] {
+            &self.core.frequencies
+        }
+    };
+}
+pub(crate) use extractor_methods;
+
+/// Whether any regex of `matcher` matches `ua`, verifying the
+/// candidates by decreasing hit counts if any.
+pub(crate) fn matches_by_frequency<R: regex_filtered::Engine>(
+    matcher: &regex_filtered::Regexes<R>,
+    frequencies: &[usize],
+    ua: &str,
+) -> bool {
+    if frequencies.is_empty() {
+        return matcher.is_match(ua);
+    }
+    let mut candidates = matcher.candidates(ua).collect::<Vec<_>>();
+    // stable, so unknown frequencies are verified in parser order
+    candidates.sort_by_key(|&idx| std::cmp::Reverse(frequencies.get(idx).copied().unwrap_or(0)));
+    candidates.into_iter().any(|idx| matcher.verify(idx, ua))
+}
+
+/// Index of the first regex of `matcher` matching `ua`, with the
+/// candidates verified in parallel by shards.
+#[cfg(feature = "rayon")]
+pub(crate) fn par_first_match(
+    matcher: &regex_filtered::Regexes<engine::Regex>,
+    ua: &str,
+    shard: usize,
+) -> Option<usize> {
+    use rayon::prelude::*;
+    let candidates = matcher.candidates(ua).collect::<Vec<_>>();
+    candidates
+        .par_chunks(shard.max(1))
+        .find_map_first(|c| c.iter().copied().find(|&idx| matcher.verify(idx, ua)))
+}
+
+/// Compiles `regex` (reusing it from `dedup` if possible), then
+/// pushes it into `builder` if `prepare` succeeds on it, returning
+/// the result of `prepare`.
+///
+/// Neither `builder` nor `dedup` is modified on failure, so the
+/// parser can just be skipped.
+pub(crate) fn push_regex<T>(
+    builder: &mut regex_filtered::Builder<engine::Regex>,
+    dedup: Option<&mut Dedup>,
+    regex: &str,
+    flags: Flags,
+    prepare: impl FnOnce(&engine::Regex) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let opts = flags.options();
+    let re = match &dedup {
+        Some(d) => d.compile(builder, regex, flags)?,
+        None => builder.compile(regex, &opts)?,
+    };
+    let prepared = prepare(&re)?;
+    builder.push_compiled_mut(re.clone(), &opts)?;
+    if let Some(d) = dedup {
+        d.record(&re, flags);
+    }
+    Ok(prepared)
+}
@@ -0,0 +1,66 @@
+//! Regex engine selection, the engine is picked at compile time via
+//! the `engine-*` features, with `engine-regex-lite` having priority
+//! over `engine-bytes` having priority over `engine-regex`.
+//!
+//! Exposes the engine's regex and captures types and a few helpers
+//! smoothing over the differences between engines (mostly whether
+//! they work on strings or bytes).
+
+#[cfg(not(any(
+    feature = "engine-regex",
+    feature = "engine-regex-lite",
+    feature = "engine-bytes"
+)))]
+compile_error!(
+    "one of the `engine-regex`, `engine-regex-lite`, or `engine-bytes` features must be enabled"
+);
+
+#[cfg(feature = "engine-regex-lite")]
+pub(crate) use regex_lite::{Captures, Regex};
+
+#[cfg(all(feature = "engine-bytes", not(feature = "engine-regex-lite")))]
+pub(crate) use regex::bytes::{Captures, Regex};
+
+#[cfg(not(any(feature = "engine-regex-lite", feature = "engine-bytes")))]
+pub(crate) use regex::{Captures, Regex};
+
+#[cfg(any(feature = "engine-regex-lite", not(feature = "engine-bytes")))]
+mod imp {
+    use super::{Captures, Regex};
+
+    #[inline]
+    pub(crate) fn captures<'h>(re: &Regex, haystack: &'h str) -> Option<Captures<'h>> {
+        re.captures(haystack)
+    }
+
+    #[inline]
+    pub(crate) fn group<'h>(c: &Captures<'h>, i: usize) -> Option<&'h str> {
+        c.get(i).map(|m| m.as_str())
+    }
+}
+
+#[cfg(all(feature = "engine-bytes", not(feature = "engine-regex-lite")))]
+mod imp {
+    use super::{Captures, Regex};
+
+    #[inline]
+    pub(crate) fn captures<'h>(re: &Regex, haystack: &'h str) -> Option<Captures<'h>> {
+        re.captures(haystack.as_bytes())
+    }
+
+    /// The haystack is always a string, and the regexes are unicode
+    /// by default so groups should always fall on character
+    /// boundaries, unless the pattern disables unicode explicitly.
+    #[inline]
+    pub(crate) fn group<'h>(c: &Captures<'h>, i: usize) -> Option<&'h str> {
+        c.get(i)
+            .and_then(|m| std::str::from_utf8(m.as_bytes()).ok())
+    }
+}
+
+pub(crate) use imp::{captures, group};
+
+/// All the groups of the captures, including the implicit group 0.
+pub(crate) fn groups<'h>(c: &Captures<'h>) -> Vec<Option<&'h str>> {
+    (0..c.len()).map(|i| group(c, i)).collect()
+}
@@ -0,0 +1,254 @@
+//! Remote regexes distribution, see [`Fetcher`].
+
+use std::io::Read;
+
+use crate::reload::ReloadingExtractor;
+use crate::{Extractor, LoadError, Regexes};
+
+/// Error while fetching remote regexes.
+#[derive(Debug)]
+pub enum FetchError {
+    /// The request failed, or the server replied with an error status.
+    Http(Box<ureq::Error>),
+    /// The fetched data could not be stored or loaded.
+    Reload(LoadError),
+}
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Http(e) => Some(e),
+            FetchError::Reload(e) => Some(e),
+        }
+    }
+}
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+impl From<ureq::Error> for FetchError {
+    fn from(value: ureq::Error) -> Self {
+        Self::Http(Box::new(value))
+    }
+}
+impl From<LoadError> for FetchError {
+    fn from(value: LoadError) -> Self {
+        Self::Reload(value)
+    }
+}
+impl From<std::io::Error> for FetchError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Reload(value.into())
+    }
+}
+
+/// Downloads a regexes file (YAML or JSON) from a URL and feeds it
+/// into a [`ReloadingExtractor`], for deployments which distribute
+/// their data centrally.
+///
+/// Requests are conditional: the `ETag` and `Last-Modified` of the
+/// previous response are sent back, so unchanged data is not
+/// downloaded (or reloaded) again.
+pub struct Fetcher {
+    url: String,
+    agent: ureq::Agent,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Fetcher {
+    /// Creates a fetcher for the regexes at `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::with_agent(url, ureq::Agent::new())
+    }
+
+    /// Creates a fetcher using the provided agent, e.g. to configure
+    /// timeouts or proxies.
+    pub fn with_agent(url: impl Into<String>, agent: ureq::Agent) -> Self {
+        Self {
+            url: url.into(),
+            agent,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    /// Fetches the regexes if they changed since the last successful
+    /// fetch, returns `None` if they did not.
+    pub fn fetch(&mut self) -> Result<Option<Vec<u8>>, FetchError> {
+        let Some(Fetched {
+            body,
+            etag,
+            last_modified,
+        }) = self.request()?
+        else {
+            return Ok(None);
+        };
+        self.etag = etag;
+        self.last_modified = last_modified;
+        Ok(Some(body))
+    }
+
+    /// Fetches the regexes conditionally, without updating the
+    /// validators.
+    fn request(&self) -> Result<Option<Fetched>, FetchError> {
+        let mut req = self.agent.get(&self.url);
+        if let Some(etag) = &self.etag {
+            req = req.set("If-None-Match", etag);
+        }
+        if let Some(lm) = &self.last_modified {
+            req = req.set("If-Modified-Since", lm);
+        }
+        let res = req.call()?;
+        if res.status() == 304 {
+            return Ok(None);
+        }
+
+        let etag = res.header("ETag").map(String::from);
+        let last_modified = res.header("Last-Modified").map(String::from);
+        let mut body = Vec::new();
+        res.into_reader().read_to_end(&mut body)?;
+        Ok(Some(Fetched {
+            body,
+            etag,
+            last_modified,
+        }))
+    }
+
+    /// Fetches the regexes and, if they changed, stores them in the
+    /// file watched by `target` and swaps them in. Returns whether
+    /// the regexes were updated.
+    ///
+    /// The data is validated before the file is replaced, so invalid
+    /// data never overwrites the current file. The validators are
+    /// only kept once the data is applied, so data which could not be
+    /// is fetched again next time.
+    pub fn update(&mut self, target: &ReloadingExtractor) -> Result<bool, FetchError> {
+        let Some(Fetched {
+            body,
+            etag,
+            last_modified,
+        }) = self.request()?
+        else {
+            return Ok(false);
+        };
+        target.replace(&body, parse(&body)?)?;
+        self.etag = etag;
+        self.last_modified = last_modified;
+        Ok(true)
+    }
+}
+
+/// Response to a fetch with changed data.
+struct Fetched {
+    body: Vec<u8>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Parses regexes in YAML (or JSON) format and builds an extractor.
+fn parse(data: &[u8]) -> Result<Extractor<'static>, LoadError> {
+    let regexes: Regexes<'static> = serde_yaml::from_slice(data)?;
+    Ok(regexes.try_into()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    const FIREFOX: &str = r#"{"user_agent_parsers": [{"regex": "(Firefox)/(\\d+)"}], "os_parsers": [], "device_parsers": []}"#;
+    const FENNEC: &str = r#"{"user_agent_parsers": [{"regex": "Firefox/(\\d+)", "family_replacement": "Fennec"}], "os_parsers": [], "device_parsers": []}"#;
+
+    /// Serves `FENNEC` with an etag, or a 304 if the request has it.
+    fn serve(listener: TcpListener) -> std::thread::JoinHandle<Vec<bool>> {
+        std::thread::spawn(move || {
+            let mut conditional = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut matched = false;
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    matched |= line.eq_ignore_ascii_case("if-none-match: \"v1\"\r\n");
+                }
+                conditional.push(matched);
+                if matched {
+                    write!(
+                        stream,
+                        "HTTP/1.1 304 Not Modified\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+                    )
+                } else {
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nConnection: close\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{FENNEC}",
+                        FENNEC.len()
+                    )
+                }
+                .unwrap();
+            }
+            conditional
+        })
+    }
+
+    fn family(e: &ReloadingExtractor) -> String {
+        e.load()
+            .ua
+            .extract("Firefox/121")
+            .unwrap()
+            .family
+            .into_owned()
+    }
+
+    #[test]
+    fn update() {
+        let path =
+            std::env::temp_dir().join(format!("ua-parser-fetch-{}.json", std::process::id()));
+        std::fs::write(&path, FIREFOX).unwrap();
+        let e = ReloadingExtractor::new(&path, Duration::from_secs(3600)).unwrap();
+        assert_eq!(family(&e), "Firefox");
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut f = Fetcher::new(format!("http://{}/", listener.local_addr().unwrap()));
+        let server = serve(listener);
+
+        assert!(f.update(&e).unwrap());
+        assert_eq!(family(&e), "Fennec");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), FENNEC);
+
+        assert!(!f.update(&e).unwrap(), "the data was not modified");
+        assert_eq!(server.join().unwrap(), [false, true]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_update() {
+        let dir = std::env::temp_dir().join(format!("ua-parser-fetch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("regexes.json");
+        std::fs::write(&path, FIREFOX).unwrap();
+        let e = ReloadingExtractor::new(&path, Duration::from_secs(3600)).unwrap();
+        // the new data can't be stored
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut f = Fetcher::new(format!("http://{}/", listener.local_addr().unwrap()));
+        let server = serve(listener);
+
+        assert!(matches!(f.update(&e), Err(FetchError::Reload(_))));
+        assert_eq!(family(&e), "Firefox");
+        assert!(f.update(&e).is_err());
+        assert_eq!(
+            server.join().unwrap(),
+            [false, false],
+            "the data is fetched again"
+        );
+    }
+}
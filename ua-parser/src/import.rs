@@ -0,0 +1,671 @@
+//! Conversion of other user agent rule formats to [`Regexes`], so
+//! existing rules can be brought along when migrating to this crate.
+//!
+//! The conversions are best-effort: the other formats have different
+//! matching semantics, and only the data which maps onto the
+//! user agent, os, and device domains is kept. Rules which can not be
+//! converted at all are reported in [`Import::skipped`]. The
+//! converted parsers can be layered over other data with
+//! [`Regexes::merge`].
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::device::Flags;
+use crate::{device, os, user_agent, Regexes};
+
+/// Result of a conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// Converted parsers, in priority order.
+    pub regexes: Regexes<'static>,
+    /// Rules which could not be converted, in source order.
+    pub skipped: Vec<Skipped>,
+}
+
+impl Import {
+    fn new() -> Self {
+        Self {
+            regexes: Regexes {
+                user_agent_parsers: Vec::new(),
+                os_parsers: Vec::new(),
+                device_parsers: Vec::new(),
+            },
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Appends the parsers and skipped rules of `other`, e.g. to
+    /// combine the conversions of several files.
+    pub fn extend(&mut self, other: Import) {
+        let Regexes {
+            user_agent_parsers,
+            os_parsers,
+            device_parsers,
+        } = other.regexes;
+        self.regexes.user_agent_parsers.extend(user_agent_parsers);
+        self.regexes.os_parsers.extend(os_parsers);
+        self.regexes.device_parsers.extend(device_parsers);
+        self.skipped.extend(other.skipped);
+    }
+}
+
+/// Rule which could not be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    /// The rule's pattern.
+    pub rule: String,
+    /// Why it was skipped.
+    pub reason: SkipReason,
+}
+
+/// Reason for a rule to be skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The rule's regex is not supported by the regex engines (e.g.
+    /// PCRE-specific syntax), with the parsing error.
+    Regex(String),
+    /// The rule has no data for any of the domains.
+    NoData,
+}
+
+/// Converts a [browscap] INI file.
+///
+/// Every section yields a user agent, os, and / or device parser
+/// depending on its properties (`Browser`, `MajorVer`, `MinorVer`;
+/// `Platform`, `Platform_Version`; `Device_Name`, `Device_Brand_Name`,
+/// `Device_Code_Name`), including those inherited from its `Parent`
+/// chain. Sections which are only used as a parent, and the
+/// `DefaultProperties` and `*` fallbacks, are not converted.
+///
+/// The section name is a case-insensitive glob matched against the
+/// entire user agent, and browscap selects the longest matching
+/// pattern, so the parsers are ordered by decreasing pattern length.
+///
+/// ```
+/// let ini = r#"
+/// [Firefox 121.0]
+/// Browser="Firefox"
+/// MajorVer=121
+/// MinorVer=0
+///
+/// [Mozilla/5.0 (*Linux*) Gecko* Firefox/121.0*]
+/// Parent="Firefox 121.0"
+/// Platform="Linux"
+/// "#;
+/// let import = ua_parser::import::browscap(ini);
+/// let extractor = ua_parser::Extractor::try_from(import.regexes)?;
+/// let client = extractor.extract("Mozilla/5.0 (X11; Linux x86_64) Gecko/20100101 Firefox/121.0");
+/// assert_eq!(client.ua.unwrap().major.as_deref(), Some("121"));
+/// assert_eq!(client.os.unwrap().os, "Linux");
+/// # Ok::<(), ua_parser::Error>(())
+/// ```
+///
+/// [browscap]: https://browscap.org
+pub fn browscap(ini: &str) -> Import {
+    let mut sections = Vec::<(&str, HashMap<&str, &str>)>::new();
+    for line in ini.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name, HashMap::new()));
+        } else if let (Some((_, properties)), Some((k, v))) =
+            (sections.last_mut(), line.split_once('='))
+        {
+            let v = v.trim();
+            let v = v
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(v);
+            properties.insert(k.trim(), v);
+        }
+    }
+
+    let index = sections
+        .iter()
+        .map(|(name, properties)| (*name, properties))
+        .collect::<HashMap<_, _>>();
+    let parents = sections
+        .iter()
+        .filter_map(|(_, p)| p.get("Parent").copied())
+        .collect::<HashSet<_>>();
+    // looks a property up along the parent chain, "unknown" is
+    // browscap's null, and the defaults are placeholders
+    let lookup = |name: &str, key: &str| {
+        let mut current = Some(name);
+        let mut depth = 0;
+        while let Some(properties) = current
+            .filter(|c| *c != "DefaultProperties")
+            .and_then(|c| index.get(c))
+        {
+            if let Some(v) = properties.get(key) {
+                return Some(*v).filter(|v| !v.is_empty() && *v != "unknown");
+            }
+            current = properties.get("Parent").copied();
+            // guard against parent cycles
+            depth += 1;
+            if depth > sections.len() {
+                break;
+            }
+        }
+        None
+    };
+
+    let mut rules = sections
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| {
+            !parents.contains(name)
+                && *name != "*"
+                && *name != "DefaultProperties"
+                && !name.starts_with("GJK_Browscap_Version")
+        })
+        .collect::<Vec<_>>();
+    rules.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+    let mut import = Import::new();
+    for name in rules {
+        let regex = glob_to_regex(name);
+        let mut converted = false;
+        if let Some(browser) = lookup(name, "Browser") {
+            let mut p = user_agent::Parser::new(regex.clone())
+                .regex_flag(Flags::IGNORE_CASE)
+                .family(owned(browser));
+            p.v1_replacement = lookup(name, "MajorVer").map(owned);
+            p.v2_replacement = lookup(name, "MinorVer").map(owned);
+            import.regexes.user_agent_parsers.push(p);
+            converted = true;
+        }
+        if let Some(platform) = lookup(name, "Platform") {
+            let mut p = os::Parser::new(regex.clone())
+                .regex_flag(Flags::IGNORE_CASE)
+                .os(owned(platform));
+            if let Some(version) = lookup(name, "Platform_Version") {
+                let mut parts = version.split('.').map(owned);
+                p.os_v1_replacement = parts.next();
+                p.os_v2_replacement = parts.next();
+                p.os_v3_replacement = parts.next();
+            }
+            import.regexes.os_parsers.push(p);
+            converted = true;
+        }
+        let device = lookup(name, "Device_Name");
+        let brand = lookup(name, "Device_Brand_Name");
+        if device.is_some() || brand.is_some() {
+            let mut p = device::Parser::new(regex).regex_flag(Flags::IGNORE_CASE);
+            p.device_replacement = device.or(brand).map(owned);
+            p.brand_replacement = brand.map(owned);
+            p.model_replacement = lookup(name, "Device_Code_Name").map(owned);
+            import.regexes.device_parsers.push(p);
+            converted = true;
+        }
+        if !converted {
+            import.skipped.push(Skipped {
+                rule: name.into(),
+                reason: SkipReason::NoData,
+            });
+        }
+    }
+    import
+}
+
+fn owned(s: &str) -> Cow<'static, str> {
+    Cow::Owned(s.into())
+}
+
+/// Translates a browscap glob (`*` for any sequence, `?` for any
+/// character) to an anchored regex.
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::with_capacity(glob.len() + 8);
+    re.push('^');
+    let mut buf = [0; 4];
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut buf))),
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// Conversion of [Matomo device detector] YAML files. Requires the
+/// `yaml` feature.
+///
+/// device detector matches its regexes case-insensitively, and only
+/// at the start of the user agent or after a separator, which the
+/// converted regexes replicate. Its regexes are PCRE, those which the
+/// regex engines don't support are skipped.
+///
+/// [Matomo device detector]: https://github.com/matomo-org/device-detector
+#[cfg(feature = "yaml")]
+pub mod device_detector {
+    use std::borrow::Cow;
+
+    use serde::Deserialize;
+
+    use super::{owned, Import, SkipReason, Skipped};
+    use crate::device::Flags;
+    use crate::{device, os, user_agent};
+
+    #[derive(Deserialize)]
+    struct Rule {
+        regex: String,
+        name: String,
+        #[serde(default)]
+        version: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Brand {
+        regex: String,
+        #[serde(default)]
+        device: Option<String>,
+        #[serde(default)]
+        model: Option<String>,
+        #[serde(default)]
+        models: Vec<Model>,
+    }
+
+    #[derive(Deserialize)]
+    struct Model {
+        regex: String,
+        #[serde(default)]
+        model: Option<String>,
+        #[serde(default)]
+        device: Option<String>,
+        #[serde(default)]
+        brand: Option<String>,
+    }
+
+    /// Prefix device detector matches its regexes after: the start of
+    /// the user agent or a separator.
+    const PREFIX: &str = r"(?:^|[^A-Z0-9_-])";
+
+    /// Checks that the engines support the converted regex of `rule`,
+    /// records the rule as skipped otherwise.
+    fn check(re: String, rule: &str, skipped: &mut Vec<Skipped>) -> Option<String> {
+        match regex_syntax::ParserBuilder::new()
+            .case_insensitive(true)
+            .build()
+            .parse(&re)
+        {
+            Ok(_) => Some(re),
+            Err(e) => {
+                skipped.push(Skipped {
+                    rule: rule.into(),
+                    reason: SkipReason::Regex(e.to_string()),
+                });
+                None
+            }
+        }
+    }
+
+    /// Version data of a rule, converted according to its template.
+    struct Version {
+        /// Rule regex, with its groups renumbered so the versions
+        /// are in the groups the parsers fall back to.
+        regex: String,
+        replacements: [Option<Cow<'static, str>>; 4],
+    }
+
+    /// Converts a rule's regex and version template for user agent
+    /// and os parsers, whose family comes from the rule's name.
+    ///
+    /// Those parsers fall back to the groups 2 to 5 for the version
+    /// fields, so a template of consecutive groups (`$1`, `$1.$2`,
+    /// ...) is converted by prepending an empty group, and a literal
+    /// version (`10`, `8.1`) to literal replacements. Other
+    /// templates can't be converted, and the version is dropped. The
+    /// groups not referenced by the template are made non-capturing,
+    /// so they don't leak into the version.
+    ///
+    /// A single group holding an entire version, as is common, is
+    /// not split: it all ends up in the major version.
+    fn version(regex: &str, template: Option<&str>) -> Version {
+        let template = template.filter(|t| !t.is_empty());
+        let keep = template
+            .filter(|t| {
+                t.split('.')
+                    .enumerate()
+                    .all(|(i, part)| part.strip_prefix('$') == Some(&*(i + 1).to_string()))
+            })
+            .map_or(0, |t| t.split('.').count());
+        let mut replacements = [None, None, None, None];
+        if let Some(t) = template.filter(|t| !t.contains('$')) {
+            for (r, part) in replacements.iter_mut().zip(t.split('.')) {
+                *r = Some(owned(part));
+            }
+        }
+        let group = if keep > 0 { "()" } else { "" };
+        Version {
+            regex: format!("{PREFIX}{group}(?:{})", uncapture(regex, keep)),
+            replacements,
+        }
+    }
+
+    /// Makes the capturing groups of `regex` after the first `keep`
+    /// non-capturing.
+    fn uncapture(regex: &str, keep: usize) -> String {
+        let mut out = String::with_capacity(regex.len());
+        let mut groups = 0;
+        let mut escape = false;
+        let mut class = false;
+        let mut it = regex.char_indices().peekable();
+        while let Some((idx, c)) = it.next() {
+            match c {
+                _ if escape => escape = false,
+                '\\' => escape = true,
+                '[' if !class => class = true,
+                ']' if class => class = false,
+                '(' if !class => {
+                    let rest = &regex[idx + 1..];
+                    let name = ["?P<", "?<"]
+                        .iter()
+                        .find(|p| rest.starts_with(*p) && !rest[p.len()..].starts_with(['=', '!']));
+                    if name.is_some() || !rest.starts_with('?') {
+                        groups += 1;
+                        if groups > keep {
+                            out.push_str("(?:");
+                            if name.is_some() {
+                                // skip the group's name
+                                for (_, c) in it.by_ref() {
+                                    if c == '>' {
+                                        break;
+                                    }
+                                }
+                            }
+                            continue;
+                        }
+                    }
+                }
+                _ => (),
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    /// Converts a browsers file (`regexes/client/browsers.yml`) to
+    /// user agent parsers.
+    pub fn browsers(yaml: &str) -> Result<Import, serde_yaml::Error> {
+        let mut import = Import::new();
+        for rule in serde_yaml::from_str::<Vec<Rule>>(yaml)? {
+            let Version {
+                regex,
+                replacements: [v1, v2, v3, v4],
+            } = version(&rule.regex, rule.version.as_deref());
+            let Some(regex) = check(regex, &rule.regex, &mut import.skipped) else {
+                continue;
+            };
+            let mut p = user_agent::Parser::new(regex)
+                .regex_flag(Flags::IGNORE_CASE)
+                .family(rule.name);
+            p.v1_replacement = v1;
+            p.v2_replacement = v2;
+            p.v3_replacement = v3;
+            p.v4_replacement = v4;
+            import.regexes.user_agent_parsers.push(p);
+        }
+        Ok(import)
+    }
+
+    /// Converts an operating systems file (`regexes/oss.yml`) to os
+    /// parsers.
+    pub fn operating_systems(yaml: &str) -> Result<Import, serde_yaml::Error> {
+        let mut import = Import::new();
+        for rule in serde_yaml::from_str::<Vec<Rule>>(yaml)? {
+            let Version {
+                regex,
+                replacements: [v1, v2, v3, v4],
+            } = version(&rule.regex, rule.version.as_deref());
+            let Some(regex) = check(regex, &rule.regex, &mut import.skipped) else {
+                continue;
+            };
+            let mut p = os::Parser::new(regex)
+                .regex_flag(Flags::IGNORE_CASE)
+                .os(rule.name);
+            p.os_v1_replacement = v1;
+            p.os_v2_replacement = v2;
+            p.os_v3_replacement = v3;
+            p.os_v4_replacement = v4;
+            import.regexes.os_parsers.push(p);
+        }
+        Ok(import)
+    }
+
+    /// Converts a devices file (e.g. `regexes/device/mobiles.yml`) to
+    /// device parsers, the device type is kept as the `device_type`
+    /// [`device::Parser::extra`] field.
+    ///
+    /// device detector only tries the models of a brand if the
+    /// brand's regex matches, the converted model parsers are not
+    /// gated that way. Every brand is followed by a parser for its
+    /// own regex, for user agents matching none of its models.
+    pub fn devices(yaml: &str) -> Result<Import, serde_yaml::Error> {
+        let mut import = Import::new();
+        // a mapping to keep the brands in file order
+        for (brand, value) in serde_yaml::from_str::<serde_yaml::Mapping>(yaml)? {
+            let brand = match brand {
+                serde_yaml::Value::String(s) => s,
+                other => serde_yaml::to_string(&other)?.trim().to_string(),
+            };
+            let b: Brand = serde_yaml::from_value(value)?;
+            let rules = b
+                .models
+                .iter()
+                .map(|m| (&m.regex, &m.model, &m.device, m.brand.as_ref()))
+                .chain([(&b.regex, &b.model, &b.device, None)]);
+            for (re, model, device_type, model_brand) in rules {
+                let Some(re) = check(format!("{PREFIX}(?:{re})"), re, &mut import.skipped) else {
+                    continue;
+                };
+                let brand = model_brand.unwrap_or(&brand);
+                let mut p = device::Parser::new(re)
+                    .regex_flag(Flags::IGNORE_CASE)
+                    .brand(brand.clone())
+                    .device(match model {
+                        Some(m) => format!("{brand} {m}"),
+                        None => brand.clone(),
+                    });
+                p.model_replacement = model.clone().map(Into::into);
+                if let Some(t) = device_type.as_ref().or(b.device.as_ref()) {
+                    p.extra.insert("device_type".into(), t.clone());
+                }
+                import.regexes.device_parsers.push(p);
+            }
+        }
+        Ok(import)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BROWSCAP: &str = r#"
+[GJK_Browscap_Version]
+Version=6001008
+
+[DefaultProperties]
+Browser="DefaultProperties"
+Platform="unknown"
+
+[Chrome 120.0]
+Parent="DefaultProperties"
+Browser="Chrome"
+MajorVer=120
+MinorVer=0
+
+; a comment
+[Mozilla/5.0 (*Windows NT 10.0*) applewebkit* (*khtml*like*gecko*) Chrome/120.0*Safari/*]
+Parent="Chrome 120.0"
+Platform="Win10"
+Platform_Version="10.0"
+Device_Name="Windows Desktop"
+
+[Mozilla/5.0 (*Linux*Android?13*Pixel 7*) applewebkit* (*khtml*like*gecko*) Chrome/120.0*Safari/*]
+Parent="Chrome 120.0"
+Platform="Android"
+Platform_Version="13"
+Device_Name="Pixel 7"
+Device_Brand_Name="Google"
+Device_Code_Name="Pixel 7"
+
+[Nothing]
+Parent="DefaultProperties"
+
+[*]
+Parent="DefaultProperties"
+Browser="Default Browser"
+"#;
+
+    #[test]
+    fn browscap() {
+        let import = super::browscap(BROWSCAP);
+        assert_eq!(
+            import.skipped,
+            [Skipped {
+                rule: "Nothing".into(),
+                reason: SkipReason::NoData,
+            }]
+        );
+        let r = &import.regexes;
+        assert_eq!(r.user_agent_parsers.len(), 2);
+        assert_eq!(r.os_parsers.len(), 2);
+        assert_eq!(r.device_parsers.len(), 2);
+        // the longer android pattern comes first
+        assert_eq!(r.os_parsers[0].os_replacement.as_deref(), Some("Android"));
+
+        let e = crate::Extractor::try_from(import.regexes).unwrap();
+        let c = e.extract("Mozilla/5.0 (Linux; Android 13; Pixel 7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36");
+        let ua = c.ua.unwrap();
+        assert_eq!(
+            (&*ua.family, ua.major.as_deref(), ua.minor.as_deref()),
+            ("Chrome", Some("120"), Some("0"))
+        );
+        let os = c.os.unwrap();
+        assert_eq!((&*os.os, os.major.as_deref()), ("Android", Some("13")));
+        let dev = c.device.unwrap();
+        assert_eq!(
+            (&*dev.device, dev.brand.as_deref(), dev.model.as_deref()),
+            ("Pixel 7", Some("Google"), Some("Pixel 7"))
+        );
+
+        let c = e.extract("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36");
+        let os = c.os.unwrap();
+        assert_eq!(
+            (&*os.os, os.major.as_deref(), os.minor.as_deref()),
+            ("Win10", Some("10"), Some("0"))
+        );
+        assert_eq!(c.device.unwrap().brand, None);
+
+        assert!(e.extract("Mozilla/5.0 Chrome/120.0").ua.is_none());
+    }
+
+    #[test]
+    fn glob() {
+        assert_eq!(glob_to_regex("a*b?(c)"), r"^a.*b.\(c\)$");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn device_detector() {
+        let mut import = device_detector::browsers(
+            r#"
+- regex: 'Firefox(?:/(\d+)\.(\d+))?'
+  name: 'Firefox'
+  version: '$1.$2'
+- regex: 'Foo(?!bar)'
+  name: 'Foo'
+  version: ''
+- regex: 'Chrome/(\d+[\.\d]+)'
+  name: 'Chrome'
+  version: '$1'
+"#,
+        )
+        .unwrap();
+        import.extend(
+            device_detector::operating_systems(
+                r#"
+- regex: 'Windows NT 10.0'
+  name: 'Windows'
+  version: '10'
+"#,
+            )
+            .unwrap(),
+        );
+        import.extend(
+            device_detector::devices(
+                r#"
+Samsung:
+  regex: 'SAMSUNG|SM-[A-Z]'
+  device: 'smartphone'
+  models:
+    - regex: 'SM-T(\d+)'
+      device: 'tablet'
+      model: 'Galaxy Tab $1'
+Apple:
+  regex: 'iPhone'
+  device: 'smartphone'
+  model: 'iPhone'
+"#,
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(import.skipped.len(), 1);
+        assert_eq!(import.skipped[0].rule, "Foo(?!bar)");
+        assert!(matches!(import.skipped[0].reason, SkipReason::Regex(_)));
+        let r = &import.regexes;
+        assert_eq!(r.device_parsers.len(), 3);
+        assert_eq!(
+            r.device_parsers[0]
+                .extra
+                .get("device_type")
+                .map(String::as_str),
+            Some("tablet")
+        );
+        assert_eq!(
+            r.device_parsers[1]
+                .extra
+                .get("device_type")
+                .map(String::as_str),
+            Some("smartphone")
+        );
+
+        let e = crate::Extractor::try_from(import.regexes).unwrap();
+        let c = e.extract("Mozilla/5.0 (Windows NT 10.0) Gecko/20100101 firefox/121.0");
+        let ua = c.ua.unwrap();
+        assert_eq!(
+            (&*ua.family, ua.major.as_deref(), ua.minor.as_deref()),
+            ("Firefox", Some("121"), Some("0"))
+        );
+        assert_eq!(c.os.unwrap().major.as_deref(), Some("10"));
+        // not preceded by a separator
+        assert!(e.extract("XFirefox/121.0").ua.is_none());
+        assert_eq!(
+            e.extract("Chrome/120.0.1").ua.unwrap().major.as_deref(),
+            Some("120.0.1")
+        );
+
+        let dev = e.extract("Linux; Android 13; SM-T970").device.unwrap();
+        assert_eq!(
+            (&*dev.device, dev.brand.as_deref(), dev.model.as_deref()),
+            (
+                "Samsung Galaxy Tab 970",
+                Some("Samsung"),
+                Some("Galaxy Tab 970")
+            )
+        );
+        let dev = e.extract("Linux; Android 13; SM-A528B").device.unwrap();
+        assert_eq!((&*dev.device, dev.model.as_deref()), ("Samsung", None));
+        let dev = e.extract("(iPhone; CPU iPhone OS 17_0)").device.unwrap();
+        assert_eq!(
+            (&*dev.device, dev.model.as_deref()),
+            ("Apple iPhone", Some("iPhone"))
+        );
+    }
+}
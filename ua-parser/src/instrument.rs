@@ -0,0 +1,90 @@
+//! Instrumentation through the [`metrics`] facade, enabled by the
+//! `metrics` feature.
+//!
+//! Without an installed recorder, the macros are no-ops.
+
+use std::time::Instant;
+
+use metrics::{counter, histogram};
+
+use crate::Domains;
+
+/// Counter of extractions performed by [`crate::Extractor`].
+pub(crate) const EXTRACTIONS: &str = "ua_parser_extractions_total";
+/// Counter of matches, labelled by `domain`.
+pub(crate) const MATCHES: &str = "ua_parser_matches_total";
+/// Histogram of the number of regexes verified per extraction.
+pub(crate) const VERIFIED: &str = "ua_parser_candidates_verified";
+/// Histogram of the extraction latency, in seconds.
+pub(crate) const DURATION: &str = "ua_parser_extraction_duration_seconds";
+/// Counters of cache lookups, labelled by `result` (`hit` or `miss`).
+#[cfg(feature = "cache")]
+pub(crate) const CACHE: &str = "ua_parser_cache_lookups_total";
+
+pub(crate) fn extraction(start: Option<Instant>, verified: usize, matched: Domains) {
+    if let Some(start) = start {
+        histogram!(DURATION).record(start.elapsed());
+    }
+    histogram!(VERIFIED).record(verified as f64);
+    counter!(EXTRACTIONS).increment(1);
+    for (d, name) in [
+        (Domains::UA, "user_agent"),
+        (Domains::OS, "os"),
+        (Domains::DEVICE, "device"),
+    ] {
+        if matched.contains(d) {
+            counter!(MATCHES, "domain" => name).increment(1);
+        }
+    }
+}
+
+#[cfg(feature = "cache")]
+pub(crate) fn cache_lookup(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    counter!(CACHE, "result" => result).increment(1);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{os, Extractor, Regexes};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    #[test]
+    fn extraction() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![],
+            os_parsers: vec![os::Parser::new(r"(Android) (\d+)")],
+            device_parsers: vec![],
+        })
+        .unwrap();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            e.extract("Linux; Android 14");
+            e.extract("curl/8.0");
+        });
+
+        let metrics = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(k, _, _, v)| {
+                let key = k.key();
+                let labels = key.labels().map(|l| l.value()).collect::<Vec<_>>();
+                ((key.name().to_string(), labels.join(",")), v)
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+        let get = |name: &str, labels: &str| &metrics[&(name.to_string(), labels.to_string())];
+
+        assert_eq!(get(EXTRACTIONS, ""), &DebugValue::Counter(2));
+        assert_eq!(get(MATCHES, "os"), &DebugValue::Counter(1));
+        assert!(!metrics.contains_key(&(MATCHES.to_string(), "device".to_string())));
+        let DebugValue::Histogram(v) = get(VERIFIED, "") else {
+            panic!("{VERIFIED} should be a histogram");
+        };
+        assert_eq!(v.iter().map(|v| v.0).collect::<Vec<_>>(), [1.0, 0.0]);
+        assert!(matches!(get(DURATION, ""), DebugValue::Histogram(v) if v.len() == 2));
+    }
+}
@@ -0,0 +1,81 @@
+//! String interning for owned results, see [`Interner`].
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// Pool of shared strings, used to convert extracted values to owned
+/// ones without allocating a new copy of every string.
+///
+/// Extracted values are very repetitive (there are only so many
+/// browser families or OS versions), so interning them saves a lot of
+/// memory and allocations when storing large numbers of owned values.
+/// The pool is never pruned, it grows with the number of distinct
+/// strings it has seen.
+///
+/// ```
+/// # use ua_parser::{user_agent::ValueRef, Interner};
+/// let interner = Interner::new();
+/// let a = ValueRef { family: "Chrome".into(), ..Default::default() }.into_interned(&interner);
+/// let b = ValueRef { family: "Chrome".into(), ..Default::default() }.into_interned(&interner);
+/// assert!(std::sync::Arc::ptr_eq(&a.family, &b.family));
+/// ```
+#[derive(Default)]
+pub struct Interner {
+    strings: Mutex<HashSet<Arc<str>>>,
+}
+
+impl Interner {
+    /// Creates an empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the pooled copy of `s`, adding it to the pool if
+    /// necessary.
+    pub fn intern(&self, s: &str) -> Arc<str> {
+        let mut strings = self.strings.lock().unwrap();
+        if let Some(s) = strings.get(s) {
+            return s.clone();
+        }
+        let s = Arc::<str>::from(s);
+        strings.insert(s.clone());
+        s
+    }
+
+    /// Number of distinct strings in the pool.
+    pub fn len(&self) -> usize {
+        self.strings.lock().unwrap().len()
+    }
+
+    /// Whether the pool is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::os;
+
+    #[test]
+    fn intern() {
+        let i = Interner::new();
+        assert!(i.is_empty());
+
+        let a = i.intern("Windows");
+        let b = i.intern("Windows");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(i.len(), 1);
+
+        let v = os::ValueRef {
+            os: "Windows".into(),
+            major: Some("10".into()),
+            ..Default::default()
+        }
+        .into_interned(&i);
+        assert!(Arc::ptr_eq(&v.os, &a));
+        assert_eq!(v.major.as_deref(), Some("10"));
+        assert_eq!(i.len(), 2);
+    }
+}
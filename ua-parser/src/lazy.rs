@@ -0,0 +1,203 @@
+//! Lazily compiled full extractor, see [`Extractor::lazy`].
+
+use std::sync::{Mutex, PoisonError};
+
+use crate::sync::OnceLock;
+use crate::{device, os, user_agent, ClientRef, Domains, Error, Extractor, Regexes};
+
+struct Lazy<P, E> {
+    /// Parsers of the extractor until it is built.
+    parsers: Mutex<Vec<P>>,
+    extractor: OnceLock<Result<E, Error>>,
+}
+impl<P: Clone, E> Lazy<P, E> {
+    fn new(parsers: Vec<P>) -> Self {
+        Self {
+            parsers: Mutex::new(parsers),
+            extractor: OnceLock::new(),
+        }
+    }
+
+    fn get(&self, build: impl FnOnce(Vec<P>) -> Result<E, Error>) -> Result<&E, &Error> {
+        self.extractor
+            .get_or_init(|| {
+                // the parsers are only released once built, so a
+                // build which panicked (poisoning the lock) can be
+                // retried
+                let mut parsers = self.parsers.lock().unwrap_or_else(PoisonError::into_inner);
+                let extractor = build(parsers.clone());
+                *parsers = Vec::new();
+                extractor
+            })
+            .as_ref()
+    }
+}
+
+/// Full extractor whose individual extractors are only built when
+/// first used, created by [`Extractor::lazy`].
+///
+/// As the regexes are only compiled on first use, errors in a domain's
+/// parsers are also only reported then (and every time the domain is
+/// used afterwards). Unlike [`Extractor`], the domains do not share
+/// their prefiltering.
+pub struct LazyExtractor<'a> {
+    ua: Lazy<user_agent::Parser<'a>, user_agent::Extractor<'a>>,
+    os: Lazy<os::Parser<'a>, os::Extractor<'a>>,
+    dev: Lazy<device::Parser<'a>, device::Extractor<'a>>,
+}
+
+impl<'a> Extractor<'a> {
+    /// Creates a [`LazyExtractor`] from the regexes, deferring the
+    /// building of each domain's extractor until it is first used.
+    ///
+    /// Useful for programs which may only need some of the domains,
+    /// but can't know which upfront: the full compilation cost is
+    /// not paid at startup, only the cost of the domains actually
+    /// used.
+    pub fn lazy(regexes: Regexes<'a>) -> LazyExtractor<'a> {
+        LazyExtractor {
+            ua: Lazy::new(regexes.user_agent_parsers),
+            os: Lazy::new(regexes.os_parsers),
+            dev: Lazy::new(regexes.device_parsers),
+        }
+    }
+}
+
+impl<'a> LazyExtractor<'a> {
+    /// The user agent extractor, built on first call.
+    pub fn ua(&self) -> Result<&user_agent::Extractor<'a>, &Error> {
+        self.ua
+            .get(|p| Ok(user_agent::Builder::new().push_all(p)?.build()?))
+    }
+
+    /// The OS extractor, built on first call.
+    pub fn os(&self) -> Result<&os::Extractor<'a>, &Error> {
+        self.os
+            .get(|p| Ok(os::Builder::new().push_all(p)?.build()?))
+    }
+
+    /// The device extractor, built on first call.
+    pub fn dev(&self) -> Result<&device::Extractor<'a>, &Error> {
+        self.dev
+            .get(|p| Ok(device::Builder::new().push_all(p)?.build()?))
+    }
+
+    /// Performs the extraction on every domain, building the
+    /// extractors as needed. Fails if any of them fails to build.
+    pub fn extract<'s>(&'s self, ua: &'s str) -> Result<ClientRef<'s>, &'s Error> {
+        self.extract_with(ua, Domains::ALL)
+    }
+
+    /// Performs the extraction on the requested domains only, the
+    /// extractors of the other domains are not built.
+    pub fn extract_with<'s>(
+        &'s self,
+        ua: &'s str,
+        domains: Domains,
+    ) -> Result<ClientRef<'s>, &'s Error> {
+        Ok(ClientRef {
+            ua: if domains.contains(Domains::UA) {
+                self.ua()?.extract(ua)
+            } else {
+                None
+            },
+            os: if domains.contains(Domains::OS) {
+                self.os()?.extract(ua)
+            } else {
+                None
+            },
+            device: if domains.contains(Domains::DEVICE) {
+                self.dev()?.extract(ua)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn regexes() -> Regexes<'static> {
+        Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                regex: r"(Firefox)/(\d+)".into(),
+                ..Default::default()
+            }],
+            os_parsers: vec![os::Parser {
+                regex: r"(Android) (\d+)".into(),
+                ..Default::default()
+            }],
+            device_parsers: vec![device::Parser {
+                // invalid
+                regex: r"(Pixel".into(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn lazy() {
+        let e = Extractor::lazy(regexes());
+        assert!(e.ua.extractor.get().is_none());
+        assert!(e.os.extractor.get().is_none());
+
+        let c = e
+            .extract_with("Mozilla/5.0 (Android 14) Firefox/121", Domains::OS)
+            .unwrap();
+        assert!(c.ua.is_none());
+        assert_eq!(c.os_family(), "Android");
+        assert!(c.device.is_none());
+        assert!(e.ua.extractor.get().is_none());
+        assert!(e.os.extractor.get().is_some());
+
+        let c = e
+            .extract_with("Mozilla/5.0 (Android 14) Firefox/121", Domains::UA)
+            .unwrap();
+        assert_eq!(c.ua.unwrap().major.as_deref(), Some("121"));
+    }
+
+    #[test]
+    fn deferred_error() {
+        let e = Extractor::lazy(regexes());
+        assert!(e.ua().is_ok());
+        for _ in 0..2 {
+            assert!(matches!(
+                e.dev(),
+                Err(Error::InvalidParser { source, .. }) if matches!(**source, Error::ParseError(_))
+            ));
+        }
+        assert!(e.extract("Firefox/121").is_err());
+    }
+
+    #[test]
+    fn stored() {
+        struct State {
+            extractor: LazyExtractor<'static>,
+        }
+
+        let state = State {
+            extractor: Extractor::lazy(regexes()),
+        };
+        let ua = String::from("Mozilla/5.0 (Android 14) Firefox/121");
+        let c = state.extractor.extract_with(&ua, Domains::UA).unwrap();
+        assert_eq!(c.family(), "Firefox");
+    }
+
+    #[test]
+    fn panicking_build() {
+        let lazy = Lazy::<u8, usize>::new(vec![1, 2]);
+        let built = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            lazy.get(|_| panic!("build failed")).is_ok()
+        }));
+        assert!(built.is_err());
+
+        assert_eq!(*lazy.get(|p| Ok(p.len())).unwrap(), 2);
+        assert!(lazy
+            .parsers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty());
+    }
+}
@@ -3,12 +3,77 @@
 #![allow(clippy::empty_docs)]
 #![doc = include_str!("../README.md")]
 
-use regex::Captures;
 use serde::Deserialize;
+use std::collections::HashMap;
 
+#[cfg(feature = "cache")]
+pub use cache::CachingExtractor;
+pub use client::{Client, ClientRef};
+pub use complexity::{Complexity, ComplexityReport};
+#[cfg(feature = "fetch")]
+pub use fetch::{FetchError, Fetcher};
+pub use interner::Interner;
+pub use lazy::LazyExtractor;
+#[cfg(feature = "yaml")]
+pub use load::LoadError;
+pub use merge::MergeStrategy;
+pub use normalize::{BrandNormalizer, UserAgentNormalizer};
+pub use profile::{ParserProfile, ParserReport, Profiler};
 pub use regex_filtered::{BuildError, ParseError};
+#[cfg(feature = "reload")]
+pub use reload::ReloadingExtractor;
+pub use shared::SharedExtractor;
+pub use version::Version;
 
+mod artifact;
+pub mod automation;
+pub mod bot;
+#[cfg(feature = "bundled")]
+pub mod bundled;
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "cbor")]
+mod cbor;
+mod client;
+mod complexity;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+pub mod custom;
+pub mod de;
+mod dedup;
+pub mod device;
+pub mod device_class;
+pub mod device_guess;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+mod domain;
+mod engine;
+#[cfg(feature = "fetch")]
+mod fetch;
+pub mod import;
+#[cfg(feature = "metrics")]
+mod instrument;
+mod interner;
+mod lazy;
+pub mod lint;
+#[cfg(feature = "yaml")]
+mod load;
+mod merge;
+mod normalize;
+#[cfg(feature = "tokio")]
+mod offload;
+pub mod os;
+mod profile;
+#[cfg(feature = "reload")]
+mod reload;
 mod resolvers;
+pub mod rewrite;
+mod shared;
+mod strict;
+mod sync;
+pub mod user_agent;
+mod version;
+mod webview;
 
 /// Error returned if the conversion of [`Regexes`] to [`Extractor`]
 /// fails.
@@ -22,13 +87,44 @@ pub enum Error {
     BuildError(BuildError),
     /// A replacement template requires a group missing from the regex
     MissingGroup(usize),
+    /// A replacement template requires a named group missing from the
+    /// regex
+    MissingNamedGroup(String),
+    /// The artifact provided to [`Extractor::from_bytes`] is invalid.
+    InvalidArtifact(&'static str),
+    /// A parser could not be pushed into its extractor, wraps the
+    /// underlying error with the parser's location.
+    InvalidParser {
+        /// Name of the parsers list the parser is part of, e.g.
+        /// `os_parsers`.
+        domain: &'static str,
+        /// Index of the parser within its list.
+        index: usize,
+        /// Regex of the parser, as provided.
+        regex: String,
+        /// Underlying error.
+        source: Box<Error>,
+    },
+}
+impl Error {
+    fn in_parser(self, domain: &'static str, index: usize, regex: &str) -> Self {
+        Self::InvalidParser {
+            domain,
+            index,
+            regex: regex.into(),
+            source: Box::new(self),
+        }
+    }
 }
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::ParseError(p) => Some(p),
             Error::BuildError(b) => Some(b),
-            Error::MissingGroup(_) => None,
+            Error::InvalidParser { source, .. } => Some(source),
+            Error::MissingGroup(_) | Error::MissingNamedGroup(_) | Error::InvalidArtifact(_) => {
+                None
+            }
         }
     }
 }
@@ -54,14 +150,47 @@ impl From<BuildError> for Error {
 ///
 /// Can then be compiled to a full [`Extractor`], or an individual
 /// list of parsers can be converted to the corresponding extractor.
+///
+/// Fields may also be spelled in camelCase (e.g. `userAgentParsers`,
+/// `familyReplacement`), as produced by some converters. Unknown
+/// fields are ignored, see [`Regexes::deserialize_strict`] to reject
+/// them instead.
 #[allow(missing_docs)]
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
 pub struct Regexes<'a> {
+    #[serde(alias = "userAgentParsers")]
     pub user_agent_parsers: Vec<user_agent::Parser<'a>>,
+    #[serde(alias = "osParsers")]
     pub os_parsers: Vec<os::Parser<'a>>,
+    #[serde(alias = "deviceParsers")]
     pub device_parsers: Vec<device::Parser<'a>>,
 }
 
+impl<'de> Regexes<'de> {
+    /// Deserializes the regexes from any serde [`Deserializer`],
+    /// for data stored in formats (or configuration systems) the
+    /// crate has no dedicated helper for.
+    ///
+    /// The only dedicated helpers are for YAML (the `yaml` feature)
+    /// and CBOR (the `cbor` feature), other formats such as TOML or
+    /// MessagePack go through this method with the deserializer of
+    /// their own crate (e.g. `toml::Deserializer` or
+    /// `rmp_serde::Deserializer`).
+    ///
+    /// ```
+    /// # use ua_parser::Regexes;
+    /// let json = r#"{"user_agent_parsers": [{"regex": "(Foo)"}], "os_parsers": [], "device_parsers": []}"#;
+    /// let regexes = Regexes::from_deserializer(&mut serde_json::Deserializer::from_str(json))?;
+    /// assert_eq!(regexes.user_agent_parsers[0].regex, "(Foo)");
+    /// # Ok::<(), serde_json::Error>(())
+    /// ```
+    ///
+    /// [`Deserializer`]: serde::Deserializer
+    pub fn from_deserializer<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Self::deserialize(d)
+    }
+}
+
 impl<'a> TryFrom<Regexes<'a>> for Extractor<'a> {
     type Error = Error;
     /// Compile parsed regexes to the corresponding full extractor.
@@ -70,675 +199,1880 @@ impl<'a> TryFrom<Regexes<'a>> for Extractor<'a> {
     /// all three domains extracted, as creating the individual
     /// extractors does have a cost.
     fn try_from(r: Regexes<'a>) -> Result<Self, Error> {
-        let ua = r
-            .user_agent_parsers
-            .into_iter()
-            .try_fold(user_agent::Builder::new(), |b, p| b.push(p))?
-            .build()?;
-        let os = r
-            .os_parsers
-            .into_iter()
-            .try_fold(os::Builder::new(), |b, p| b.push(p))?
-            .build()?;
-        let dev = r
-            .device_parsers
-            .into_iter()
-            .try_fold(device::Builder::new(), |b, p| b.push(p))?
-            .build()?;
-        Ok(Extractor { ua, os, dev })
+        Extractor::assemble_from(
+            user_agent::Builder::new(),
+            os::Builder::new(),
+            device::Builder::new(),
+            dedup::Dedup::default(),
+            r,
+            Err,
+        )
     }
 }
 
-/// Full extractor, simply delegates to the underlying individual
-/// extractors for the actual job.
+/// Full extractor, delegates to the underlying individual extractors
+/// for the actual job.
+///
+/// The individual extractors can be used on their own, but
+/// [`Extractor::extract`] prefilters the user agent string for all
+/// three domains in a single pass.
+///
+/// This combined prefilter is built in addition to the prefilters of
+/// the individual extractors, which the latter need to be usable on
+/// their own: it holds the atoms of every domain again, roughly
+/// doubling the memory used by prefilters (see
+/// [`Extractor::memory_usage`]). Programs only needing some of the
+/// domains should use the individual extractors instead.
 #[allow(missing_docs)]
 pub struct Extractor<'a> {
     pub ua: user_agent::Extractor<'a>,
     pub os: os::Extractor<'a>,
     pub dev: device::Extractor<'a>,
+    /// Prefilter over the regexes of all three extractors, in order.
+    prefilter: regex_filtered::Prefilter,
+    /// For each regex of the prefilter, the index of its first
+    /// occurrence if it is duplicated (possibly in another domain).
+    duplicates: Box<[Option<usize>]>,
+    budget: Budget,
+    max_length: Option<usize>,
+    /// Known user agents and their results, checked before matching.
+    precomputed: HashMap<Box<str>, Client>,
+    /// Provided data metadata, the parser counts are not filled.
+    info: DataInfo,
 }
 impl<'a> Extractor<'a> {
-    /// Performs the extraction on every sub-extractor in sequence.
-    pub fn extract(
-        &'a self,
-        ua: &'a str,
-    ) -> (
-        Option<user_agent::ValueRef<'a>>,
-        Option<os::ValueRef<'a>>,
-        Option<device::ValueRef<'a>>,
-    ) {
-        (
-            self.ua.extract(ua),
-            self.os.extract(ua),
-            self.dev.extract(ua),
+    /// Same as [`Extractor::try_from`], but the regexes are rewritten
+    /// according to `rewrite` rather than the default configuration,
+    /// e.g. to keep the engine's Unicode semantics:
+    ///
+    /// ```
+    /// # use ua_parser::{rewrite, Extractor, Regexes};
+    /// # let regexes = Regexes {
+    /// #     user_agent_parsers: vec![],
+    /// #     os_parsers: vec![],
+    /// #     device_parsers: vec![],
+    /// # };
+    /// let extractor = Extractor::try_from_with(
+    ///     regexes,
+    ///     rewrite::Config::new().ascii_classes(false),
+    /// )?;
+    /// # Ok::<(), ua_parser::Error>(())
+    /// ```
+    pub fn try_from_with(r: Regexes<'a>, rewrite: rewrite::Config) -> Result<Self, Error> {
+        Self::assemble_from(
+            user_agent::Builder::new().rewrite(rewrite),
+            os::Builder::new().rewrite(rewrite),
+            device::Builder::new().rewrite(rewrite),
+            dedup::Dedup::default(),
+            r,
+            Err,
         )
     }
-}
 
-/// User agent module.
-///
-/// The user agent is the representation of the browser, in UAP lingo
-/// the user agent is composed of a *family* (the browser project) and
-/// a *version* of up to 4 segments.
-pub mod user_agent {
-    use serde::Deserialize;
-    use std::borrow::Cow;
-
-    use crate::resolvers::{FallbackResolver, FamilyResolver};
-    use regex_filtered::BuildError;
-
-    /// Individual user agent parser description. Plain data which can
-    /// be deserialized from serde-compatible storage, or created
-    /// literally (e.g. using a conversion or build script).
-    #[derive(Deserialize, Default)]
-    pub struct Parser<'a> {
-        /// Regex to check the UA against, if the regex matches the
-        /// parser applies.
-        pub regex: Cow<'a, str>,
-        /// If set, used for the [`ValueRef::family`] field. If it
-        /// contains a `$1` placeholder, that is replaced by the value
-        /// of the first match group.
-        ///
-        /// If unset, the first match group is used directly.
-        pub family_replacement: Option<Cow<'a, str>>,
-        /// If set, provides the value of the major version number,
-        /// otherwise the second match group is used.
-        pub v1_replacement: Option<Cow<'a, str>>,
-        /// If set, provides the value of the minor version number,
-        /// otherwise the third match group is used.
-        pub v2_replacement: Option<Cow<'a, str>>,
-        /// If set, provides the value of the patch version number,
-        /// otherwise the fourth match group is used.
-        pub v3_replacement: Option<Cow<'a, str>>,
-        /// If set, provides the value of the minor patch version
-        /// number, otherwise the fifth match group is used.
-        pub v4_replacement: Option<Cow<'a, str>>,
-    }
-
-    type Repl<'a> = (
-        FamilyResolver<'a>,
-        // Per spec, should actually be restrict-templated (same as
-        // family but for indexes 2-5 instead of 1).
-        FallbackResolver<'a>,
-        FallbackResolver<'a>,
-        FallbackResolver<'a>,
-        FallbackResolver<'a>,
-    );
-
-    /// Extractor builder, used to `push` parsers into before building
-    /// the extractor.
-    #[derive(Default)]
-    pub struct Builder<'a> {
-        builder: regex_filtered::Builder,
-        repl: Vec<Repl<'a>>,
-    }
-    impl<'a> Builder<'a> {
-        /// Initialise an empty builder.
-        pub fn new() -> Self {
-            Self::default()
-        }
+    /// Sets the handling of empty capture groups for all three
+    /// domains.
+    pub fn empty_captures(mut self, empty: EmptyCapture) -> Self {
+        self.ua = self.ua.empty_captures(empty);
+        self.os = self.os.empty_captures(empty);
+        self.dev = self.dev.empty_captures(empty);
+        self
+    }
 
-        /// Build the extractor, may be called without pushing any
-        /// parser in though that is not very useful.
-        pub fn build(self) -> Result<Extractor<'a>, BuildError> {
-            let Self { builder, repl } = self;
+    /// Sets the budget of each extraction, unlimited by default.
+    ///
+    /// ```
+    /// # use ua_parser::{Budget, Extractor, Regexes};
+    /// # use std::time::Duration;
+    /// # let regexes = Regexes {
+    /// #     user_agent_parsers: vec![],
+    /// #     os_parsers: vec![],
+    /// #     device_parsers: vec![],
+    /// # };
+    /// let extractor = Extractor::try_from(regexes)?.budget(
+    ///     Budget::new()
+    ///         .max_verified(50)
+    ///         .max_duration(Duration::from_millis(1)),
+    /// );
+    /// # Ok::<(), ua_parser::Error>(())
+    /// ```
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
 
-            Ok(Extractor {
-                matcher: builder.build()?,
-                repl,
-            })
-        }
+    /// Only considers the first `n` bytes of the user agents (on a
+    /// character boundary), the rest being ignored by both the
+    /// matching and the extraction.
+    ///
+    /// Legitimate user agents are rarely longer than a few hundred
+    /// bytes, while multi-kilobyte junk can be very costly to scan.
+    ///
+    /// ```
+    /// # use ua_parser::{Extractor, Regexes};
+    /// # let regexes = Regexes {
+    /// #     user_agent_parsers: vec![ua_parser::user_agent::Parser::new(r"(Firefox)/(\d+)")],
+    /// #     os_parsers: vec![],
+    /// #     device_parsers: vec![],
+    /// # };
+    /// let extractor = Extractor::try_from(regexes)?.max_length(10);
+    /// assert_eq!(extractor.extract("Firefox/121").ua.unwrap().major.as_deref(), Some("12"));
+    /// # Ok::<(), ua_parser::Error>(())
+    /// ```
+    pub fn max_length(mut self, n: usize) -> Self {
+        self.max_length = Some(n);
+        self
+    }
 
-        /// Pushes a parser into the builder, may fail if the
-        /// [`Parser::regex`] is invalid.
-        pub fn push(mut self, ua: Parser<'a>) -> Result<Self, super::Error> {
-            self.builder = self.builder.push(&super::rewrite_regex(&ua.regex))?;
-            let r = &self.builder.regexes()[self.builder.regexes().len() - 1];
-            // number of groups in regex, excluding implicit entire match group
-            let groups = r.captures_len() - 1;
-            self.repl.push((
-                FamilyResolver::new(ua.family_replacement, groups)?,
-                FallbackResolver::new(ua.v1_replacement, groups, 2),
-                FallbackResolver::new(ua.v2_replacement, groups, 3),
-                FallbackResolver::new(ua.v3_replacement, groups, 4),
-                FallbackResolver::new(ua.v4_replacement, groups, 5),
-            ));
-            Ok(self)
-        }
+    /// Registers known user agents with their results, which are
+    /// returned as-is by the extraction methods without running any
+    /// regex.
+    ///
+    /// The table is typically built offline from the most frequent
+    /// user agents of actual traffic, and loaded from any format
+    /// supported by serde, [`Client`] being (de)serializable. As the
+    /// bulk of the traffic is a few thousand user agents, this turns
+    /// most extractions into a single hash lookup. The results are not
+    /// checked against the parsers, and the table is kept by
+    /// [`Self::extend`].
+    ///
+    /// The user agents are looked up in full, before any
+    /// [`Self::max_length`] truncation.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use ua_parser::{Client, Extractor, Regexes};
+    /// # let regexes = Regexes {
+    /// #     user_agent_parsers: vec![],
+    /// #     os_parsers: vec![],
+    /// #     device_parsers: vec![],
+    /// # };
+    /// let table: HashMap<String, Client> = serde_json::from_str(r#"{
+    ///     "Mozilla/5.0 Firefox/121.0": {"ua": {"family": "Firefox", "major": "121", "minor": "0"}}
+    /// }"#)?;
+    /// let extractor = Extractor::try_from(regexes)?.precomputed(table);
+    /// assert_eq!(extractor.extract("Mozilla/5.0 Firefox/121.0").family(), "Firefox");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn precomputed(mut self, table: impl IntoIterator<Item = (String, Client)>) -> Self {
+        self.precomputed
+            .extend(table.into_iter().map(|(ua, c)| (ua.into_boxed_str(), c)));
+        self
+    }
 
-        /// Bulk loading of parsers into the builder.
-        pub fn push_all<I>(self, ua: I) -> Result<Self, super::Error>
-        where
-            I: IntoIterator<Item = Parser<'a>>,
-        {
-            ua.into_iter().try_fold(self, |s, p| s.push(p))
-        }
+    /// Sets the historical hit counts of the parsers of each domain,
+    /// e.g. from [`Profiler::frequencies`], see
+    /// [`user_agent::Extractor::frequencies`].
+    ///
+    /// [`Self::matches_any`] also verifies the candidates by
+    /// decreasing frequency.
+    pub fn frequencies(mut self, [ua, os, dev]: [Vec<usize>; 3]) -> Self {
+        self.ua = self.ua.frequencies(ua);
+        self.os = self.os.frequencies(os);
+        self.dev = self.dev.frequencies(dev);
+        self
     }
 
-    /// User Agent extractor.
-    pub struct Extractor<'a> {
-        matcher: regex_filtered::Regexes,
-        repl: Vec<Repl<'a>>,
-    }
-    impl<'a> Extractor<'a> {
-        /// Tries the loaded [`Parser`], upon finding the first
-        /// matching [`Parser`] performs data extraction following its
-        /// replacement directives and returns the result.
-        ///
-        /// Returns [`None`] if:
-        ///
-        /// - no matching parser was found
-        /// - the match does not have any matching groups *and*
-        ///   [`Parser::family_replacement`] is unset
-        /// - [`Parser::family_replacement`] has a substitution
-        ///   but there is no group in the regex
-        pub fn extract(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
-            let (idx, re) = self.matcher.matching(ua).next()?;
-            let c = re.captures(ua)?;
-
-            let (f, v1, v2, v3, v4) = &self.repl[idx];
-
-            Some(ValueRef {
-                family: f.resolve(&c),
-                major: v1.resolve(&c),
-                minor: v2.resolve(&c),
-                patch: v3.resolve(&c),
-                patch_minor: v4.resolve(&c),
-            })
+    /// Sets the version of the data the extractor is built from
+    /// (e.g. the `git describe` of uap-core), for [`Self::data_info`].
+    pub fn data_version(mut self, version: impl Into<String>) -> Self {
+        self.info.version = Some(version.into());
+        self
+    }
+
+    /// Sets the digest of the data file the extractor is built from
+    /// (see [`DataInfo::digest`]), for [`Self::data_info`].
+    pub fn data_digest(mut self, digest: u64) -> Self {
+        self.info.digest = Some(digest);
+        self
+    }
+
+    /// Metadata about the data the extractor was built from, e.g. to
+    /// report which ruleset produced a service's analytics.
+    ///
+    /// ```
+    /// # use ua_parser::{DataInfo, Extractor, Regexes};
+    /// let yaml = "
+    /// user_agent_parsers: [{regex: '(Firefox)/(\\d+)'}]
+    /// os_parsers: []
+    /// device_parsers: []
+    /// ";
+    /// let regexes: Regexes = serde_yaml::from_str(yaml)?;
+    /// let extractor = Extractor::try_from(regexes)?
+    ///     .data_version("v0.18.0")
+    ///     .data_digest(DataInfo::digest(yaml.as_bytes()));
+    ///
+    /// let info = extractor.data_info();
+    /// assert_eq!(info.version.as_deref(), Some("v0.18.0"));
+    /// assert_eq!(info.parsers, [1, 0, 0]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn data_info(&self) -> DataInfo {
+        DataInfo {
+            parsers: [
+                self.ua.matcher().regexes().len(),
+                self.os.matcher().regexes().len(),
+                self.dev.matcher().regexes().len(),
+            ],
+            ..self.info.clone()
         }
     }
-    /// Borrowed extracted value, borrows the content of the original
-    /// parser or the content of the user agent string, unless a
-    /// replacement is performed. (which is only possible for the )
-    #[derive(PartialEq, Eq, Default, Debug)]
-    pub struct ValueRef<'a> {
-        ///
-        pub family: Cow<'a, str>,
-        ///
-        pub major: Option<&'a str>,
-        ///
-        pub minor: Option<&'a str>,
-        ///
-        pub patch: Option<&'a str>,
-        ///
-        pub patch_minor: Option<&'a str>,
-    }
-
-    impl ValueRef<'_> {
-        /// Converts the borrowed result into an owned one,
-        /// independent from both the extractor and the user agent
-        /// string.
-        pub fn into_owned(self) -> Value {
-            Value {
-                family: self.family.into_owned(),
-                major: self.major.map(|c| c.to_string()),
-                minor: self.minor.map(|c| c.to_string()),
-                patch: self.patch.map(|c| c.to_string()),
-                patch_minor: self.patch_minor.map(|c| c.to_string()),
+
+    /// Truncates the user agent to the maximum length, if any.
+    fn truncate<'u>(&self, ua: &'u str) -> &'u str {
+        match self.max_length {
+            Some(n) if n < ua.len() => {
+                let end = (0..=n).rev().find(|&i| ua.is_char_boundary(i));
+                &ua[..end.unwrap_or(0)]
             }
+            _ => ua,
         }
     }
 
-    /// Owned extracted value, identical to [`ValueRef`] but not
-    /// linked to either the UA string or the extractor.
-    #[derive(PartialEq, Eq, Default, Debug)]
-    pub struct Value {
-        ///
-        pub family: String,
-        ///
-        pub major: Option<String>,
-        ///
-        pub minor: Option<String>,
-        ///
-        pub patch: Option<String>,
-        ///
-        pub patch_minor: Option<String>,
+    /// Same as [`Extractor::try_from`], but parsers which fail to
+    /// build are skipped instead of failing the entire extractor.
+    ///
+    /// Returns the extractor built from the valid parsers, along with
+    /// the [`Error::InvalidParser`] of every skipped parser. Building
+    /// the prefilters can still fail.
+    pub fn try_from_lenient(r: Regexes<'a>) -> Result<(Self, Vec<Error>), Error> {
+        let mut skipped = Vec::new();
+        let extractor = Self::assemble_from(
+            user_agent::Builder::new(),
+            os::Builder::new(),
+            device::Builder::new(),
+            dedup::Dedup::default(),
+            r,
+            |e| {
+                skipped.push(e);
+                Ok(())
+            },
+        )?;
+        Ok((extractor, skipped))
     }
-}
 
-/// OS extraction module
-pub mod os {
-    use serde::Deserialize;
-    use std::borrow::Cow;
-
-    use regex_filtered::{BuildError, ParseError};
-
-    use crate::resolvers::{OptResolver, Resolver};
-
-    /// OS parser configuration
-    #[derive(Deserialize, Default)]
-    pub struct Parser<'a> {
-        ///
-        pub regex: Cow<'a, str>,
-        /// Replacement for the [`ValueRef::os`], must be set if there
-        /// is no capture in the [`Self::regex`], if there are
-        /// captures may be fully templated (with `$n` placeholders
-        /// for any group of the [`Self::regex`]).
-        pub os_replacement: Option<Cow<'a, str>>,
-        /// Replacement for the [`ValueRef::major`], may be fully templated.
-        pub os_v1_replacement: Option<Cow<'a, str>>,
-        /// Replacement for the [`ValueRef::minor`], may be fully templated.
-        pub os_v2_replacement: Option<Cow<'a, str>>,
-        /// Replacement for the [`ValueRef::patch`], may be fully templated.
-        pub os_v3_replacement: Option<Cow<'a, str>>,
-        /// Replacement for the [`ValueRef::patch_minor`], may be fully templated.
-        pub os_v4_replacement: Option<Cow<'a, str>>,
-    }
-    /// Builder for [`Extractor`].
-    #[derive(Default)]
-    pub struct Builder<'a> {
-        builder: regex_filtered::Builder,
-        repl: Vec<(
-            Resolver<'a>,
-            OptResolver<'a>,
-            OptResolver<'a>,
-            OptResolver<'a>,
-            OptResolver<'a>,
-        )>,
-    }
-    impl<'a> Builder<'a> {
-        ///
-        pub fn new() -> Self {
-            Self::default()
+    /// Pushes the parsers of `regexes` into the builders, then builds
+    /// the extractor. Every failed push is passed to `on_error`,
+    /// which either aborts the build or skips the parser.
+    fn assemble_from(
+        mut ua: user_agent::Builder<'a>,
+        mut os: os::Builder<'a>,
+        mut dev: device::Builder<'a>,
+        mut dedup: dedup::Dedup,
+        regexes: Regexes<'a>,
+        mut on_error: impl FnMut(Error) -> Result<(), Error>,
+    ) -> Result<Self, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "build",
+            user_agent = regexes.user_agent_parsers.len(),
+            os = regexes.os_parsers.len(),
+            device = regexes.device_parsers.len(),
+        )
+        .entered();
+        for (i, p) in regexes.user_agent_parsers.into_iter().enumerate() {
+            if let Err(e) = ua.push_with(p, i, Some(&mut dedup)) {
+                on_error(e)?;
+            }
+        }
+        for (i, p) in regexes.os_parsers.into_iter().enumerate() {
+            if let Err(e) = os.push_with(p, i, Some(&mut dedup)) {
+                on_error(e)?;
+            }
+        }
+        for (i, p) in regexes.device_parsers.into_iter().enumerate() {
+            if let Err(e) = dev.push_with(p, i, Some(&mut dedup)) {
+                on_error(e)?;
+            }
         }
 
-        /// Builds the [`Extractor`], may fail if building the
-        /// prefilter fails.
-        pub fn build(self) -> Result<Extractor<'a>, BuildError> {
-            let Self { builder, repl } = self;
+        let prefilter =
+            regex_filtered::Prefilter::build([ua.matcher(), os.matcher(), dev.matcher()])?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            user_agent = ua.matcher().regexes().len(),
+            os = os.matcher().regexes().len(),
+            device = dev.matcher().regexes().len(),
+            "prefilter built"
+        );
+        Ok(Extractor {
+            ua: ua.build()?,
+            os: os.build()?,
+            dev: dev.build()?,
+            prefilter,
+            duplicates: dedup.finish(),
+            budget: Budget::default(),
+            max_length: None,
+            precomputed: HashMap::new(),
+            info: DataInfo::default(),
+        })
+    }
 
-            Ok(Extractor {
-                matcher: builder.build()?,
-                repl,
-            })
-        }
+    /// Appends the parsers of `regexes` to the corresponding domains,
+    /// e.g. to register plugin or tenant-specific rules at runtime.
+    ///
+    /// The existing regexes are not recompiled, but the prefilters
+    /// are rebuilt entirely.
+    pub fn extend(self, regexes: Regexes<'a>) -> Result<Self, Error> {
+        let mut dedup = dedup::Dedup::default();
+        let ua = self.ua.into_builder(Some(&mut dedup))?;
+        let os = self.os.into_builder(Some(&mut dedup))?;
+        let dev = self.dev.into_builder(Some(&mut dedup))?;
+        let (budget, max_length, info) = (self.budget, self.max_length, self.info);
+        let precomputed = self.precomputed;
+        let mut e = Self::assemble_from(ua, os, dev, dedup, regexes, Err)?;
+        e.budget = budget;
+        e.max_length = max_length;
+        e.precomputed = precomputed;
+        // the data has been extended, so the digest no longer matches
+        e.info = DataInfo {
+            digest: None,
+            ..info
+        };
+        Ok(e)
+    }
 
-        /// Add a [`Parser`] configuration, fails if the regex can not
-        /// be parsed, or if [`Parser::os_replacement`] is missing and
-        /// the regex has no groups.
-        pub fn push(mut self, os: Parser<'a>) -> Result<Self, ParseError> {
-            self.builder = self.builder.push(&super::rewrite_regex(&os.regex))?;
-            let r = &self.builder.regexes()[self.builder.regexes().len() - 1];
-            // number of groups in regex, excluding implicit entire match group
-            let groups = r.captures_len() - 1;
-            self.repl.push((
-                Resolver::new(os.os_replacement, groups, 1),
-                OptResolver::new(os.os_v1_replacement, groups, 2),
-                OptResolver::new(os.os_v2_replacement, groups, 3),
-                OptResolver::new(os.os_v3_replacement, groups, 4),
-                OptResolver::new(os.os_v4_replacement, groups, 5),
-            ));
-            Ok(self)
-        }
+    /// Approximate heap memory used by the extractor, in bytes: the
+    /// three domain extractors (including their own prefilters) plus
+    /// the combined prefilter, which duplicates their atoms. The
+    /// compiled regexes' internals are not accounted for.
+    pub fn memory_usage(&self) -> usize {
+        self.ua.memory_usage()
+            + self.os.memory_usage()
+            + self.dev.memory_usage()
+            + self.prefilter.memory_usage()
+            + std::mem::size_of_val(&*self.duplicates)
+            + self.precomputed.capacity() * std::mem::size_of::<(Box<str>, Client)>()
+            + self.precomputed.keys().map(|k| k.len()).sum::<usize>()
+    }
 
-        /// Bulk loading of parsers into the builder.
-        pub fn push_all<I>(self, ua: I) -> Result<Self, ParseError>
-        where
-            I: IntoIterator<Item = Parser<'a>>,
-        {
-            ua.into_iter().try_fold(self, |s, p| s.push(p))
-        }
+    /// Performs the extraction for every domain, scanning the user
+    /// agent string once for the candidate regexes of all three, then
+    /// verifying each domain's candidates in order until one matches.
+    pub fn extract(&'a self, ua: &'a str) -> ClientRef<'a> {
+        self.extract_with(ua, Domains::ALL)
     }
 
-    /// OS extractor structure
-    pub struct Extractor<'a> {
-        matcher: regex_filtered::Regexes,
-        repl: Vec<(
-            Resolver<'a>,
-            OptResolver<'a>,
-            OptResolver<'a>,
-            OptResolver<'a>,
-            OptResolver<'a>,
-        )>,
-    }
-    impl<'a> Extractor<'a> {
-        /// Matches & extracts the OS data for this user agent,
-        /// returns `None` if the UA string could not be matched.
-        pub fn extract(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
-            let (idx, re) = self.matcher.matching(ua).next()?;
-            let c = re.captures(ua)?;
-
-            let (o, v1, v2, v3, v4) = &self.repl[idx];
-
-            Some(ValueRef {
-                os: o.resolve(&c),
-                major: v1.resolve(&c),
-                minor: v2.resolve(&c),
-                patch: v3.resolve(&c),
-                patch_minor: v4.resolve(&c),
-            })
+    /// Performs the extraction on every user agent of the batch.
+    pub fn extract_batch(&'a self, uas: &[&'a str]) -> Vec<ClientRef<'a>> {
+        uas.iter().map(|ua| self.extract(ua)).collect()
+    }
+
+    /// Same as [`Self::extract_batch`], but spreads the extractions
+    /// over rayon's global thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn par_extract_batch(&'a self, uas: &[&'a str]) -> Vec<ClientRef<'a>> {
+        use rayon::prelude::*;
+        uas.par_iter().map(|ua| self.extract(ua)).collect()
+    }
+
+    /// Returns whether any parser of any domain matches the user
+    /// agent, without performing any extraction.
+    pub fn matches_any(&self, ua: &str) -> bool {
+        if let Some(c) = self.precomputed.get(ua) {
+            return c.ua.is_some() || c.os.is_some() || c.device.is_some();
+        }
+        let ua = self.truncate(ua);
+        let matchers = [self.ua.matcher(), self.os.matcher(), self.dev.matcher()];
+        let frequencies = [
+            self.ua.hit_counts(),
+            self.os.hit_counts(),
+            self.dev.hit_counts(),
+        ];
+        let mut domain = 0;
+        let mut offset = 0;
+        let mut candidates = self.prefilter.candidates(ua).filter_map(|idx| {
+            // duplicates are always candidates along with their first
+            // occurrence, which is verified instead
+            if self.duplicates[idx].is_some_and(|first| first != idx) {
+                return None;
+            }
+            while idx - offset >= matchers[domain].regexes().len() {
+                offset += matchers[domain].regexes().len();
+                domain += 1;
+            }
+            Some((domain, idx - offset))
+        });
+        if frequencies.iter().all(|f| f.is_empty()) {
+            return candidates.any(|(d, idx)| matchers[d].verify(idx, ua));
         }
+        let mut candidates = candidates.collect::<Vec<_>>();
+        candidates.sort_by_key(|&(d, idx)| {
+            std::cmp::Reverse(frequencies[d].get(idx).copied().unwrap_or(0))
+        });
+        candidates
+            .into_iter()
+            .any(|(d, idx)| matchers[d].verify(idx, ua))
+    }
+
+    /// Performs the extraction for the requested domains only, the
+    /// others are always [`None`].
+    ///
+    /// ```
+    /// # use ua_parser::{ClientRef, Domains, Extractor, Regexes};
+    /// # let extractor = Extractor::try_from(Regexes {
+    /// #     user_agent_parsers: vec![],
+    /// #     os_parsers: vec![ua_parser::os::Parser {
+    /// #         regex: r"(Android) (\d+)".into(),
+    /// #         ..Default::default()
+    /// #     }],
+    /// #     device_parsers: vec![],
+    /// # })?;
+    /// let ClientRef { ua, os, device } = extractor.extract_with(
+    ///     "Mozilla/5.0 (Linux; Android 14)",
+    ///     Domains::UA | Domains::OS,
+    /// );
+    /// assert!(ua.is_none());
+    /// assert_eq!(os.unwrap().major.as_deref(), Some("14"));
+    /// assert!(device.is_none());
+    /// # Ok::<(), ua_parser::Error>(())
+    /// ```
+    pub fn extract_with(&'a self, ua: &'a str, domains: Domains) -> ClientRef<'a> {
+        self.extract_counted(ua, domains, None)
+    }
+
+    /// Same as [`Self::extract`], but also returns statistics about
+    /// the work performed, e.g. to monitor the effectiveness of the
+    /// prefilter on actual traffic.
+    ///
+    /// Gathering the statistics requires going through all the
+    /// candidates, so this is slightly slower than [`Self::extract`].
+    pub fn extract_stats(&'a self, ua: &'a str) -> (ClientRef<'a>, ExtractStats) {
+        let mut stats = ExtractStats::default();
+        let client = self.extract_counted(ua, Domains::ALL, Some(&mut stats));
+        (client, stats)
     }
 
-    /// An OS extraction result.
-    #[derive(PartialEq, Eq, Default, Debug)]
-    pub struct ValueRef<'a> {
-        ///
-        pub os: Cow<'a, str>,
-        ///
-        pub major: Option<Cow<'a, str>>,
-        ///
-        pub minor: Option<Cow<'a, str>>,
-        ///
-        pub patch: Option<Cow<'a, str>>,
-        ///
-        pub patch_minor: Option<Cow<'a, str>>,
-    }
-
-    impl ValueRef<'_> {
-        /// Converts a [`ValueRef`] into a [`Value`] to avoid lifetime
-        /// concerns, may need to allocate and copy any data currently
-        /// borrowed from a [`Parser`] or user agent string.
-        pub fn into_owned(self) -> Value {
-            Value {
-                os: self.os.into_owned(),
-                major: self.major.map(|c| c.into_owned()),
-                minor: self.minor.map(|c| c.into_owned()),
-                patch: self.patch.map(|c| c.into_owned()),
-                patch_minor: self.patch_minor.map(|c| c.into_owned()),
+    fn extract_counted(
+        &'a self,
+        ua: &'a str,
+        domains: Domains,
+        stats: Option<&mut ExtractStats>,
+    ) -> ClientRef<'a> {
+        // the clock is only read if the budget or the metrics need it
+        let start = (self.budget.duration.is_some() || cfg!(feature = "metrics"))
+            .then(std::time::Instant::now);
+        if let Some(c) = self.precomputed.get(ua) {
+            let client = c.borrow_with(domains);
+            if let Some(s) = stats {
+                for (v, d) in [
+                    (client.ua.is_some(), Domains::UA),
+                    (client.os.is_some(), Domains::OS),
+                    (client.device.is_some(), Domains::DEVICE),
+                ] {
+                    if v {
+                        s.matched |= d;
+                    }
+                }
+            }
+            return client;
+        }
+        let ua = self.truncate(ua);
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "extract",
+            candidates = tracing::field::Empty,
+            verified = tracing::field::Empty,
+        )
+        .entered();
+        let matchers = [self.ua.matcher(), self.os.matcher(), self.dev.matcher()];
+        let wanted = [Domains::UA, Domains::OS, Domains::DEVICE].map(|d| domains.contains(d));
+        let Some(last) = wanted.iter().rposition(|&w| w) else {
+            return ClientRef::default();
+        };
+        let mut found = [None; 3];
+        // verification results of duplicated regexes, by first occurrence
+        let mut verified = Vec::new();
+        let mut candidates = [0; 3];
+        let mut verifications = 0;
+        let mut exhausted = false;
+        let mut done = false;
+        let mut domain = 0;
+        let mut offset = 0;
+        for idx in self.prefilter.candidates(ua) {
+            while idx - offset >= matchers[domain].regexes().len() {
+                offset += matchers[domain].regexes().len();
+                domain += 1;
+            }
+            candidates[domain] += 1;
+            if done || domain > last {
+                // keep counting the candidates if requested
+                if stats.is_none() {
+                    break;
+                }
+                continue;
+            }
+            if !wanted[domain] || found[domain].is_some() {
+                continue;
+            }
+            if self.budget.exceeded(verifications, start) {
+                exhausted = true;
+                done = true;
+                continue;
+            }
+            let mut verify = || {
+                verifications += 1;
+                matchers[domain].verify(idx - offset, ua)
+            };
+            let matched = match self.duplicates[idx] {
+                None => verify(),
+                Some(first) => match verified.iter().find(|&&(i, _)| i == first) {
+                    Some(&(_, m)) => m,
+                    None => {
+                        let m = verify();
+                        verified.push((first, m));
+                        m
+                    }
+                },
+            };
+            if matched {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(
+                    domain = ["user_agent", "os", "device"][domain],
+                    parser = idx - offset,
+                    "match"
+                );
+                found[domain] = Some(idx - offset);
+                done = std::iter::zip(found, wanted).all(|(f, w)| f.is_some() || !w);
+            }
+        }
+
+        let mut matched = Domains::default();
+        for (f, d) in std::iter::zip(found, [Domains::UA, Domains::OS, Domains::DEVICE]) {
+            if f.is_some() {
+                matched |= d;
             }
         }
+        #[cfg(feature = "tracing")]
+        span.record("candidates", tracing::field::debug(candidates))
+            .record("verified", verifications);
+        if let Some(s) = stats {
+            s.candidates = candidates;
+            s.verified = verifications;
+            s.matched = matched;
+            s.exhausted = exhausted;
+        }
+        let [u, o, d] = found;
+        let client = ClientRef {
+            ua: u.and_then(|idx| self.ua.extract_at(idx, ua)),
+            os: o.and_then(|idx| self.os.extract_at(idx, ua)),
+            device: d.and_then(|idx| self.dev.extract_at(idx, ua)),
+        };
+        #[cfg(feature = "metrics")]
+        instrument::extraction(start, verifications, matched);
+        client
     }
+}
+
+/// Common interface of the single domain extractors, allowing them
+/// to be used generically e.g. to wrap or mock them.
+///
+/// ```
+/// # use ua_parser::{Extract, Regexes};
+/// fn extract_all<'a, E: Extract<'a>>(e: &'a E, uas: &[&'a str]) -> Vec<Option<E::Output>> {
+///     uas.iter().map(|ua| e.extract(ua)).collect()
+/// }
+///
+/// let os = ua_parser::os::Builder::new()
+///     .push(ua_parser::os::Parser {
+///         regex: r"(Android) (\d+)".into(),
+///         ..Default::default()
+///     })?
+///     .build()?;
+/// let values = extract_all(&os, &["Linux; Android 14", "curl/8.0"]);
+/// assert_eq!(values[0].as_ref().unwrap().os, "Android");
+/// assert!(values[1].is_none());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait Extract<'a> {
+    /// Value extracted from the user agent.
+    type Output;
 
-    /// Owned version of [`ValueRef`].
-    #[derive(PartialEq, Eq, Default, Debug)]
-    pub struct Value {
-        ///
-        pub os: String,
-        ///
-        pub major: Option<String>,
-        ///
-        pub minor: Option<String>,
-        ///
-        pub patch: Option<String>,
-        ///
-        pub patch_minor: Option<String>,
+    /// Performs the extraction, returns [`None`] if no parser
+    /// matches.
+    fn extract(&'a self, ua: &'a str) -> Option<Self::Output>;
+}
+impl<'a> Extract<'a> for user_agent::Extractor<'a> {
+    type Output = user_agent::ValueRef<'a>;
+    fn extract(&'a self, ua: &'a str) -> Option<Self::Output> {
+        user_agent::Extractor::extract(self, ua)
+    }
+}
+impl<'a> Extract<'a> for os::Extractor<'a> {
+    type Output = os::ValueRef<'a>;
+    fn extract(&'a self, ua: &'a str) -> Option<Self::Output> {
+        os::Extractor::extract(self, ua)
+    }
+}
+impl<'a> Extract<'a> for device::Extractor<'a> {
+    type Output = device::ValueRef<'a>;
+    fn extract(&'a self, ua: &'a str) -> Option<Self::Output> {
+        device::Extractor::extract(self, ua)
+    }
+}
+impl<'a> Extract<'a> for bot::Extractor<'a> {
+    type Output = bot::ValueRef<'a>;
+    fn extract(&'a self, ua: &'a str) -> Option<Self::Output> {
+        bot::Extractor::extract(self, ua)
+    }
+}
+impl<'a> Extract<'a> for custom::Extractor<'a> {
+    type Output = custom::ValueRef<'a>;
+    fn extract(&'a self, ua: &'a str) -> Option<Self::Output> {
+        custom::Extractor::extract(self, ua)
     }
 }
 
-/// Extraction module for the device data of the user agent string.
-pub mod device {
-    use serde::Deserialize;
-    use std::borrow::Cow;
-
-    use regex_filtered::{BuildError, ParseError};
-
-    use crate::resolvers::{OptResolver, Resolver};
-
-    /// regex flags
-    #[derive(Deserialize, PartialEq, Eq)]
-    pub enum Flag {
-        /// Enables case-insensitive regex matching, deserializes from
-        /// the string `"i"`
-        #[serde(rename = "i")]
-        IgnoreCase,
-    }
-    /// Device parser description.
-    #[derive(Deserialize, Default)]
-    pub struct Parser<'a> {
-        /// Regex pattern to use for matching and data extraction.
-        pub regex: Cow<'a, str>,
-        /// Configuration flags for the regex, if any.
-        pub regex_flag: Option<Flag>,
-        /// Device replacement data, fully templated, must be present
-        /// *or* the regex must have at least one group, which will be
-        /// used instead.
-        pub device_replacement: Option<Cow<'a, str>>,
-        /// Brand replacement data, fully templated, optional, if
-        /// missing there is no fallback.
-        pub brand_replacement: Option<Cow<'a, str>>,
-        /// Model replacement data, fully templated, optional, if
-        /// missing will be replaced by the first group if the regex
-        /// has one.
-        pub model_replacement: Option<Cow<'a, str>>,
-    }
-
-    /// Extractor builder.
-    #[derive(Default)]
-    pub struct Builder<'a> {
-        builder: regex_filtered::Builder,
-        repl: Vec<(Resolver<'a>, OptResolver<'a>, OptResolver<'a>)>,
-    }
-    impl<'a> Builder<'a> {
-        /// Creates a builder in the default configurtion, which is
-        /// the only configuration.
-        pub fn new() -> Self {
-            Self::default()
-        }
+/// Statistics of a single extraction, returned by
+/// [`Extractor::extract_stats`].
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct ExtractStats {
+    /// Number of candidate regexes nominated by the prefilter, for
+    /// the [`user_agent`], [`os`], and [`device`] domains in order.
+    pub candidates: [usize; 3],
+    /// Number of candidates verified by running their regex, lower
+    /// than the total number of candidates as verification stops at
+    /// the first match of each domain.
+    pub verified: usize,
+    /// Domains in which a parser matched.
+    pub matched: Domains,
+    /// Whether the extraction was cut short by the [`Budget`].
+    pub exhausted: bool,
+}
 
-        /// Builds an Extractor, may fail if compiling the prefilter fails.
-        pub fn build(self) -> Result<Extractor<'a>, BuildError> {
-            let Self { builder, repl } = self;
+/// Version of the crate, e.g. to report the provenance of results
+/// along with [`DataInfo`].
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-            Ok(Extractor {
-                matcher: builder.build()?,
-                repl,
-            })
+/// Metadata about the data an [`Extractor`] was built from, returned
+/// by [`Extractor::data_info`].
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct DataInfo {
+    /// Version of the data, if provided via
+    /// [`Extractor::data_version`].
+    pub version: Option<String>,
+    /// Digest of the data file, if provided via
+    /// [`Extractor::data_digest`].
+    pub digest: Option<u64>,
+    /// Number of parsers of the [`user_agent`], [`os`], and
+    /// [`device`] domains in order.
+    pub parsers: [usize; 3],
+}
+impl DataInfo {
+    /// Computes the digest of a data file, a 64-bit FNV-1a hash which
+    /// is stable across platforms and versions of the crate. This
+    /// is for identification, not integrity.
+    pub const fn digest(data: &[u8]) -> u64 {
+        let mut hash = 0xcbf29ce484222325u64;
+        let mut i = 0;
+        while i < data.len() {
+            hash ^= data[i] as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+            i += 1;
         }
+        hash
+    }
+}
+
+/// Limits on the work performed by a single extraction, see
+/// [`Extractor::budget`].
+///
+/// Once the budget is exceeded, no further regex is verified and the
+/// extraction returns what it found so far, the remaining domains
+/// being [`None`]. This protects latency-sensitive services from
+/// pathological user agents, especially with large custom parser
+/// lists.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Budget {
+    verified: Option<usize>,
+    duration: Option<std::time::Duration>,
+}
+impl Budget {
+    /// Creates an unlimited budget.
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        /// Add a parser to the set, may fail if parsing the regex
-        /// fails *or* if [`Parser::device_replacement`] is unset and
-        /// [`Parser::regex`] does not have at least one group, or a
-        /// templated [`Parser::device_replacement`] requests groups
-        /// which [`Parser::regex`] is missing.
-        pub fn push(mut self, device: Parser<'a>) -> Result<Self, ParseError> {
-            self.builder = self.builder.push_opt(
-                &super::rewrite_regex(&device.regex),
-                regex_filtered::Options::new()
-                    .case_insensitive(device.regex_flag == Some(Flag::IgnoreCase)),
-            )?;
-            let r = &self.builder.regexes()[self.builder.regexes().len() - 1];
-            // number of groups in regex, excluding implicit entire match group
-            let groups = r.captures_len() - 1;
-            self.repl.push((
-                Resolver::new(device.device_replacement, groups, 1),
-                OptResolver::new(device.brand_replacement, 0, 999),
-                OptResolver::new(device.model_replacement, groups, 1),
-            ));
-            Ok(self)
+    /// Limits the number of regexes verified.
+    pub fn max_verified(mut self, n: usize) -> Self {
+        self.verified = Some(n);
+        self
+    }
+
+    /// Limits the duration of the extraction. Only checked between
+    /// verifications, so a single slow regex can still exceed it.
+    pub fn max_duration(mut self, duration: std::time::Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// `start` is only needed (and expected) if the duration is
+    /// limited.
+    fn exceeded(&self, verified: usize, start: Option<std::time::Instant>) -> bool {
+        self.verified.is_some_and(|n| verified >= n)
+            || self
+                .duration
+                .zip(start)
+                .is_some_and(|(d, start)| start.elapsed() >= d)
+    }
+}
+
+/// Set of extraction domains, for [`Extractor::extract_with`].
+/// Domains can be combined using `|`, the default is the empty set.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Domains(u8);
+impl Domains {
+    /// The [`user_agent`] domain.
+    pub const UA: Self = Self(1);
+    /// The [`os`] domain.
+    pub const OS: Self = Self(1 << 1);
+    /// The [`device`] domain.
+    pub const DEVICE: Self = Self(1 << 2);
+    /// All three domains.
+    pub const ALL: Self = Self(Self::UA.0 | Self::OS.0 | Self::DEVICE.0);
+
+    /// Returns whether all the domains of `other` are in `self`.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+impl std::ops::BitOr for Domains {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+impl std::ops::BitOrAssign for Domains {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Handling of capture groups which matched an empty string, when
+/// an optional value (e.g. a version) is taken directly from a group.
+/// Templated replacements are not affected, they always resolve to
+/// [`None`] if empty.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum EmptyCapture {
+    /// Empty groups are treated the same as groups which did not
+    /// participate in the match, the value is [`None`].
+    #[default]
+    Absent,
+    /// Empty groups resolve to an empty value, only groups which did
+    /// not participate in the match resolve to [`None`].
+    Keep,
+}
+
+/// Specificity score of a match for best-match resolution: the
+/// length of the match, then the number of participating groups.
+fn specificity(c: &engine::Captures<'_>) -> (usize, usize) {
+    let matched = engine::group(c, 0).map_or(0, str::len);
+    let groups = (1..c.len())
+        .filter(|&i| engine::group(c, i).is_some_and(|g| !g.is_empty()))
+        .count();
+    (matched, groups)
+}
+
+/// Provenance of an extracted value, as returned by the domain
+/// extractors' `extract_traced` methods.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MatchInfo<'a> {
+    /// Index of the matching parser, in the order the parsers were
+    /// pushed into the builder (so the order of the corresponding
+    /// list in `regexes.yaml`).
+    pub parser: usize,
+    /// Raw capture groups of the match, the first is the entire
+    /// match.
+    pub groups: Vec<Option<&'a str>>,
+}
+impl<'a> MatchInfo<'a> {
+    fn new(parser: usize, c: &engine::Captures<'a>) -> Self {
+        Self {
+            parser,
+            groups: engine::groups(c),
         }
+    }
+}
+
+#[cfg(test)]
+mod test_extractor {
+    use super::*;
+
+    /// Parsers of all three domains, with a regex shared by the os
+    /// and device domains.
+    fn extractor() -> Extractor<'static> {
+        Extractor::try_from(Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser {
+                    regex: r"(Firefox)/(\d+)".into(),
+                    ..Default::default()
+                },
+                user_agent::Parser {
+                    regex: r"(Mobile) Safari".into(),
+                    ..Default::default()
+                },
+            ],
+            os_parsers: vec![
+                os::Parser {
+                    regex: r"(Android) (\d+)".into(),
+                    ..Default::default()
+                },
+                // duplicate of the first device regex
+                os::Parser {
+                    regex: r"(iPhone)".into(),
+                    os_replacement: Some("iOS".into()),
+                    ..Default::default()
+                },
+            ],
+            device_parsers: vec![
+                device::Parser {
+                    regex: r"(iPhone)".into(),
+                    ..Default::default()
+                },
+                device::Parser {
+                    regex: r"; (Pixel \d+)".into(),
+                    brand_replacement: Some("Google".into()),
+                    ..Default::default()
+                },
+            ],
+        })
+        .unwrap()
+    }
 
-        /// Bulk loading of parsers into the builder.
-        pub fn push_all<I>(self, ua: I) -> Result<Self, ParseError>
-        where
-            I: IntoIterator<Item = Parser<'a>>,
-        {
-            ua.into_iter().try_fold(self, |s, p| s.push(p))
+    #[test]
+    fn shared_prefilter() {
+        let e = extractor();
+        for ua in [
+            "Mozilla/5.0 (Linux; Android 14; Pixel 8) Firefox/121",
+            "Mozilla/5.0 (iPhone) Mobile Safari",
+            "Mozilla/5.0 (Android 13) Mobile Safari",
+            "curl/8.0",
+        ] {
+            assert_eq!(
+                e.extract(ua),
+                ClientRef {
+                    ua: e.ua.extract(ua),
+                    os: e.os.extract(ua),
+                    device: e.dev.extract(ua),
+                },
+                "{ua}"
+            );
         }
+        let ClientRef {
+            ua,
+            os,
+            device: dev,
+        } = e.extract("Mozilla/5.0 (Linux; Android 14; Pixel 8) Firefox/121");
+        assert_eq!(ua.unwrap().family, "Firefox");
+        assert_eq!(os.unwrap().major.as_deref(), Some("14"));
+        assert_eq!(dev.unwrap().brand.as_deref(), Some("Google"));
+    }
+
+    #[test]
+    fn duplicates() {
+        let e = extractor();
+        assert_eq!(&*e.duplicates, &[None, None, None, Some(3), Some(3), None]);
+        let c = e.extract("Mozilla/5.0 (iPhone)");
+        assert_eq!(c.os_family(), "iOS");
+        assert_eq!(c.device_family(), "iPhone");
     }
 
-    /// Device extractor object.
-    pub struct Extractor<'a> {
-        matcher: regex_filtered::Regexes,
-        repl: Vec<(Resolver<'a>, OptResolver<'a>, OptResolver<'a>)>,
+    #[test]
+    fn is_match() {
+        let e = extractor();
+        assert!(e.matches_any("Mozilla/5.0 (iPhone)"));
+        assert!(e.matches_any("Mozilla/5.0 (Linux; Android 14)"));
+        assert!(!e.matches_any("curl/8.0"));
+        assert!(e.os.is_match("Mozilla/5.0 (iPhone)"));
+        assert!(!e.ua.is_match("Mozilla/5.0 (iPhone)"));
     }
-    impl<'a> Extractor<'a> {
-        /// Perform data extraction from the user agent string,
-        /// returns `None` if no regex in the [`Extractor`] matches
-        /// the input.
-        pub fn extract(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
-            let (idx, re) = self.matcher.matching(ua).next()?;
-            let c = re.captures(ua)?;
 
-            let (d, v1, v2) = &self.repl[idx];
+    #[test]
+    fn extract_family() {
+        let e = extractor();
+        assert_eq!(
+            e.ua.extract_family("Mozilla/5.0 Firefox/121").as_deref(),
+            Some("Firefox")
+        );
+        assert_eq!(e.ua.extract_family("Mozilla/5.0 (iPhone)"), None);
+    }
 
-            Some(ValueRef {
-                device: d.resolve(&c),
-                brand: v1.resolve(&c),
-                model: v2.resolve(&c),
-            })
-        }
+    #[test]
+    fn extract_all() {
+        let e = extractor();
+        assert_eq!(
+            e.os.extract_all("Mozilla/5.0 (iPhone; Android 13)")
+                .map(|(idx, v)| (idx, v.os))
+                .collect::<Vec<_>>(),
+            vec![(0, "Android".into()), (1, "iOS".into())],
+        );
     }
 
-    /// Extracted device content, may borrow from one of the
-    /// [`Parser`] or from the user agent string.
-    #[derive(PartialEq, Eq, Default, Debug)]
-    pub struct ValueRef<'a> {
-        ///
-        pub device: Cow<'a, str>,
-        ///
-        pub brand: Option<Cow<'a, str>>,
-        ///
-        pub model: Option<Cow<'a, str>>,
-    }
-
-    impl ValueRef<'_> {
-        /// Converts [`Self`] to an owned [`Value`] getting rid of
-        /// borrowing concerns, may need to allocate and copy if any
-        /// of the attributes actually borrows from a [`Parser`] or
-        /// the user agent string.
-        pub fn into_owned(self) -> Value {
-            Value {
-                device: self.device.into_owned(),
-                brand: self.brand.map(|c| c.into_owned()),
-                model: self.model.map(|c| c.into_owned()),
+    #[test]
+    fn extract_best() {
+        let e = extractor();
+        // the first parser matches less of the UA than the second
+        let ua = "Mozilla/5.0 (Android 13) Mobile Safari Firefox/1";
+        assert_eq!(e.ua.extract(ua).unwrap().family, "Firefox");
+        assert_eq!(e.ua.extract_best(ua).unwrap().family, "Mobile");
+    }
+
+    #[test]
+    fn extract_batch() {
+        let e = extractor();
+        let uas = [
+            "Mozilla/5.0 (Linux; Android 14; Pixel 8) Firefox/121",
+            "Mozilla/5.0 (iPhone) Mobile Safari",
+            "curl/8.0",
+        ];
+        let batch = uas.iter().map(|ua| e.extract(ua)).collect::<Vec<_>>();
+        assert_eq!(e.extract_batch(&uas), batch);
+        #[cfg(feature = "rayon")]
+        assert_eq!(e.par_extract_batch(&uas), batch);
+    }
+
+    #[test]
+    fn extract_traced() {
+        let e = extractor();
+        let (v, m) = e
+            .dev
+            .extract_traced("Mozilla/5.0 (Linux; Android 14; Pixel 8)")
+            .unwrap();
+        assert_eq!(v.model.as_deref(), Some("Pixel 8"));
+        assert_eq!(
+            m,
+            MatchInfo {
+                parser: 1,
+                groups: vec![Some("; Pixel 8"), Some("Pixel 8")],
             }
+        );
+    }
+
+    #[test]
+    fn extract_with() {
+        let e = extractor();
+        for domains in [
+            Domains::UA,
+            Domains::OS,
+            Domains::DEVICE,
+            Domains::UA | Domains::DEVICE,
+            Domains::OS | Domains::DEVICE,
+        ] {
+            let ua = "Mozilla/5.0 (iPhone; Android 13) Mobile Safari";
+            let ClientRef {
+                ua: u,
+                os: o,
+                device: d,
+            } = e.extract_with(ua, domains);
+            assert_eq!(u.is_some(), domains.contains(Domains::UA));
+            assert_eq!(
+                o,
+                domains
+                    .contains(Domains::OS)
+                    .then(|| e.os.extract(ua))
+                    .flatten()
+            );
+            assert_eq!(d.is_some(), domains.contains(Domains::DEVICE));
         }
     }
 
-    /// Owned version of [`ValueRef`].
-    #[derive(PartialEq, Eq, Default, Debug)]
-    pub struct Value {
-        ///
-        pub device: String,
-        ///
-        pub brand: Option<String>,
-        ///
-        pub model: Option<String>,
+    #[test]
+    fn extract_or_default() {
+        let e = extractor();
+        assert_eq!(e.ua.extract_or_default("curl/8.0").family, "Other");
+        assert_eq!(
+            e.dev.extract_or_default("curl/8.0").into_owned(),
+            device::Value::other()
+        );
     }
-}
 
-/// Rewrites a regex's character classes to ascii and bounded
-/// repetitions to unbounded, the second to reduce regex memory
-/// requirements, and the first for both that and to better match the
-/// (inferred) semantics intended for ua-parser.
-fn rewrite_regex(re: &str) -> std::borrow::Cow<'_, str> {
-    let mut from = 0;
-    let mut out = String::new();
-
-    let mut it = re.char_indices();
-    let mut escape = false;
-    let mut inclass = 0;
-    'main: while let Some((idx, c)) = it.next() {
-        match c {
-            '\\' if !escape => {
-                escape = true;
-                continue;
-            }
-            '{' if !escape && inclass == 0 => {
-                if idx == 0 {
-                    // we're repeating nothing, this regex is broken, bail
-                    return re.into();
-                }
-                // we don't need to loop, we only want to replace {0, ...} and {1, ...}
-                let Some((_, start)) = it.next() else {
-                    continue;
-                };
-                if start != '0' && start != '1' {
-                    continue;
-                }
+    #[test]
+    fn invalid_parser() {
+        let r = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![],
+            os_parsers: vec![
+                os::Parser {
+                    regex: r"(Android) (\d+)".into(),
+                    ..Default::default()
+                },
+                os::Parser {
+                    regex: r"(Linux".into(),
+                    ..Default::default()
+                },
+            ],
+            device_parsers: vec![],
+        });
+        let Err(Error::InvalidParser {
+            domain,
+            index,
+            regex,
+            source,
+        }) = r
+        else {
+            panic!("expected an invalid parser");
+        };
+        assert_eq!((domain, index, &*regex), ("os_parsers", 1, "(Linux"));
+        assert!(matches!(*source, Error::ParseError(_)));
 
-                if !matches!(it.next(), Some((_, ','))) {
-                    continue;
-                }
+        let r = user_agent::Builder::new()
+            .strict_templates(true)
+            .push(user_agent::Parser {
+                regex: "Firefox".into(),
+                family_replacement: Some("$1".into()),
+                ..Default::default()
+            });
+        assert!(matches!(
+            r.err().unwrap(),
+            Error::InvalidParser { source, .. } if matches!(*source, Error::MissingGroup(1))
+        ));
+    }
 
-                let mut digits = 0;
-                for (ri, rc) in it.by_ref() {
-                    match rc {
-                        '}' if digits > 2 => {
-                            // here idx is the index of the start of
-                            // the range and ri is the end of range
-                            out.push_str(&re[from..idx]);
-                            from = ri + 1;
-                            out.push_str(if start == '0' { "*" } else { "+" });
-                            break;
-                        }
-                        c if c.is_ascii_digit() => {
-                            digits += 1;
-                        }
-                        _ => continue 'main,
-                    }
-                }
-            }
-            '[' if !escape => {
-                inclass += 1;
-            }
-            ']' if !escape => {
-                inclass += 1;
-            }
-            // no need for special cases because regex allows nesting
-            // character classes, whereas js or python don't \o/
-            'd' if escape => {
-                // idx is d so idx-1 is \\, and we want to exclude it
-                out.push_str(&re[from..idx - 1]);
-                from = idx + 1;
-                out.push_str("[0-9]");
-            }
-            'D' if escape => {
-                out.push_str(&re[from..idx - 1]);
-                from = idx + 1;
-                out.push_str("[^0-9]");
-            }
-            'w' if escape => {
-                out.push_str(&re[from..idx - 1]);
-                from = idx + 1;
-                out.push_str("[A-Za-z0-9_]");
+    #[test]
+    fn lenient() {
+        let (e, skipped) = Extractor::try_from_lenient(Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser {
+                    regex: "Firefox)".into(),
+                    ..Default::default()
+                },
+                user_agent::Parser {
+                    regex: r"(Firefox)/(\d+)".into(),
+                    ..Default::default()
+                },
+            ],
+            os_parsers: vec![
+                os::Parser {
+                    regex: r"(Linux".into(),
+                    ..Default::default()
+                },
+                os::Parser {
+                    regex: r"(Android) (\d+)".into(),
+                    ..Default::default()
+                },
+            ],
+            device_parsers: vec![
+                device::Parser {
+                    regex: r"(Android) (\d+)".into(),
+                    ..Default::default()
+                },
+                device::Parser {
+                    regex: r"[".into(),
+                    ..Default::default()
+                },
+            ],
+        })
+        .unwrap();
+
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|e| match e {
+                    Error::InvalidParser { domain, index, .. } => (*domain, *index),
+                    e => panic!("unexpected error {e}"),
+                })
+                .collect::<Vec<_>>(),
+            [
+                ("user_agent_parsers", 0),
+                ("os_parsers", 0),
+                ("device_parsers", 1)
+            ],
+        );
+
+        let c = e.extract("Mozilla/5.0 (Linux; Android 14) Firefox/121");
+        assert_eq!(c.family(), "Firefox");
+        assert_eq!(c.os_family(), "Android");
+        assert_eq!(c.device_family(), "Android");
+        assert!(e.matches_any("Android 14"));
+    }
+
+    #[test]
+    fn regex_flag() {
+        let r: Regexes = serde_yaml::from_str(
+            r#"
+user_agent_parsers:
+  - regex: (firefox)/(\d+)
+    regex_flag: i
+os_parsers:
+  - regex: (android) (\d+)
+    regex_flag: i
+  - regex: (Linux)
+device_parsers: []
+"#,
+        )
+        .unwrap();
+        let e = Extractor::try_from(r).unwrap();
+
+        let c = e.extract("Mozilla/5.0 (Linux; Android 14) Firefox/121");
+        assert_eq!(c.family(), "Firefox");
+        assert_eq!(c.os_family(), "Android");
+        assert!(e.os.extract("linux").is_none());
+        // the flags are kept when extending
+        let os = e.os.extend([]).unwrap();
+        assert_eq!(os.extract("ANDROID 14").unwrap().os, "ANDROID");
+    }
+
+    #[test]
+    fn regex_flags() {
+        use device::Flags;
+
+        assert_eq!("".parse(), Ok(Flags::empty()));
+        assert_eq!("is".parse(), Ok(Flags::IGNORE_CASE | Flags::DOT_ALL));
+        assert_eq!(
+            "xmi".parse(),
+            Ok(Flags::VERBOSE | Flags::MULTI_LINE | Flags::IGNORE_CASE)
+        );
+        assert_eq!("Rs".parse(), Ok(Flags::CRLF | Flags::DOT_ALL));
+        assert_eq!("iu".parse::<Flags>(), Err(regex_filtered::UnknownFlag('u')));
+
+        let r: Regexes = serde_yaml::from_str(
+            r#"
+user_agent_parsers:
+  - regex: |
+      (firefox) / (\d+)  # family and major
+    regex_flag: ix
+os_parsers: []
+device_parsers:
+  - regex: ^(pixel .+)$
+    regex_flag: ims
+"#,
+        )
+        .unwrap();
+        let e = Extractor::try_from(r).unwrap();
+
+        let ua = e.ua.extract("Mozilla/5.0 Firefox/121").unwrap();
+        assert_eq!((&*ua.family, ua.major.as_deref()), ("Firefox", Some("121")));
+        // `^` matches after the first newline, then `.` goes past the second
+        let dev = e.dev.extract("Android\nPIXEL 8\nx").unwrap();
+        assert_eq!(dev.device, "PIXEL 8\nx");
+        assert!(serde_yaml::from_str::<Regexes>(
+            "user_agent_parsers: [{regex: a, regex_flag: iu}]\nos_parsers: []\ndevice_parsers: []"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn named_groups() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![],
+            os_parsers: vec![os::Parser {
+                regex: r"(?P<name>Android) (?P<major>\d+)".into(),
+                os_replacement: Some("${name} OS".into()),
+                os_v1_replacement: Some("$<major>".into()),
+                os_v3_replacement: Some("$$<major>".into()),
+                ..Default::default()
+            }],
+            device_parsers: vec![device::Parser {
+                regex: r"; (?P<brand>Pixel) (?P<model>\d+)".into(),
+                device_replacement: Some("$<brand> $2".into()),
+                model_replacement: Some("${model}a".into()),
+                ..Default::default()
+            }],
+        })
+        .unwrap();
+
+        let os = e.os.extract("Linux; Android 14").unwrap();
+        assert_eq!(os.os, "Android OS");
+        assert_eq!(os.major.as_deref(), Some("14"));
+        assert_eq!(os.minor, None);
+        assert_eq!(os.patch.as_deref(), Some("$<major>"));
+        let dev = e.dev.extract("Linux; Pixel 8").unwrap();
+        assert_eq!(dev.device, "Pixel 8");
+        assert_eq!(dev.model.as_deref(), Some("8a"));
+
+        let parser = || device::Parser {
+            regex: r"(?P<brand>Pixel) (\d+)".into(),
+            brand_replacement: Some("$<missing>".into()),
+            model_replacement: Some("$3 $2".into()),
+            ..Default::default()
+        };
+        let err = device::Builder::new().strict_templates(true).push(parser());
+        assert!(matches!(
+            err,
+            Err(Error::InvalidParser { source, .. })
+                if matches!(&*source, Error::MissingNamedGroup(n) if n == "missing")
+        ));
+        // missing groups expand to an empty string by default
+        let dev = device::Builder::new()
+            .push(parser())
+            .unwrap()
+            .build()
+            .unwrap();
+        let dev = dev.extract("Pixel 8").unwrap();
+        assert_eq!(dev.brand, None);
+        assert_eq!(dev.model.as_deref(), Some("8"));
+    }
+
+    #[test]
+    fn family_replacement() {
+        use user_agent::FamilyReplacement;
+
+        let parser = || user_agent::Parser {
+            regex: r"(Foo) (Mobile)/(\d+)".into(),
+            family_replacement: Some("$1 $2".into()),
+            ..Default::default()
+        };
+        let restricted = user_agent::Builder::new()
+            .push(parser())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(restricted.extract("Foo Mobile/1").unwrap().family, "Foo $2");
+
+        let templated = user_agent::Builder::new()
+            .family_replacement(FamilyReplacement::Templated)
+            .push(parser())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            templated.extract("Foo Mobile/1").unwrap().family,
+            "Foo Mobile"
+        );
+        let templated = templated.extend([]).unwrap();
+        assert_eq!(
+            templated.extract("Foo Mobile/2").unwrap().family,
+            "Foo Mobile"
+        );
+
+        let err = user_agent::Builder::new()
+            .family_replacement(FamilyReplacement::Templated)
+            .strict_templates(true)
+            .push(user_agent::Parser {
+                regex: r"(Foo)".into(),
+                family_replacement: Some("$1 $2".into()),
+                ..Default::default()
+            });
+        assert!(matches!(
+            err,
+            Err(Error::InvalidParser { source, .. }) if matches!(*source, Error::MissingGroup(2))
+        ));
+    }
+
+    #[test]
+    fn version_replacement() {
+        use user_agent::VersionReplacement;
+
+        let parser = || user_agent::Parser {
+            regex: r"(Foo)/(\d+)\.(\d+)".into(),
+            v1_replacement: Some("$2b".into()),
+            v2_replacement: Some("$2.$3".into()),
+            ..Default::default()
+        };
+        let verbatim = user_agent::Builder::new()
+            .push(parser())
+            .unwrap()
+            .build()
+            .unwrap();
+        let v = verbatim.extract("Foo/1.2").unwrap();
+        assert_eq!(v.major.as_deref(), Some("$2b"));
+        assert_eq!(v.minor.as_deref(), Some("$2.$3"));
+
+        let templated = user_agent::Builder::new()
+            .version_replacement(VersionReplacement::Templated)
+            .push(parser())
+            .unwrap()
+            .build()
+            .unwrap();
+        let v = templated.extract("Foo/1.2").unwrap();
+        assert_eq!(v.major.as_deref(), Some("1b"));
+        // only the group being replaced is substituted
+        assert_eq!(v.minor.as_deref(), Some("$2.2"));
+        // the mode is kept when extending
+        let templated = templated.extend([]).unwrap();
+        assert_eq!(
+            templated.extract("Foo/3.4").unwrap().major.as_deref(),
+            Some("3b")
+        );
+
+        let err = user_agent::Builder::new()
+            .version_replacement(VersionReplacement::Templated)
+            .strict_templates(true)
+            .push(user_agent::Parser {
+                regex: r"(Foo)".into(),
+                v1_replacement: Some("$2".into()),
+                ..Default::default()
+            });
+        assert!(matches!(
+            err,
+            Err(Error::InvalidParser { source, .. }) if matches!(*source, Error::MissingGroup(2))
+        ));
+    }
+
+    #[test]
+    fn empty_captures() {
+        let parsers = || Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                regex: r"(Foo)/(\d*)(?:\.(\d+))?".into(),
+                ..Default::default()
+            }],
+            os_parsers: vec![os::Parser {
+                regex: r"(Bar) ?(\d*)".into(),
+                ..Default::default()
+            }],
+            device_parsers: vec![device::Parser {
+                regex: r"(Baz)(\d*)".into(),
+                model_replacement: Some("$2".into()),
+                ..Default::default()
+            }],
+        };
+        let ua = "Foo/ Bar Baz";
+
+        let e = Extractor::try_from(parsers()).unwrap();
+        let c = e.extract(ua);
+        assert_eq!(c.ua.as_ref().unwrap().major, None);
+        assert_eq!(c.os.as_ref().unwrap().major, None);
+
+        let e = Extractor::try_from(parsers())
+            .unwrap()
+            .empty_captures(EmptyCapture::Keep);
+        let c = e.extract(ua);
+        let v = c.ua.unwrap();
+        assert_eq!(v.major.as_deref(), Some(""));
+        // the group did not participate at all
+        assert_eq!(v.minor, None);
+        assert_eq!(c.os.unwrap().major.as_deref(), Some(""));
+        // templates are not affected
+        assert_eq!(c.device.unwrap().model, None);
+    }
+
+    #[test]
+    fn extract_trait() {
+        struct Mock;
+        impl<'a> Extract<'a> for Mock {
+            type Output = &'a str;
+            fn extract(&'a self, ua: &'a str) -> Option<&'a str> {
+                ua.split('/').next()
             }
-            'W' if escape => {
-                out.push_str(&re[from..idx - 1]);
-                from = idx + 1;
-                out.push_str("[^A-Za-z0-9_]");
+        }
+        fn families<'a>(
+            e: &'a dyn Extract<'a, Output = user_agent::ValueRef<'a>>,
+            ua: &'a str,
+        ) -> Option<String> {
+            e.extract(ua).map(|v| v.family.into_owned())
+        }
+
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                regex: r"(Firefox)/(\d+)".into(),
+                ..Default::default()
+            }],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        })
+        .unwrap();
+        assert_eq!(families(&e.ua, "Firefox/121").as_deref(), Some("Firefox"));
+        assert_eq!(families(&e.ua, "curl/8.0"), None);
+        assert_eq!(Mock.extract("curl/8.0"), Some("curl"));
+    }
+
+    #[test]
+    fn push_mut() {
+        let mut b = os::Builder::new();
+        let mut skipped = Vec::new();
+        for regex in [r"(Android) (\d+)", r"(", r"(Windows) NT"] {
+            let p = os::Parser {
+                regex: regex.into(),
+                ..Default::default()
+            };
+            if let Err(e) = b.push_mut(p) {
+                skipped.push(e);
             }
-            _ => (),
         }
-        escape = false;
+        assert!(matches!(&*skipped, [Error::InvalidParser { index: 1, .. }]));
+        let e = b.build().unwrap();
+        assert_eq!(e.extract("Android 14").unwrap().os, "Android");
+        assert_eq!(e.extract("Windows NT").unwrap().os, "Windows");
+
+        let mut b = device::Builder::new();
+        let err = b.push_all_mut([
+            device::Parser {
+                regex: "(Pixel)".into(),
+                ..Default::default()
+            },
+            device::Parser {
+                regex: "(Nexus".into(),
+                ..Default::default()
+            },
+        ]);
+        assert!(err.is_err());
+        let e = b.build().unwrap();
+        assert_eq!(e.extract("Pixel").unwrap().device, "Pixel");
+    }
+
+    #[test]
+    fn collect() {
+        let parsers = || {
+            ["(Android) (\\d+)", "(Windows) NT"]
+                .into_iter()
+                .map(|regex| os::Parser {
+                    regex: regex.into(),
+                    ..Default::default()
+                })
+        };
+        let b: Result<os::Builder, _> = parsers().collect();
+        let e = b
+            .unwrap()
+            .push(os::Parser {
+                regex: "(Linux)".into(),
+                ..Default::default()
+            })
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(e.extract("Android 14").unwrap().os, "Android");
+        assert_eq!(e.extract("Linux").unwrap().os, "Linux");
+
+        let b = parsers()
+            .chain([os::Parser {
+                regex: "(".into(),
+                ..Default::default()
+            }])
+            .collect::<Result<os::Builder, _>>();
+        assert!(matches!(b, Err(Error::InvalidParser { index: 2, .. })));
     }
 
-    if from == 0 {
-        re.into()
-    } else {
-        out.push_str(&re[from..]);
-        out.into()
+    #[test]
+    fn parser_constructors() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser::new(r"(Fennec)/(\d+)")
+                .family("$1 Mobile")
+                .v2("0")],
+            os_parsers: vec![os::Parser::new(r"android (\d+)")
+                .regex_flag(device::Flags::IGNORE_CASE)
+                .os("Android")
+                .v1(String::from("$1"))],
+            device_parsers: vec![device::Parser::new(r"; (Pixel) (\w+)")
+                .device("$1 $2")
+                .brand("Google")
+                .model("$2")],
+        })
+        .unwrap();
+
+        let c = e.extract("Mozilla/5.0 (Android 14; Pixel 8) Fennec/120");
+        let ua = c.ua.unwrap();
+        assert_eq!(ua.family, "Fennec Mobile");
+        assert_eq!(ua.major.as_deref(), Some("120"));
+        assert_eq!(ua.minor.as_deref(), Some("0"));
+        let os = c.os.unwrap();
+        assert_eq!((&*os.os, os.major.as_deref()), ("Android", Some("14")));
+        let dev = c.device.unwrap();
+        assert_eq!(dev.device, "Pixel 8");
+        assert_eq!(dev.brand.as_deref(), Some("Google"));
+        assert_eq!(dev.model.as_deref(), Some("8"));
     }
-}
 
-#[cfg(test)]
-mod test_rewrite_regex {
-    use super::rewrite_regex as rewrite;
+    #[test]
+    fn derives() {
+        let r: Regexes = serde_yaml::from_str(
+            "user_agent_parsers: [{regex: '(Foo)/([0-9]+)'}]\nos_parsers: []\ndevice_parsers: [{regex: '(Bar)', device_type: tv}]",
+        )
+        .unwrap();
+        let e = Extractor::try_from(r.clone()).unwrap();
+        assert_eq!(e.ua.parsers().collect::<Vec<_>>(), r.user_agent_parsers);
+        // the extension fields are kept by the extractor
+        assert_eq!(e.dev.parsers().collect::<Vec<_>>(), r.device_parsers);
+
+        let values = ["Foo/2", "Foo/10", "Foo/2"]
+            .into_iter()
+            .map(|ua| e.extract(ua).ua.unwrap())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(values.len(), 2);
+        let owned = values
+            .iter()
+            .cloned()
+            .map(user_agent::ValueRef::into_owned)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(owned.len(), 2);
+    }
+
+    #[test]
+    fn extract_stats() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser::new(r"(Firefox)/(\d+)"),
+                user_agent::Parser::new(r"(Fire)fox"),
+            ],
+            os_parsers: vec![os::Parser::new(r"(Android) (\d+)")],
+            device_parsers: vec![
+                device::Parser::new(r"Android.*(Pixel \d+)"),
+                device::Parser::new(r"(Firefox)").device("Generic"),
+            ],
+        })
+        .unwrap();
+
+        let ua = "Mozilla/5.0 (Linux; Android 14; Pixel Fold) Firefox/121";
+        let (client, stats) = e.extract_stats(ua);
+        assert_eq!(client, e.extract(ua));
+        assert_eq!(stats.candidates, [2, 1, 2]);
+        // only the second device candidate matches
+        assert_eq!(stats.verified, 4);
+        assert_eq!(stats.matched, Domains::ALL);
+
+        let (client, stats) = e.extract_stats("curl/8.0");
+        assert_eq!(client, ClientRef::default());
+        assert_eq!(stats, ExtractStats::default());
+    }
+
+    #[test]
+    fn budget() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser::new(r"(Fire)fox/(\d+)x"),
+                user_agent::Parser::new(r"(Firefox)/(\d+)"),
+            ],
+            os_parsers: vec![os::Parser::new(r"(Android) (\d+)")],
+            device_parsers: vec![],
+        })
+        .unwrap();
+        let ua = "Mozilla/5.0 (Linux; Android 14) Firefox/121";
+
+        let e = e.budget(Budget::new().max_verified(2));
+        let (client, stats) = e.extract_stats(ua);
+        assert_eq!(client.ua.unwrap().family, "Firefox");
+        assert!(client.os.is_none());
+        assert_eq!(stats.verified, 2);
+        assert!(stats.exhausted);
+
+        let e = e.budget(Budget::new().max_duration(std::time::Duration::ZERO));
+        let (client, stats) = e.extract_stats(ua);
+        assert_eq!(client, ClientRef::default());
+        assert_eq!(stats.verified, 0);
+        assert!(stats.exhausted);
+
+        let e = e.budget(Budget::new());
+        let (client, stats) = e.extract_stats(ua);
+        assert!(client.ua.is_some() && client.os.is_some());
+        assert!(!stats.exhausted);
+    }
+
+    #[test]
+    fn max_length() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser::new(r"(Firefox)/(\d+)")],
+            os_parsers: vec![os::Parser::new(r"(Android) (\d+)")],
+            device_parsers: vec![],
+        })
+        .unwrap();
+        let ua = "Android 14; Firefox/121";
+        assert!(e.extract(ua).ua.is_some());
+
+        let e = e.max_length(21);
+        let c = e.extract(ua);
+        assert_eq!(c.os.unwrap().major.as_deref(), Some("14"));
+        assert_eq!(c.ua.unwrap().major.as_deref(), Some("1"));
+        let e = e.max_length(15);
+        assert!(e.extract(ua).ua.is_none());
+        let e = e.max_length(10);
+        assert!(e.matches_any("Android 14"));
+        assert!(!e.matches_any("Linux; Android 14"));
+        // truncated on the character boundary before the limit
+        let e = e.max_length(11);
+        assert_eq!(e.truncate("Android \u{e9}\u{e9}"), "Android \u{e9}");
+    }
 
     #[test]
-    fn ignore_small_repetition() {
-        assert_eq!(rewrite(".{0,2}x"), ".{0,2}x");
-        assert_eq!(rewrite(".{0,}"), ".{0,}");
-        assert_eq!(rewrite(".{1,}"), ".{1,}");
+    fn memory_usage() {
+        let parsers = |n| Regexes {
+            user_agent_parsers: (0..n)
+                .map(|i| user_agent::Parser::new(format!("(Foo{i})/([0-9]+)")).family("Foo $1"))
+                .collect(),
+            os_parsers: vec![],
+            device_parsers: vec![],
+        };
+        let small = Extractor::try_from(parsers(1)).unwrap();
+        let large = Extractor::try_from(parsers(10)).unwrap();
+
+        assert!(small.memory_usage() > small.ua.memory_usage());
+        // the combined prefilter is accounted for on top of the domains
+        assert!(
+            small.memory_usage()
+                > small.ua.memory_usage() + small.os.memory_usage() + small.dev.memory_usage()
+        );
+        assert!(large.ua.memory_usage() > small.ua.memory_usage());
+        assert!(large.memory_usage() > small.memory_usage());
+        assert_eq!(
+            small.os.memory_usage(),
+            Extractor::try_from(parsers(0)).unwrap().os.memory_usage()
+        );
     }
 
     #[test]
-    fn rewrite_large_repetitions() {
-        assert_eq!(rewrite(".{0,20}x"), ".{0,20}x");
-        assert_eq!(rewrite("(.{0,100})"), "(.*)");
-        assert_eq!(rewrite("(.{1,50})"), "(.{1,50})");
-        assert_eq!(rewrite(".{1,300}x"), ".+x");
+    fn data_info() {
+        assert_eq!(DataInfo::digest(b""), 0xcbf29ce484222325);
+        assert_eq!(DataInfo::digest(b"a"), 0xaf63dc4c8601ec8c);
+
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser::new(r"(Firefox)/(\d+)")],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        })
+        .unwrap()
+        .data_version("v1")
+        .data_digest(42);
+        assert_eq!(
+            e.data_info(),
+            DataInfo {
+                version: Some("v1".into()),
+                digest: Some(42),
+                parsers: [1, 0, 0],
+            }
+        );
+
+        let e = e
+            .extend(Regexes {
+                user_agent_parsers: vec![],
+                os_parsers: vec![os::Parser::new(r"(Android) (\d+)")],
+                device_parsers: vec![],
+            })
+            .unwrap();
+        let info = e.data_info();
+        assert_eq!(info.version.as_deref(), Some("v1"));
+        assert_eq!(info.digest, None);
+        assert_eq!(info.parsers, [1, 1, 0]);
     }
 
     #[test]
-    fn ignore_non_repetitions() {
+    fn fields() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser::new(r"(Firefox)/(\d+)\.(\d+)")],
+            os_parsers: vec![],
+            device_parsers: vec![device::Parser {
+                regex: "(Pixel)".into(),
+                brand_replacement: Some("Google".into()),
+                ..Default::default()
+            }],
+        })
+        .unwrap();
+        let c = e.extract("Firefox/121.0 Pixel");
+
+        let ua = c.ua.unwrap();
         assert_eq!(
-            rewrite(r"\{1,2}"),
-            r"\{1,2}",
-            "if the opening brace is escaped it's not a repetition"
+            ua.fields(),
+            [
+                ("family", Some("Firefox")),
+                ("major", Some("121")),
+                ("minor", Some("0")),
+                ("patch", None),
+                ("patch_minor", None),
+            ]
         );
+        assert_eq!(ua.clone().into_owned().fields(), ua.fields());
+
+        let dev = c.device.unwrap().into_owned();
+        let m = dev.to_map();
+        assert_eq!(m.len(), 3);
+        assert_eq!(m["brand"], Some("Google"));
+        assert_eq!(m["model"], Some("Pixel"));
+        assert_eq!(os::Value::other().fields()[0], ("os", Some("Other")));
+    }
+
+    #[test]
+    fn display() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser::new(r"(Firefox)/(\d+)\.(\d+)")],
+            os_parsers: vec![os::Parser {
+                regex: r"(Mac OS X) (\d+)_(\d+)_(\d+)".into(),
+                ..Default::default()
+            }],
+            device_parsers: vec![device::Parser::new("(iPhone)")],
+        })
+        .unwrap();
+
+        let c = e.extract("Mozilla/5.0 (iPhone; Mac OS X 10_15_7) Firefox/121.0");
+        assert_eq!(c.ua.as_ref().unwrap().to_string(), "Firefox 121.0");
+        assert_eq!(c.os.as_ref().unwrap().to_string(), "Mac OS X 10.15.7");
+        assert_eq!(c.device.as_ref().unwrap().to_string(), "iPhone");
+        let c = c.into_owned();
+        assert_eq!(c.ua.unwrap().to_string(), "Firefox 121.0");
+        assert_eq!(c.os.unwrap().to_string(), "Mac OS X 10.15.7");
+        assert_eq!(c.device.unwrap().to_string(), "iPhone");
+
+        assert_eq!(user_agent::ValueRef::other().to_string(), "Other");
+    }
+
+    #[test]
+    fn precomputed() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser::new(r"(Firefox)/(\d+)")],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        })
+        .unwrap();
+        let hot = Client {
+            ua: Some(user_agent::Value {
+                family: "Hot".into(),
+                ..Default::default()
+            }),
+            os: Some(os::Value::other()),
+            device: None,
+        };
+        let e = e.precomputed([
+            ("Firefox/121".to_string(), hot.clone()),
+            ("nothing".to_string(), Client::default()),
+        ]);
+
+        assert_eq!(e.extract("Firefox/121").into_owned(), hot);
+        let c = e.extract_with("Firefox/121", Domains::OS);
+        assert!(c.ua.is_none());
+        assert_eq!(c.os_family(), "Other");
+        // not in the table
+        assert_eq!(e.extract("Firefox/122").family(), "Firefox");
+        assert!(e.matches_any("Firefox/121"));
+        assert!(!e.matches_any("nothing"));
+        let (_, stats) = e.extract_stats("Firefox/121");
+        assert_eq!(stats.verified, 0);
+        assert_eq!(stats.matched, Domains::UA | Domains::OS);
+
+        let e = e
+            .extend(Regexes {
+                user_agent_parsers: vec![user_agent::Parser::new("(nothing)")],
+                os_parsers: vec![],
+                device_parsers: vec![],
+            })
+            .unwrap();
+        assert_eq!(e.extract("nothing").family(), "Other");
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_extract() {
+        let e = device::Builder::new()
+            .push_all((0..200).map(|i| device::Parser::new(format!("Model{i}(\\d)"))))
+            .unwrap()
+            .push(device::Parser::new("(Model)"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        for ua in ["Model150 Model42 Model7", "Model1999", "Model", "nothing"] {
+            for shard in [0, 1, 3, 1000] {
+                assert_eq!(e.par_extract(ua, shard), e.extract(ua), "{ua} / {shard}");
+            }
+        }
+    }
+
+    #[test]
+    fn frequencies() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mut m = regex_filtered::Builder::new()
+            .push(r"Firefox/\d+")
+            .unwrap()
+            .push(r"Firefox")
+            .unwrap()
+            .build()
+            .unwrap();
+        let verified = Arc::new(AtomicUsize::new(0));
+        let v = verified.clone();
+        m.set_verification_hook(move |_| {
+            v.fetch_add(1, Ordering::Relaxed);
+        });
+        assert!(crate::domain::matches_by_frequency(&m, &[], "Firefox/abc"));
+        assert_eq!(verified.swap(0, Ordering::Relaxed), 2);
+        assert!(crate::domain::matches_by_frequency(
+            &m,
+            &[0, 10],
+            "Firefox/abc"
+        ));
+        assert_eq!(verified.swap(0, Ordering::Relaxed), 1);
+
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser::new(r"(Firefox)/(\d+)"),
+                user_agent::Parser::new(r"(Firefox)"),
+            ],
+            os_parsers: vec![os::Parser::new(r"(Linux)")],
+            device_parsers: vec![],
+        })
+        .unwrap()
+        .frequencies([vec![0, 10], vec![], vec![]]);
+        assert!(e.ua.is_match("Firefox"));
+        assert!(!e.ua.is_match("Chrome"));
+        assert!(e.matches_any("Linux"));
+        assert!(!e.matches_any("Chrome"));
+        // extraction still selects the first matching parser
         assert_eq!(
-            rewrite("[.{1,100}]"),
-            "[.{1,100}]",
-            "inside a set it's not a repetition"
+            e.extract("Firefox/121").ua.unwrap().major.as_deref(),
+            Some("121")
         );
     }
 
     #[test]
-    fn rewrite_classes() {
-        assert_eq!(rewrite(r"\dx"), "[0-9]x");
-        assert_eq!(rewrite(r"\wx"), "[A-Za-z0-9_]x");
-        assert_eq!(rewrite(r"[\d]x"), r"[[0-9]]x");
+    fn rewrite() {
+        let parsers = || Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                regex: r"(Foo)/(\d+).{0,100}".into(),
+                ..Default::default()
+            }],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        };
+        let regexes = |e: &Extractor| {
+            e.ua.parsers()
+                .map(|p| p.regex.into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let e = Extractor::try_from(parsers()).unwrap();
+        assert_eq!(regexes(&e), [r"(Foo)/([0-9]+).*"]);
+
+        let e = Extractor::try_from_with(
+            parsers(),
+            rewrite::Config::new()
+                .ascii_classes(false)
+                .widen_repetitions(false),
+        )
+        .unwrap();
+        assert_eq!(regexes(&e), [r"(Foo)/(\d+).{0,100}"]);
+        // the configuration is kept when extending
+        let e = e.extend(parsers()).unwrap();
+        assert_eq!(regexes(&e), [r"(Foo)/(\d+).{0,100}"; 2]);
+    }
+
+    #[test]
+    fn extend() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                regex: r"(Firefox)/(\d+)".into(),
+                ..Default::default()
+            }],
+            os_parsers: vec![],
+            device_parsers: vec![device::Parser {
+                regex: r"(pixel) \d+".into(),
+                regex_flag: Some(device::Flags::IGNORE_CASE),
+                ..Default::default()
+            }],
+        })
+        .unwrap();
+        let ua = "Mozilla/5.0 (Linux; Android 14; Pixel 8) Firefox/121";
+        assert!(e.extract(ua).os.is_none());
+
+        let e = e
+            .extend(Regexes {
+                user_agent_parsers: vec![],
+                os_parsers: vec![os::Parser {
+                    regex: r"(Android) (\d+)".into(),
+                    ..Default::default()
+                }],
+                device_parsers: vec![device::Parser {
+                    regex: r"(Firefox)/(\d+)".into(),
+                    ..Default::default()
+                }],
+            })
+            .unwrap();
+        let c = e.extract(ua);
+        assert_eq!(c.family(), "Firefox");
+        assert_eq!(c.os.as_ref().unwrap().major.as_deref(), Some("14"));
+        assert_eq!(c.device_family(), "Pixel");
+        assert_eq!(&*e.duplicates, &[Some(0), None, None, Some(0)]);
+
+        let os =
+            e.os.extend([os::Parser {
+                regex: r"(Linux)".into(),
+                ..Default::default()
+            }])
+            .unwrap();
+        assert_eq!(os.parsers().count(), 2);
+        assert_eq!(os.extract("X11; Linux").unwrap().os, "Linux");
     }
 }
@@ -0,0 +1,437 @@
+//! Static checks of parser definitions, see [`Regexes::lint`].
+//!
+//! The checks only look at the definitions, nothing is compiled for
+//! an extractor. Regexes are analysed following the syntax of the
+//! `regex` crate, without the rewriting applied by the builders.
+
+use std::collections::HashMap;
+
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use regex_syntax::hir::{Hir, HirKind};
+
+use crate::device::Flags;
+use crate::resolvers::{reference, Ref};
+use crate::Regexes;
+
+/// Issue found by [`Regexes::lint`]. Fields are named as in the
+/// extracted values (e.g. `family`, `major`, `brand`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// The regex can not be parsed or compiled, the other checks are
+    /// skipped.
+    Invalid(String),
+    /// The parser has the same regex and flags as the earlier parser
+    /// `of`, so it is never selected.
+    Duplicate {
+        /// Index of the earlier parser.
+        of: usize,
+    },
+    /// The earlier parser `by` matches every user agent this parser
+    /// matches, so it is never selected (unless resolving the best
+    /// match).
+    Shadowed {
+        /// Index of the earlier parser.
+        by: usize,
+    },
+    /// The replacement of `field` references a group the regex does
+    /// not have.
+    MissingGroup {
+        /// Field of the replacement.
+        field: &'static str,
+        /// Index of the referenced group.
+        group: usize,
+    },
+    /// The replacement of `field` references a named group the regex
+    /// does not have.
+    MissingNamedGroup {
+        /// Field of the replacement.
+        field: &'static str,
+        /// Name of the referenced group.
+        name: String,
+    },
+    /// `field` is taken directly from a group which can match an
+    /// empty string, in which case the field is empty or absent
+    /// (e.g. `(\d*)` on a user agent without a version) rather than
+    /// falling back to a default.
+    EmptyCapture {
+        /// Field taken from the group.
+        field: &'static str,
+        /// Index of the group.
+        group: usize,
+    },
+}
+
+/// Parser flagged by [`Regexes::lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Name of the parsers list the parser is part of, e.g.
+    /// `os_parsers`.
+    pub domain: &'static str,
+    /// Index of the parser within its list.
+    pub index: usize,
+    /// Issue found with the parser.
+    pub issue: Issue,
+}
+
+impl Regexes<'_> {
+    /// Checks the parsers for common mistakes, and reports them in
+    /// order:
+    ///
+    /// - invalid regexes
+    /// - duplicate parsers, and parsers shadowed by an earlier parser
+    ///   with a broader regex
+    /// - replacements referencing groups which don't exist
+    /// - fields taken from groups which can match an empty string
+    ///
+    /// Shadowing is only detected when every match of the parser
+    /// starts (or ends) with one of a small set of literals, and the
+    /// earlier parser has no anchors or boundaries and matches all of
+    /// them, so it may miss some shadowed parsers, but never reports
+    /// a parser which can be selected.
+    ///
+    /// ```
+    /// # use ua_parser::{user_agent, Regexes};
+    /// # use ua_parser::lint::{Finding, Issue};
+    /// let regexes = Regexes {
+    ///     user_agent_parsers: vec![
+    ///         user_agent::Parser::new("Foo"),
+    ///         user_agent::Parser::new(r"Foo/(\d+)"),
+    ///     ],
+    ///     os_parsers: vec![],
+    ///     device_parsers: vec![],
+    /// };
+    /// assert_eq!(
+    ///     regexes.lint(),
+    ///     [Finding {
+    ///         domain: "user_agent_parsers",
+    ///         index: 1,
+    ///         issue: Issue::Shadowed { by: 0 },
+    ///     }],
+    /// );
+    /// ```
+    pub fn lint(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        lint_all(
+            "user_agent_parsers",
+            self.user_agent_parsers.iter().map(|p| Rule {
+                regex: &p.regex,
+                flags: p.regex_flag.unwrap_or_default(),
+                fields: vec![
+                    ("family", p.family_replacement.as_deref(), 1),
+                    ("major", p.v1_replacement.as_deref(), 2),
+                    ("minor", p.v2_replacement.as_deref(), 3),
+                    ("patch", p.v3_replacement.as_deref(), 4),
+                    ("patch_minor", p.v4_replacement.as_deref(), 5),
+                ],
+            }),
+            &mut findings,
+        );
+        lint_all(
+            "os_parsers",
+            self.os_parsers.iter().map(|p| Rule {
+                regex: &p.regex,
+                flags: p.regex_flag.unwrap_or_default(),
+                fields: vec![
+                    ("os", p.os_replacement.as_deref(), 1),
+                    ("major", p.os_v1_replacement.as_deref(), 2),
+                    ("minor", p.os_v2_replacement.as_deref(), 3),
+                    ("patch", p.os_v3_replacement.as_deref(), 4),
+                    ("patch_minor", p.os_v4_replacement.as_deref(), 5),
+                ],
+            }),
+            &mut findings,
+        );
+        lint_all(
+            "device_parsers",
+            self.device_parsers.iter().map(|p| Rule {
+                regex: &p.regex,
+                flags: p.regex_flag.unwrap_or_default(),
+                fields: vec![
+                    ("device", p.device_replacement.as_deref(), 1),
+                    // the brand has no fallback group
+                    ("brand", p.brand_replacement.as_deref(), 0),
+                    ("model", p.model_replacement.as_deref(), 1),
+                ],
+            }),
+            &mut findings,
+        );
+        findings
+    }
+}
+
+/// Parser definition, independent from its domain.
+struct Rule<'r> {
+    regex: &'r str,
+    flags: Flags,
+    /// Name, replacement, and fallback group (if non-zero) of each
+    /// field.
+    fields: Vec<(&'static str, Option<&'r str>, usize)>,
+}
+
+/// Parsed and compiled parser regex.
+struct Compiled {
+    hir: Hir,
+    regex: regex::Regex,
+    /// Name of each group, and whether it can match an empty string,
+    /// by index (the first is the entire match).
+    groups: Vec<(Option<Box<str>>, bool)>,
+}
+
+fn compile(regex: &str, flags: Flags) -> Result<Compiled, String> {
+    let hir = regex_syntax::Parser::from(flags.options())
+        .parse(regex)
+        .map_err(|e| e.to_string())?;
+    let compiled = regex::RegexBuilder::new(regex)
+        .case_insensitive(flags.contains(Flags::IGNORE_CASE))
+        .dot_matches_new_line(flags.contains(Flags::DOT_ALL))
+        .multi_line(flags.contains(Flags::MULTI_LINE))
+        .ignore_whitespace(flags.contains(Flags::VERBOSE))
+        .crlf(flags.contains(Flags::CRLF))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut groups = vec![(None, false); compiled.captures_len()];
+    collect_groups(&hir, &mut groups);
+    Ok(Compiled {
+        hir,
+        regex: compiled,
+        groups,
+    })
+}
+
+fn collect_groups(hir: &Hir, groups: &mut [(Option<Box<str>>, bool)]) {
+    match hir.kind() {
+        HirKind::Capture(c) => {
+            groups[c.index as usize] =
+                (c.name.clone(), c.sub.properties().minimum_len() == Some(0));
+            collect_groups(&c.sub, groups);
+        }
+        HirKind::Repetition(r) => collect_groups(&r.sub, groups),
+        HirKind::Concat(hs) | HirKind::Alternation(hs) => {
+            hs.iter().for_each(|h| collect_groups(h, groups))
+        }
+        HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) | HirKind::Look(_) => (),
+    }
+}
+
+/// Literals one of which every match of the regex starts with (or
+/// ends with), if there is a finite set of them.
+fn literals(hir: &Hir) -> Option<Vec<String>> {
+    [ExtractKind::Prefix, ExtractKind::Suffix]
+        .into_iter()
+        .find_map(|kind| {
+            Extractor::new()
+                .kind(kind)
+                .extract(hir)
+                .literals()?
+                .iter()
+                .map(|l| String::from_utf8(l.as_bytes().to_vec()).ok())
+                .collect()
+        })
+}
+
+fn lint_all<'r>(
+    domain: &'static str,
+    rules: impl Iterator<Item = Rule<'r>>,
+    findings: &mut Vec<Finding>,
+) {
+    let mut first = HashMap::new();
+    // compiled regexes of the earlier parsers which can shadow later
+    // ones: without look-around assertions, a regex matching a string
+    // matches everything containing it
+    let mut broad: Vec<(usize, regex::Regex)> = Vec::new();
+    for (index, rule) in rules.enumerate() {
+        let mut report = |issue| {
+            findings.push(Finding {
+                domain,
+                index,
+                issue,
+            })
+        };
+        let compiled = match compile(rule.regex, rule.flags) {
+            Ok(c) => c,
+            Err(e) => {
+                report(Issue::Invalid(e));
+                continue;
+            }
+        };
+
+        let of = *first.entry((rule.regex, rule.flags)).or_insert(index);
+        if of != index {
+            report(Issue::Duplicate { of });
+        } else if let Some(by) = literals(&compiled.hir).and_then(|lits| {
+            broad
+                .iter()
+                .find(|(_, re)| lits.iter().all(|l| re.is_match(l)))
+                .map(|&(by, _)| by)
+        }) {
+            report(Issue::Shadowed { by });
+        }
+
+        for (field, replacement, fallback) in rule.fields {
+            if let Some(r) = replacement.filter(|r| !r.trim().is_empty()) {
+                for issue in missing_groups(field, r, &compiled.groups) {
+                    report(issue);
+                }
+            } else if compiled
+                .groups
+                .get(fallback)
+                .is_some_and(|g| fallback > 0 && g.1)
+            {
+                report(Issue::EmptyCapture {
+                    field,
+                    group: fallback,
+                });
+            }
+        }
+
+        if compiled.hir.properties().look_set().is_empty() {
+            broad.push((index, compiled.regex));
+        }
+    }
+}
+
+/// References of the `replacement` template to groups which are not
+/// in `groups`.
+fn missing_groups(
+    field: &'static str,
+    replacement: &str,
+    groups: &[(Option<Box<str>>, bool)],
+) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut rest = replacement;
+    while let Some(offset) = rest.find('$') {
+        rest = &rest[offset + 1..];
+        let Some((r, len)) = reference(rest) else {
+            continue;
+        };
+        match r {
+            Ref::Dollar => (),
+            Ref::Index(group) if group >= groups.len() => {
+                issues.push(Issue::MissingGroup { field, group })
+            }
+            Ref::Index(_) => (),
+            Ref::Name(name) => {
+                if !groups.iter().any(|(n, _)| n.as_deref() == Some(name)) {
+                    issues.push(Issue::MissingNamedGroup {
+                        field,
+                        name: name.into(),
+                    })
+                }
+            }
+        }
+        rest = &rest[len..];
+    }
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{device, os, user_agent};
+
+    fn lint(regexes: Regexes) -> Vec<(&'static str, usize, Issue)> {
+        regexes
+            .lint()
+            .into_iter()
+            .map(|f| (f.domain, f.index, f.issue))
+            .collect()
+    }
+
+    #[test]
+    fn shadowing() {
+        let regexes = Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser::new("(Foo)"),
+                // matched by the previous regex
+                user_agent::Parser::new(r"(Foo)/(\d+)"),
+                user_agent::Parser::new(r"(Bar|Baz)/"),
+                // both matched by the previous regex
+                user_agent::Parser::new(r"(Bar)/(\d+)"),
+                user_agent::Parser::new(r"(Bar)/(\d+)"),
+                // the previous regexes are case-sensitive
+                user_agent::Parser::new(r"(Foo)").regex_flag(device::Flags::IGNORE_CASE),
+                // the literal is not all the previous regex needs
+                user_agent::Parser::new(r"(Qux)/(\d+)"),
+                user_agent::Parser::new(r"(Qux)"),
+                // anchored regexes shadow nothing
+                user_agent::Parser::new(r"^(Quux)"),
+                user_agent::Parser::new(r"(Quux)/(\d+)"),
+                // but can be shadowed
+                user_agent::Parser::new(r"\b(Foo)/(\d+)"),
+            ],
+            os_parsers: vec![
+                // domains are separate
+                os::Parser::new(r"(Foo)/(\d+)"),
+            ],
+            device_parsers: vec![],
+        };
+        assert_eq!(
+            lint(regexes),
+            [
+                ("user_agent_parsers", 1, Issue::Shadowed { by: 0 }),
+                ("user_agent_parsers", 3, Issue::Shadowed { by: 2 }),
+                ("user_agent_parsers", 4, Issue::Duplicate { of: 3 }),
+                ("user_agent_parsers", 10, Issue::Shadowed { by: 0 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups() {
+        let regexes = Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser::new(r"(Foo)/(\d*)"),
+                user_agent::Parser::new(r"(Foo) (\d*)").v1("1"),
+                user_agent::Parser::new(r"(\w*) (\d+)").family("$1 Browser"),
+                user_agent::Parser::new(r"Bar/(\d+)").family("Bar $2"),
+            ],
+            os_parsers: vec![os::Parser::new(r"(?<name>\w+) (\d+)").v1("${name}/${version}")],
+            device_parsers: vec![
+                device::Parser::new("(Baz)").brand("$$1 $1").model("$2"),
+                device::Parser::new("("),
+            ],
+        };
+        assert_eq!(
+            lint(regexes),
+            [
+                (
+                    "user_agent_parsers",
+                    0,
+                    Issue::EmptyCapture {
+                        field: "major",
+                        group: 2
+                    }
+                ),
+                (
+                    "user_agent_parsers",
+                    3,
+                    Issue::MissingGroup {
+                        field: "family",
+                        group: 2
+                    }
+                ),
+                (
+                    "os_parsers",
+                    0,
+                    Issue::MissingNamedGroup {
+                        field: "major",
+                        name: "version".into()
+                    }
+                ),
+                (
+                    "device_parsers",
+                    0,
+                    Issue::MissingGroup {
+                        field: "model",
+                        group: 2
+                    }
+                ),
+                (
+                    "device_parsers",
+                    1,
+                    Issue::Invalid(regex_syntax::parse("(").unwrap_err().to_string())
+                ),
+            ]
+        );
+    }
+}
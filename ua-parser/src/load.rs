@@ -0,0 +1,113 @@
+//! Loading of `regexes.yaml` files, see [`Regexes::from_yaml_file`]
+//! and [`Extractor::from_yaml_file`].
+
+use std::path::Path;
+
+use crate::{DataInfo, Error, Extractor, Regexes};
+
+/// Error while loading a regexes file.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The file could not be deserialized.
+    Yaml(serde_yaml::Error),
+    /// The extractor could not be built from the file's content.
+    Extractor(Error),
+}
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Yaml(e) => Some(e),
+            LoadError::Extractor(e) => Some(e),
+        }
+    }
+}
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+impl From<std::io::Error> for LoadError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+impl From<serde_yaml::Error> for LoadError {
+    fn from(value: serde_yaml::Error) -> Self {
+        Self::Yaml(value)
+    }
+}
+impl From<Error> for LoadError {
+    fn from(value: Error) -> Self {
+        Self::Extractor(value)
+    }
+}
+
+impl Regexes<'static> {
+    /// Reads and deserializes a regexes file, in YAML (or JSON)
+    /// format. Requires the `yaml` feature.
+    pub fn from_yaml_file(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let f = std::fs::File::open(path)?;
+        Ok(serde_yaml::from_reader(std::io::BufReader::new(f))?)
+    }
+}
+
+impl Extractor<'static> {
+    /// Reads a regexes file (see [`Regexes::from_yaml_file`]) and
+    /// builds an extractor from it, with the file's digest as
+    /// [`DataInfo::digest`]. Requires the `yaml` feature.
+    ///
+    /// ```no_run
+    /// let extractor = ua_parser::Extractor::from_yaml_file("regexes.yaml")?;
+    /// # Ok::<(), ua_parser::LoadError>(())
+    /// ```
+    pub fn from_yaml_file(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let data = std::fs::read(path)?;
+        let regexes: Regexes<'static> = serde_yaml::from_slice(&data)?;
+        Ok(Extractor::try_from(regexes)?.data_digest(DataInfo::digest(&data)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_yaml_file() {
+        let yaml =
+            "user_agent_parsers: [{regex: '(Firefox)/(\\d+)'}]\nos_parsers: []\ndevice_parsers: []";
+        let path = std::env::temp_dir().join(format!("ua-parser-load-{}.yaml", std::process::id()));
+        std::fs::write(&path, yaml).unwrap();
+
+        let r = Regexes::from_yaml_file(&path).unwrap();
+        assert_eq!(r.user_agent_parsers.len(), 1);
+        let e = Extractor::from_yaml_file(&path).unwrap();
+        assert_eq!(
+            e.data_info().digest,
+            Some(DataInfo::digest(yaml.as_bytes()))
+        );
+        assert_eq!(e.extract("Firefox/121").ua.unwrap().family, "Firefox");
+
+        std::fs::write(
+            &path,
+            "user_agent_parsers: [{regex: '('}]\nos_parsers: []\ndevice_parsers: []",
+        )
+        .unwrap();
+        assert!(matches!(
+            Extractor::from_yaml_file(&path),
+            Err(LoadError::Extractor(_))
+        ));
+        std::fs::write(&path, "user_agent_parsers: 1").unwrap();
+        assert!(matches!(
+            Regexes::from_yaml_file(&path),
+            Err(LoadError::Yaml(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            Regexes::from_yaml_file(&path),
+            Err(LoadError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+//! Layering of regexes definitions, see [`Regexes::merge`].
+
+use crate::{device, os, user_agent, Regexes};
+
+/// How the parsers of two [`Regexes`] are combined by
+/// [`Regexes::merge`], for each domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The other parsers are tried before the existing ones.
+    Prepend,
+    /// The other parsers are tried after the existing ones.
+    Append,
+    /// The other parsers replace the existing parsers with the same
+    /// regex, in place. Other parsers without a counterpart are
+    /// prepended.
+    Replace,
+}
+
+impl<'a> Regexes<'a> {
+    /// Merges `other` into these regexes, e.g. to layer
+    /// organization-specific parsers over upstream data while keeping
+    /// both updatable independently.
+    pub fn merge(self, other: Regexes<'a>, strategy: MergeStrategy) -> Self {
+        self.with_user_agent_parsers(other.user_agent_parsers, strategy)
+            .with_os_parsers(other.os_parsers, strategy)
+            .with_device_parsers(other.device_parsers, strategy)
+    }
+
+    /// Registers custom user agent parsers, with [`MergeStrategy::Prepend`]
+    /// they take priority over the existing parsers, with
+    /// [`MergeStrategy::Append`] they only apply when none of the
+    /// existing parsers matches.
+    pub fn with_user_agent_parsers<I>(mut self, parsers: I, strategy: MergeStrategy) -> Self
+    where
+        I: IntoIterator<Item = user_agent::Parser<'a>>,
+    {
+        self.user_agent_parsers = merge(
+            self.user_agent_parsers,
+            parsers.into_iter().collect(),
+            strategy,
+            |p: &user_agent::Parser| &p.regex,
+        );
+        self
+    }
+
+    /// Registers custom OS parsers, see
+    /// [`Self::with_user_agent_parsers`].
+    pub fn with_os_parsers<I>(mut self, parsers: I, strategy: MergeStrategy) -> Self
+    where
+        I: IntoIterator<Item = os::Parser<'a>>,
+    {
+        self.os_parsers = merge(
+            self.os_parsers,
+            parsers.into_iter().collect(),
+            strategy,
+            |p: &os::Parser| &p.regex,
+        );
+        self
+    }
+
+    /// Registers custom device parsers, see
+    /// [`Self::with_user_agent_parsers`].
+    pub fn with_device_parsers<I>(mut self, parsers: I, strategy: MergeStrategy) -> Self
+    where
+        I: IntoIterator<Item = device::Parser<'a>>,
+    {
+        self.device_parsers = merge(
+            self.device_parsers,
+            parsers.into_iter().collect(),
+            strategy,
+            |p: &device::Parser| &p.regex,
+        );
+        self
+    }
+}
+
+fn merge<P>(
+    mut base: Vec<P>,
+    mut other: Vec<P>,
+    strategy: MergeStrategy,
+    regex: impl Fn(&P) -> &str,
+) -> Vec<P> {
+    match strategy {
+        MergeStrategy::Prepend => {
+            other.append(&mut base);
+            other
+        }
+        MergeStrategy::Append => {
+            base.append(&mut other);
+            base
+        }
+        MergeStrategy::Replace => {
+            let mut unmatched = Vec::new();
+            for p in other {
+                if let Some(slot) = base.iter_mut().find(|b| regex(b) == regex(&p)) {
+                    *slot = p;
+                } else {
+                    unmatched.push(p);
+                }
+            }
+            unmatched.append(&mut base);
+            unmatched
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn regexes(parsers: &[(&'static str, &'static str)]) -> Regexes<'static> {
+        Regexes {
+            user_agent_parsers: parsers
+                .iter()
+                .map(|&(regex, family)| user_agent::Parser {
+                    regex: regex.into(),
+                    family_replacement: Some(family.into()),
+                    ..Default::default()
+                })
+                .collect(),
+            os_parsers: Vec::new(),
+            device_parsers: Vec::new(),
+        }
+    }
+
+    fn parsers<'a>(r: &'a Regexes) -> Vec<(&'a str, &'a str)> {
+        r.user_agent_parsers
+            .iter()
+            .map(|p| (&*p.regex, p.family_replacement.as_deref().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn strategies() {
+        let base = || regexes(&[("a", "A"), ("b", "B")]);
+        let other = || regexes(&[("c", "C"), ("b", "Bee")]);
+
+        let r = base().merge(other(), MergeStrategy::Prepend);
+        assert_eq!(
+            parsers(&r),
+            [("c", "C"), ("b", "Bee"), ("a", "A"), ("b", "B")]
+        );
+
+        let r = base().merge(other(), MergeStrategy::Append);
+        assert_eq!(
+            parsers(&r),
+            [("a", "A"), ("b", "B"), ("c", "C"), ("b", "Bee")]
+        );
+
+        let r = base().merge(other(), MergeStrategy::Replace);
+        assert_eq!(parsers(&r), [("c", "C"), ("a", "A"), ("b", "Bee")]);
+    }
+
+    #[test]
+    fn per_domain() {
+        let r = regexes(&[("(Firefox)", "Firefox")])
+            .with_user_agent_parsers(
+                [user_agent::Parser {
+                    regex: "(Firefox)".into(),
+                    family_replacement: Some("Custom".into()),
+                    ..Default::default()
+                }],
+                MergeStrategy::Prepend,
+            )
+            .with_os_parsers(
+                [os::Parser {
+                    regex: "(Linux)".into(),
+                    ..Default::default()
+                }],
+                MergeStrategy::Append,
+            );
+        assert_eq!(
+            parsers(&r),
+            [("(Firefox)", "Custom"), ("(Firefox)", "Firefox")]
+        );
+        assert_eq!(r.os_parsers.len(), 1);
+        assert!(r.device_parsers.is_empty());
+
+        let e = crate::Extractor::try_from(r).unwrap();
+        let c = e.extract("X11; Linux; Firefox");
+        assert_eq!(c.family(), "Custom");
+        assert_eq!(c.os_family(), "Linux");
+    }
+}
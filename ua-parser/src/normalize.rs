@@ -0,0 +1,275 @@
+//! Opt-in normalization of user agents before matching, see
+//! [`UserAgentNormalizer`], and of device brands after, see
+//! [`BrandNormalizer`].
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::device;
+
+/// Prefix repeated by some broken clients and proxies.
+const MOZILLA: &str = "Mozilla/5.0";
+
+type Hook = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Pre-processing of user agents before matching, so that dirty
+/// user agents (e.g. padded, with embedded control characters, or
+/// with a repeated `Mozilla/5.0` prefix) are parsed like their clean
+/// version rather than falling through to `Other`.
+///
+/// The default normalizer:
+///
+/// - strips null bytes and control characters, whitespace control
+///   characters (e.g. tabs, newlines) are replaced by a space
+/// - trims leading and trailing whitespace
+/// - collapses a repeated `Mozilla/5.0` prefix into a single one
+///
+/// Custom normalizers can be added as hooks, which are run in order
+/// after the built-in steps.
+///
+/// ```
+/// # use ua_parser::{Extractor, Regexes, UserAgentNormalizer};
+/// # let extractor = Extractor::try_from(Regexes {
+/// #     user_agent_parsers: vec![ua_parser::user_agent::Parser::new(r"^Mozilla/5\.0 \((Linux)")],
+/// #     os_parsers: vec![],
+/// #     device_parsers: vec![],
+/// # })?;
+/// let normalizer = UserAgentNormalizer::default()
+///     .hook(|ua| ua.strip_suffix(" (via proxy)").map(String::from));
+///
+/// let ua = normalizer.normalize(" Mozilla/5.0\0 Mozilla/5.0 (Linux) (via proxy)\r\n");
+/// assert_eq!(ua, "Mozilla/5.0 (Linux)");
+/// assert_eq!(extractor.extract(&ua).family(), "Linux");
+/// # Ok::<(), ua_parser::Error>(())
+/// ```
+pub struct UserAgentNormalizer {
+    builtins: bool,
+    hooks: Vec<Hook>,
+}
+
+impl Default for UserAgentNormalizer {
+    /// Creates a normalizer with the built-in steps.
+    fn default() -> Self {
+        Self {
+            builtins: true,
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl UserAgentNormalizer {
+    /// Creates a normalizer without the built-in steps, which only
+    /// runs its hooks.
+    pub fn empty() -> Self {
+        Self {
+            builtins: false,
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Adds a custom normalization step, which returns the normalized
+    /// user agent or [`None`] to leave it unchanged.
+    pub fn hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Returns the normalized version of `ua`, only allocating if a
+    /// step had to rewrite it.
+    pub fn normalize<'u>(&self, ua: &'u str) -> Cow<'u, str> {
+        let mut ua = if self.builtins {
+            clean(ua)
+        } else {
+            Cow::Borrowed(ua)
+        };
+        for hook in &self.hooks {
+            if let Some(normalized) = hook(&ua) {
+                ua = Cow::Owned(normalized);
+            }
+        }
+        ua
+    }
+}
+
+/// Applies the built-in normalization steps.
+fn clean(ua: &str) -> Cow<'_, str> {
+    let stripped = if ua.contains(char::is_control) {
+        Cow::Owned(
+            ua.chars()
+                .filter_map(|c| match c {
+                    c if c.is_whitespace() && c.is_control() => Some(' '),
+                    c if c.is_control() => None,
+                    c => Some(c),
+                })
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(ua)
+    };
+    match stripped {
+        Cow::Borrowed(s) => Cow::Borrowed(collapse(s.trim())),
+        Cow::Owned(s) => Cow::Owned(collapse(s.trim()).to_string()),
+    }
+}
+
+/// Skips all but the last of repeated `Mozilla/5.0` prefixes.
+fn collapse(mut ua: &str) -> &str {
+    while let Some(rest) = ua.strip_prefix(MOZILLA) {
+        let rest = rest.trim_start();
+        if !rest.starts_with(MOZILLA) {
+            break;
+        }
+        ua = rest;
+    }
+    ua
+}
+
+/// Built-in aliases, keyed by lowercase alias, normalizing to the
+/// spellings of uap-core (and [`crate::device_guess`]).
+const BRANDS: &[(&str, &str)] = &[
+    ("apple", "Apple"),
+    ("asus", "Asus"),
+    ("google", "Google"),
+    ("htc", "HTC"),
+    ("huawei", "Huawei"),
+    ("lenovo", "Lenovo"),
+    ("lg", "LG"),
+    ("lge", "LG"),
+    ("moto", "Motorola"),
+    ("motorola", "Motorola"),
+    ("nokia", "Nokia"),
+    ("oneplus", "OnePlus"),
+    ("oppo", "Oppo"),
+    ("realme", "Realme"),
+    ("samsung", "Samsung"),
+    ("sony", "Sony"),
+    ("vivo", "vivo"),
+    ("xiaomi", "XiaoMi"),
+    ("zte", "ZTE"),
+];
+
+/// Post-processing table normalizing the casing and aliases of device
+/// brands (e.g. `SAMSUNG` and `samsung` to `Samsung`), so that
+/// aggregations don't fragment on cosmetic differences in the data.
+///
+/// Lookups are case-insensitive, brands which are not in the table
+/// are left as-is. The built-in table uses the spellings of uap-core
+/// (e.g. `XiaoMi`, `Oppo`), other spellings (e.g. the brands' own
+/// `Xiaomi`, `OPPO`) can be selected using [`Self::alias`].
+///
+/// ```
+/// # use ua_parser::BrandNormalizer;
+/// let n = BrandNormalizer::default()
+///     .alias("Fairphone", "Fairphone")
+///     .alias("xiaomi", "Xiaomi");
+/// assert_eq!(n.normalize("SAMSUNG"), "Samsung");
+/// assert_eq!(n.normalize("OPPO"), "Oppo");
+/// assert_eq!(n.normalize("XIAOMI"), "Xiaomi");
+/// assert_eq!(n.normalize("FAIRPHONE"), "Fairphone");
+/// assert_eq!(n.normalize("Unknown"), "Unknown");
+/// ```
+pub struct BrandNormalizer {
+    aliases: HashMap<String, Cow<'static, str>>,
+}
+
+impl Default for BrandNormalizer {
+    /// Creates a normalizer with the built-in table of common brands.
+    fn default() -> Self {
+        Self {
+            aliases: BRANDS
+                .iter()
+                .map(|&(alias, brand)| (alias.into(), brand.into()))
+                .collect(),
+        }
+    }
+}
+
+impl BrandNormalizer {
+    /// Creates an empty normalizer, without the built-in table.
+    pub fn empty() -> Self {
+        Self {
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Normalizes `alias` (case-insensitively) to `brand`, overriding
+    /// any existing mapping.
+    pub fn alias(mut self, alias: &str, brand: impl Into<Cow<'static, str>>) -> Self {
+        self.aliases.insert(alias.to_lowercase(), brand.into());
+        self
+    }
+
+    /// Returns the normalized version of `brand`.
+    pub fn normalize<'a>(&'a self, brand: &'a str) -> &'a str {
+        self.aliases.get(&brand.to_lowercase()).map_or(brand, |b| b)
+    }
+
+    /// Normalizes the brand of an extracted device.
+    pub fn normalize_device<'a>(
+        &'a self,
+        mut device: device::ValueRef<'a>,
+    ) -> device::ValueRef<'a> {
+        if let Some(brand) = &device.brand {
+            if let Some(b) = self.aliases.get(&brand.to_lowercase()) {
+                device.brand = Some(Cow::Borrowed(b));
+            }
+        }
+        device
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn user_agents() {
+        let n = UserAgentNormalizer::default();
+        let clean = "Mozilla/5.0 (X11; Linux x86_64) Firefox/121.0";
+        assert!(matches!(n.normalize(clean), Cow::Borrowed(s) if s == clean));
+        assert!(matches!(
+            n.normalize("  curl/8.0  "),
+            Cow::Borrowed("curl/8.0")
+        ));
+        for dirty in [
+            "Mozilla/5.0 Mozilla/5.0 (X11; Linux x86_64) Firefox/121.0",
+            "Mozilla/5.0  Mozilla/5.0 Mozilla/5.0 (X11; Linux x86_64) Firefox/121.0",
+            "Mozilla/5.0 (X11;\tLinux x86_64)\0 Firefox/121.0\r\n",
+            "\u{7f} Mozilla/5.0\nMozilla/5.0 (X11; Linux x86_64) Firefox/121.0",
+        ] {
+            assert_eq!(n.normalize(dirty), clean, "{dirty:?}");
+        }
+        // only the prefix is collapsed
+        let nested = "Mozilla/5.0 (Linux) Mozilla/5.0 Mozilla/5.0";
+        assert_eq!(n.normalize(nested), nested);
+
+        let hooked = UserAgentNormalizer::empty()
+            .hook(|ua| Some(ua.to_lowercase()))
+            .hook(|ua| ua.strip_suffix("!").map(String::from));
+        assert_eq!(hooked.normalize(" CURL! "), " curl! ");
+        assert_eq!(hooked.normalize("CURL!"), "curl");
+    }
+
+    #[test]
+    fn normalize() {
+        let n = BrandNormalizer::default();
+        assert_eq!(n.normalize("samsung"), "Samsung");
+        assert_eq!(n.normalize("OPPO"), "Oppo");
+        assert_eq!(n.normalize("xiaomi"), "XiaoMi");
+        assert_eq!(n.normalize("REALME"), "Realme");
+        let n = n.alias("Oppo", "OPPO");
+        assert_eq!(n.normalize("oppo"), "OPPO");
+        assert_eq!(n.normalize("Generic"), "Generic");
+        assert_eq!(BrandNormalizer::empty().normalize("samsung"), "samsung");
+
+        let d = n.normalize_device(device::ValueRef {
+            device: "SM-S918B".into(),
+            brand: Some("SAMSUNG".into()),
+            model: None,
+        });
+        assert_eq!(d.brand.as_deref(), Some("Samsung"));
+    }
+}
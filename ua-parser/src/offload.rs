@@ -0,0 +1,53 @@
+//! Offloading of extractions to tokio's blocking pool.
+
+use std::sync::Arc;
+
+use tokio::task::JoinError;
+
+use crate::{Client, Extractor};
+
+impl Extractor<'static> {
+    /// Performs the extraction on tokio's blocking thread pool,
+    /// returning owned values.
+    ///
+    /// The extractor is shared via an [`Arc`] and the user agent is
+    /// owned, so the future does not hold any borrow across awaits
+    /// and can be used freely in async handlers, without stalling the
+    /// runtime's worker threads with regex matching.
+    ///
+    /// Fails if the extraction panicked or the runtime is shutting
+    /// down.
+    pub async fn extract_blocking(self: Arc<Self>, ua: String) -> Result<Client, JoinError> {
+        tokio::task::spawn_blocking(move || self.extract(&ua).into_owned()).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{user_agent, Regexes};
+
+    #[tokio::test]
+    async fn extract_blocking() {
+        let e = Arc::new(
+            Extractor::try_from(Regexes {
+                user_agent_parsers: vec![user_agent::Parser {
+                    regex: r"(Firefox)/(\d+)".into(),
+                    ..Default::default()
+                }],
+                os_parsers: vec![],
+                device_parsers: vec![],
+            })
+            .unwrap(),
+        );
+
+        let c = e
+            .clone()
+            .extract_blocking("Mozilla/5.0 Firefox/121".into())
+            .await
+            .unwrap();
+        assert_eq!(c.ua.unwrap().major.as_deref(), Some("121"));
+        assert!(c.os.is_none());
+        assert!(c.device.is_none());
+    }
+}
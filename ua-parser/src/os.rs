@@ -0,0 +1,360 @@
+//! OS extraction module
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use regex_filtered::BuildError;
+
+use crate::dedup::Dedup;
+use crate::device::Flags;
+use crate::domain;
+use crate::engine;
+use crate::resolvers::{OptResolver, Resolver};
+use crate::{Interner, Version};
+
+/// OS parser configuration
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+pub struct Parser<'a> {
+    ///
+    pub regex: Cow<'a, str>,
+    /// Configuration flags for the regex, if any.
+    #[serde(alias = "regexFlag")]
+    pub regex_flag: Option<Flags>,
+    /// Replacement for the [`ValueRef::os`], must be set if there
+    /// is no capture in the [`Self::regex`], if there are
+    /// captures may be fully templated (with `$n` placeholders
+    /// for any group of the [`Self::regex`]).
+    #[serde(alias = "osReplacement")]
+    pub os_replacement: Option<Cow<'a, str>>,
+    /// Replacement for the [`ValueRef::major`], may be fully templated.
+    #[serde(alias = "osV1Replacement")]
+    pub os_v1_replacement: Option<Cow<'a, str>>,
+    /// Replacement for the [`ValueRef::minor`], may be fully templated.
+    #[serde(alias = "osV2Replacement")]
+    pub os_v2_replacement: Option<Cow<'a, str>>,
+    /// Replacement for the [`ValueRef::patch`], may be fully templated.
+    #[serde(alias = "osV3Replacement")]
+    pub os_v3_replacement: Option<Cow<'a, str>>,
+    /// Replacement for the [`ValueRef::patch_minor`], may be fully templated.
+    #[serde(alias = "osV4Replacement")]
+    pub os_v4_replacement: Option<Cow<'a, str>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser with the provided regex and no
+    /// replacements, to be completed by the other methods.
+    pub fn new(regex: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            regex: regex.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets [`Self::regex_flag`].
+    pub fn regex_flag(mut self, flags: Flags) -> Self {
+        self.regex_flag = Some(flags);
+        self
+    }
+
+    /// Sets [`Self::os_replacement`].
+    pub fn os(mut self, os: impl Into<Cow<'a, str>>) -> Self {
+        self.os_replacement = Some(os.into());
+        self
+    }
+
+    /// Sets [`Self::os_v1_replacement`].
+    pub fn v1(mut self, v1: impl Into<Cow<'a, str>>) -> Self {
+        self.os_v1_replacement = Some(v1.into());
+        self
+    }
+
+    /// Sets [`Self::os_v2_replacement`].
+    pub fn v2(mut self, v2: impl Into<Cow<'a, str>>) -> Self {
+        self.os_v2_replacement = Some(v2.into());
+        self
+    }
+
+    /// Sets [`Self::os_v3_replacement`].
+    pub fn v3(mut self, v3: impl Into<Cow<'a, str>>) -> Self {
+        self.os_v3_replacement = Some(v3.into());
+        self
+    }
+
+    /// Sets [`Self::os_v4_replacement`].
+    pub fn v4(mut self, v4: impl Into<Cow<'a, str>>) -> Self {
+        self.os_v4_replacement = Some(v4.into());
+        self
+    }
+}
+
+type Repl<'a> = (
+    Resolver<'a>,
+    OptResolver<'a>,
+    OptResolver<'a>,
+    OptResolver<'a>,
+    OptResolver<'a>,
+);
+
+/// Builder for [`Extractor`].
+#[derive(Default)]
+pub struct Builder<'a> {
+    core: domain::Builder<Repl<'a>>,
+}
+impl<'a> Builder<'a> {
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the [`Extractor`], may fail if building the
+    /// prefilter fails.
+    pub fn build(self) -> Result<Extractor<'a>, BuildError> {
+        Ok(Extractor {
+            core: self.core.build()?,
+        })
+    }
+
+    /// Add a [`Parser`] configuration, fails if the regex can not
+    /// be parsed, or if [`Parser::os_replacement`] is missing and
+    /// the regex has no groups.
+    pub fn push(mut self, os: Parser<'a>) -> Result<Self, crate::Error> {
+        self.push_mut(os)?;
+        Ok(self)
+    }
+
+    /// Pushes a parser, leaving the builder unchanged on failure.
+    /// `index` is only used to report errors.
+    pub(crate) fn push_with(
+        &mut self,
+        os: Parser<'a>,
+        index: usize,
+        dedup: Option<&mut Dedup>,
+    ) -> Result<(), crate::Error> {
+        let strict = self.core.strict;
+        self.core
+            .push(&os.regex, os.regex_flag, dedup, |r| {
+                Ok((
+                    Resolver::new(os.os_replacement, r, 1, strict)?,
+                    OptResolver::new(os.os_v1_replacement, r, 2, strict)?,
+                    OptResolver::new(os.os_v2_replacement, r, 3, strict)?,
+                    OptResolver::new(os.os_v3_replacement, r, 4, strict)?,
+                    OptResolver::new(os.os_v4_replacement, r, 5, strict)?,
+                ))
+            })
+            .map_err(|e| e.in_parser("os_parsers", index, &os.regex))
+    }
+
+    domain::builder_methods!();
+}
+
+/// Collects parsers into a builder, failing on the first invalid
+/// parser.
+impl<'a> FromIterator<Parser<'a>> for Result<Builder<'a>, crate::Error> {
+    fn from_iter<I: IntoIterator<Item = Parser<'a>>>(iter: I) -> Self {
+        Builder::new().push_all(iter)
+    }
+}
+
+/// OS extractor structure
+pub struct Extractor<'a> {
+    pub(crate) core: domain::Extractor<Repl<'a>>,
+}
+impl<'a> Extractor<'a> {
+    /// Matches & extracts the OS data for this user agent,
+    /// returns `None` if the UA string could not be matched.
+    pub fn extract(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
+        let (idx, _) = self.core.first(ua)?;
+        self.extract_at(idx, ua)
+    }
+
+    fn resolve(&'a self, idx: usize, c: &engine::Captures<'a>) -> ValueRef<'a> {
+        let (o, v1, v2, v3, v4) = self.core.repl(idx);
+        let empty = self.core.empty;
+
+        ValueRef {
+            os: o.resolve(c),
+            major: v1.resolve(c, empty),
+            minor: v2.resolve(c, empty),
+            patch: v3.resolve(c, empty),
+            patch_minor: v4.resolve(c, empty),
+        }
+    }
+
+    domain::extractor_methods!();
+
+    /// Turns the extractor back into a builder, reusing the
+    /// compiled regexes.
+    pub(crate) fn into_builder(
+        self,
+        dedup: Option<&mut Dedup>,
+    ) -> Result<Builder<'a>, regex_filtered::ParseError> {
+        Ok(Builder {
+            core: self.core.into_builder(dedup)?,
+        })
+    }
+
+    /// Reconstructs the parsers the extractor was built from,
+    /// with their regexes as rewritten during building.
+    pub(crate) fn parsers(&self) -> impl Iterator<Item = Parser<'_>> {
+        self.core
+            .parsers()
+            .map(|(re, flag, (o, v1, v2, v3, v4))| Parser {
+                regex: re.as_str().into(),
+                regex_flag: flag,
+                os_replacement: o.replacement().map(From::from),
+                os_v1_replacement: v1.replacement().map(From::from),
+                os_v2_replacement: v2.replacement().map(From::from),
+                os_v3_replacement: v3.replacement().map(From::from),
+                os_v4_replacement: v4.replacement().map(From::from),
+            })
+    }
+}
+
+/// An OS extraction result.
+#[derive(Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct ValueRef<'a> {
+    ///
+    pub os: Cow<'a, str>,
+    ///
+    pub major: Option<Cow<'a, str>>,
+    ///
+    pub minor: Option<Cow<'a, str>>,
+    ///
+    pub patch: Option<Cow<'a, str>>,
+    ///
+    pub patch_minor: Option<Cow<'a, str>>,
+}
+
+impl<'a> ValueRef<'a> {
+    /// The uap-core fallback value, for user agents no parser
+    /// matches: `os` is `"Other"`, everything else is unset.
+    pub const fn other() -> Self {
+        Self {
+            os: Cow::Borrowed("Other"),
+            major: None,
+            minor: None,
+            patch: None,
+            patch_minor: None,
+        }
+    }
+    /// The typed version of the value, for ordering comparisons.
+    pub fn version(&self) -> Version<'_> {
+        Version {
+            major: self.major.as_deref(),
+            minor: self.minor.as_deref(),
+            patch: self.patch.as_deref(),
+            patch_minor: self.patch_minor.as_deref(),
+        }
+    }
+
+    /// Converts a [`ValueRef`] into a [`Value`] to avoid lifetime
+    /// concerns, may need to allocate and copy any data currently
+    /// borrowed from a [`Parser`] or user agent string.
+    pub fn into_owned(self) -> Value {
+        Value {
+            os: self.os.into_owned(),
+            major: self.major.map(|c| c.into_owned()),
+            minor: self.minor.map(|c| c.into_owned()),
+            patch: self.patch.map(|c| c.into_owned()),
+            patch_minor: self.patch_minor.map(|c| c.into_owned()),
+        }
+    }
+
+    /// Converts a [`ValueRef`] into a [`Value`] whose strings are
+    /// shared through the `interner`.
+    pub fn into_interned(self, interner: &Interner) -> Value<Arc<str>> {
+        Value {
+            os: interner.intern(&self.os),
+            major: self.major.map(|c| interner.intern(&c)),
+            minor: self.minor.map(|c| interner.intern(&c)),
+            patch: self.patch.map(|c| interner.intern(&c)),
+            patch_minor: self.patch_minor.map(|c| interner.intern(&c)),
+        }
+    }
+}
+
+impl ValueRef<'_> {
+    /// The fields of the value as `(name, value)` pairs, see
+    /// [`crate::user_agent::ValueRef::fields`].
+    pub fn fields(&self) -> [(&'static str, Option<&str>); 5] {
+        [
+            ("os", Some(&self.os)),
+            ("major", self.major.as_deref()),
+            ("minor", self.minor.as_deref()),
+            ("patch", self.patch.as_deref()),
+            ("patch_minor", self.patch_minor.as_deref()),
+        ]
+    }
+
+    /// The fields of the value as a map, see [`Self::fields`].
+    pub fn to_map(&self) -> BTreeMap<&'static str, Option<&str>> {
+        self.fields().into_iter().collect()
+    }
+}
+
+/// Formats the OS followed by the version, e.g. `Mac OS X
+/// 10.15.7`.
+impl std::fmt::Display for ValueRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        crate::version::write_versioned(f, &self.os, self.version())
+    }
+}
+
+/// Owned version of [`ValueRef`], with [`String`] by default or
+/// interned [`Arc<str>`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct Value<S = String> {
+    ///
+    pub os: S,
+    ///
+    pub major: Option<S>,
+    ///
+    pub minor: Option<S>,
+    ///
+    pub patch: Option<S>,
+    ///
+    pub patch_minor: Option<S>,
+}
+
+impl Value {
+    /// Owned version of [`ValueRef::other`].
+    pub fn other() -> Self {
+        ValueRef::other().into_owned()
+    }
+}
+
+impl<S: AsRef<str>> Value<S> {
+    /// Same as [`ValueRef::fields`].
+    pub fn fields(&self) -> [(&'static str, Option<&str>); 5] {
+        [
+            ("os", Some(self.os.as_ref())),
+            ("major", self.major.as_ref().map(AsRef::as_ref)),
+            ("minor", self.minor.as_ref().map(AsRef::as_ref)),
+            ("patch", self.patch.as_ref().map(AsRef::as_ref)),
+            ("patch_minor", self.patch_minor.as_ref().map(AsRef::as_ref)),
+        ]
+    }
+
+    /// The fields of the value as a map, see [`Self::fields`].
+    pub fn to_map(&self) -> BTreeMap<&'static str, Option<&str>> {
+        self.fields().into_iter().collect()
+    }
+
+    /// The typed version of the value, for ordering comparisons.
+    pub fn version(&self) -> Version<'_> {
+        Version {
+            major: self.major.as_ref().map(AsRef::as_ref),
+            minor: self.minor.as_ref().map(AsRef::as_ref),
+            patch: self.patch.as_ref().map(AsRef::as_ref),
+            patch_minor: self.patch_minor.as_ref().map(AsRef::as_ref),
+        }
+    }
+}
+
+impl<S: AsRef<str>> std::fmt::Display for Value<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        crate::version::write_versioned(f, self.os.as_ref(), self.version())
+    }
+}
@@ -0,0 +1,196 @@
+//! Per-parser profiling over a corpus, see [`Profiler`].
+
+use std::time::{Duration, Instant};
+
+use crate::{engine, Domains, Extractor};
+
+/// Verification statistics of a single parser.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct ParserProfile {
+    /// Number of times the parser's regex was checked against a user
+    /// agent, i.e. it was a prefilter candidate and no earlier parser
+    /// of its domain matched.
+    pub verified: usize,
+    /// Number of user agents the parser was selected for.
+    pub matched: usize,
+    /// Total time spent checking the parser's regex.
+    pub time: Duration,
+}
+
+/// Profile of a parser, as reported by [`Profiler::report`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParserReport<'e> {
+    /// The parser's domain, one of [`Domains::UA`], [`Domains::OS`],
+    /// or [`Domains::DEVICE`].
+    pub domain: Domains,
+    /// Index of the parser in its domain.
+    pub index: usize,
+    /// The parser's regex, as rewritten by the extractor.
+    pub regex: &'e str,
+    ///
+    pub profile: ParserProfile,
+}
+
+/// Records per-parser verification times and hit counts of an
+/// extractor over a corpus, to find the slowest and hottest rules of
+/// a regexes file.
+///
+/// The extractions are replicated domain by domain, timing every
+/// verification, so profiling is much slower than extracting.
+///
+/// ```
+/// # use ua_parser::{Extractor, Profiler, Regexes};
+/// # let regexes = Regexes {
+/// #     user_agent_parsers: vec![
+/// #         ua_parser::user_agent::Parser::new(r"(Firefox)/(\d+)"),
+/// #         ua_parser::user_agent::Parser::new(r"(curl)/(\d+)"),
+/// #     ],
+/// #     os_parsers: vec![],
+/// #     device_parsers: vec![],
+/// # };
+/// let extractor = Extractor::try_from(regexes)?;
+/// let mut profiler = Profiler::new(&extractor);
+/// for ua in ["Firefox/121", "Firefox/122", "curl/8"] {
+///     profiler.profile(ua);
+/// }
+///
+/// let hottest = &profiler.hottest(1)[0];
+/// assert_eq!(hottest.index, 0);
+/// assert_eq!(hottest.profile.matched, 2);
+/// # Ok::<(), ua_parser::Error>(())
+/// ```
+pub struct Profiler<'e, 'a> {
+    extractor: &'e Extractor<'a>,
+    profiles: [Vec<ParserProfile>; 3],
+    user_agents: usize,
+}
+
+const DOMAINS: [Domains; 3] = [Domains::UA, Domains::OS, Domains::DEVICE];
+
+impl<'e, 'a> Profiler<'e, 'a> {
+    /// Creates a profiler for `extractor`, with empty profiles.
+    pub fn new(extractor: &'e Extractor<'a>) -> Self {
+        let profiles = [
+            extractor.ua.matcher(),
+            extractor.os.matcher(),
+            extractor.dev.matcher(),
+        ]
+        .map(|m| vec![ParserProfile::default(); m.regexes().len()]);
+        Self {
+            extractor,
+            profiles,
+            user_agents: 0,
+        }
+    }
+
+    fn matchers(&self) -> [&'e regex_filtered::Regexes<engine::Regex>; 3] {
+        [
+            self.extractor.ua.matcher(),
+            self.extractor.os.matcher(),
+            self.extractor.dev.matcher(),
+        ]
+    }
+
+    /// Profiles the matching of `ua` in all three domains.
+    pub fn profile(&mut self, ua: &str) {
+        self.user_agents += 1;
+        for (matcher, profiles) in std::iter::zip(self.matchers(), &mut self.profiles) {
+            for idx in matcher.candidates(ua) {
+                let start = Instant::now();
+                let matched = matcher.verify(idx, ua);
+                let p = &mut profiles[idx];
+                p.time += start.elapsed();
+                p.verified += 1;
+                if matched {
+                    p.matched += 1;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Number of user agents profiled so far.
+    pub fn user_agents(&self) -> usize {
+        self.user_agents
+    }
+
+    /// The profiles of every parser which was verified at least once,
+    /// in domain then parser order.
+    pub fn report(&self) -> Vec<ParserReport<'e>> {
+        std::iter::zip(DOMAINS, std::iter::zip(self.matchers(), &self.profiles))
+            .flat_map(|(domain, (matcher, profiles))| {
+                std::iter::zip(matcher.regexes(), profiles)
+                    .enumerate()
+                    .filter(|(_, (_, p))| p.verified > 0)
+                    .map(move |(index, (re, &profile))| ParserReport {
+                        domain,
+                        index,
+                        regex: re.as_str(),
+                        profile,
+                    })
+            })
+            .collect()
+    }
+
+    /// The number of user agents each parser of each domain matched,
+    /// by index, for [`Extractor::frequencies`].
+    pub fn frequencies(&self) -> [Vec<usize>; 3] {
+        let [ua, os, dev] = &self.profiles;
+        [ua, os, dev].map(|p| p.iter().map(|p| p.matched).collect())
+    }
+
+    /// The `n` parsers which took the most time overall.
+    pub fn slowest(&self, n: usize) -> Vec<ParserReport<'e>> {
+        let mut r = self.report();
+        r.sort_by_key(|p| std::cmp::Reverse(p.profile.time));
+        r.truncate(n);
+        r
+    }
+
+    /// The `n` parsers which matched the most user agents.
+    pub fn hottest(&self, n: usize) -> Vec<ParserReport<'e>> {
+        let mut r = self.report();
+        r.sort_by_key(|p| std::cmp::Reverse(p.profile.matched));
+        r.truncate(n);
+        r
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{os, user_agent, Regexes};
+
+    #[test]
+    fn profile() {
+        let e = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![
+                user_agent::Parser::new(r"(Firefox)/(\d+)\.5"),
+                user_agent::Parser::new(r"(Firefox)/(\d+)"),
+            ],
+            os_parsers: vec![os::Parser::new(r"(Linux)")],
+            device_parsers: vec![],
+        })
+        .unwrap();
+        let mut p = Profiler::new(&e);
+        for ua in ["Firefox/121.0 (Linux)", "Firefox/122.5", "curl/8.0"] {
+            p.profile(ua);
+        }
+        assert_eq!(p.user_agents(), 3);
+
+        let r = p.report();
+        assert_eq!(r.len(), 3);
+        assert_eq!((r[0].domain, r[0].index), (Domains::UA, 0));
+        assert_eq!(r[0].profile.verified, 2);
+        assert_eq!(r[0].profile.matched, 1);
+        // only verified when the first parser did not match
+        assert_eq!(r[1].profile.verified, 1);
+        assert_eq!(r[1].profile.matched, 1);
+        assert_eq!((r[2].domain, r[2].regex), (Domains::OS, "(Linux)"));
+        assert_eq!(r[2].profile.matched, 1);
+
+        assert_eq!(p.frequencies(), [vec![1, 1], vec![1], vec![]]);
+        assert_eq!(p.hottest(5).len(), 3);
+        assert_eq!(p.slowest(1).len(), 1);
+    }
+}
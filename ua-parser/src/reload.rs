@@ -0,0 +1,186 @@
+//! Hot-reloading of the regexes file, see [`ReloadingExtractor`].
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use arc_swap::ArcSwap;
+
+use crate::{Extractor, LoadError};
+
+struct Shared {
+    path: PathBuf,
+    current: ArcSwap<Extractor<'static>>,
+    /// Modification time of the file when it was last loaded, also
+    /// serializes the loads.
+    modified: Mutex<Option<SystemTime>>,
+    /// Last error of the background reloads.
+    error: Mutex<Option<LoadError>>,
+}
+
+impl Shared {
+    fn reload(&self) -> Result<(), LoadError> {
+        let mut last = self.modified.lock().unwrap();
+        *last = modified(&self.path);
+        self.current.store(Arc::new(load(&self.path)?));
+        Ok(())
+    }
+}
+
+fn load(path: &Path) -> Result<Extractor<'static>, LoadError> {
+    Extractor::from_yaml_file(path)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Extractor backed by a regexes file, which is watched for changes
+/// and reloaded in the background.
+///
+/// The file is polled for modifications at the configured interval,
+/// on change a new [`Extractor`] is built in the background and then
+/// atomically swapped in, so long-running services can pick up data
+/// updates without restarting. Extractions in progress keep using the
+/// previous extractor.
+///
+/// If a reload fails, the previous extractor is kept and the error
+/// is available via [`ReloadingExtractor::take_error`].
+///
+/// The background thread stops when the [`ReloadingExtractor`] is
+/// dropped.
+pub struct ReloadingExtractor {
+    shared: Arc<Shared>,
+    // dropping the sender wakes up and stops the reloader
+    _stop: mpsc::Sender<()>,
+}
+
+impl ReloadingExtractor {
+    /// Loads the regexes file at `path` and starts watching it for
+    /// changes every `interval`.
+    ///
+    /// Fails if the initial loading fails.
+    pub fn new(path: impl Into<PathBuf>, interval: Duration) -> Result<Self, LoadError> {
+        let path = path.into();
+        let last = modified(&path);
+        let shared = Arc::new(Shared {
+            current: ArcSwap::from_pointee(load(&path)?),
+            path,
+            modified: Mutex::new(last),
+            error: Mutex::new(None),
+        });
+
+        let (stop, stopped) = mpsc::channel();
+        let s = shared.clone();
+        std::thread::Builder::new()
+            .name("ua-parser-reload".into())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    if modified(&s.path) == *s.modified.lock().unwrap() {
+                        continue;
+                    }
+                    if let Err(e) = s.reload() {
+                        *s.error.lock().unwrap() = Some(e);
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            shared,
+            _stop: stop,
+        })
+    }
+
+    /// Returns the current extractor. The returned extractor is not
+    /// affected by later reloads, so it should not be held onto for
+    /// long.
+    pub fn load(&self) -> Arc<Extractor<'static>> {
+        self.shared.current.load_full()
+    }
+
+    /// The path of the regexes file being watched.
+    pub fn path(&self) -> &Path {
+        &self.shared.path
+    }
+
+    /// Replaces the regexes file by `data`, and the current
+    /// extractor by `extractor` built from it, without the background
+    /// thread reloading the new file.
+    #[cfg(feature = "fetch")]
+    pub(crate) fn replace(
+        &self,
+        data: &[u8],
+        extractor: Extractor<'static>,
+    ) -> std::io::Result<()> {
+        let path = self.path();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".download");
+        std::fs::write(&tmp, data)?;
+
+        let mut last = self.shared.modified.lock().unwrap();
+        std::fs::rename(&tmp, path)?;
+        *last = modified(path);
+        self.shared.current.store(Arc::new(extractor));
+        Ok(())
+    }
+
+    /// Reloads the regexes file immediately, regardless of changes.
+    pub fn reload(&self) -> Result<(), LoadError> {
+        self.shared.reload()
+    }
+
+    /// Takes the error of the last failed background reload, if any.
+    pub fn take_error(&self) -> Option<LoadError> {
+        self.shared.error.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FIREFOX: &str = r#"
+user_agent_parsers:
+  - regex: '(Firefox)/(\d+)'
+os_parsers: []
+device_parsers: []
+"#;
+    const FENNEC: &str = r#"
+user_agent_parsers:
+  - regex: 'Firefox/(\d+)'
+    family_replacement: 'Fennec'
+os_parsers: []
+device_parsers: []
+"#;
+
+    fn family(e: &ReloadingExtractor) -> String {
+        e.load()
+            .ua
+            .extract("Firefox/121")
+            .unwrap()
+            .family
+            .into_owned()
+    }
+
+    #[test]
+    fn reload() {
+        let path =
+            std::env::temp_dir().join(format!("ua-parser-reload-{}.yaml", std::process::id()));
+        std::fs::write(&path, FIREFOX).unwrap();
+
+        let e = ReloadingExtractor::new(&path, Duration::from_secs(3600)).unwrap();
+        assert_eq!(family(&e), "Firefox");
+
+        std::fs::write(&path, FENNEC).unwrap();
+        e.reload().unwrap();
+        assert_eq!(family(&e), "Fennec");
+
+        std::fs::write(&path, "user_agent_parsers: [{regex: '('}]").unwrap();
+        assert!(e.reload().is_err());
+        assert_eq!(family(&e), "Fennec", "the previous extractor is kept");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(e.reload(), Err(LoadError::Io(_))));
+    }
+}
@@ -4,25 +4,151 @@
 // static_captures_len it only specifies whether all groups are
 // required, if any group is optional that returns `None`.
 
-use crate::Error;
-use regex::Captures;
+use crate::engine::{self, Captures};
+use crate::user_agent::FamilyReplacement;
+use crate::{EmptyCapture, Error};
 use std::borrow::Cow;
+use std::ops::Range;
 
 fn get<'s>(c: &Captures<'s>, group: usize) -> Option<&'s str> {
-    c.get(group).map(|g| g.as_str()).filter(|s| !s.is_empty())
+    engine::group(c, group).filter(|s| !s.is_empty())
 }
 
-// TODO:
-// - memchr?
-// - u16 checks against u16 buffer (check all positions)?
-// - svar/simd?
-fn has_substitution(s: &str) -> bool {
-    debug_assert!(!s.is_empty());
-    std::iter::zip(s.as_bytes(), &s.as_bytes()[1..]).any(|(&d, n)| d == b'$' && n.is_ascii_digit())
+/// Optional value of the group, following the `empty` handling.
+fn get_opt<'s>(c: &Captures<'s>, group: usize, empty: EmptyCapture) -> Option<&'s str> {
+    match empty {
+        EmptyCapture::Absent => get(c, group),
+        EmptyCapture::Keep => engine::group(c, group),
+    }
+}
+
+/// Group reference in a template, following a `$`.
+pub(crate) enum Ref<'s> {
+    /// `$$`, an escaped `$`.
+    Dollar,
+    /// `$N` (single digit) or `${N}`.
+    Index(usize),
+    /// `${name}` or `$<name>`.
+    Name(&'s str),
+}
+
+/// Parses the reference at the start of `s`, returns it and its
+/// length.
+pub(crate) fn reference(s: &str) -> Option<(Ref<'_>, usize)> {
+    match *s.as_bytes().first()? {
+        b'$' => Some((Ref::Dollar, 1)),
+        d @ b'0'..=b'9' => Some((Ref::Index(usize::from(d - b'0')), 1)),
+        open @ (b'{' | b'<') => {
+            let close = if open == b'{' { '}' } else { '>' };
+            let (name, _) = s[1..].split_once(close)?;
+            if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+                return None;
+            }
+            let r = match name.parse() {
+                Ok(n) if open == b'{' => Ref::Index(n),
+                _ => Ref::Name(name),
+            };
+            Some((r, name.len() + 2))
+        }
+        _ => None,
+    }
+}
+
+enum Part {
+    /// Range of the template source.
+    Literal(Range<usize>),
+    Group(usize),
 }
 
-/// Resolver with full templating: the template string can contain
-/// $1-9 markers which get replaced by the corresponding regex string.
+/// Replacement template, parsed and validated against the parser's
+/// regex when building:
+///
+/// - `$N` (a single digit) and `${N}` reference the group `N`
+/// - `${name}` and `$<name>` reference the named group `name`
+/// - `$$` is a literal `$`, as is a `$` not followed by a reference
+///
+/// References to groups missing from the regex expand to an empty
+/// string, unless the template is strict.
+pub(crate) struct Template<'a> {
+    source: Cow<'a, str>,
+    parts: Vec<Part>,
+}
+impl<'a> Template<'a> {
+    /// Parses `source`, if `only` is set references to other groups
+    /// are left as-is. If `strict` is set references to missing
+    /// groups are errors.
+    fn new(
+        source: Cow<'a, str>,
+        re: &engine::Regex,
+        only: Option<usize>,
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let groups = re.captures_len() - 1;
+        let mut parts = Vec::new();
+        let mut literal = 0;
+        let mut i = 0;
+        while let Some(offset) = source[i..].find('$') {
+            let dollar = i + offset;
+            i = dollar + 1;
+            let Some((r, len)) = reference(&source[i..]) else {
+                continue;
+            };
+            let group = match r {
+                Ref::Dollar => {
+                    parts.push(Part::Literal(literal..i));
+                    i += len;
+                    literal = i;
+                    continue;
+                }
+                Ref::Index(n) => n,
+                Ref::Name(name) => match re.capture_names().position(|n| n == Some(name)) {
+                    Some(n) => n,
+                    None if only.is_some() => continue,
+                    None if strict => return Err(Error::MissingNamedGroup(name.into())),
+                    None => usize::MAX,
+                },
+            };
+            if only.is_some_and(|o| o != group) {
+                continue;
+            }
+            if group > groups && strict {
+                return Err(Error::MissingGroup(group));
+            }
+            parts.push(Part::Literal(literal..dollar));
+            if group <= groups {
+                parts.push(Part::Group(group));
+            }
+            i += len;
+            literal = i;
+        }
+        parts.push(Part::Literal(literal..source.len()));
+        parts.retain(|p| !matches!(p, Part::Literal(r) if r.is_empty()));
+        Ok(Self { source, parts })
+    }
+
+    /// Whether the template is its source verbatim.
+    fn is_literal(&self) -> bool {
+        match &*self.parts {
+            [] => self.source.is_empty(),
+            [Part::Literal(r)] => r.len() == self.source.len(),
+            _ => false,
+        }
+    }
+
+    fn expand(&self, c: &Captures<'_>) -> String {
+        let mut r = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(range) => r.push_str(&self.source[range.clone()]),
+                Part::Group(n) => r.push_str(engine::group(c, *n).unwrap_or("")),
+            }
+        }
+        r
+    }
+}
+
+/// Resolver with full templating: the replacement is a [`Template`]
+/// whose references get replaced by the corresponding groups.
 ///
 /// - if there is a non-null replacement pattern, then it must be used with
 ///   match groups as template parameters (at indices 1+)
@@ -33,20 +159,35 @@ fn has_substitution(s: &str) -> bool {
 pub(crate) enum Resolver<'a> {
     Replacement(Cow<'a, str>),
     Capture(usize),
-    Template(Cow<'a, str>),
+    Template(Template<'a>),
 }
 impl<'a> Resolver<'a> {
-    pub(crate) fn new(repl: Option<Cow<'a, str>>, groups: usize, idx: usize) -> Self {
-        if let Some(s) = repl.filter(|s| !s.trim().is_empty()) {
-            if has_substitution(&s) {
-                Self::Template(s)
+    pub(crate) fn new(
+        repl: Option<Cow<'a, str>>,
+        re: &engine::Regex,
+        idx: usize,
+        strict: bool,
+    ) -> Result<Self, Error> {
+        Ok(if let Some(s) = repl.filter(|s| !s.trim().is_empty()) {
+            let t = Template::new(s, re, None, strict)?;
+            if t.is_literal() {
+                Self::Replacement(t.source)
             } else {
-                Self::Replacement(s)
+                Self::Template(t)
             }
-        } else if groups >= idx {
+        } else if re.captures_len() > idx {
             Self::Capture(idx)
         } else {
             Self::Replacement("".into())
+        })
+    }
+
+    /// Replacement this resolver was created from, if any.
+    pub(crate) fn replacement(&self) -> Option<&str> {
+        match self {
+            Self::Replacement(s) => Some(s),
+            Self::Template(t) => Some(&t.source),
+            Self::Capture(_) => None,
         }
     }
 
@@ -55,8 +196,7 @@ impl<'a> Resolver<'a> {
             Self::Replacement(s) => (**s).into(),
             Self::Capture(i) => get(c, *i).unwrap_or("").into(),
             Self::Template(t) => {
-                let mut r = String::new();
-                c.expand(t, &mut r);
+                let r = t.expand(c);
                 let trimmed = r.trim();
                 if r.len() == trimmed.len() {
                     r.into()
@@ -73,31 +213,45 @@ pub(crate) enum OptResolver<'a> {
     None,
     Replacement(Cow<'a, str>),
     Capture(usize),
-    Template(Cow<'a, str>),
+    Template(Template<'a>),
 }
 impl<'a> OptResolver<'a> {
-    pub(crate) fn new(repl: Option<Cow<'a, str>>, groups: usize, idx: usize) -> Self {
-        if let Some(s) = repl.filter(|s| !s.trim().is_empty()) {
-            if has_substitution(&s) {
-                Self::Template(s)
+    pub(crate) fn new(
+        repl: Option<Cow<'a, str>>,
+        re: &engine::Regex,
+        idx: usize,
+        strict: bool,
+    ) -> Result<Self, Error> {
+        Ok(if let Some(s) = repl.filter(|s| !s.trim().is_empty()) {
+            let t = Template::new(s, re, None, strict)?;
+            if t.is_literal() {
+                Self::Replacement(t.source)
             } else {
-                Self::Replacement(s)
+                Self::Template(t)
             }
-        } else if groups >= idx {
+        } else if re.captures_len() > idx {
             Self::Capture(idx)
         } else {
             Self::None
+        })
+    }
+
+    /// Replacement this resolver was created from, if any.
+    pub(crate) fn replacement(&self) -> Option<&str> {
+        match self {
+            Self::Replacement(s) => Some(s),
+            Self::Template(t) => Some(&t.source),
+            Self::None | Self::Capture(_) => None,
         }
     }
 
-    pub(crate) fn resolve(&'a self, c: &Captures<'a>) -> Option<Cow<'a, str>> {
+    pub(crate) fn resolve(&'a self, c: &Captures<'a>, empty: EmptyCapture) -> Option<Cow<'a, str>> {
         match self {
             Self::None => None,
             Self::Replacement(s) => Some((**s).into()),
-            Self::Capture(i) => get(c, *i).map(From::from),
+            Self::Capture(i) => get_opt(c, *i, empty).map(From::from),
             Self::Template(t) => {
-                let mut r = String::new();
-                c.expand(t, &mut r);
+                let r = t.expand(c);
                 let trimmed = r.trim();
                 if trimmed.is_empty() {
                     None
@@ -113,43 +267,71 @@ impl<'a> OptResolver<'a> {
 
 /// Dedicated restrict-templated resolver for UserAgent#family:
 /// supports templating in the replacement, but only for the `$1`
-/// value / group.
+/// value / group, unless full templating is enabled.
 pub(crate) enum FamilyResolver<'a> {
     Capture,
     Replacement(Cow<'a, str>),
-    Template(Cow<'a, str>),
+    Template(Template<'a>),
 }
 impl<'a> FamilyResolver<'a> {
-    pub(crate) fn new(repl: Option<Cow<'a, str>>, groups: usize) -> Result<Self, Error> {
-        match repl {
-            Some(s) if s.contains("$1") => {
-                if groups < 1 {
-                    Err(Error::MissingGroup(1))
+    pub(crate) fn new(
+        repl: Option<Cow<'a, str>>,
+        re: &engine::Regex,
+        mode: FamilyReplacement,
+        strict: bool,
+    ) -> Result<Self, Error> {
+        match repl.filter(|s| !s.is_empty()) {
+            Some(s) => {
+                let only = match mode {
+                    FamilyReplacement::Restricted => Some(1),
+                    FamilyReplacement::Templated => None,
+                };
+                let t = Template::new(s, re, only, strict)?;
+                Ok(if t.is_literal() {
+                    FamilyResolver::Replacement(t.source)
                 } else {
-                    Ok(FamilyResolver::Template(s))
-                }
+                    FamilyResolver::Template(t)
+                })
             }
-            Some(s) if !s.is_empty() => Ok(FamilyResolver::Replacement(s)),
-            _ if groups >= 1 => Ok(FamilyResolver::Capture),
-            _ => Ok(FamilyResolver::Replacement("".into())),
+            None if re.captures_len() > 1 => Ok(FamilyResolver::Capture),
+            None => Ok(FamilyResolver::Replacement("".into())),
         }
     }
 
-    pub(crate) fn resolve(&'a self, c: &super::Captures<'a>) -> Cow<'a, str> {
+    /// Replacement this resolver was created from, if any.
+    pub(crate) fn replacement(&self) -> Option<&str> {
+        match self {
+            FamilyResolver::Replacement(s) => Some(s),
+            FamilyResolver::Template(t) => Some(&t.source),
+            FamilyResolver::Capture => None,
+        }
+    }
+
+    /// Resolved value if it does not depend on the captures.
+    pub(crate) fn constant(&self) -> Option<&str> {
+        match self {
+            FamilyResolver::Replacement(s) => Some(s),
+            FamilyResolver::Capture | FamilyResolver::Template(_) => None,
+        }
+    }
+
+    pub(crate) fn resolve(&'a self, c: &Captures<'a>) -> Cow<'a, str> {
         match self {
             FamilyResolver::Capture => get(c, 1).unwrap_or("").into(),
             FamilyResolver::Replacement(s) => (**s).into(),
-            FamilyResolver::Template(t) => t.replace("$1", get(c, 1).unwrap_or("")).into(),
+            FamilyResolver::Template(t) => t.expand(c).into(),
         }
     }
 }
 
-/// Untemplated resolver, the replacement value is used as-is if
-/// present.
+/// Resolver for UserAgent#v1-4: by default untemplated, the
+/// replacement value is used as-is if present, or restrict-templated
+/// (same as family but for the group of the field's fallback).
 pub(crate) enum FallbackResolver<'a> {
     None,
     Capture(usize),
     Replacement(Cow<'a, str>),
+    Template(Template<'a>),
 }
 impl<'a> FallbackResolver<'a> {
     pub(crate) fn new(repl: Option<Cow<'a, str>>, groups: usize, idx: usize) -> Self {
@@ -161,11 +343,185 @@ impl<'a> FallbackResolver<'a> {
             Self::None
         }
     }
-    pub(crate) fn resolve(&'a self, c: &super::Captures<'a>) -> Option<&'a str> {
+
+    /// Restrict-templated resolver, only `$idx` is substituted.
+    pub(crate) fn templated(
+        repl: Option<Cow<'a, str>>,
+        re: &engine::Regex,
+        idx: usize,
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let groups = re.captures_len() - 1;
+        let Some(s) = repl.filter(|s| !s.is_empty()) else {
+            return Ok(Self::new(None, groups, idx));
+        };
+        let t = Template::new(s, re, Some(idx), strict)?;
+        Ok(if t.is_literal() {
+            Self::Replacement(t.source)
+        } else {
+            Self::Template(t)
+        })
+    }
+
+    /// Replacement this resolver was created from, if any.
+    pub(crate) fn replacement(&self) -> Option<&str> {
+        match self {
+            FallbackResolver::Replacement(s) => Some(s),
+            FallbackResolver::Template(t) => Some(&t.source),
+            FallbackResolver::None | FallbackResolver::Capture(_) => None,
+        }
+    }
+
+    pub(crate) fn resolve(&'a self, c: &Captures<'a>, empty: EmptyCapture) -> Option<Cow<'a, str>> {
         match self {
             FallbackResolver::None => None,
-            FallbackResolver::Capture(n) => get(c, *n),
-            FallbackResolver::Replacement(r) => Some(r),
+            FallbackResolver::Capture(n) => get_opt(c, *n, empty).map(From::from),
+            FallbackResolver::Replacement(r) => Some((**r).into()),
+            FallbackResolver::Template(t) => {
+                Some(t.expand(c)).filter(|s| !s.is_empty()).map(From::from)
+            }
+        }
+    }
+}
+
+/// Heap memory owned by a replacement table, for the extractors'
+/// `memory_usage`. Borrowed data is not counted.
+pub(crate) trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+impl HeapSize for Cow<'_, str> {
+    fn heap_size(&self) -> usize {
+        match self {
+            Cow::Borrowed(_) => 0,
+            Cow::Owned(s) => s.capacity(),
+        }
+    }
+}
+impl HeapSize for Template<'_> {
+    fn heap_size(&self) -> usize {
+        self.source.heap_size() + self.parts.capacity() * std::mem::size_of::<Part>()
+    }
+}
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * std::mem::size_of::<T>()
+            + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+impl HeapSize for Option<crate::device::Flags> {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+macro_rules! heap_size {
+    ($($t:ident),+) => {
+        impl<$($t: HeapSize),+> HeapSize for ($($t,)+) {
+            #[allow(non_snake_case)]
+            fn heap_size(&self) -> usize {
+                let ($($t,)+) = self;
+                0 $(+ $t.heap_size())+
+            }
         }
+    };
+}
+heap_size!(A, B, C);
+heap_size!(A, B, C, D, E);
+macro_rules! resolver_heap_size {
+    ($($t:ident),+) => {$(
+        impl HeapSize for $t<'_> {
+            fn heap_size(&self) -> usize {
+                match self {
+                    $t::Replacement(r) => r.heap_size(),
+                    $t::Template(t) => t.heap_size(),
+                    _ => 0,
+                }
+            }
+        }
+    )+};
+}
+resolver_heap_size!(Resolver, OptResolver, FamilyResolver, FallbackResolver);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn expand_with(template: &str, re: &str, ua: &str, strict: bool) -> Result<String, Error> {
+        let re = engine::Regex::new(re).unwrap();
+        let t = Template::new(template.into(), &re, None, strict)?;
+        Ok(t.expand(&engine::captures(&re, ua).unwrap()))
+    }
+
+    fn expand(template: &str, re: &str, ua: &str) -> Result<String, Error> {
+        expand_with(template, re, ua, true)
+    }
+
+    #[test]
+    fn template() {
+        let re = r"(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)(?<k>k)";
+        let ua = "abcdefghijk";
+        assert_eq!(expand("$1-$9", re, ua).unwrap(), "a-i");
+        assert_eq!(expand("$10", re, ua).unwrap(), "a0");
+        assert_eq!(expand("${10}$1a", re, ua).unwrap(), "jaa");
+        assert_eq!(expand("${k}/$<k>/${11}", re, ua).unwrap(), "k/k/k");
+        assert_eq!(expand("$$1 $$$1 $", re, ua).unwrap(), "$1 $a $");
+        assert_eq!(
+            expand("$x ${} $<1 ${a-b}", re, ua).unwrap(),
+            "$x ${} $<1 ${a-b}"
+        );
+
+        assert!(matches!(
+            expand("$2", "(a)", "a"),
+            Err(Error::MissingGroup(2))
+        ));
+        assert!(matches!(
+            expand("${12}", re, ua),
+            Err(Error::MissingGroup(12))
+        ));
+        assert!(matches!(
+            expand("$<l>", re, ua),
+            Err(Error::MissingNamedGroup(n)) if n == "l"
+        ));
+
+        // missing groups expand to nothing unless strict
+        assert_eq!(expand_with("$1 $2.", "(a)", "a", false).unwrap(), "a .");
+        assert_eq!(expand_with("${12}$<l>-${k}", re, ua, false).unwrap(), "-k");
+        assert_eq!(expand_with("$<l>", re, ua, false).unwrap(), "");
+    }
+
+    #[test]
+    fn family() {
+        let re = engine::Regex::new("(a)(b)").unwrap();
+        let c = engine::captures(&re, "ab").unwrap();
+        let f = FamilyResolver::new(
+            Some("$1 $2 ${1} $$1".into()),
+            &re,
+            FamilyReplacement::Restricted,
+            true,
+        )
+        .unwrap();
+        assert_eq!(f.resolve(&c), "a $2 a $1");
+        assert_eq!(f.replacement(), Some("$1 $2 ${1} $$1"));
+        let f = FamilyResolver::new(
+            Some("Foo $2".into()),
+            &re,
+            FamilyReplacement::Restricted,
+            true,
+        )
+        .unwrap();
+        assert_eq!(f.constant(), Some("Foo $2"));
+
+        let re = engine::Regex::new("a").unwrap();
+        assert!(matches!(
+            FamilyResolver::new(Some("$1".into()), &re, FamilyReplacement::Restricted, true),
+            Err(Error::MissingGroup(1))
+        ));
+        let f = FamilyResolver::new(
+            Some("$1 Foo".into()),
+            &re,
+            FamilyReplacement::Restricted,
+            false,
+        )
+        .unwrap();
+        assert_eq!(f.resolve(&engine::captures(&re, "a").unwrap()), " Foo");
     }
 }
@@ -0,0 +1,636 @@
+//! Rewriting of the parsers' regexes before compilation, see
+//! [`Config`], and translation of foreign regex flavors, see
+//! [`Regexes::translate_js`].
+
+use std::borrow::Cow;
+
+use crate::Regexes;
+
+/// Regex syntax targeted by the rewritten regexes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// Syntax of the `regex` crate, which supports nested character
+    /// classes.
+    Regex,
+    /// Syntax of the `regex-lite` crate, which does not support
+    /// nested character classes: class escapes are left as-is inside
+    /// classes (they are always ASCII in `regex-lite`), and nested
+    /// classes are flattened into their parent when possible (that
+    /// is, unless negated).
+    RegexLite,
+}
+
+impl Default for Dialect {
+    /// The dialect of the selected engine.
+    fn default() -> Self {
+        if cfg!(feature = "engine-regex-lite") {
+            Self::RegexLite
+        } else {
+            Self::Regex
+        }
+    }
+}
+
+/// Configuration of the rewriting applied to the parsers' regexes by
+/// the builders, before compiling them.
+///
+/// By default, character classes and word boundaries are rewritten to
+/// ASCII and bounded repetitions to unbounded, the second to reduce regex memory
+/// requirements, and the first for both that and to better match the
+/// (inferred) semantics intended for ua-parser. Both can be disabled
+/// for faithful (Unicode) semantics:
+///
+/// ```
+/// # use ua_parser::rewrite::Config;
+/// assert_eq!(Config::new().rewrite(r"(\d+).{0,100}"), "([0-9]+).*");
+///
+/// let faithful = Config::new().ascii_classes(false).widen_repetitions(false);
+/// assert_eq!(faithful.rewrite(r"(\d+).{0,100}"), r"(\d+).{0,100}");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    ascii_classes: bool,
+    ascii_word_boundaries: bool,
+    widen_repetitions: bool,
+    dialect: Dialect,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ascii_classes: true,
+            ascii_word_boundaries: true,
+            widen_repetitions: true,
+            dialect: Dialect::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `\d`, `\D`, `\s`, `\S`, `\w` and `\W` are rewritten
+    /// to their ASCII equivalents, enabled by default.
+    pub fn ascii_classes(mut self, yes: bool) -> Self {
+        self.ascii_classes = yes;
+        self
+    }
+
+    /// Whether `\b` and `\B` are rewritten to ASCII word boundaries,
+    /// enabled by default.
+    pub fn ascii_word_boundaries(mut self, yes: bool) -> Self {
+        self.ascii_word_boundaries = yes;
+        self
+    }
+
+    /// Whether large bounded repetitions (`{0,n}` and `{1,n}` with
+    /// `n >= 100`) are rewritten to `*` and `+`, enabled by default.
+    /// Repetitions of items which can match the empty string are left
+    /// as-is, as widening them is not equivalent.
+    pub fn widen_repetitions(mut self, yes: bool) -> Self {
+        self.widen_repetitions = yes;
+        self
+    }
+
+    /// Sets the syntax the rewritten regexes must conform to.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Configuration which leaves every regex unchanged, for regexes
+    /// which have already been rewritten.
+    pub(crate) fn verbatim() -> Self {
+        Self {
+            ascii_classes: false,
+            ascii_word_boundaries: false,
+            widen_repetitions: false,
+            dialect: Dialect::Regex,
+        }
+    }
+
+    /// Packs the configuration into a byte, see [`Self::from_bits`].
+    pub(crate) fn bits(&self) -> u8 {
+        u8::from(self.ascii_classes)
+            | u8::from(self.ascii_word_boundaries) << 1
+            | u8::from(self.widen_repetitions) << 2
+            | u8::from(self.dialect == Dialect::RegexLite) << 3
+    }
+
+    /// Unpacks a configuration packed by [`Self::bits`], fails on
+    /// unknown bits.
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        (bits < 1 << 4).then_some(Self {
+            ascii_classes: bits & 1 != 0,
+            ascii_word_boundaries: bits & 1 << 1 != 0,
+            widen_repetitions: bits & 1 << 2 != 0,
+            dialect: if bits & 1 << 3 != 0 {
+                Dialect::RegexLite
+            } else {
+                Dialect::Regex
+            },
+        })
+    }
+
+    /// Rewrites the regex according to the configuration, borrows it
+    /// if there is nothing to rewrite.
+    pub fn rewrite<'r>(&self, re: &'r str) -> Cow<'r, str> {
+        let mut from = 0;
+        let mut out = String::new();
+
+        let mut it = re.char_indices();
+        let mut escape = false;
+        // for each open character class, whether its brackets are
+        // elided (flattened into its parent)
+        let mut classes = Vec::new();
+        // start of the open groups, and of the last item (character,
+        // escape, class, or group) which a repetition would apply to
+        let mut groups = Vec::new();
+        let mut item = 0;
+        'main: while let Some((idx, c)) = it.next() {
+            if classes.is_empty() {
+                match c {
+                    '(' if !escape => groups.push(idx),
+                    ')' if !escape => item = groups.pop().unwrap_or(0),
+                    '\\' | '{' if !escape => (),
+                    _ if escape => item = idx - 1,
+                    _ => item = idx,
+                }
+            }
+            match c {
+                '\\' if !escape => {
+                    escape = true;
+                    continue;
+                }
+                '{' if !escape && classes.is_empty() && self.widen_repetitions => {
+                    if idx == 0 {
+                        // we're repeating nothing, this regex is broken, bail
+                        return re.into();
+                    }
+                    // we don't need to loop, we only want to replace {0, ...} and {1, ...}
+                    let Some((_, start)) = it.next() else {
+                        continue;
+                    };
+                    if start != '0' && start != '1' {
+                        continue;
+                    }
+
+                    if !matches!(it.next(), Some((_, ','))) {
+                        continue;
+                    }
+
+                    let mut digits = 0;
+                    for (ri, rc) in it.by_ref() {
+                        match rc {
+                            '}' if digits > 2 && !matches_empty(&re[item..idx]) => {
+                                // here idx is the index of the start of
+                                // the range and ri is the end of range
+                                out.push_str(&re[from..idx]);
+                                from = ri + 1;
+                                out.push_str(if start == '0' { "*" } else { "+" });
+                                break;
+                            }
+                            c if c.is_ascii_digit() => {
+                                digits += 1;
+                            }
+                            _ => continue 'main,
+                        }
+                    }
+                }
+                '[' if !escape => {
+                    let flatten = !classes.is_empty()
+                        && self.dialect == Dialect::RegexLite
+                        // negated and ASCII (`[:alpha:]`) classes
+                        && !matches!(re[idx + 1..].chars().next(), Some('^' | ':'));
+                    if flatten {
+                        out.push_str(&re[from..idx]);
+                        from = idx + 1;
+                    }
+                    classes.push(flatten);
+                }
+                ']' if !escape && classes.last() == Some(&true) => {
+                    classes.pop();
+                    out.push_str(&re[from..idx]);
+                    from = idx + 1;
+                }
+                ']' if !escape => {
+                    classes.pop();
+                }
+                'd' | 'D' | 's' | 'S' | 'w' | 'W'
+                    if escape
+                        && self.ascii_classes
+                        && (classes.is_empty() || self.dialect == Dialect::Regex) =>
+                {
+                    // idx is the class letter so idx-1 is \\, and we
+                    // want to exclude it
+                    out.push_str(&re[from..idx - 1]);
+                    from = idx + 1;
+                    out.push_str(match c {
+                        'd' => "[0-9]",
+                        'D' => "[^0-9]",
+                        's' => r"[\t\n\x0B\x0C\r ]",
+                        'S' => r"[^\t\n\x0B\x0C\r ]",
+                        'w' => "[A-Za-z0-9_]",
+                        _ => "[^A-Za-z0-9_]",
+                    });
+                }
+                'b' | 'B'
+                    if escape
+                        && self.ascii_word_boundaries
+                        && classes.is_empty()
+                        && self.dialect == Dialect::Regex =>
+                {
+                    out.push_str(&re[from..idx - 1]);
+                    from = idx + 1;
+                    out.push_str(if c == 'b' { r"(?-u:\b)" } else { r"(?-u:\B)" });
+                }
+                _ => (),
+            }
+            escape = false;
+        }
+
+        if from == 0 {
+            re.into()
+        } else {
+            out.push_str(&re[from..]);
+            out.into()
+        }
+    }
+}
+
+/// Whether the repeated item may match the empty string (or can't
+/// be parsed on its own). Such a bounded repetition can't be widened:
+/// unbounded repetitions skip empty iterations, which changes the
+/// alternatives they prefer e.g. `(a?|b){0,100}` and `(a?|b)*` don't
+/// match the same thing on `"b"`.
+fn matches_empty(item: &str) -> bool {
+    regex_syntax::parse(item).map_or(true, |hir| hir.properties().minimum_len() == Some(0))
+}
+
+/// Construct altered by [`translate_js`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alteration {
+    /// A lookahead or lookbehind assertion was removed, making the
+    /// regex more permissive.
+    Lookaround(String),
+    /// A backreference was removed, making the regex more permissive.
+    Backreference(String),
+    /// An escape was translated to its equivalent, e.g. `\cJ` to
+    /// `\x0A`.
+    Escape(String),
+    /// A `[^]` (any character) was translated to `(?s:.)`.
+    AnyCharacter,
+}
+
+/// Parser altered by [`Regexes::translate_js`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translation {
+    /// Name of the parsers list the parser is part of, e.g.
+    /// `os_parsers`.
+    pub domain: &'static str,
+    /// Index of the parser within its list.
+    pub index: usize,
+    /// Regex of the parser before translation.
+    pub original: String,
+    /// Constructs which were altered, in order.
+    pub alterations: Vec<Alteration>,
+}
+
+impl Regexes<'_> {
+    /// Translates the JavaScript-flavored constructs of every
+    /// parser's regex to the syntax of the regex engines, see
+    /// [`translate_js`], and reports the parsers which were altered.
+    ///
+    /// This is opt-in as some translations are lossy, the report
+    /// should be checked to decide whether they are acceptable.
+    pub fn translate_js(&mut self) -> Vec<Translation> {
+        let mut translations = Vec::new();
+        translate_all(
+            "user_agent_parsers",
+            self.user_agent_parsers.iter_mut().map(|p| &mut p.regex),
+            &mut translations,
+        );
+        translate_all(
+            "os_parsers",
+            self.os_parsers.iter_mut().map(|p| &mut p.regex),
+            &mut translations,
+        );
+        translate_all(
+            "device_parsers",
+            self.device_parsers.iter_mut().map(|p| &mut p.regex),
+            &mut translations,
+        );
+        translations
+    }
+}
+
+fn translate_all<'r, 'a: 'r>(
+    domain: &'static str,
+    regexes: impl Iterator<Item = &'r mut Cow<'a, str>>,
+    translations: &mut Vec<Translation>,
+) {
+    for (index, regex) in regexes.enumerate() {
+        let (translated, alterations) = translate_js(regex);
+        if alterations.is_empty() {
+            continue;
+        }
+        let translated = translated.into_owned();
+        translations.push(Translation {
+            domain,
+            index,
+            original: std::mem::replace(regex, translated.into()).into_owned(),
+            alterations,
+        });
+    }
+}
+
+/// Translates the JavaScript-flavored constructs of a regex which the
+/// regex engines do not support, returns the translated regex and
+/// the constructs which were altered, in order.
+///
+/// Lookarounds and backreferences can not be translated, so they are
+/// removed entirely. Other constructs are translated to equivalents.
+///
+/// ```
+/// # use ua_parser::rewrite::{translate_js, Alteration};
+/// let (re, alterations) = translate_js(r"Foo(?!Bar)/(\d+)\cI");
+/// assert_eq!(re, r"Foo/(\d+)\x09");
+/// assert_eq!(alterations, [
+///     Alteration::Lookaround("(?!Bar)".into()),
+///     Alteration::Escape(r"\cI".into()),
+/// ]);
+/// ```
+pub fn translate_js(re: &str) -> (Cow<'_, str>, Vec<Alteration>) {
+    let b = re.as_bytes();
+    let mut from = 0;
+    let mut out = String::new();
+    let mut alterations = Vec::new();
+    let mut inclass = false;
+    // only ascii bytes are structural, so iterating over bytes never
+    // splits a character where it matters
+    let mut i = 0;
+    while i < b.len() {
+        let (replacement, end, alteration) = match b[i] {
+            b'\\' => match b.get(i + 1) {
+                Some(b'c') if b.get(i + 2).is_some_and(u8::is_ascii_alphabetic) => (
+                    format!("\\x{:02X}", b[i + 2] % 32),
+                    i + 3,
+                    Alteration::Escape(re[i..i + 3].into()),
+                ),
+                Some(b'0') if !b.get(i + 2).is_some_and(u8::is_ascii_digit) => (
+                    "\\x00".into(),
+                    i + 2,
+                    Alteration::Escape(re[i..i + 2].into()),
+                ),
+                Some(b'1'..=b'9') if !inclass => {
+                    let end = i + 1 + b[i + 1..].iter().take_while(|c| c.is_ascii_digit()).count();
+                    (
+                        String::new(),
+                        end,
+                        Alteration::Backreference(re[i..end].into()),
+                    )
+                }
+                _ => {
+                    i += 2;
+                    continue;
+                }
+            },
+            b'[' if inclass => ("\\[".into(), i + 1, Alteration::Escape("[".into())),
+            b'[' if re[i..].starts_with("[^]") => {
+                ("(?s:.)".into(), i + 3, Alteration::AnyCharacter)
+            }
+            b'[' => {
+                inclass = true;
+                i += 1;
+                continue;
+            }
+            b']' if inclass => {
+                inclass = false;
+                i += 1;
+                continue;
+            }
+            b'(' if !inclass
+                && ["(?=", "(?!", "(?<=", "(?<!"]
+                    .iter()
+                    .any(|p| re[i..].starts_with(p)) =>
+            {
+                let end = group_end(b, i);
+                (
+                    String::new(),
+                    end,
+                    Alteration::Lookaround(re[i..end].into()),
+                )
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        out.push_str(&re[from..i]);
+        out.push_str(&replacement);
+        alterations.push(alteration);
+        from = end;
+        i = end;
+    }
+
+    if from == 0 {
+        (re.into(), alterations)
+    } else {
+        out.push_str(&re[from..]);
+        (out.into(), alterations)
+    }
+}
+
+/// Returns the index after the group opening at `start`, or the end
+/// of the regex if the group is not closed.
+fn group_end(b: &[u8], start: usize) -> usize {
+    let mut depth = 0;
+    let mut inclass = false;
+    let mut i = start;
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 1,
+            b'[' if !inclass => inclass = true,
+            b']' if inclass => inclass = false,
+            b'(' if !inclass => depth += 1,
+            b')' if !inclass => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    b.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rewrite(re: &str) -> Cow<'_, str> {
+        Config::new().dialect(Dialect::Regex).rewrite(re)
+    }
+
+    #[test]
+    fn ignore_small_repetition() {
+        assert_eq!(rewrite(".{0,2}x"), ".{0,2}x");
+        assert_eq!(rewrite(".{0,}"), ".{0,}");
+        assert_eq!(rewrite(".{1,}"), ".{1,}");
+    }
+
+    #[test]
+    fn rewrite_large_repetitions() {
+        assert_eq!(rewrite(".{0,20}x"), ".{0,20}x");
+        assert_eq!(rewrite("(.{0,100})"), "(.*)");
+        assert_eq!(rewrite("(.{1,50})"), "(.{1,50})");
+        assert_eq!(rewrite(".{1,300}x"), ".+x");
+        assert_eq!(rewrite("[a-z].{1,300}x"), "[a-z].+x");
+    }
+
+    #[test]
+    fn ignore_empty_repetitions() {
+        assert_eq!(rewrite("(a?|b){0,100}"), "(a?|b){0,100}");
+        assert_eq!(rewrite(r"x\b{1,100}"), r"x(?-u:\b){1,100}");
+        assert_eq!(rewrite("(?:a(b)*){1,100}"), "(?:a(b)*)+");
+        assert_eq!(rewrite(r"(\(|x){0,100}"), r"(\(|x)*");
+    }
+
+    #[test]
+    fn ignore_non_repetitions() {
+        assert_eq!(
+            rewrite(r"\{1,2}"),
+            r"\{1,2}",
+            "if the opening brace is escaped it's not a repetition"
+        );
+        assert_eq!(
+            rewrite("[.{1,100}]"),
+            "[.{1,100}]",
+            "inside a set it's not a repetition"
+        );
+    }
+
+    #[test]
+    fn rewrite_classes() {
+        assert_eq!(rewrite(r"\dx"), "[0-9]x");
+        assert_eq!(rewrite(r"\wx"), "[A-Za-z0-9_]x");
+        assert_eq!(rewrite(r"[\d]x"), r"[[0-9]]x");
+        assert_eq!(rewrite(r"a\sb\S"), r"a[\t\n\x0B\x0C\r ]b[^\t\n\x0B\x0C\r ]");
+    }
+
+    #[test]
+    fn rewrite_word_boundaries() {
+        assert_eq!(rewrite(r"\bfoo\B"), r"(?-u:\b)foo(?-u:\B)");
+        assert_eq!(rewrite(r"\\b"), r"\\b", "escaped backslash");
+
+        let c = Config::new()
+            .dialect(Dialect::Regex)
+            .ascii_word_boundaries(false);
+        assert_eq!(c.rewrite(r"\bfoo\s"), r"\bfoo[\t\n\x0B\x0C\r ]");
+        let c = Config::new().dialect(Dialect::RegexLite);
+        assert_eq!(c.rewrite(r"\bfoo"), r"\bfoo");
+
+        let re = crate::engine::Regex::new(&Config::new().rewrite(r"\bfoo\b")).unwrap();
+        assert!(re.is_match("a foo b".as_ref()));
+        assert!(!re.is_match("afoo".as_ref()));
+    }
+
+    #[test]
+    fn config() {
+        let c = Config::new().ascii_classes(false);
+        assert_eq!(c.rewrite(r"\d.{0,100}"), r"\d.*");
+        let c = Config::new().widen_repetitions(false);
+        assert_eq!(c.rewrite(r"\d.{0,100}"), r"[0-9].{0,100}");
+
+        let c = Config::new().dialect(Dialect::RegexLite);
+        assert_eq!(c.rewrite(r"\d[\d.]\W"), r"[0-9][\d.][^A-Za-z0-9_]");
+    }
+
+    #[test]
+    fn flatten_classes() {
+        let c = Config::new().dialect(Dialect::RegexLite);
+        assert_eq!(c.rewrite(r"[[a-z]x]"), r"[a-zx]");
+        assert_eq!(c.rewrite(r"[^a[b[c]]]\[[d]"), r"[^abc]\[[d]");
+        assert_eq!(c.rewrite(r"[[:alpha:][0-9]]"), r"[[:alpha:]0-9]");
+        assert_eq!(c.rewrite(r"[a[^b]]"), r"[a[^b]]", "can't flatten negations");
+        assert_eq!(rewrite(r"[[a-z]x]"), r"[[a-z]x]");
+
+        // the default dialect is always supported by the engine
+        for re in [r"[[a-z]x]\d", r"[\d.]+\b", r"[\w[:punct:]]"] {
+            let re = Config::new().rewrite(re);
+            assert!(crate::engine::Regex::new(&re).is_ok(), "{re}");
+        }
+    }
+
+    #[test]
+    fn js() {
+        for (re, expected, alterations) in [
+            (r"Foo/(\d+)", r"Foo/(\d+)", vec![]),
+            (r"(?:a(b)?)?c", r"(?:a(b)?)?c", vec![]),
+            (
+                r"(?<!Edg)(Chrome)(?=/(?:\d|\)))[\(]",
+                r"(Chrome)[\(]",
+                vec![
+                    Alteration::Lookaround("(?<!Edg)".into()),
+                    Alteration::Lookaround(r"(?=/(?:\d|\)))".into()),
+                ],
+            ),
+            (
+                r"(a)-\1\0[^][a[]",
+                r"(a)-\x00(?s:.)[a\[]",
+                vec![
+                    Alteration::Backreference(r"\1".into()),
+                    Alteration::Escape(r"\0".into()),
+                    Alteration::AnyCharacter,
+                    Alteration::Escape("[".into()),
+                ],
+            ),
+            ("(?<name>é)", "(?<name>é)", vec![]),
+        ] {
+            let (re, alts) = translate_js(re);
+            assert_eq!(re, expected);
+            assert_eq!(alts, alterations);
+            assert!(crate::engine::Regex::new(&re).is_ok(), "{re}");
+        }
+    }
+
+    #[test]
+    fn translate_regexes() {
+        let mut r = Regexes {
+            user_agent_parsers: vec![crate::user_agent::Parser {
+                regex: "(Foo)".into(),
+                ..Default::default()
+            }],
+            os_parsers: vec![crate::os::Parser {
+                regex: "(Foo)".into(),
+                ..Default::default()
+            }],
+            device_parsers: vec![
+                crate::device::Parser {
+                    regex: "(Foo)".into(),
+                    ..Default::default()
+                },
+                crate::device::Parser {
+                    regex: "(Bar)(?! Baz)".into(),
+                    ..Default::default()
+                },
+            ],
+        };
+        assert_eq!(
+            r.translate_js(),
+            [Translation {
+                domain: "device_parsers",
+                index: 1,
+                original: "(Bar)(?! Baz)".into(),
+                alterations: vec![Alteration::Lookaround("(?! Baz)".into())],
+            }]
+        );
+        assert_eq!(r.device_parsers[1].regex, "(Bar)");
+        assert!(crate::Extractor::try_from(r).is_ok());
+    }
+}
@@ -0,0 +1,100 @@
+//! Cheaply clonable handle on an extractor, see [`SharedExtractor`].
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::Extractor;
+
+/// Reference-counted handle on an [`Extractor`], created by
+/// [`Extractor::into_shared`].
+///
+/// Cloning the handle is cheap and does not require a `'static`
+/// borrow, so it can be stored in per-connection or per-request
+/// state, e.g. the state of a web framework. The handle dereferences
+/// to the extractor for the actual extraction.
+///
+/// ```
+/// # use ua_parser::{Extractor, Regexes};
+/// # let regexes = Regexes {
+/// #     user_agent_parsers: vec![ua_parser::user_agent::Parser {
+/// #         regex: r"(Firefox)/(\d+)".into(),
+/// #         ..Default::default()
+/// #     }],
+/// #     os_parsers: vec![],
+/// #     device_parsers: vec![],
+/// # };
+/// let shared = Extractor::try_from(regexes)?.into_shared();
+///
+/// let handle = shared.clone();
+/// let family = std::thread::spawn(move || {
+///     handle.extract("Mozilla/5.0 Firefox/121").ua.unwrap().family.into_owned()
+/// })
+/// .join()
+/// .unwrap();
+/// assert_eq!(family, "Firefox");
+/// # Ok::<(), ua_parser::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct SharedExtractor<'a>(Arc<Extractor<'a>>);
+
+impl<'a> Extractor<'a> {
+    /// Moves the extractor into a [`SharedExtractor`].
+    pub fn into_shared(self) -> SharedExtractor<'a> {
+        SharedExtractor(Arc::new(self))
+    }
+}
+
+impl<'a> SharedExtractor<'a> {
+    /// The underlying [`Arc`], e.g. for `Extractor::extract_blocking`.
+    pub fn as_arc(&self) -> &Arc<Extractor<'a>> {
+        &self.0
+    }
+}
+
+impl<'a> Deref for SharedExtractor<'a> {
+    type Target = Extractor<'a>;
+
+    fn deref(&self) -> &Extractor<'a> {
+        &self.0
+    }
+}
+
+impl<'a> From<Extractor<'a>> for SharedExtractor<'a> {
+    fn from(extractor: Extractor<'a>) -> Self {
+        extractor.into_shared()
+    }
+}
+
+impl<'a> From<Arc<Extractor<'a>>> for SharedExtractor<'a> {
+    fn from(extractor: Arc<Extractor<'a>>) -> Self {
+        Self(extractor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{user_agent, Regexes};
+
+    #[test]
+    fn shared() {
+        let shared = Extractor::try_from(Regexes {
+            user_agent_parsers: vec![user_agent::Parser {
+                regex: r"(Firefox)/(\d+)".into(),
+                ..Default::default()
+            }],
+            os_parsers: vec![],
+            device_parsers: vec![],
+        })
+        .unwrap()
+        .into_shared();
+
+        let handle = shared.clone();
+        assert!(Arc::ptr_eq(shared.as_arc(), handle.as_arc()));
+        assert_eq!(Arc::strong_count(shared.as_arc()), 2);
+        let c = handle.extract("Mozilla/5.0 Firefox/121");
+        assert_eq!(c.ua.unwrap().major.as_deref(), Some("121"));
+        drop(handle);
+        assert_eq!(Arc::strong_count(shared.as_arc()), 1);
+    }
+}
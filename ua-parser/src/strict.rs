@@ -0,0 +1,251 @@
+//! Strict deserialization of parser definitions, see
+//! [`Regexes::deserialize_strict`], and strict building of
+//! extractors, see [`Extractor::try_from_strict`].
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{dedup, device, os, user_agent, Error, Extractor, Regexes};
+
+#[derive(Deserialize)]
+#[serde(remote = "user_agent::Parser", deny_unknown_fields)]
+struct UserAgentParser<'a> {
+    regex: Cow<'a, str>,
+    #[serde(alias = "regexFlag")]
+    regex_flag: Option<device::Flags>,
+    #[serde(alias = "familyReplacement")]
+    family_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "v1Replacement")]
+    v1_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "v2Replacement")]
+    v2_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "v3Replacement")]
+    v3_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "v4Replacement")]
+    v4_replacement: Option<Cow<'a, str>>,
+}
+
+#[derive(Deserialize)]
+#[serde(remote = "os::Parser", deny_unknown_fields)]
+struct OsParser<'a> {
+    regex: Cow<'a, str>,
+    #[serde(alias = "regexFlag")]
+    regex_flag: Option<device::Flags>,
+    #[serde(alias = "osReplacement")]
+    os_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "osV1Replacement")]
+    os_v1_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "osV2Replacement")]
+    os_v2_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "osV3Replacement")]
+    os_v3_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "osV4Replacement")]
+    os_v4_replacement: Option<Cow<'a, str>>,
+}
+
+#[derive(Deserialize)]
+#[serde(remote = "device::Parser", deny_unknown_fields)]
+struct DeviceParser<'a> {
+    regex: Cow<'a, str>,
+    #[serde(alias = "regexFlag")]
+    regex_flag: Option<device::Flags>,
+    #[serde(alias = "deviceReplacement")]
+    device_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "brandReplacement")]
+    brand_replacement: Option<Cow<'a, str>>,
+    #[serde(alias = "modelReplacement")]
+    model_replacement: Option<Cow<'a, str>>,
+    #[serde(skip)]
+    extra: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct UserAgent<'a>(#[serde(with = "UserAgentParser")] user_agent::Parser<'a>);
+#[derive(Deserialize)]
+struct Os<'a>(#[serde(with = "OsParser")] os::Parser<'a>);
+#[derive(Deserialize)]
+struct Device<'a>(#[serde(with = "DeviceParser")] device::Parser<'a>);
+
+#[derive(Deserialize)]
+#[serde(remote = "Regexes", deny_unknown_fields)]
+struct StrictRegexes<'a> {
+    #[serde(alias = "userAgentParsers", deserialize_with = "user_agent_parsers")]
+    user_agent_parsers: Vec<user_agent::Parser<'a>>,
+    #[serde(alias = "osParsers", deserialize_with = "os_parsers")]
+    os_parsers: Vec<os::Parser<'a>>,
+    #[serde(alias = "deviceParsers", deserialize_with = "device_parsers")]
+    device_parsers: Vec<device::Parser<'a>>,
+}
+
+fn user_agent_parsers<'de, 'a, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Vec<user_agent::Parser<'a>>, D::Error> {
+    Ok(Vec::<UserAgent>::deserialize(d)?
+        .into_iter()
+        .map(|UserAgent(p)| p)
+        .collect())
+}
+
+fn os_parsers<'de, 'a, D: Deserializer<'de>>(d: D) -> Result<Vec<os::Parser<'a>>, D::Error> {
+    Ok(Vec::<Os>::deserialize(d)?
+        .into_iter()
+        .map(|Os(p)| p)
+        .collect())
+}
+
+fn device_parsers<'de, 'a, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Vec<device::Parser<'a>>, D::Error> {
+    Ok(Vec::<Device>::deserialize(d)?
+        .into_iter()
+        .map(|Device(p)| p)
+        .collect())
+}
+
+impl Regexes<'_> {
+    /// Same as the [`Deserialize`] implementation, but fails on
+    /// unknown fields (e.g. a misspelled `famly_replacement`) rather
+    /// than ignoring them, to validate hand-written files. This
+    /// includes the extension fields otherwise collected in
+    /// [`device::Parser::extra`].
+    ///
+    /// ```
+    /// # use ua_parser::Regexes;
+    /// let yaml = "
+    /// user_agent_parsers:
+    ///   - regex: (Firefox)
+    ///     famly_replacement: Fx
+    /// os_parsers: []
+    /// device_parsers: []
+    /// ";
+    /// assert!(serde_yaml::from_str::<Regexes>(yaml).is_ok());
+    /// let strict = Regexes::deserialize_strict(serde_yaml::Deserializer::from_str(yaml));
+    /// assert!(strict.is_err());
+    /// ```
+    pub fn deserialize_strict<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        StrictRegexes::deserialize(d)
+    }
+}
+
+impl<'a> Extractor<'a> {
+    /// Same as [`Extractor::try_from`], but fails if a replacement
+    /// references a group missing from its regex (see the
+    /// `strict_templates` method of the builders), rather than
+    /// expanding the reference to an empty string, to validate
+    /// hand-written files.
+    ///
+    /// ```
+    /// # use ua_parser::{Extractor, Regexes};
+    /// let yaml = "
+    /// user_agent_parsers: []
+    /// os_parsers:
+    ///   - regex: Windows NT (\\d+)
+    ///     os_replacement: Windows $2
+    /// device_parsers: []
+    /// ";
+    /// let regexes = || serde_yaml::from_str::<Regexes>(yaml).unwrap();
+    /// let e = Extractor::try_from(regexes())?;
+    /// assert_eq!(e.extract("Windows NT 10").os_family(), "Windows");
+    /// assert!(Extractor::try_from_strict(regexes()).is_err());
+    /// # Ok::<(), ua_parser::Error>(())
+    /// ```
+    pub fn try_from_strict(r: Regexes<'a>) -> Result<Self, Error> {
+        Self::assemble_from(
+            user_agent::Builder::new().strict_templates(true),
+            os::Builder::new().strict_templates(true),
+            device::Builder::new().strict_templates(true),
+            dedup::Dedup::default(),
+            r,
+            Err,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strict() {
+        let yaml = r#"
+user_agent_parsers:
+  - regex: (Firefox)/(\d+)
+    v1_replacement: "1"
+os_parsers:
+  - regex: (Android) (\d+)
+    os_v1_replacement: "14"
+device_parsers:
+  - regex: (Pixel \d+)
+    regex_flag: i
+    brand_replacement: Google
+"#;
+        let r = Regexes::deserialize_strict(serde_yaml::Deserializer::from_str(yaml)).unwrap();
+        assert_eq!(r.user_agent_parsers[0].v1_replacement.as_deref(), Some("1"));
+        assert_eq!(r.os_parsers[0].os_v1_replacement.as_deref(), Some("14"));
+        assert_eq!(
+            r.device_parsers[0].regex_flag,
+            Some(device::Flags::IGNORE_CASE)
+        );
+
+        for typo in [
+            "user_agent_parsers: [{regex: a, famly_replacement: b}]\nos_parsers: []\ndevice_parsers: []",
+            "user_agent_parsers: []\nos_parsers: [{regex: a, os_replacment: b}]\ndevice_parsers: []",
+            "user_agent_parsers: []\nos_parsers: []\ndevice_parsers: [{regex: a, brand: b}]",
+            "user_agent_parsers: []\nos_parsers: []\ndevice_parsers: []\nbot_parsers: []",
+        ] {
+            assert!(serde_yaml::from_str::<Regexes>(typo).is_ok(), "{typo}");
+            let e = Regexes::deserialize_strict(serde_yaml::Deserializer::from_str(typo));
+            assert!(e.is_err(), "{typo}");
+        }
+    }
+
+    #[test]
+    fn variants() {
+        let yaml = r#"
+userAgentParsers:
+  - regex: (Firefox)
+    familyReplacement: Fx
+osParsers:
+  - regex: (Android)
+    osV1Replacement: "14"
+deviceParsers:
+  - regex: (Pixel \d+)
+    regexFlag: i
+    brandReplacement: Google
+    device_type: smartphone
+    screen: 6.1
+"#;
+        let r: Regexes = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            r.user_agent_parsers[0].family_replacement.as_deref(),
+            Some("Fx")
+        );
+        assert_eq!(r.os_parsers[0].os_v1_replacement.as_deref(), Some("14"));
+        let d = &r.device_parsers[0];
+        assert_eq!(d.regex_flag, Some(device::Flags::IGNORE_CASE));
+        assert_eq!(d.brand_replacement.as_deref(), Some("Google"));
+        assert_eq!(
+            d.extra
+                .iter()
+                .map(|(k, v)| (&**k, &**v))
+                .collect::<Vec<_>>(),
+            [("device_type", "smartphone"), ("screen", "6.1")],
+        );
+        // other values are rejected rather than dropped
+        let e =
+            serde_yaml::from_str::<Regexes>(&format!("{yaml}    tags: [mobile]\n")).unwrap_err();
+        assert!(e.to_string().contains(r#""tags""#), "{e}");
+
+        // camelCase is a valid spelling, extension fields are not
+        assert!(Regexes::deserialize_strict(serde_yaml::Deserializer::from_str(yaml)).is_err());
+        let yaml = yaml.split("    device_type").next().unwrap();
+        let r = Regexes::deserialize_strict(serde_yaml::Deserializer::from_str(yaml)).unwrap();
+        assert_eq!(
+            r.device_parsers[0].regex_flag,
+            Some(device::Flags::IGNORE_CASE)
+        );
+        assert!(r.device_parsers[0].extra.is_empty());
+    }
+}
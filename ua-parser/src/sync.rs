@@ -0,0 +1,10 @@
+//! Lazy initialization primitives: from `std` by default, or from
+//! `once_cell` with the `once_cell` feature, for toolchains predating
+//! the stabilization of `LazyLock` (Rust 1.80).
+// `LazyLock` is only used by some features
+#![allow(unused_imports)]
+
+#[cfg(feature = "once_cell")]
+pub(crate) use once_cell::sync::{Lazy as LazyLock, OnceCell as OnceLock};
+#[cfg(not(feature = "once_cell"))]
+pub(crate) use std::sync::{LazyLock, OnceLock};
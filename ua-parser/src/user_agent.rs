@@ -0,0 +1,474 @@
+//! User agent module.
+//!
+//! The user agent is the representation of the browser, in UAP lingo
+//! the user agent is composed of a *family* (the browser project) and
+//! a *version* of up to 4 segments.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::dedup::Dedup;
+use crate::device::Flags;
+use crate::domain;
+use crate::engine;
+use crate::resolvers::{FallbackResolver, FamilyResolver};
+use crate::{Interner, Version};
+use regex_filtered::BuildError;
+
+/// Individual user agent parser description. Plain data which can
+/// be deserialized from serde-compatible storage, or created
+/// literally (e.g. using a conversion or build script).
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+pub struct Parser<'a> {
+    /// Regex to check the UA against, if the regex matches the
+    /// parser applies.
+    pub regex: Cow<'a, str>,
+    /// Configuration flags for the regex, if any.
+    #[serde(alias = "regexFlag")]
+    pub regex_flag: Option<Flags>,
+    /// If set, used for the [`ValueRef::family`] field. If it
+    /// contains a `$1` placeholder, that is replaced by the value
+    /// of the first match group.
+    ///
+    /// If unset, the first match group is used directly.
+    #[serde(alias = "familyReplacement")]
+    pub family_replacement: Option<Cow<'a, str>>,
+    /// If set, provides the value of the major version number,
+    /// otherwise the second match group is used.
+    #[serde(alias = "v1Replacement")]
+    pub v1_replacement: Option<Cow<'a, str>>,
+    /// If set, provides the value of the minor version number,
+    /// otherwise the third match group is used.
+    #[serde(alias = "v2Replacement")]
+    pub v2_replacement: Option<Cow<'a, str>>,
+    /// If set, provides the value of the patch version number,
+    /// otherwise the fourth match group is used.
+    #[serde(alias = "v3Replacement")]
+    pub v3_replacement: Option<Cow<'a, str>>,
+    /// If set, provides the value of the minor patch version
+    /// number, otherwise the fifth match group is used.
+    #[serde(alias = "v4Replacement")]
+    pub v4_replacement: Option<Cow<'a, str>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser with the provided regex and no
+    /// replacements, to be completed by the other methods.
+    pub fn new(regex: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            regex: regex.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets [`Self::regex_flag`].
+    pub fn regex_flag(mut self, flags: Flags) -> Self {
+        self.regex_flag = Some(flags);
+        self
+    }
+
+    /// Sets [`Self::family_replacement`].
+    pub fn family(mut self, family: impl Into<Cow<'a, str>>) -> Self {
+        self.family_replacement = Some(family.into());
+        self
+    }
+
+    /// Sets [`Self::v1_replacement`].
+    pub fn v1(mut self, v1: impl Into<Cow<'a, str>>) -> Self {
+        self.v1_replacement = Some(v1.into());
+        self
+    }
+
+    /// Sets [`Self::v2_replacement`].
+    pub fn v2(mut self, v2: impl Into<Cow<'a, str>>) -> Self {
+        self.v2_replacement = Some(v2.into());
+        self
+    }
+
+    /// Sets [`Self::v3_replacement`].
+    pub fn v3(mut self, v3: impl Into<Cow<'a, str>>) -> Self {
+        self.v3_replacement = Some(v3.into());
+        self
+    }
+
+    /// Sets [`Self::v4_replacement`].
+    pub fn v4(mut self, v4: impl Into<Cow<'a, str>>) -> Self {
+        self.v4_replacement = Some(v4.into());
+        self
+    }
+}
+
+/// Resolution of the version replacements,
+/// [`Parser::v1_replacement`] to [`Parser::v4_replacement`].
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum VersionReplacement {
+    /// The replacements are used verbatim.
+    #[default]
+    Verbatim,
+    /// The replacements are restrict-templated as per the uap-core
+    /// specification: similar to [`Parser::family_replacement`]
+    /// but each replacement can only reference the group it
+    /// replaces, `$2` for `v1_replacement` to `$5` for
+    /// `v4_replacement`, and the regex must have that group.
+    Templated,
+}
+
+/// Resolution of [`Parser::family_replacement`].
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum FamilyReplacement {
+    /// The replacement is restrict-templated as per the uap-core
+    /// specification, only `$1` is substituted.
+    #[default]
+    Restricted,
+    /// The replacement is fully templated, like the OS and device
+    /// replacements, so it can reference any group.
+    Templated,
+}
+
+type Repl<'a> = (
+    FamilyResolver<'a>,
+    FallbackResolver<'a>,
+    FallbackResolver<'a>,
+    FallbackResolver<'a>,
+    FallbackResolver<'a>,
+);
+
+/// Extractor builder, used to `push` parsers into before building
+/// the extractor.
+#[derive(Default)]
+pub struct Builder<'a> {
+    core: domain::Builder<Repl<'a>>,
+    family: FamilyReplacement,
+    versions: VersionReplacement,
+}
+impl<'a> Builder<'a> {
+    /// Initialise an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the extractor, may be called without pushing any
+    /// parser in though that is not very useful.
+    pub fn build(self) -> Result<Extractor<'a>, BuildError> {
+        Ok(Extractor {
+            core: self.core.build()?,
+            family: self.family,
+            versions: self.versions,
+        })
+    }
+
+    /// Sets the resolution of the family replacements of the
+    /// parsers pushed afterwards.
+    pub fn family_replacement(mut self, family: FamilyReplacement) -> Self {
+        self.family = family;
+        self
+    }
+
+    /// Sets the resolution of the version replacements of the
+    /// parsers pushed afterwards.
+    pub fn version_replacement(mut self, versions: VersionReplacement) -> Self {
+        self.versions = versions;
+        self
+    }
+
+    /// Pushes a parser into the builder, may fail if the
+    /// [`Parser::regex`] is invalid.
+    pub fn push(mut self, ua: Parser<'a>) -> Result<Self, crate::Error> {
+        self.push_mut(ua)?;
+        Ok(self)
+    }
+
+    /// Pushes a parser, leaving the builder unchanged on failure.
+    /// `index` is only used to report errors.
+    pub(crate) fn push_with(
+        &mut self,
+        ua: Parser<'a>,
+        index: usize,
+        dedup: Option<&mut Dedup>,
+    ) -> Result<(), crate::Error> {
+        let (family, versions, strict) = (self.family, self.versions, self.core.strict);
+        self.core
+            .push(&ua.regex, ua.regex_flag, dedup, |r| {
+                let version = |repl, idx| match versions {
+                    VersionReplacement::Verbatim => {
+                        // number of groups in regex, excluding implicit entire match group
+                        Ok(FallbackResolver::new(repl, r.captures_len() - 1, idx))
+                    }
+                    VersionReplacement::Templated => {
+                        FallbackResolver::templated(repl, r, idx, strict)
+                    }
+                };
+                Ok((
+                    FamilyResolver::new(ua.family_replacement, r, family, strict)?,
+                    version(ua.v1_replacement, 2)?,
+                    version(ua.v2_replacement, 3)?,
+                    version(ua.v3_replacement, 4)?,
+                    version(ua.v4_replacement, 5)?,
+                ))
+            })
+            .map_err(|e| e.in_parser("user_agent_parsers", index, &ua.regex))
+    }
+
+    domain::builder_methods!();
+}
+
+/// Collects parsers into a builder, failing on the first invalid
+/// parser.
+impl<'a> FromIterator<Parser<'a>> for Result<Builder<'a>, crate::Error> {
+    fn from_iter<I: IntoIterator<Item = Parser<'a>>>(iter: I) -> Self {
+        Builder::new().push_all(iter)
+    }
+}
+
+/// User Agent extractor.
+pub struct Extractor<'a> {
+    pub(crate) core: domain::Extractor<Repl<'a>>,
+    pub(crate) family: FamilyReplacement,
+    pub(crate) versions: VersionReplacement,
+}
+impl<'a> Extractor<'a> {
+    /// Tries the loaded [`Parser`], upon finding the first
+    /// matching [`Parser`] performs data extraction following its
+    /// replacement directives and returns the result.
+    ///
+    /// Returns [`None`] if:
+    ///
+    /// - no matching parser was found
+    /// - the match does not have any matching groups *and*
+    ///   [`Parser::family_replacement`] is unset
+    /// - [`Parser::family_replacement`] has a substitution
+    ///   but there is no group in the regex
+    pub fn extract(&'a self, ua: &'a str) -> Option<ValueRef<'a>> {
+        let (idx, _) = self.core.first(ua)?;
+        self.extract_at(idx, ua)
+    }
+
+    /// Resolves only the [`ValueRef::family`] of the first
+    /// matching parser, skipping the version fields entirely, and
+    /// the captures if the family is a plain replacement.
+    ///
+    /// Returns [`None`] if no parser matches.
+    pub fn extract_family(&'a self, ua: &'a str) -> Option<Cow<'a, str>> {
+        let (idx, re) = self.core.first(ua)?;
+        let (f, ..) = self.core.repl(idx);
+        if let Some(family) = f.constant() {
+            return Some(family.into());
+        }
+        let c = engine::captures(re, ua)?;
+        Some(f.resolve(&c))
+    }
+
+    /// Detects the in-app webview the user agent runs in (e.g.
+    /// `Android WebView`, `Facebook`), see [`crate#webviews`].
+    ///
+    /// This heuristic is not part of the extraction, and only
+    /// performed when requested.
+    pub fn webview(&self, ua: &str) -> Option<&'static str> {
+        crate::webview::detect(ua)
+    }
+
+    fn resolve(&'a self, idx: usize, c: &engine::Captures<'a>) -> ValueRef<'a> {
+        let (f, v1, v2, v3, v4) = self.core.repl(idx);
+        let empty = self.core.empty;
+
+        ValueRef {
+            family: f.resolve(c),
+            major: v1.resolve(c, empty),
+            minor: v2.resolve(c, empty),
+            patch: v3.resolve(c, empty),
+            patch_minor: v4.resolve(c, empty),
+        }
+    }
+
+    domain::extractor_methods!();
+
+    /// Turns the extractor back into a builder, reusing the
+    /// compiled regexes.
+    pub(crate) fn into_builder(
+        self,
+        dedup: Option<&mut Dedup>,
+    ) -> Result<Builder<'a>, regex_filtered::ParseError> {
+        Ok(Builder {
+            core: self.core.into_builder(dedup)?,
+            family: self.family,
+            versions: self.versions,
+        })
+    }
+
+    /// Reconstructs the parsers the extractor was built from,
+    /// with their regexes as rewritten during building.
+    pub(crate) fn parsers(&self) -> impl Iterator<Item = Parser<'_>> {
+        self.core
+            .parsers()
+            .map(|(re, flag, (f, v1, v2, v3, v4))| Parser {
+                regex: re.as_str().into(),
+                regex_flag: flag,
+                family_replacement: f.replacement().map(From::from),
+                v1_replacement: v1.replacement().map(From::from),
+                v2_replacement: v2.replacement().map(From::from),
+                v3_replacement: v3.replacement().map(From::from),
+                v4_replacement: v4.replacement().map(From::from),
+            })
+    }
+}
+/// Borrowed extracted value, borrows the content of the original
+/// parser or the content of the user agent string, unless a
+/// replacement is performed. (which is only possible for the )
+#[derive(Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct ValueRef<'a> {
+    ///
+    pub family: Cow<'a, str>,
+    ///
+    pub major: Option<Cow<'a, str>>,
+    ///
+    pub minor: Option<Cow<'a, str>>,
+    ///
+    pub patch: Option<Cow<'a, str>>,
+    ///
+    pub patch_minor: Option<Cow<'a, str>>,
+}
+
+impl<'a> ValueRef<'a> {
+    /// The uap-core fallback value, for user agents no parser
+    /// matches: `family` is `"Other"`, everything else is unset.
+    pub const fn other() -> Self {
+        Self {
+            family: Cow::Borrowed("Other"),
+            major: None,
+            minor: None,
+            patch: None,
+            patch_minor: None,
+        }
+    }
+    /// Whether the family is a known headless browser, e.g.
+    /// `HeadlessChrome`. See [`crate::automation::detect`] to also
+    /// detect automation tools.
+    pub fn is_headless(&self) -> bool {
+        crate::automation::HEADLESS_FAMILIES.contains(&&*self.family)
+    }
+
+    /// The typed version of the value, for ordering comparisons.
+    pub fn version(&self) -> Version<'_> {
+        Version {
+            major: self.major.as_deref(),
+            minor: self.minor.as_deref(),
+            patch: self.patch.as_deref(),
+            patch_minor: self.patch_minor.as_deref(),
+        }
+    }
+
+    /// Converts the borrowed result into an owned one,
+    /// independent from both the extractor and the user agent
+    /// string.
+    pub fn into_owned(self) -> Value {
+        Value {
+            family: self.family.into_owned(),
+            major: self.major.map(|c| c.into_owned()),
+            minor: self.minor.map(|c| c.into_owned()),
+            patch: self.patch.map(|c| c.into_owned()),
+            patch_minor: self.patch_minor.map(|c| c.into_owned()),
+        }
+    }
+
+    /// Converts the borrowed result into an owned one whose
+    /// strings are shared through the `interner`.
+    pub fn into_interned(self, interner: &Interner) -> Value<Arc<str>> {
+        Value {
+            family: interner.intern(&self.family),
+            major: self.major.map(|c| interner.intern(&c)),
+            minor: self.minor.map(|c| interner.intern(&c)),
+            patch: self.patch.map(|c| interner.intern(&c)),
+            patch_minor: self.patch_minor.map(|c| interner.intern(&c)),
+        }
+    }
+}
+
+impl ValueRef<'_> {
+    /// The fields of the value as `(name, value)` pairs, in
+    /// declaration order and named as when serialized, for generic
+    /// exporters (e.g. CSV writers or key-value loggers).
+    pub fn fields(&self) -> [(&'static str, Option<&str>); 5] {
+        [
+            ("family", Some(&self.family)),
+            ("major", self.major.as_deref()),
+            ("minor", self.minor.as_deref()),
+            ("patch", self.patch.as_deref()),
+            ("patch_minor", self.patch_minor.as_deref()),
+        ]
+    }
+
+    /// The fields of the value as a map, see [`Self::fields`].
+    pub fn to_map(&self) -> BTreeMap<&'static str, Option<&str>> {
+        self.fields().into_iter().collect()
+    }
+}
+
+/// Formats the family followed by the version, e.g. `Firefox
+/// 121.0`.
+impl std::fmt::Display for ValueRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        crate::version::write_versioned(f, &self.family, self.version())
+    }
+}
+
+/// Owned extracted value, identical to [`ValueRef`] but not
+/// linked to either the UA string or the extractor.
+///
+/// The strings are [`String`] by default, or [`Arc<str>`] when
+/// interned.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct Value<S = String> {
+    ///
+    pub family: S,
+    ///
+    pub major: Option<S>,
+    ///
+    pub minor: Option<S>,
+    ///
+    pub patch: Option<S>,
+    ///
+    pub patch_minor: Option<S>,
+}
+
+impl Value {
+    /// Owned version of [`ValueRef::other`].
+    pub fn other() -> Self {
+        ValueRef::other().into_owned()
+    }
+}
+
+impl<S: AsRef<str>> Value<S> {
+    /// Same as [`ValueRef::fields`].
+    pub fn fields(&self) -> [(&'static str, Option<&str>); 5] {
+        [
+            ("family", Some(self.family.as_ref())),
+            ("major", self.major.as_ref().map(AsRef::as_ref)),
+            ("minor", self.minor.as_ref().map(AsRef::as_ref)),
+            ("patch", self.patch.as_ref().map(AsRef::as_ref)),
+            ("patch_minor", self.patch_minor.as_ref().map(AsRef::as_ref)),
+        ]
+    }
+
+    /// The fields of the value as a map, see [`Self::fields`].
+    pub fn to_map(&self) -> BTreeMap<&'static str, Option<&str>> {
+        self.fields().into_iter().collect()
+    }
+
+    /// The typed version of the value, for ordering comparisons.
+    pub fn version(&self) -> Version<'_> {
+        Version {
+            major: self.major.as_ref().map(AsRef::as_ref),
+            minor: self.minor.as_ref().map(AsRef::as_ref),
+            patch: self.patch.as_ref().map(AsRef::as_ref),
+            patch_minor: self.patch_minor.as_ref().map(AsRef::as_ref),
+        }
+    }
+}
+
+impl<S: AsRef<str>> std::fmt::Display for Value<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        crate::version::write_versioned(f, self.family.as_ref(), self.version())
+    }
+}
@@ -0,0 +1,154 @@
+//! Typed versions, see [`Version`].
+
+use std::cmp::Ordering;
+
+/// Version made of the (up to) four version segments of a user agent
+/// or OS value, see e.g. [`crate::user_agent::ValueRef::version`].
+///
+/// Versions are ordered segment by segment, missing segments sort
+/// before present ones. Segments are compared by their leading
+/// number, numerically, then by the rest of the segment,
+/// lexicographically, so `9 < 10` and `3b1 < 3b2 < 10`.
+///
+/// Equality follows the ordering, so `"01"` and `"1"` are equal.
+///
+/// ```
+/// # use ua_parser::Version;
+/// assert!(Version::parse("10.0") > Version::parse("9.3.1"));
+/// assert!(Version::parse("1.2") < Version::parse("1.2.0"));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Version<'a> {
+    ///
+    pub major: Option<&'a str>,
+    ///
+    pub minor: Option<&'a str>,
+    ///
+    pub patch: Option<&'a str>,
+    ///
+    pub patch_minor: Option<&'a str>,
+}
+
+impl<'a> Version<'a> {
+    /// Parses a dotted version string, anything after the fourth
+    /// segment is left in [`Self::patch_minor`].
+    pub fn parse(s: &'a str) -> Self {
+        let mut it = s.splitn(4, '.').filter(|s| !s.is_empty());
+        Self {
+            major: it.next(),
+            minor: it.next(),
+            patch: it.next(),
+            patch_minor: it.next(),
+        }
+    }
+
+    fn segments(&self) -> [Option<&'a str>; 4] {
+        [self.major, self.minor, self.patch, self.patch_minor]
+    }
+}
+
+fn compare_segments(a: &str, b: &str) -> Ordering {
+    fn split(s: &str) -> (&str, &str) {
+        let (num, rest) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+        (num.trim_start_matches('0'), rest)
+    }
+    let (na, ra) = split(a);
+    let (nb, rb) = split(b);
+    // without leading zeroes, longer numbers are larger
+    na.len()
+        .cmp(&nb.len())
+        .then_with(|| na.cmp(nb))
+        .then_with(|| ra.cmp(rb))
+}
+
+impl Ord for Version<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        std::iter::zip(self.segments(), other.segments())
+            .map(|(a, b)| match (a, b) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Less,
+                (Some(_), None) => Ordering::Greater,
+                (Some(a), Some(b)) => compare_segments(a, b),
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+impl PartialOrd for Version<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for Version<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+impl Eq for Version<'_> {}
+
+/// Writes `family` followed by the version if there is one, e.g.
+/// `Firefox 121.0`.
+pub(crate) fn write_versioned(
+    f: &mut std::fmt::Formatter<'_>,
+    family: &str,
+    version: Version<'_>,
+) -> std::fmt::Result {
+    f.write_str(family)?;
+    if version.major.is_some() {
+        write!(f, " {version}")?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for Version<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, s) in self.segments().into_iter().map_while(|s| s).enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            f.write_str(s)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ordering() {
+        let mut versions = [
+            "10", "9.3", "9", "", "3b2", "3b1", "10.0.1", "10.0", "1.02", "1.1",
+        ]
+        .map(Version::parse);
+        versions.sort();
+        assert_eq!(
+            versions.map(|v| v.to_string()),
+            ["", "1.1", "1.02", "3b1", "3b2", "9", "9.3", "10", "10.0", "10.0.1"]
+        );
+
+        assert_eq!(Version::parse("01.2"), Version::parse("1.2"));
+        assert!(Version::parse("beta") < Version::parse("1"));
+        assert_eq!(Version::parse("1.2.3.4.5").patch_minor, Some("4.5"));
+    }
+
+    #[test]
+    fn values() {
+        let ua = crate::user_agent::ValueRef {
+            family: "Firefox".into(),
+            major: Some("10".into()),
+            minor: Some("0".into()),
+            ..Default::default()
+        };
+        assert!(ua.version() > Version::parse("9.9"));
+        assert_eq!(ua.into_owned().version(), Version::parse("10.0"));
+
+        let os = crate::os::ValueRef {
+            os: "Android".into(),
+            major: Some("14".into()),
+            ..Default::default()
+        };
+        assert_eq!(os.version().to_string(), "14");
+    }
+}
@@ -0,0 +1,67 @@
+//! Heuristic detection of in-app webviews, which the user agent
+//! family alone does not distinguish from the regular browser.
+
+/// Markers of embedded browsers of specific apps, checked first.
+const APPS: &[(&str, &str)] = &[
+    ("FBAN/", "Facebook"),
+    ("FBAV/", "Facebook"),
+    ("FB_IAB/", "Facebook"),
+    ("Instagram ", "Instagram"),
+    (" Line/", "Line"),
+    ("MicroMessenger/", "WeChat"),
+];
+
+/// Returns the name of the webview the user agent runs in, if any.
+pub(crate) fn detect(ua: &str) -> Option<&'static str> {
+    if let Some(&(_, app)) = APPS.iter().find(|(marker, _)| ua.contains(marker)) {
+        Some(app)
+    } else if ua.contains("; wv)") {
+        Some("Android WebView")
+    } else if (ua.contains("iPhone") || ua.contains("iPad") || ua.contains("iPod"))
+        && ua.contains("AppleWebKit/")
+        // iOS browsers (including third-party ones) advertise Safari,
+        // webviews embedded in apps do not
+        && !ua.contains("Safari/")
+    {
+        Some("iOS WebView")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::detect;
+
+    #[test]
+    fn webviews() {
+        for (ua, webview) in [
+            (
+                "Mozilla/5.0 (Linux; Android 14; Pixel 8 Build/UQ1A; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/120.0 Mobile Safari/537.36",
+                Some("Android WebView"),
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148",
+                Some("iOS WebView"),
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 [FBAN/FBIOS;FBAV/442.0]",
+                Some("Facebook"),
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 14; wv) AppleWebKit/537.36 Chrome/120.0 Mobile Safari/537.36 Instagram 312.0.0.0",
+                Some("Instagram"),
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+                None,
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 Chrome/120.0 Mobile Safari/537.36",
+                None,
+            ),
+        ] {
+            assert_eq!(detect(ua), webview, "{ua}");
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Property-based check of the regex rewriting: on ASCII haystacks
+//! shorter than the widening threshold, a rewritten regex must match
+//! exactly like the original, down to the capture groups.
+//!
+//! Patterns are generated from the constructs the rewriter handles
+//! (class escapes, word boundaries, nested classes, bounded
+//! repetitions) and the ones it must leave alone (escaped braces and
+//! backslashes, small repetitions). Every case is derived from its
+//! seed, which is reported on failure. The number of cases can be
+//! raised through `UA_PARSER_PROPTEST_CASES`.
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use regex::{Regex, RegexBuilder};
+use ua_parser::rewrite::{Config, Dialect};
+
+/// Haystacks are kept below the smallest widened bound (100), so the
+/// bound can never be what limits a match.
+const MAX_HAYSTACK: usize = 60;
+const HAYSTACK_ALPHABET: &[u8] = b"ab1Z _.-{}[]\\\t";
+
+fn choose<'a>(rng: &mut SmallRng, items: &[&'a str]) -> &'a str {
+    items[rng.random_range(0..items.len())]
+}
+
+fn class(rng: &mut SmallRng, out: &mut String) {
+    out.push('[');
+    if rng.random_bool(0.2) {
+        out.push('^');
+    }
+    for _ in 0..rng.random_range(1..4) {
+        out.push_str(choose(
+            rng,
+            &[
+                "a",
+                "1",
+                "a-z",
+                r"\d",
+                r"\D",
+                r"\w",
+                r"\s",
+                r"\S",
+                ".",
+                "{",
+                r"\[",
+                r"\\",
+                "[a-z]",
+                "[^b]",
+                "[[:alpha:]]",
+                "[:digit:]",
+            ],
+        ));
+    }
+    out.push(']');
+}
+
+fn atom(rng: &mut SmallRng, depth: u32, large: bool, out: &mut String) {
+    // large repetitions are not nested, as that makes for very slow
+    // to compile regexes
+    let repetition = rng.random_bool(0.4).then(|| {
+        choose(
+            rng,
+            if large {
+                &[
+                    "?", "*", "+", "{0,}", "{1,}", "{2}", "{0,2}", "{1,9}", "{0,99}", "{0,100}",
+                    "{1,100}", "{0,150}", "{1,300}", "{2,120}",
+                ]
+            } else {
+                &["?", "*", "+", "{0,}", "{1,}", "{2}", "{0,2}", "{1,9}"]
+            },
+        )
+    });
+    let large = large && repetition.map_or(true, |r| r.len() < 6);
+
+    match rng.random_range(0..if depth == 0 { 4 } else { 6 }) {
+        0 => out.push_str(choose(rng, &["a", "b", "1", "Z", " ", "_", r"\.", "-"])),
+        1 => out.push_str(choose(
+            rng,
+            &[
+                ".", r"\d", r"\D", r"\s", r"\S", r"\w", r"\W", r"\{", r"\}", r"\\d", r"\\",
+            ],
+        )),
+        2 => class(rng, out),
+        3 => out.push_str(choose(rng, &[r"\b", r"\B", "^", "$"])),
+        4 => {
+            out.push_str(choose(rng, &["(", "(?:"]));
+            sequence(rng, depth - 1, large, out);
+            out.push(')');
+        }
+        _ => {
+            out.push('(');
+            for i in 0..rng.random_range(2..4) {
+                if i > 0 {
+                    out.push('|');
+                }
+                sequence(rng, depth - 1, large, out);
+            }
+            out.push(')');
+        }
+    }
+    if let Some(r) = repetition {
+        out.push_str(r);
+    }
+}
+
+fn sequence(rng: &mut SmallRng, depth: u32, large: bool, out: &mut String) {
+    for _ in 0..rng.random_range(1..5) {
+        atom(rng, depth, large, out);
+    }
+}
+
+fn haystack(rng: &mut SmallRng) -> String {
+    (0..rng.random_range(0..MAX_HAYSTACK))
+        .map(|_| char::from(HAYSTACK_ALPHABET[rng.random_range(0..HAYSTACK_ALPHABET.len())]))
+        .collect()
+}
+
+fn spans(re: &Regex, haystack: &str) -> Option<Vec<Option<(usize, usize)>>> {
+    re.captures(haystack)
+        .map(|c| c.iter().map(|m| m.map(|m| (m.start(), m.end()))).collect())
+}
+
+fn check(seed: u64) {
+    let mut rng = SmallRng::seed_from_u64(seed);
+
+    let mut pattern = String::new();
+    sequence(&mut rng, 2, true, &mut pattern);
+    // large repetitions of unicode classes are very slow to compile,
+    // and not really more interesting than smaller ones
+    let Ok(original) = RegexBuilder::new(&pattern).size_limit(1 << 20).build() else {
+        // the generator can produce invalid or oversized regexes,
+        // there is nothing to preserve then
+        return;
+    };
+
+    let config = Config::new()
+        .ascii_classes(rng.random_bool(0.8))
+        .ascii_word_boundaries(rng.random_bool(0.8))
+        .widen_repetitions(rng.random_bool(0.8))
+        .dialect(if rng.random_bool(0.5) {
+            Dialect::Regex
+        } else {
+            Dialect::RegexLite
+        });
+    let rewritten = config.rewrite(&pattern);
+    let rewritten = Regex::new(&rewritten).unwrap_or_else(|e| {
+        panic!(
+            "seed {seed}: /{pattern}/ was rewritten to the invalid /{rewritten}/ ({config:?}): {e}"
+        )
+    });
+
+    for _ in 0..20 {
+        let haystack = haystack(&mut rng);
+        assert_eq!(
+            spans(&original, &haystack),
+            spans(&rewritten, &haystack),
+            "seed {seed}: /{pattern}/ and /{}/ ({config:?}) diverge on {haystack:?}",
+            rewritten.as_str(),
+        );
+    }
+}
+
+#[test]
+fn rewrite_equivalence() {
+    let cases = std::env::var("UA_PARSER_PROPTEST_CASES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(500);
+    for seed in 0..cases {
+        check(seed);
+    }
+}